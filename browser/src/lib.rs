@@ -1,5 +1,4 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{Element, HtmlElement};
 use wasm_bindgen::JsCast;
 
 // Called when the wasm module is instantiated
@@ -9,7 +8,7 @@ pub fn main() -> Result<(), JsValue> {
     // window object.
     let window = web_sys::window().expect("no global `window` exists");
     let document = window.document().expect("should have a document on window");
-    let body = document.body().expect("document should have a body");
+    let _body = document.body().expect("document should have a body");
 
 
     Ok(())
@@ -33,9 +32,9 @@ pub fn render_all(selector: &str) -> u32 {
                 let node = node_list.get(i).unwrap();
                 let e = node.dyn_into::<web_sys::HtmlElement>().expect("query_selector_all only returns elements");
 
-                let doc = core::sequence_diagram::render(&e.text_content().unwrap()).unwrap();
+                let doc = core::render(&e.text_content().unwrap(), &core::Options::default()).unwrap();
 
-                e.set_inner_html(&doc.to_string().as_str());
+                e.set_inner_html(doc.to_string().as_str());
                 num += 1;
             }
             num
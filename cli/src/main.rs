@@ -1,6 +1,292 @@
+use std::io::Read;
+use std::process::ExitCode;
 
-fn main() {
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("fmt") => fmt(&args[1..]),
+        Some("text") => text(&args[1..]),
+        #[cfg(feature = "png")]
+        Some("png") => png(&args[1..]),
+        #[cfg(feature = "pdf")]
+        Some("pdf") => pdf(&args[1..]),
+        _ => {
+            example();
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+/// `fmt [--check] [FILE]...` formats the files in place, or stdin to stdout
+/// when there are none. With `--check` nothing is written and the exit code
+/// tells whether the sources were formatted already.
+fn fmt(args: &[String]) -> ExitCode {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if let Some(flag) = files.iter().find(|arg| arg.starts_with('-')) {
+        eprintln!("unknown option {flag}\nusage: triton fmt [--check] [FILE]...");
+        return ExitCode::from(2);
+    }
+
+    if files.is_empty() {
+        let mut src = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut src) {
+            eprintln!("<stdin>: {e}");
+            return ExitCode::FAILURE;
+        }
+        return match core::format(&src) {
+            Ok(formatted) if check && formatted != src => {
+                eprintln!("<stdin> is not formatted");
+                ExitCode::FAILURE
+            }
+            Ok(_) if check => ExitCode::SUCCESS,
+            Ok(formatted) => {
+                print!("{formatted}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("<stdin>: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let mut code = ExitCode::SUCCESS;
+    for file in files {
+        let result = std::fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|src| core::format(&src).map(|formatted| (src, formatted)));
+        match result {
+            Ok((src, formatted)) if formatted == src => {}
+            Ok(_) if check => {
+                eprintln!("{file} is not formatted");
+                code = ExitCode::FAILURE;
+            }
+            Ok((_, formatted)) => {
+                if let Err(e) = std::fs::write(file, formatted) {
+                    eprintln!("{file}: {e}");
+                    code = ExitCode::FAILURE;
+                }
+            }
+            Err(e) => {
+                eprintln!("{file}: {e}");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    code
+}
+
+/// `text [--ascii] [FILE]` prints a sequence diagram, read from the file or
+/// stdin, with box-drawing characters or only ASCII ones.
+fn text(args: &[String]) -> ExitCode {
+    let mut config = core::sequence_diagram::TextConfig::default();
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            "--ascii" => config.ascii = true,
+            path if !path.starts_with('-') && file.is_none() => file = Some(path),
+            _ => {
+                eprintln!("invalid argument {arg}\nusage: triton text [--ascii] [FILE]");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let src = match file {
+        Some(file) => std::fs::read_to_string(file),
+        None => {
+            let mut src = String::new();
+            std::io::stdin().read_to_string(&mut src).map(|_| src)
+        }
+    };
+    let name = file.unwrap_or("<stdin>");
+    match src
+        .map_err(|e| e.to_string())
+        .and_then(|src| core::render_text(&src, &config))
+    {
+        Ok(text) => {
+            print!("{text}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+const PNG_USAGE: &str =
+    "usage: triton png [--scale N] [--dpi N] [--background #RRGGBB[AA]|none] [--today YYYY-MM-DD] [-o OUT] [FILE]";
+
+/// `png [OPTION]... [FILE]` renders a diagram to a PNG image, from stdin to
+/// stdout when there is no file, otherwise next to the file unless `-o`
+/// tells where.
+#[cfg(feature = "png")]
+fn png(args: &[String]) -> ExitCode {
+    let mut options = core::raster::PngOptions::default();
+    let mut diagram_options = core::Options::default();
+    let mut file = None;
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--scale" => args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .map(|n| options.scale = n),
+            "--dpi" => args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .map(|n| options.dpi = n),
+            "--background" => args
+                .next()
+                .and_then(|color| parse_color(color))
+                .map(|color| options.background = color),
+            "--today" => args
+                .next()
+                .and_then(|date| core::gantt::parse_date(date, "YYYY-MM-DD"))
+                .map(|today| diagram_options.today = Some(today)),
+            "-o" => args.next().map(|path| out = Some(path.clone())),
+            flag if flag.starts_with('-') => None,
+            path if file.is_none() => {
+                file = Some(path.to_string());
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            eprintln!("invalid argument {arg}\n{PNG_USAGE}");
+            return ExitCode::from(2);
+        }
+    }
+
+    export(file, out, "png", |src| {
+        core::render(src, &diagram_options).and_then(|doc| core::raster::to_png(&doc, &options))
+    })
+}
 
+#[cfg(feature = "pdf")]
+const PDF_USAGE: &str =
+    "usage: triton pdf [--page a4|letter|fit|WIDTHxHEIGHT] [--margin MM] [--split] [-o OUT] [FILE]";
+
+/// `pdf [OPTION]... [FILE]` renders a sequence diagram to a PDF document,
+/// the same way `png` does to an image.
+#[cfg(feature = "pdf")]
+fn pdf(args: &[String]) -> ExitCode {
+    let mut options = core::pdf::PdfOptions::default();
+    let mut file = None;
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--page" => args
+                .next()
+                .and_then(|page| parse_page(page))
+                .map(|page| options.page = page),
+            "--margin" => args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .map(|n| options.margin = n),
+            "--split" => {
+                options.split = true;
+                Some(())
+            }
+            "-o" => args.next().map(|path| out = Some(path.clone())),
+            flag if flag.starts_with('-') => None,
+            path if file.is_none() => {
+                file = Some(path.to_string());
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            eprintln!("invalid argument {arg}\n{PDF_USAGE}");
+            return ExitCode::from(2);
+        }
+    }
+
+    export(file, out, "pdf", |src| {
+        core::render_pdf(src, &core::Options::default(), &options)
+    })
+}
+
+/// A page size in millimetres, or `None` to fit the page to the diagram.
+#[cfg(feature = "pdf")]
+fn parse_page(page: &str) -> Option<Option<(f32, f32)>> {
+    match page {
+        "a4" => Some(Some(core::pdf::A4)),
+        "letter" => Some(Some(core::pdf::LETTER)),
+        "fit" => Some(None),
+        _ => {
+            let (width, height) = page.split_once('x')?;
+            Some(Some((width.parse().ok()?, height.parse().ok()?)))
+        }
+    }
+}
+
+/// Reads `file`, or stdin, and writes what `render` makes of it to `out`,
+/// next to `file` with the given extension, or to stdout.
+#[cfg(any(feature = "png", feature = "pdf"))]
+fn export(
+    file: Option<String>,
+    out: Option<String>,
+    extension: &str,
+    render: impl Fn(&str) -> Result<Vec<u8>, String>,
+) -> ExitCode {
+    use std::io::Write;
+
+    let src = match &file {
+        Some(file) => std::fs::read_to_string(file),
+        None => {
+            let mut src = String::new();
+            std::io::stdin().read_to_string(&mut src).map(|_| src)
+        }
+    };
+    let name = file.as_deref().unwrap_or("<stdin>");
+    let bytes = match src.map_err(|e| e.to_string()).and_then(|src| render(&src)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{name}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let out = out.or_else(|| {
+        file.map(|file| {
+            std::path::Path::new(&file)
+                .with_extension(extension)
+                .to_string_lossy()
+                .into_owned()
+        })
+    });
+    let written = match &out {
+        Some(out) => std::fs::write(out, bytes),
+        None => std::io::stdout().write_all(&bytes),
+    };
+    if let Err(e) = written {
+        eprintln!("{}: {e}", out.as_deref().unwrap_or("<stdout>"));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// `#RRGGBB` or `#RRGGBBAA`, or `none` for a transparent background.
+#[cfg(feature = "png")]
+fn parse_color(color: &str) -> Option<Option<[u8; 4]>> {
+    if color == "none" {
+        return Some(None);
+    }
+    let hex = color.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Some([channel(0)?, channel(2)?, channel(4)?, alpha]))
+}
+
+fn example() {
     // "Alice->Bob long name:Solving the system each time make for faster updates and allow to keep the solver in a consinstent state. However, the variable values are not updated automatically and you need to ask the solver to perform this operation before reading the values as illustrated below\nJohn->Bob long name:iiiiiiiiiiiiiiiiiiiii\nBob long name->John:It's Alice\nBob long name->Alice:I'm fine\n".to_string(),
 
     let _src = r#"
@@ -15,8 +301,7 @@ fn main() {
         Note left of John: yeah
     "#;
 
-
-    let doc = core::sequence_diagram::render(src).unwrap();
+    let doc = core::render(src, &core::Options::default()).unwrap();
 
     svg::save("image.svg", &doc).unwrap();
 }
@@ -0,0 +1,22 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(
+    C4,
+    C4Layout,
+    ["C4Context", "C4Container", "C4Component", "C4Dynamic"],
+    |options| SvgConfig {
+        max_label_length: 30,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        corner_radius: 5.0,
+        rank_gutter: 60.0,
+        node_gutter: 40.0,
+        boundary_padding: 20.0,
+        element_width: 180.0,
+    }
+);
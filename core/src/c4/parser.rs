@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char};
+use combine::parser::choice::choice;
+use combine::{eof, many, many1, none_of, optional, satisfy, sep_by, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, blank1, comment_parser, keyword};
+
+pub type ElementId = usize;
+pub type BoundaryId = usize;
+pub type RelationId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum C4Kind {
+    Context,
+    Container,
+    Component,
+    Dynamic,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementKind {
+    Person,
+    System,
+    Container,
+    Component,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementShape {
+    Box,
+    /// `SystemDb`, `ContainerDb` and `ComponentDb`
+    Database,
+    /// `SystemQueue`, `ContainerQueue` and `ComponentQueue`
+    Queue,
+}
+
+/// Colours set by `UpdateElementStyle`, in place of the conventional ones.
+#[derive(PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Style {
+    pub font_color: Option<String>,
+    pub background_color: Option<String>,
+    pub border_color: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Element {
+    pub id: ElementId,
+    pub name: String,
+    pub kind: ElementKind,
+    pub shape: ElementShape,
+    /// `_Ext` elements, outside of the scope of the diagram.
+    pub external: bool,
+    pub label: String,
+    pub technology: Option<String>,
+    pub description: Option<String>,
+    pub boundary: Option<BoundaryId>,
+    pub style: Style,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Boundary {
+    pub id: BoundaryId,
+    pub name: String,
+    pub label: String,
+    /// Shown below the label, such as `Enterprise` or the type given to a
+    /// generic `Boundary`.
+    pub kind: Option<String>,
+    pub parent: Option<BoundaryId>,
+    pub style: Style,
+}
+
+/// Colours set by `UpdateRelStyle`.
+#[derive(PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RelationStyle {
+    pub text_color: Option<String>,
+    pub line_color: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relation {
+    pub id: RelationId,
+    pub from: ElementId,
+    pub to: ElementId,
+    pub label: String,
+    pub technology: Option<String>,
+    /// `BiRel`, with arrows at both ends.
+    pub bidirectional: bool,
+    /// The step of a dynamic diagram.
+    pub index: Option<usize>,
+    pub style: RelationStyle,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct C4 {
+    pub kind: C4Kind,
+    pub title: Option<String>,
+    pub elements: Vec<Element>,
+    pub boundaries: Vec<Boundary>,
+    pub relations: Vec<Relation>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Argument {
+    Positional(String),
+    /// `$key="value"`
+    Named(String, String),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header(C4Kind),
+    Title(String),
+    /// `Name(arguments)`, followed by `{` for boundaries.
+    Call {
+        name: String,
+        arguments: Vec<Argument>,
+        opens: bool,
+    },
+    Close,
+}
+
+pub fn parse(src: String) -> Result<C4, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_c4(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// The kind, shape and scope of the elements such as `ContainerDb_Ext`.
+fn element_type(name: &str) -> Option<(ElementKind, ElementShape, bool)> {
+    let (name, external) = match name.strip_suffix("_Ext") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let (name, shape) = if let Some(name) = name.strip_suffix("Db") {
+        (name, ElementShape::Database)
+    } else if let Some(name) = name.strip_suffix("Queue") {
+        (name, ElementShape::Queue)
+    } else {
+        (name, ElementShape::Box)
+    };
+    let kind = match name {
+        "Person" if shape == ElementShape::Box => ElementKind::Person,
+        "System" => ElementKind::System,
+        "Container" => ElementKind::Container,
+        "Component" => ElementKind::Component,
+        _ => return None,
+    };
+    Some((kind, shape, external))
+}
+
+fn boundary_kind(name: &str) -> Option<Option<&'static str>> {
+    match name {
+        "Boundary" => Some(None),
+        "Enterprise_Boundary" => Some(Some("Enterprise")),
+        "System_Boundary" => Some(Some("System")),
+        "Container_Boundary" => Some(Some("Container")),
+        _ => None,
+    }
+}
+
+/// Positional arguments by index, named ones by key, empty ones left out.
+struct Arguments {
+    positional: Vec<String>,
+    named: HashMap<String, String>,
+}
+
+impl Arguments {
+    fn new(arguments: Vec<Argument>) -> Arguments {
+        let mut positional = vec![];
+        let mut named = HashMap::new();
+        for argument in arguments {
+            match argument {
+                Argument::Positional(value) => positional.push(value),
+                Argument::Named(key, value) => {
+                    named.insert(key, value);
+                }
+            }
+        }
+        Arguments { positional, named }
+    }
+
+    fn get(&self, index: usize) -> Option<String> {
+        self.positional
+            .get(index)
+            .filter(|value| !value.is_empty())
+            .cloned()
+    }
+
+    fn required(&self, index: usize) -> Result<String, StringStreamError> {
+        self.get(index).ok_or(StringStreamError::UnexpectedParse)
+    }
+
+    fn named(&self, key: &str) -> Option<String> {
+        self.named
+            .get(key)
+            .filter(|value| !value.is_empty())
+            .cloned()
+    }
+}
+
+fn build_c4(statements: Vec<Statement>) -> Result<C4, StringStreamError> {
+    let mut c4 = C4 {
+        kind: C4Kind::Context,
+        title: None,
+        elements: vec![],
+        boundaries: vec![],
+        relations: vec![],
+    };
+    let mut elements: HashMap<String, ElementId> = HashMap::new();
+    let mut boundaries: HashMap<String, BoundaryId> = HashMap::new();
+    let mut stack: Vec<BoundaryId> = vec![];
+    for statement in statements {
+        let (name, arguments, opens) = match statement {
+            Statement::Empty => continue,
+            Statement::Header(kind) => {
+                c4.kind = kind;
+                continue;
+            }
+            Statement::Title(title) => {
+                c4.title = Some(title);
+                continue;
+            }
+            Statement::Close => {
+                stack.pop().ok_or(StringStreamError::UnexpectedParse)?;
+                continue;
+            }
+            Statement::Call {
+                name,
+                arguments,
+                opens,
+            } => (name, Arguments::new(arguments), opens),
+        };
+
+        if let Some(kind) = boundary_kind(&name) {
+            let id = c4.boundaries.len();
+            let boundary_name = arguments.required(0)?;
+            if boundaries.insert(boundary_name.clone(), id).is_some() {
+                return Err(StringStreamError::UnexpectedParse);
+            }
+            c4.boundaries.push(Boundary {
+                id,
+                label: arguments.get(1).unwrap_or(boundary_name.clone()),
+                name: boundary_name,
+                kind: kind
+                    .map(str::to_string)
+                    .or_else(|| arguments.get(2))
+                    .or_else(|| arguments.named("type")),
+                parent: stack.last().copied(),
+                style: Style::default(),
+            });
+            if opens {
+                stack.push(id);
+            }
+            continue;
+        }
+        // Only boundaries hold other elements.
+        if opens {
+            return Err(StringStreamError::UnexpectedParse);
+        }
+
+        if let Some((kind, shape, external)) = element_type(&name) {
+            let id = c4.elements.len();
+            let element_name = arguments.required(0)?;
+            if elements.insert(element_name.clone(), id).is_some() {
+                return Err(StringStreamError::UnexpectedParse);
+            }
+            // Only containers and components have a technology, before the
+            // description.
+            let (technology, description) = match kind {
+                ElementKind::Person | ElementKind::System => (None, arguments.get(2)),
+                ElementKind::Container | ElementKind::Component => {
+                    (arguments.get(2), arguments.get(3))
+                }
+            };
+            c4.elements.push(Element {
+                id,
+                label: arguments.get(1).unwrap_or(element_name.clone()),
+                name: element_name,
+                kind,
+                shape,
+                external,
+                technology: arguments.named("techn").or(technology),
+                description: arguments.named("descr").or(description),
+                boundary: stack.last().copied(),
+                style: Style::default(),
+            });
+            continue;
+        }
+
+        let element = |index: usize| {
+            arguments
+                .get(index)
+                .and_then(|name| elements.get(&name).copied())
+                .ok_or(StringStreamError::UnexpectedParse)
+        };
+        match name.as_str() {
+            "Rel" | "BiRel" | "Rel_Back" | "Rel_U" | "Rel_Up" | "Rel_D" | "Rel_Down" | "Rel_L"
+            | "Rel_Left" | "Rel_R" | "Rel_Right" | "RelIndex" => {
+                // `RelIndex` starts with the step of a dynamic diagram.
+                let (index, first) = match name.as_str() {
+                    "RelIndex" => (
+                        Some(
+                            arguments
+                                .required(0)?
+                                .parse::<usize>()
+                                .map_err(|_| StringStreamError::UnexpectedParse)?,
+                        ),
+                        1,
+                    ),
+                    _ => (None, 0),
+                };
+                let (from, to) = match name.as_str() {
+                    "Rel_Back" => (element(first + 1)?, element(first)?),
+                    _ => (element(first)?, element(first + 1)?),
+                };
+                let id = c4.relations.len();
+                c4.relations.push(Relation {
+                    id,
+                    from,
+                    to,
+                    label: arguments.get(first + 2).unwrap_or_default(),
+                    technology: arguments.named("techn").or(arguments.get(first + 3)),
+                    bidirectional: name == "BiRel",
+                    index: match c4.kind {
+                        C4Kind::Dynamic => Some(index.unwrap_or(id + 1)),
+                        _ => index,
+                    },
+                    style: RelationStyle::default(),
+                });
+            }
+            "UpdateElementStyle" | "UpdateBoundaryStyle" => {
+                let target = arguments.required(0)?;
+                let style = match (elements.get(&target), boundaries.get(&target)) {
+                    (Some(&id), _) => &mut c4.elements[id].style,
+                    (None, Some(&id)) => &mut c4.boundaries[id].style,
+                    (None, None) => return Err(StringStreamError::UnexpectedParse),
+                };
+                if let Some(color) = arguments.named("fontColor") {
+                    style.font_color = Some(color);
+                }
+                if let Some(color) = arguments.named("bgColor") {
+                    style.background_color = Some(color);
+                }
+                if let Some(color) = arguments.named("borderColor") {
+                    style.border_color = Some(color);
+                }
+            }
+            "UpdateRelStyle" => {
+                let (from, to) = (element(0)?, element(1)?);
+                for relation in c4
+                    .relations
+                    .iter_mut()
+                    .filter(|r| r.from == from && r.to == to)
+                {
+                    if let Some(color) = arguments.named("textColor") {
+                        relation.style.text_color = Some(color);
+                    }
+                    if let Some(color) = arguments.named("lineColor") {
+                        relation.style.line_color = Some(color);
+                    }
+                }
+            }
+            // Shapes are placed by rank rather than in rows.
+            "UpdateLayoutConfig" => {}
+            _ => return Err(StringStreamError::UnexpectedParse),
+        }
+    }
+    if !stack.is_empty() {
+        return Err(StringStreamError::UnexpectedParse);
+    }
+    Ok(c4)
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        keyword("C4Context").map(|_| C4Kind::Context),
+        keyword("C4Container").map(|_| C4Kind::Container),
+        keyword("C4Component").map(|_| C4Kind::Component),
+        keyword("C4Dynamic").map(|_| C4Kind::Dynamic),
+    ))
+    .map(Statement::Header)
+}
+
+fn title_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword("title"), blank1(), many1(none_of("\n".chars())))
+        .map(|(_, _, title): (_, _, String)| Statement::Title(title.trim().to_string()))
+}
+
+fn quoted_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, text, _)| text)
+}
+
+/// A value without quotes, up to the next comma or parenthesis.
+fn bare_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(none_of(",()\"\n".chars())).map(|text: String| text.trim().to_string())
+}
+
+fn argument_parser<Input>() -> impl Parser<Input, Output = Argument>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        choice((
+            (
+                char('$'),
+                many1(alpha_num()),
+                blank(),
+                char('='),
+                blank(),
+                choice((quoted_parser(), bare_parser())),
+            )
+                .map(|(_, key, _, _, _, value)| Argument::Named(key, value)),
+            quoted_parser().map(Argument::Positional),
+            bare_parser().map(Argument::Positional),
+        )),
+        blank(),
+    )
+        .map(|(_, argument, _)| argument)
+}
+
+/// `Name(argument, ...)`, optionally followed by `{`.
+fn call_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        many1(satisfy(|c: char| c.is_alphanumeric() || c == '_')),
+        blank(),
+        char('('),
+        sep_by(argument_parser(), char(',')),
+        char(')'),
+        blank(),
+        optional(char('{')),
+    )
+        .map(
+            |(name, _, _, arguments, _, _, opens): (String, _, _, _, _, _, _)| Statement::Call {
+                name,
+                arguments,
+                opens: opens.is_some(),
+            },
+        )
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            title_parser(),
+            accessibility_parser().map(|_| Statement::Empty),
+            char('}').map(|_| Statement::Close),
+            call_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Call {
+                    name: "Container_Ext".to_string(),
+                    arguments: vec![
+                        Argument::Positional("web".to_string()),
+                        Argument::Positional("Web App, legacy".to_string()),
+                        Argument::Positional("Java".to_string()),
+                        Argument::Named("tags".to_string(), "v1".to_string()),
+                    ],
+                    opens: false,
+                },
+                ""
+            )),
+            call_parser().parse(r#"Container_Ext(web, "Web App, legacy", Java, $tags="v1")"#)
+        );
+        assert_eq!(
+            Ok((
+                Statement::Call {
+                    name: "System_Boundary".to_string(),
+                    arguments: vec![
+                        Argument::Positional("b".to_string()),
+                        Argument::Positional("Bank".to_string()),
+                    ],
+                    opens: true,
+                },
+                ""
+            )),
+            call_parser().parse(r#"System_Boundary(b, "Bank") {"#)
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"C4Container
+    title Internet Banking System
+    Person(customer, "Customer", "A customer of the bank")
+    System_Boundary(c1, "Internet Banking") {
+        Container(spa, "Single-Page App", "JavaScript, Angular", "Banking in the browser")
+        ContainerDb(db, "Database", "SQL Database")
+    }
+    SystemQueue_Ext(mail, "E-mail System")
+    Rel(customer, spa, "Uses", "HTTPS")
+    Rel_Back(db, spa, "Reads from")
+    BiRel(spa, mail, "Sends e-mails")
+    UpdateElementStyle(customer, $fontColor="red", $bgColor="grey")
+    UpdateLayoutConfig($c4ShapeInRow="3")
+"#;
+        let c4 = parse(input.to_string()).unwrap();
+        assert_eq!(C4Kind::Container, c4.kind);
+        assert_eq!(Some("Internet Banking System".to_string()), c4.title);
+        assert_eq!(4, c4.elements.len());
+        assert_eq!(
+            Some("JavaScript, Angular".to_string()),
+            c4.elements[1].technology
+        );
+        assert_eq!(Some(0), c4.elements[2].boundary);
+        assert_eq!(ElementShape::Database, c4.elements[2].shape);
+        assert_eq!(
+            (ElementKind::System, ElementShape::Queue, true),
+            (
+                c4.elements[3].kind,
+                c4.elements[3].shape,
+                c4.elements[3].external
+            )
+        );
+        assert_eq!(None, c4.elements[3].boundary);
+        assert_eq!((1, 2), (c4.relations[1].from, c4.relations[1].to));
+        assert!(c4.relations[2].bidirectional);
+        assert_eq!(Some("red".to_string()), c4.elements[0].style.font_color);
+        assert_eq!(
+            Some("grey".to_string()),
+            c4.elements[0].style.background_color
+        );
+    }
+
+    #[test]
+    fn test_parse_dynamic() {
+        let input = r#"C4Dynamic
+    Container(a, "A")
+    Container(b, "B")
+    Rel(a, b, "first")
+    RelIndex(5, b, a, "second")
+"#;
+        let c4 = parse(input.to_string()).unwrap();
+        assert_eq!(Some(1), c4.relations[0].index);
+        assert_eq!(Some(5), c4.relations[1].index);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("C4Context\nRel(a, b, \"Uses\")".to_string()).is_err());
+        assert!(parse("C4Context\nSystem_Boundary(b, \"B\") {".to_string()).is_err());
+        assert!(parse("C4Context\nPerson(a, \"A\") {\n}".to_string()).is_err());
+        assert!(parse("C4Context\nNode(a, \"A\")".to_string()).is_err());
+    }
+}
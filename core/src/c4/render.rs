@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+
+use svg::node::element::{Circle, Group, Path, Rectangle, SVG};
+use svg::Node as _;
+
+use cassowary::strength::{MEDIUM, REQUIRED};
+use cassowary::WeightedRelation::*;
+
+use crate::c4::*;
+use crate::flowchart::{
+    self, edge_markers, edge_path, Arrangement, Direction, Edge, EdgeEnd, Flowchart, Node,
+    NodeShape, NodeSize, Stroke, Subgraph,
+};
+use crate::layout::{BlockId, Layout};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+    pub boundary_padding: f64,
+    /// Minimum width of an element.
+    pub element_width: f64,
+}
+
+const BOUNDARY_COLOR: &str = "#444444";
+const RELATION_COLOR: &str = "#707070";
+/// Radius of the head of a person, and of the ends of databases and queues.
+const HEAD_RADIUS: f64 = 12.0;
+const END_RADIUS: f64 = 8.0;
+/// Stereotypes and technologies are written smaller than labels.
+const SMALL_TEXT: f64 = 0.85;
+
+/// Fill, border and text colours of the conventional C4 notation.
+fn colors(kind: ElementKind, external: bool) -> (&'static str, &'static str, &'static str) {
+    match (kind, external) {
+        (ElementKind::Person, false) => ("#08427B", "#073B6F", "white"),
+        (ElementKind::Person, true) => ("#686868", "#8A8A8A", "white"),
+        (ElementKind::System, false) => ("#1168BD", "#3C7FC0", "white"),
+        (ElementKind::System, true) => ("#999999", "#8A8A8A", "white"),
+        (ElementKind::Container, false) => ("#438DD5", "#3C7FC0", "white"),
+        (ElementKind::Container, true) => ("#B3B3B3", "#A6A6A6", "white"),
+        (ElementKind::Component, false) => ("#85BBF0", "#78A8D8", "black"),
+        (ElementKind::Component, true) => ("#CCCCCC", "#BFBFBF", "black"),
+    }
+}
+
+/// Applies the colours of an element to any shape.
+fn painted<T: svg::Node>(mut shape: T, fill: &str, border: &str) -> T {
+    shape.assign("fill", fill);
+    shape.assign("stroke", border);
+    shape
+}
+
+/// `«container_db»`, `«external_person»` and so on.
+fn stereotype(element: &Element) -> String {
+    let kind = match element.kind {
+        ElementKind::Person => "person",
+        ElementKind::System => "system",
+        ElementKind::Container => "container",
+        ElementKind::Component => "component",
+    };
+    let shape = match element.shape {
+        ElementShape::Box => "",
+        ElementShape::Database => "_db",
+        ElementShape::Queue => "_queue",
+    };
+    let external = if element.external { "external_" } else { "" };
+    format!("«{external}{kind}{shape}»")
+}
+
+/// Room the outline of a shape takes around the text: top, bottom and
+/// either side.
+fn insets(element: &Element) -> (f64, f64, f64) {
+    match (element.kind, element.shape) {
+        (ElementKind::Person, _) => (HEAD_RADIUS * 2.0, 0.0, 0.0),
+        (_, ElementShape::Database) => (END_RADIUS * 2.0, END_RADIUS, 0.0),
+        (_, ElementShape::Queue) => (0.0, 0.0, END_RADIUS * 2.0),
+        (_, ElementShape::Box) => (0.0, 0.0, 0.0),
+    }
+}
+
+struct ElementText {
+    block: BlockId,
+    lines: Vec<String>,
+    font_size: f64,
+    bold: bool,
+}
+
+struct ElementBox {
+    node: ElementId,
+    texts: Vec<ElementText>,
+}
+
+fn add_element_box(
+    layout: &mut Layout,
+    element: &Element,
+    block: BlockId,
+    config: &SvgConfig,
+) -> ElementBox {
+    let scale = config.font_scale_factor;
+    let small = config.font_size * SMALL_TEXT;
+    let mut texts = vec![];
+    let mut add = |layout: &mut Layout, content: &str, font_size: f64, bold: bool| {
+        let (block, lines) = layout.add_text_block(
+            content,
+            config.max_label_length,
+            config.padding,
+            font_size * scale,
+        );
+        texts.push(ElementText {
+            block,
+            lines,
+            font_size,
+            bold,
+        });
+    };
+    add(layout, &stereotype(element), small, false);
+    add(layout, &element.label, config.font_size, true);
+    if let Some(technology) = &element.technology {
+        add(layout, &format!("[{technology}]"), small, false);
+    }
+    if let Some(description) = &element.description {
+        add(layout, description, config.font_size, false);
+    }
+
+    // The texts are stacked in the middle of the shape.
+    let (top, bottom, side) = insets(element);
+    for text in &texts {
+        let b = text.block;
+        layout.add_constraint(layout.b(b).left() | EQ(REQUIRED) | (layout.b(block).left() + side));
+        layout.add_constraint(
+            layout.b(b).width | EQ(REQUIRED) | (layout.b(block).width - side * 2.0),
+        );
+    }
+    let blocks: Vec<BlockId> = texts.iter().map(|text| text.block).collect();
+    for (previous, next) in blocks.iter().zip(blocks.iter().skip(1)) {
+        layout.add_constraint(layout.b(*next).top() | EQ(REQUIRED) | layout.b(*previous).bottom());
+    }
+    if let (Some(&first), Some(&last)) = (blocks.first(), blocks.last()) {
+        layout.add_constraint(
+            layout.b(first).top() | EQ(REQUIRED) | (layout.b(block).top() + top + config.padding),
+        );
+        layout.add_constraint(
+            layout.b(block).bottom()
+                | EQ(REQUIRED)
+                | (layout.b(last).bottom() + bottom + config.padding),
+        );
+    }
+    layout.add_constraint(layout.b(block).width | GE(REQUIRED) | config.element_width);
+    layout.add_constraint(layout.b(block).width | EQ(MEDIUM) | 0.0);
+
+    ElementBox {
+        node: element.id,
+        texts,
+    }
+}
+
+impl ElementBox {
+    fn to_svg(&self, element: &Element, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.node(self.node);
+        let (w, h) = (block.width, block.height);
+        let (fill, border, text_color) = colors(element.kind, element.external);
+        let fill = element.style.background_color.as_deref().unwrap_or(fill);
+        let border = element.style.border_color.as_deref().unwrap_or(border);
+        let text_color = element.style.font_color.as_deref().unwrap_or(text_color);
+
+        let mut group = translated(&block);
+        match (element.kind, element.shape) {
+            (ElementKind::Person, _) => {
+                let r = HEAD_RADIUS;
+                let body = Rectangle::new()
+                    .set("y", r * 1.6)
+                    .set("width", w)
+                    .set("height", h - r * 1.6)
+                    .set("rx", r);
+                let head = Circle::new().set("cx", w * 0.5).set("cy", r).set("r", r);
+                group = group
+                    .add(painted(body, fill, border))
+                    .add(painted(head, fill, border));
+            }
+            (_, ElementShape::Database) => {
+                let (rx, ry) = (w * 0.5, END_RADIUS);
+                let body = format!(
+                    "M 0 {ry} A {rx} {ry} 0 0 1 {w} {ry} L {w} {bottom} A {rx} {ry} 0 0 1 0 {bottom} Z",
+                    bottom = h - ry
+                );
+                let rim = format!("M 0 {ry} A {rx} {ry} 0 0 0 {w} {ry}");
+                group = group
+                    .add(painted(Path::new().set("d", body), fill, border))
+                    .add(painted(Path::new().set("d", rim), "none", border));
+            }
+            (_, ElementShape::Queue) => {
+                let (rx, ry) = (END_RADIUS, h * 0.5);
+                let right = w - rx;
+                let body = format!(
+                    "M {rx} 0 L {right} 0 A {rx} {ry} 0 0 1 {right} {h} L {rx} {h} A {rx} {ry} 0 0 1 {rx} 0 Z"
+                );
+                let rim = format!("M {right} 0 A {rx} {ry} 0 0 0 {right} {h}");
+                group = group
+                    .add(painted(Path::new().set("d", body), fill, border))
+                    .add(painted(Path::new().set("d", rim), "none", border));
+            }
+            (_, ElementShape::Box) => {
+                group = group.add(painted(
+                    outline(&block, fill, config.corner_radius),
+                    fill,
+                    border,
+                ));
+            }
+        }
+        let mut group = Group::new().add(group);
+        for text in &self.texts {
+            let b = arrangement.node_part(self.node, text.block);
+            let mut texts = translated(&b);
+            for (i, line) in text.lines.iter().enumerate() {
+                let y = config.padding + ((i + 1) as f64) * b.line_height;
+                let mut t = text_line(line, y, &b, text.font_size, config.padding)
+                    .set("x", b.width * 0.5)
+                    .set("text-anchor", "middle")
+                    .set("fill", text_color);
+                if text.bold {
+                    t = t.set("font-weight", "bold");
+                }
+                texts = texts.add(t);
+            }
+            group = group.add(texts);
+        }
+        group
+    }
+}
+
+/// The text of a relation: its step in dynamic diagrams, the label and the
+/// technology on its own line.
+fn relation_label(relation: &Relation) -> Option<String> {
+    let mut label = match relation.index {
+        Some(index) => format!("{index}: {}", relation.label),
+        None => relation.label.clone(),
+    };
+    if let Some(technology) = &relation.technology {
+        label = format!("{label}\n[{technology}]");
+    }
+    Some(label).filter(|label| !label.trim().is_empty())
+}
+
+/// A C4 diagram with its elements, boundaries and relations placed.
+pub struct C4Layout {
+    c4: C4,
+    config: SvgConfig,
+    /// The diagram as a flowchart.
+    flowchart: Flowchart,
+    arrangement: Arrangement,
+    element_boxes: Vec<ElementBox>,
+}
+
+pub fn layout(c4: &C4, config: &SvgConfig) -> C4Layout {
+    let flowchart = Flowchart {
+        direction: Direction::TopToBottom,
+        nodes: c4
+            .elements
+            .iter()
+            .map(|element| Node {
+                id: element.id,
+                name: element.name.clone(),
+                label: String::new(),
+                shape: NodeShape::Rectangle,
+            })
+            .collect(),
+        edges: c4
+            .relations
+            .iter()
+            .map(|relation| Edge {
+                id: relation.id,
+                from: relation.from,
+                to: relation.to,
+                label: relation_label(relation),
+                stroke: Stroke::Solid,
+                start: if relation.bidirectional {
+                    EdgeEnd::Arrow
+                } else {
+                    EdgeEnd::None
+                },
+                end: EdgeEnd::Arrow,
+                length: 1,
+            })
+            .collect(),
+        subgraphs: c4
+            .boundaries
+            .iter()
+            .map(|boundary| Subgraph {
+                id: boundary.id,
+                name: boundary.name.clone(),
+                title: match &boundary.kind {
+                    Some(kind) => format!("{}\n[{kind}]", boundary.label),
+                    None => boundary.label.clone(),
+                },
+                direction: None,
+                parent: boundary.parent,
+                nodes: c4
+                    .elements
+                    .iter()
+                    .filter(|element| element.boundary == Some(boundary.id))
+                    .map(|element| element.id)
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    let flowchart_config = flowchart::SvgConfig {
+        max_label_length: config.max_label_length,
+        font_size: config.font_size,
+        font_scale_factor: config.font_scale_factor,
+        padding: config.padding,
+        corner_radius: config.corner_radius,
+        rank_gutter: config.rank_gutter,
+        node_gutter: config.node_gutter,
+        subgraph_padding: config.boundary_padding,
+    };
+    let sizes: HashMap<_, _> = c4
+        .elements
+        .iter()
+        .map(|element| (element.id, NodeSize::Free))
+        .collect();
+    let mut arrangement = Arrangement::new(&flowchart, &flowchart_config, &sizes);
+    let element_boxes: Vec<ElementBox> = c4
+        .elements
+        .iter()
+        .map(|element| {
+            let block = arrangement.node_block(element.id);
+            add_element_box(arrangement.layout(), element, block, config)
+        })
+        .collect();
+    arrangement.solve();
+    C4Layout {
+        c4: c4.clone(),
+        config: config.clone(),
+        flowchart,
+        arrangement,
+        element_boxes,
+    }
+}
+
+impl C4Layout {
+    pub fn to_svg(&self) -> SVG {
+        let C4Layout {
+            c4,
+            config,
+            flowchart,
+            arrangement,
+            element_boxes,
+        } = self;
+
+        // The title goes above the diagram, which is moved down to make room.
+        let mut title_layout = Layout::new();
+        let title = c4.title.as_ref().map(|title| {
+            let (block, lines) = title_layout.add_text_block(
+                title,
+                config.max_label_length * 3,
+                config.padding,
+                config.font_size * config.font_scale_factor,
+            );
+            title_layout.solve();
+            (title_layout.b(block).solved(), lines)
+        });
+        let title_height = title.as_ref().map_or(0.0, |(b, _)| b.height);
+        let title_width = title.as_ref().map_or(0.0, |(b, _)| b.width);
+
+        let mut doc = document(
+            arrangement.width().max(title_width),
+            arrangement.height() + title_height,
+        )
+        .add(edge_markers());
+        if let Some((b, lines)) = &title {
+            doc = doc.add(
+                text_lines(lines, b, config.font_size, config.padding)
+                    .fold(translated(b), |group, t| {
+                        group.add(t.set("font-weight", "bold"))
+                    }),
+            );
+        }
+        let mut diagram = Group::new().set("transform", format!("translate(0, {title_height})"));
+
+        for boundary in &c4.boundaries {
+            let (block, title, lines) = arrangement.subgraph(boundary.id);
+            let border = boundary
+                .style
+                .border_color
+                .as_deref()
+                .unwrap_or(BOUNDARY_COLOR);
+            let fill = boundary.style.background_color.as_deref().unwrap_or("none");
+            let text_color = boundary
+                .style
+                .font_color
+                .as_deref()
+                .unwrap_or(BOUNDARY_COLOR);
+            diagram = diagram
+                .add(
+                    translated(&block).add(
+                        outline(&block, fill, config.corner_radius)
+                            .set("stroke", border)
+                            .set("stroke-dasharray", "7 7"),
+                    ),
+                )
+                .add(
+                    text_lines(lines, &title, config.font_size, config.padding)
+                        .fold(translated(&title), |group, t| {
+                            group.add(t.set("fill", text_color).set("font-weight", "bold"))
+                        }),
+                );
+        }
+
+        for edge_id in arrangement.edges() {
+            let relation = &c4.relations[edge_id];
+            let mut path = edge_path(&flowchart.edges[edge_id], &arrangement.edge_points(edge_id));
+            path.assign(
+                "stroke",
+                relation
+                    .style
+                    .line_color
+                    .as_deref()
+                    .unwrap_or(RELATION_COLOR),
+            );
+            diagram = diagram.add(path);
+        }
+        for edge_id in arrangement.loops() {
+            let relation = &c4.relations[edge_id];
+            let mut path = edge_path(&flowchart.edges[edge_id], &[]);
+            path.assign("d", arrangement.loop_path(relation.from));
+            path.assign(
+                "stroke",
+                relation
+                    .style
+                    .line_color
+                    .as_deref()
+                    .unwrap_or(RELATION_COLOR),
+            );
+            diagram = diagram.add(path);
+        }
+
+        for element_box in element_boxes {
+            let element = &c4.elements[element_box.node];
+            diagram = diagram.add(element_box.to_svg(element, arrangement, config));
+        }
+        for relation in &c4.relations {
+            if let Some((block, lines)) = arrangement.edge_label(relation.id) {
+                let text_color = relation.style.text_color.as_deref().unwrap_or(TEXT_COLOR);
+                let background = Rectangle::new()
+                    .set("width", block.width)
+                    .set("height", block.height)
+                    .set("fill", LABEL_FILL);
+                diagram = diagram.add(
+                    text_lines(lines, &block, config.font_size, config.padding)
+                        .fold(translated(&block).add(background), |group, t| {
+                            group.add(t.set("fill", text_color))
+                        }),
+                );
+            }
+        }
+
+        doc.add(diagram)
+    }
+}
+
+pub fn to_svg(c4: &C4, config: &SvgConfig) -> SVG {
+    layout(c4, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c4::Definition;
+    use crate::diagram::Options;
+    use crate::layout::geometry::{inside, overlap};
+    use crate::layout::Block;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let input = r#"C4Container
+    Person(customer, "Customer", "A customer of the bank")
+    Enterprise_Boundary(bank, "Bank") {
+        System_Boundary(c1, "Internet Banking") {
+            Container(spa, "Single-Page App", "JavaScript", "Banking in the browser")
+            ContainerDb(db, "Database", "SQL Database")
+        }
+        System(core, "Core Banking")
+    }
+    SystemQueue_Ext(mail, "E-mail System")
+    Rel(customer, spa, "Uses")
+    Rel(spa, db, "Reads from")
+    Rel(spa, mail, "Sends e-mails")
+    Rel(db, core, "Syncs")
+"#;
+        let c4 = parse(input.to_string()).unwrap();
+        let config = config();
+        let C4Layout {
+            arrangement,
+            element_boxes,
+            ..
+        } = layout(&c4, &config);
+
+        for boundary in &c4.boundaries {
+            let (block, title, _) = arrangement.subgraph(boundary.id);
+            assert!(inside(&title, &block));
+            if let Some(parent) = boundary.parent {
+                assert!(inside(&block, &arrangement.subgraph(parent).0));
+            }
+            for element in &c4.elements {
+                let node = arrangement.node(element.id);
+                let mut boundary_of = element.boundary;
+                let mut within = false;
+                while let Some(id) = boundary_of {
+                    within |= id == boundary.id;
+                    boundary_of = c4.boundaries[id].parent;
+                }
+                if within {
+                    assert!(inside(&node, &block), "{}", element.name);
+                    assert!(!overlap(&node, &title), "{}", element.name);
+                } else {
+                    assert!(!overlap(&node, &block), "{}", element.name);
+                }
+            }
+        }
+
+        // the texts stacked inside the outline of their shape
+        for element_box in &element_boxes {
+            let element = &c4.elements[element_box.node];
+            let block = arrangement.node(element.id);
+            assert!(block.width >= config.element_width);
+            let (top, bottom, side) = insets(element);
+            let texts: Vec<Block> = element_box
+                .texts
+                .iter()
+                .map(|text| arrangement.node_part(element.id, text.block))
+                .collect();
+            let first = texts[0].position.y;
+            assert!((first - block.position.y - top - config.padding).abs() < 1e-6);
+            for (upper, lower) in texts.iter().zip(&texts[1..]) {
+                assert!((upper.position.y + upper.height - lower.position.y).abs() < 1e-6);
+            }
+            let last = texts.last().unwrap();
+            let end = last.position.y + last.height + bottom + config.padding;
+            assert!((end - block.position.y - block.height).abs() < 1e-6);
+            for text in &texts {
+                assert!((text.position.x - block.position.x - side).abs() < 1e-6);
+                assert!((text.width - block.width + 2.0 * side).abs() < 1e-6);
+            }
+        }
+        assert_eq!(4, element_boxes[1].texts.len());
+    }
+}
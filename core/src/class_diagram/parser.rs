@@ -0,0 +1,721 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::parser::choice::choice;
+use combine::parser::repeat::take_until;
+use combine::{
+    attempt, eof, many, many1, none_of, one_of, optional, skip_many1, ParseError, Parser, Stream,
+};
+
+use crate::parsing::{blank, blank1, comment_parser, keyword, quoted, skipped_parser};
+
+pub use crate::flowchart::Direction;
+
+pub type ClassId = usize;
+pub type RelationId = usize;
+pub type NamespaceId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+    Package,
+}
+
+impl Visibility {
+    pub fn symbol(&self) -> char {
+        match self {
+            Visibility::Public => '+',
+            Visibility::Private => '-',
+            Visibility::Protected => '#',
+            Visibility::Package => '~',
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Classifier {
+    Static,
+    Abstract,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Member {
+    pub visibility: Option<Visibility>,
+    /// The declaration without visibility and classifier, generics written `<T>`.
+    pub text: String,
+    pub classifier: Option<Classifier>,
+}
+
+impl Member {
+    pub fn is_method(&self) -> bool {
+        self.text.contains('(')
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Class {
+    pub id: ClassId,
+    pub name: String,
+    /// Shown instead of the name, from `class Name["Label"]`.
+    pub label: Option<String>,
+    /// Type parameters, from `class Name~T~`.
+    pub generic: Option<String>,
+    /// Annotations such as `interface`, without the angle brackets.
+    pub annotations: Vec<String>,
+    pub fields: Vec<Member>,
+    pub methods: Vec<Member>,
+    pub namespace: Option<NamespaceId>,
+}
+
+impl Class {
+    /// The title of the class box, with its type parameters.
+    pub fn title(&self) -> String {
+        let name = self.label.as_deref().unwrap_or(&self.name);
+        match &self.generic {
+            Some(generic) => format!("{name}<{generic}>"),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// The decoration at one end of a relation.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelationEnd {
+    None,
+    /// Hollow triangle, inheritance with a solid and realization with a dashed line.
+    Triangle,
+    /// Filled diamond, composition.
+    FilledDiamond,
+    /// Hollow diamond, aggregation.
+    Diamond,
+    /// Open arrow, association with a solid and dependency with a dashed line.
+    Arrow,
+    Lollipop,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineStyle {
+    Solid,
+    Dashed,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relation {
+    pub id: RelationId,
+    pub from: ClassId,
+    pub to: ClassId,
+    pub from_end: RelationEnd,
+    pub to_end: RelationEnd,
+    pub line: LineStyle,
+    pub from_cardinality: Option<String>,
+    pub to_cardinality: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Namespace {
+    pub id: NamespaceId,
+    pub name: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassDiagram {
+    pub direction: Direction,
+    pub classes: Vec<Class>,
+    pub relations: Vec<Relation>,
+    pub namespaces: Vec<Namespace>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arrow {
+    pub from_end: RelationEnd,
+    pub line: LineStyle,
+    pub to_end: RelationEnd,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header,
+    Direction(Direction),
+    /// `class Name~T~["Label"] { body }`, the body is kept as raw lines.
+    Class {
+        name: String,
+        generic: Option<String>,
+        label: Option<String>,
+        body: Vec<String>,
+    },
+    Member(String, String),
+    Annotation(String, String),
+    Relation {
+        from: String,
+        from_cardinality: Option<String>,
+        arrow: Arrow,
+        to_cardinality: Option<String>,
+        to: String,
+        label: Option<String>,
+    },
+    Namespace(String),
+    NamespaceEnd,
+}
+
+pub fn parse(src: String) -> Result<ClassDiagram, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_class_diagram(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// Turns mermaid's `~` delimited generics into angle brackets, `List~Map~K, V~~`
+/// becomes `List<Map<K, V>>`.
+pub fn generics(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| match c {
+            '~' if word(i.checked_sub(1).and_then(|p| chars.get(p))) && word(chars.get(i + 1)) => {
+                '<'
+            }
+            '~' => '>',
+            c => *c,
+        })
+        .collect()
+}
+
+/// A line of a class body or of a `Class : member` statement.
+pub fn parse_member(line: &str) -> Member {
+    let line = line.trim();
+    let visibility = match line.chars().next() {
+        Some('+') => Some(Visibility::Public),
+        Some('-') => Some(Visibility::Private),
+        Some('#') => Some(Visibility::Protected),
+        Some('~') => Some(Visibility::Package),
+        _ => None,
+    };
+    let mut text = match visibility {
+        Some(_) => line[1..].trim_start().to_string(),
+        None => line.to_string(),
+    };
+    // methods carry the classifier right after their parameters, fields at the end
+    let classifier_at = match text.rfind(')') {
+        Some(close) => Some(close + 1),
+        None => text.char_indices().last().map(|(i, _)| i),
+    };
+    let classifier = classifier_at.and_then(|at| match text[at..].chars().next() {
+        Some('$') => Some(Classifier::Static),
+        Some('*') => Some(Classifier::Abstract),
+        _ => None,
+    });
+    if let (Some(_), Some(at)) = (classifier, classifier_at) {
+        text.remove(at);
+    }
+    Member {
+        visibility,
+        text: generics(text.trim()),
+        classifier,
+    }
+}
+
+fn build_class_diagram(statements: Vec<Statement>) -> Result<ClassDiagram, StringStreamError> {
+    let mut diagram = ClassDiagram {
+        direction: Direction::TopToBottom,
+        classes: vec![],
+        relations: vec![],
+        namespaces: vec![],
+    };
+    let mut ids: HashMap<String, ClassId> = HashMap::new();
+    let mut namespace: Option<NamespaceId> = None;
+
+    let mut class = |diagram: &mut ClassDiagram, name: &str, namespace: Option<NamespaceId>| {
+        *ids.entry(name.to_string()).or_insert_with(|| {
+            diagram.classes.push(Class {
+                id: diagram.classes.len(),
+                name: name.to_string(),
+                label: None,
+                generic: None,
+                annotations: vec![],
+                fields: vec![],
+                methods: vec![],
+                namespace,
+            });
+            diagram.classes.len() - 1
+        })
+    };
+    let add_member = |class: &mut Class, member: Member| {
+        if member.is_method() {
+            class.methods.push(member);
+        } else {
+            class.fields.push(member);
+        }
+    };
+
+    for statement in statements {
+        match statement {
+            Statement::Empty | Statement::Header => {}
+            Statement::Direction(direction) => diagram.direction = direction,
+            Statement::Class {
+                name,
+                generic,
+                label,
+                body,
+            } => {
+                let id = class(&mut diagram, &name, namespace);
+                let class = &mut diagram.classes[id];
+                if generic.is_some() {
+                    class.generic = generic.map(|g| generics(&g));
+                }
+                if label.is_some() {
+                    class.label = label;
+                }
+                for line in body.iter().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+                    match line.strip_prefix("<<").and_then(|l| l.strip_suffix(">>")) {
+                        Some(annotation) => class.annotations.push(annotation.trim().to_string()),
+                        None => add_member(class, parse_member(line)),
+                    }
+                }
+            }
+            Statement::Member(name, member) => {
+                let id = class(&mut diagram, &name, namespace);
+                add_member(&mut diagram.classes[id], parse_member(&member));
+            }
+            Statement::Annotation(annotation, name) => {
+                let id = class(&mut diagram, &name, namespace);
+                diagram.classes[id].annotations.push(annotation);
+            }
+            Statement::Relation {
+                from,
+                from_cardinality,
+                arrow,
+                to_cardinality,
+                to,
+                label,
+            } => {
+                let from = class(&mut diagram, &from, namespace);
+                let to = class(&mut diagram, &to, namespace);
+                diagram.relations.push(Relation {
+                    id: diagram.relations.len(),
+                    from,
+                    to,
+                    from_end: arrow.from_end,
+                    to_end: arrow.to_end,
+                    line: arrow.line,
+                    from_cardinality,
+                    to_cardinality,
+                    label,
+                });
+            }
+            Statement::Namespace(name) => {
+                if namespace.is_some() {
+                    return Err(StringStreamError::UnexpectedParse);
+                }
+                namespace = Some(diagram.namespaces.len());
+                diagram.namespaces.push(Namespace {
+                    id: diagram.namespaces.len(),
+                    name,
+                });
+            }
+            Statement::NamespaceEnd => {
+                namespace.take().ok_or(StringStreamError::UnexpectedParse)?;
+            }
+        }
+    }
+
+    if namespace.is_none() {
+        Ok(diagram)
+    } else {
+        Err(StringStreamError::UnexpectedParse)
+    }
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((keyword("classDiagram-v2"), keyword("classDiagram"))).map(|_| Statement::Header)
+}
+
+fn direction_statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let direction = choice((
+        attempt(string("TB")).map(|_| Direction::TopToBottom),
+        attempt(string("TD")).map(|_| Direction::TopToBottom),
+        string("BT").map(|_| Direction::BottomToTop),
+        string("LR").map(|_| Direction::LeftToRight),
+        string("RL").map(|_| Direction::RightToLeft),
+    ));
+    (keyword("direction"), blank1(), direction).map(|(_, _, d)| Statement::Direction(d))
+}
+
+fn class_name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let style = attempt((
+        string(":::"),
+        skip_many1(choice((alpha_num(), one_of("_-".chars())))),
+    ));
+    let plain = many1(choice((alpha_num(), char('_'))));
+    let quoted = (char('`'), many1(none_of("`\n".chars())), char('`')).map(|(_, name, _)| name);
+    (choice((quoted, plain)), optional(style)).map(|(name, _): (String, _)| name)
+}
+
+fn class_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let generic =
+        (char('~'), many1(none_of("~\n{".chars())), char('~')).map(|(_, g, _): (_, String, _)| g);
+    let name = many1(choice((alpha_num(), char('_'))));
+    let label = (char('['), quoted(), char(']')).map(|(_, label, _)| label);
+    let style = attempt((
+        string(":::"),
+        skip_many1(choice((alpha_num(), one_of("_-".chars())))),
+    ));
+    let body = attempt((blank(), char('{'), take_until(char('}')), char('}')))
+        .map(|(_, _, body, _): (_, _, String, _)| body.lines().map(|l| l.to_string()).collect());
+    (
+        keyword("class"),
+        blank1(),
+        name,
+        optional(attempt(generic)),
+        optional(label),
+        optional(style),
+        optional(body),
+    )
+        .map(|(_, _, name, generic, label, _, body)| Statement::Class {
+            name,
+            generic,
+            label,
+            body: body.unwrap_or_default(),
+        })
+}
+
+fn member_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((class_name_parser(), blank(), char(':'), blank()))
+        .and(many1(none_of("\n".chars())))
+        .map(|((name, _, _, _), member)| Statement::Member(name, member))
+}
+
+fn annotation_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        string("<<"),
+        take_until(string(">>")),
+        string(">>"),
+        blank(),
+        class_name_parser(),
+    )
+        .map(|(_, annotation, _, _, name): (_, String, _, _, _)| {
+            Statement::Annotation(annotation.trim().to_string(), name)
+        })
+}
+
+fn relation_end<Input>(right: bool) -> impl Parser<Input, Output = RelationEnd>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let (triangle, arrow) = if right { ("|>", ">") } else { ("<|", "<") };
+    choice((
+        attempt(string(triangle)).map(|_| RelationEnd::Triangle),
+        string(arrow).map(|_| RelationEnd::Arrow),
+        char('*').map(|_| RelationEnd::FilledDiamond),
+        char('o').map(|_| RelationEnd::Diamond),
+        string("()").map(|_| RelationEnd::Lollipop),
+    ))
+}
+
+fn arrow_parser<Input>() -> impl Parser<Input, Output = Arrow>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let line = choice((
+        attempt(string("--")).map(|_| LineStyle::Solid),
+        string("..").map(|_| LineStyle::Dashed),
+    ));
+    (
+        optional(attempt(relation_end(false))),
+        line,
+        optional(relation_end(true)),
+    )
+        .map(|(from_end, line, to_end)| Arrow {
+            from_end: from_end.unwrap_or(RelationEnd::None),
+            line,
+            to_end: to_end.unwrap_or(RelationEnd::None),
+        })
+}
+
+fn relation_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let label = optional(attempt((blank(), char(':'), many(none_of("\n".chars())))))
+        .map(|label| label.map(|(_, _, text): (_, _, String)| text.trim().to_string()));
+    attempt((
+        class_name_parser(),
+        blank(),
+        optional(attempt((quoted(), blank()))),
+        arrow_parser(),
+        blank(),
+        optional(attempt((quoted(), blank()))),
+        class_name_parser(),
+    ))
+    .and(label)
+    .map(
+        |((from, _, from_cardinality, arrow, _, to_cardinality, to), label)| Statement::Relation {
+            from,
+            from_cardinality: from_cardinality.map(|(c, _)| c),
+            arrow,
+            to_cardinality: to_cardinality.map(|(c, _)| c),
+            to,
+            label,
+        },
+    )
+}
+
+fn namespace_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("namespace"),
+        blank1(),
+        class_name_parser(),
+        blank(),
+        char('{'),
+    )
+        .map(|(_, _, name, _, _)| Statement::Namespace(name))
+}
+
+/// Notes, styling and interaction statements don't affect the layout and are skipped.
+fn ignored_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&[
+        "note", "classDef", "cssClass", "style", "click", "link", "callback", "accTitle",
+        "accDescr",
+    ])
+    .map(|_| Statement::Empty)
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            direction_statement_parser(),
+            class_parser(),
+            namespace_parser(),
+            ignored_parser(),
+            char('}').map(|_| Statement::NamespaceEnd),
+            annotation_parser(),
+            relation_parser(),
+            member_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generics() {
+        assert_eq!("List<int> ids", generics("List~int~ ids"));
+        assert_eq!("List<Map<K, V>>", generics("List~Map~K, V~~"));
+        assert_eq!("plain", generics("plain"));
+    }
+
+    #[test]
+    fn test_parse_member() {
+        assert_eq!(
+            Member {
+                visibility: Some(Visibility::Private),
+                text: "Vec<u8> buffer".to_string(),
+                classifier: None,
+            },
+            parse_member("-Vec~u8~ buffer")
+        );
+        assert_eq!(
+            Member {
+                visibility: Some(Visibility::Public),
+                text: "count() usize".to_string(),
+                classifier: Some(Classifier::Static),
+            },
+            parse_member("+count()$ usize")
+        );
+        assert_eq!(
+            Member {
+                visibility: None,
+                text: "draw()".to_string(),
+                classifier: Some(Classifier::Abstract),
+            },
+            parse_member("draw()*")
+        );
+        assert!(parse_member("#run(int a) bool").is_method());
+    }
+
+    #[test]
+    fn test_arrow_parser() {
+        let arrow = |from_end, line, to_end| Arrow {
+            from_end,
+            line,
+            to_end,
+        };
+        assert_eq!(
+            Ok((
+                arrow(RelationEnd::Triangle, LineStyle::Solid, RelationEnd::None),
+                ""
+            )),
+            arrow_parser().parse("<|--")
+        );
+        assert_eq!(
+            Ok((
+                arrow(RelationEnd::None, LineStyle::Dashed, RelationEnd::Triangle),
+                ""
+            )),
+            arrow_parser().parse("..|>")
+        );
+        assert_eq!(
+            Ok((
+                arrow(
+                    RelationEnd::FilledDiamond,
+                    LineStyle::Solid,
+                    RelationEnd::None
+                ),
+                ""
+            )),
+            arrow_parser().parse("*--")
+        );
+        assert_eq!(
+            Ok((
+                arrow(RelationEnd::Diamond, LineStyle::Solid, RelationEnd::Arrow),
+                ""
+            )),
+            arrow_parser().parse("o-->")
+        );
+        assert_eq!(
+            Ok((
+                arrow(RelationEnd::None, LineStyle::Dashed, RelationEnd::None),
+                ""
+            )),
+            arrow_parser().parse("..")
+        );
+    }
+
+    #[test]
+    fn test_relation_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Relation {
+                    from: "Customer".to_string(),
+                    from_cardinality: Some("1".to_string()),
+                    arrow: Arrow {
+                        from_end: RelationEnd::None,
+                        line: LineStyle::Solid,
+                        to_end: RelationEnd::Arrow,
+                    },
+                    to_cardinality: Some("*".to_string()),
+                    to: "Ticket".to_string(),
+                    label: Some("buys".to_string()),
+                },
+                "\n"
+            )),
+            relation_parser().parse("Customer \"1\" --> \"*\" Ticket : buys\n")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"classDiagram
+            direction LR
+            class Shape~T~ {
+                <<interface>>
+                +area() f64
+                -String name
+            }
+            Shape <|.. Square
+            Square : +f64 side
+            <<abstract>> Square
+            namespace geometry {
+                class Point
+            }
+            Square *-- "4" Point
+        "#;
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(Direction::LeftToRight, diagram.direction);
+        assert_eq!(3, diagram.classes.len());
+        let shape = &diagram.classes[0];
+        assert_eq!("Shape<T>", shape.title());
+        assert_eq!(vec!["interface".to_string()], shape.annotations);
+        assert_eq!(1, shape.fields.len());
+        assert_eq!(1, shape.methods.len());
+        let square = &diagram.classes[1];
+        assert_eq!(vec!["abstract".to_string()], square.annotations);
+        assert_eq!("f64 side", square.fields[0].text);
+        assert_eq!(Some(0), diagram.classes[2].namespace);
+        assert_eq!(2, diagram.relations.len());
+        assert_eq!(RelationEnd::Triangle, diagram.relations[0].from_end);
+        assert_eq!(LineStyle::Dashed, diagram.relations[0].line);
+        assert_eq!(Some("4".to_string()), diagram.relations[1].to_cardinality);
+    }
+
+    #[test]
+    fn test_parse_unbalanced_namespace() {
+        assert!(parse("classDiagram\nnamespace a {\nclass B\n".to_string()).is_err());
+        assert!(parse("classDiagram\n}\n".to_string()).is_err());
+    }
+}
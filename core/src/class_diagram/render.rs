@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+
+use svg::node::element::{Circle, Definitions, Group, Line, Marker, Path, Text, SVG};
+use svg::Node as _;
+
+use cassowary::strength::{MEDIUM, REQUIRED};
+use cassowary::WeightedRelation::*;
+use itertools::Itertools;
+
+use crate::class_diagram::*;
+use crate::flowchart::{
+    self, Arrangement, Edge, EdgeEnd, Flowchart, Node, NodeId, NodeShape, NodeSize, Stroke,
+    Subgraph,
+};
+use crate::layout::{BlockId, Layout, Orientation};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub max_member_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+    pub subgraph_padding: f64,
+}
+
+/// Distance of a cardinality from the end of its relation, along and across it.
+const CARDINALITY_OFFSET: (f64, f64) = (16.0, 10.0);
+
+struct MemberText {
+    classifier: Option<Classifier>,
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+/// A compartment of a class box, holding the fields or the methods.
+struct Compartment {
+    block: BlockId,
+    members: Vec<MemberText>,
+}
+
+struct ClassBox {
+    node: NodeId,
+    header: BlockId,
+    header_lines: Vec<String>,
+    fields: Compartment,
+    methods: Compartment,
+}
+
+fn add_compartment(layout: &mut Layout, members: &[Member], config: &SvgConfig) -> Compartment {
+    let font_size = config.font_size * config.font_scale_factor;
+    let block = layout.add_block();
+    let members: Vec<MemberText> = members
+        .iter()
+        .map(|member| {
+            let text = match member.visibility {
+                Some(visibility) => format!("{}{}", visibility.symbol(), member.text),
+                None => member.text.clone(),
+            };
+            let (block, lines) = layout.add_text_block(
+                &text,
+                config.max_member_length,
+                config.padding * 0.5,
+                font_size,
+            );
+            MemberText {
+                classifier: member.classifier,
+                block,
+                lines,
+            }
+        })
+        .collect();
+
+    match (members.first(), members.last()) {
+        (Some(first), Some(last)) => {
+            layout
+                .add_constraint(layout.b(first.block).top() | EQ(REQUIRED) | layout.b(block).top());
+            layout.add_constraint(
+                layout.b(last.block).bottom() | EQ(REQUIRED) | layout.b(block).bottom(),
+            );
+        }
+        _ => layout.add_constraint(layout.b(block).height | EQ(REQUIRED) | config.padding),
+    }
+    let ids: Vec<BlockId> = members.iter().map(|m| m.block).collect();
+    layout.distribute(Orientation::Vertical, 0.0, ids.iter());
+    for id in ids {
+        layout.add_constraint(
+            layout.b(id).left() | EQ(REQUIRED) | (layout.b(block).left() + config.padding * 0.5),
+        );
+        layout.add_constraint(layout.b(id).right() | LE(REQUIRED) | layout.b(block).right());
+    }
+    Compartment { block, members }
+}
+
+/// Stacks the header and the two compartments, each as wide as the class box.
+fn add_class_box(
+    layout: &mut Layout,
+    class: &Class,
+    block: BlockId,
+    config: &SvgConfig,
+) -> ClassBox {
+    let font_size = config.font_size * config.font_scale_factor;
+    let header_text = class
+        .annotations
+        .iter()
+        .map(|annotation| format!("«{annotation}»"))
+        .chain(std::iter::once(class.title()))
+        .join("\n");
+    let (header, header_lines) = layout.add_text_block(
+        &header_text,
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let fields = add_compartment(layout, &class.fields, config);
+    let methods = add_compartment(layout, &class.methods, config);
+
+    let stack = [header, fields.block, methods.block];
+    layout.add_constraint(layout.b(header).top() | EQ(REQUIRED) | layout.b(block).top());
+    layout.distribute(Orientation::Vertical, 0.0, stack.iter());
+    layout
+        .add_constraint(layout.b(methods.block).bottom() | EQ(REQUIRED) | layout.b(block).bottom());
+    for id in stack {
+        layout.add_constraint(layout.b(id).left() | EQ(REQUIRED) | layout.b(block).left());
+        layout.add_constraint(layout.b(id).width | EQ(REQUIRED) | layout.b(block).width);
+    }
+    // as small as the compartments allow
+    layout.add_constraint(layout.b(block).width | EQ(MEDIUM) | 0.0);
+    layout.add_constraint(layout.b(block).height | EQ(MEDIUM) | 0.0);
+
+    ClassBox {
+        node: class.id,
+        header,
+        header_lines,
+        fields,
+        methods,
+    }
+}
+
+impl ClassBox {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.node(self.node);
+        let mut group = Group::new().add(translated(&block).add(outline(&block, "white", 0.0)));
+
+        let header = arrangement.node_part(self.node, self.header);
+        let mut title = translated(&header);
+        for (i, line) in self.header_lines.iter().enumerate() {
+            let y = config.padding + ((i + 1) as f64) * header.line_height;
+            let mut text = text_line(line, y, &header, config.font_size, config.padding)
+                .set("x", header.width * 0.5)
+                .set("text-anchor", "middle");
+            if line.starts_with('«') {
+                text.assign("font-style", "italic");
+            }
+            title = title.add(text);
+        }
+        group = group.add(title);
+
+        for compartment in [&self.fields, &self.methods] {
+            let b = arrangement.node_part(self.node, compartment.block);
+            group = group.add(stroked(
+                Line::new()
+                    .set("x1", b.position.x)
+                    .set("y1", b.position.y)
+                    .set("x2", b.position.x + b.width)
+                    .set("y2", b.position.y),
+                "none",
+            ));
+            for member in &compartment.members {
+                let b = arrangement.node_part(self.node, member.block);
+                let mut texts = translated(&b);
+                for mut text in
+                    text_lines(&member.lines, &b, config.font_size, config.padding * 0.5)
+                {
+                    match member.classifier {
+                        Some(Classifier::Static) => text.assign("text-decoration", "underline"),
+                        Some(Classifier::Abstract) => text.assign("font-style", "italic"),
+                        None => {}
+                    }
+                    texts = texts.add(text);
+                }
+                group = group.add(texts);
+            }
+        }
+        group
+    }
+}
+
+fn relation_markers() -> Definitions {
+    let marker = |id: &str, width: f64, height: f64| {
+        Marker::new()
+            .set("id", id)
+            .set("markerWidth", width)
+            .set("markerHeight", height)
+            .set("refX", width)
+            .set("refY", height * 0.5)
+            .set("orient", "auto-start-reverse")
+            .set("markerUnits", "userSpaceOnUse")
+    };
+    let shape = |d: &str, fill: &str| stroked(Path::new().set("d", d), fill);
+    Definitions::new()
+        .add(marker("class-triangle", 14.0, 14.0).add(shape("M 0 1 L 13 7 L 0 13 z", "white")))
+        .add(
+            marker("class-filled-diamond", 18.0, 12.0)
+                .add(shape("M 0 6 L 9 1 L 18 6 L 9 11 z", LINE_COLOR)),
+        )
+        .add(marker("class-diamond", 18.0, 12.0).add(shape("M 0 6 L 9 1 L 18 6 L 9 11 z", "white")))
+        .add(marker("class-arrow", 10.0, 10.0).add(shape("M 0 1 L 10 5 L 0 9", "none")))
+        .add(marker("class-lollipop", 12.0, 12.0).add(stroked(
+            Circle::new().set("cx", 6).set("cy", 6).set("r", 5),
+            "white",
+        )))
+}
+
+fn marker_url(end: RelationEnd) -> Option<&'static str> {
+    match end {
+        RelationEnd::None => None,
+        RelationEnd::Triangle => Some("url(#class-triangle)"),
+        RelationEnd::FilledDiamond => Some("url(#class-filled-diamond)"),
+        RelationEnd::Diamond => Some("url(#class-diamond)"),
+        RelationEnd::Arrow => Some("url(#class-arrow)"),
+        RelationEnd::Lollipop => Some("url(#class-lollipop)"),
+    }
+}
+
+fn relation_path(relation: &Relation, d: String) -> Path {
+    let mut path = stroked(Path::new().set("d", d), "none");
+    if relation.line == LineStyle::Dashed {
+        path.assign("stroke-dasharray", "5 3");
+    }
+    if let Some(url) = marker_url(relation.from_end) {
+        path.assign("marker-start", url);
+    }
+    if let Some(url) = marker_url(relation.to_end) {
+        path.assign("marker-end", url);
+    }
+    path
+}
+
+/// A cardinality next to `end`, the point where the relation meets a class,
+/// on the right hand side of the relation seen from `towards`.
+fn cardinality(text: &str, end: (f64, f64), towards: (f64, f64), font_size: f64) -> Text {
+    let (dx, dy) = (towards.0 - end.0, towards.1 - end.1);
+    let length = dx.hypot(dy).max(1.0);
+    let (ux, uy) = (dx / length, dy / length);
+    let (along, across) = CARDINALITY_OFFSET;
+    text_at(
+        text,
+        end.0 + ux * along - uy * across,
+        end.1 + uy * along + ux * across,
+        font_size,
+    )
+}
+
+/// A class diagram with its class boxes and relations placed.
+pub struct ClassLayout {
+    diagram: ClassDiagram,
+    config: SvgConfig,
+    arrangement: Arrangement,
+    class_boxes: Vec<ClassBox>,
+}
+
+pub fn layout(diagram: &ClassDiagram, config: &SvgConfig) -> ClassLayout {
+    let flowchart = Flowchart {
+        direction: diagram.direction,
+        nodes: diagram
+            .classes
+            .iter()
+            .map(|class| Node {
+                id: class.id,
+                name: class.name.clone(),
+                label: String::new(),
+                shape: NodeShape::Rectangle,
+            })
+            .collect(),
+        edges: diagram
+            .relations
+            .iter()
+            .map(|relation| Edge {
+                id: relation.id,
+                from: relation.from,
+                to: relation.to,
+                label: relation.label.clone(),
+                stroke: Stroke::Solid,
+                start: EdgeEnd::None,
+                end: EdgeEnd::None,
+                length: 1,
+            })
+            .collect(),
+        subgraphs: diagram
+            .namespaces
+            .iter()
+            .map(|namespace| Subgraph {
+                id: namespace.id,
+                name: namespace.name.clone(),
+                title: namespace.name.clone(),
+                direction: None,
+                parent: None,
+                nodes: diagram
+                    .classes
+                    .iter()
+                    .filter(|class| class.namespace == Some(namespace.id))
+                    .map(|class| class.id)
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    let flowchart_config = flowchart::SvgConfig {
+        max_label_length: config.max_label_length,
+        font_size: config.font_size,
+        font_scale_factor: config.font_scale_factor,
+        padding: config.padding,
+        corner_radius: config.corner_radius,
+        rank_gutter: config.rank_gutter,
+        node_gutter: config.node_gutter,
+        subgraph_padding: config.subgraph_padding,
+    };
+    let sizes: HashMap<_, _> = diagram
+        .classes
+        .iter()
+        .map(|c| (c.id, NodeSize::Free))
+        .collect();
+    let mut arrangement = Arrangement::new(&flowchart, &flowchart_config, &sizes);
+    let class_boxes: Vec<ClassBox> = diagram
+        .classes
+        .iter()
+        .map(|class| {
+            let block = arrangement.node_block(class.id);
+            add_class_box(arrangement.layout(), class, block, config)
+        })
+        .collect();
+    arrangement.solve();
+    ClassLayout {
+        diagram: diagram.clone(),
+        config: config.clone(),
+        arrangement,
+        class_boxes,
+    }
+}
+
+impl ClassLayout {
+    pub fn to_svg(&self) -> SVG {
+        let ClassLayout {
+            diagram,
+            config,
+            arrangement,
+            class_boxes,
+        } = self;
+
+        let mut doc = document(arrangement.width(), arrangement.height()).add(relation_markers());
+        for namespace in &diagram.namespaces {
+            let (block, title, title_lines) = arrangement.subgraph(namespace.id);
+            let mut title_group = translated(&title);
+            for t in text_lines(title_lines, &title, config.font_size, config.padding) {
+                title_group = title_group.add(t);
+            }
+            doc = doc
+                .add(translated(&block).add(outline(&block, CLUSTER_FILL, config.corner_radius)))
+                .add(title_group);
+        }
+
+        for edge_id in arrangement.edges() {
+            let relation = &diagram.relations[edge_id];
+            let points = arrangement.edge_points(edge_id);
+            let d = points
+                .iter()
+                .enumerate()
+                .map(|(i, (x, y))| format!("{} {x} {y}", if i == 0 { "M" } else { "L" }))
+                .join(" ");
+            doc = doc.add(relation_path(relation, d));
+
+            let last = points.len() - 1;
+            if let Some(text) = &relation.from_cardinality {
+                doc = doc.add(cardinality(text, points[0], points[1], config.font_size));
+            }
+            if let Some(text) = &relation.to_cardinality {
+                doc = doc.add(cardinality(
+                    text,
+                    points[last],
+                    points[last - 1],
+                    config.font_size,
+                ));
+            }
+        }
+        for edge_id in arrangement.loops() {
+            let relation = &diagram.relations[edge_id];
+            doc = doc.add(relation_path(
+                relation,
+                arrangement.loop_path(relation.from),
+            ));
+        }
+
+        for class_box in class_boxes {
+            doc = doc.add(class_box.to_svg(arrangement, config));
+        }
+        for relation in &diagram.relations {
+            if let Some((block, lines)) = arrangement.edge_label(relation.id) {
+                doc = doc.add(label_box(lines, &block, config.font_size, config.padding));
+            }
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(diagram: &ClassDiagram, config: &SvgConfig) -> SVG {
+    layout(diagram, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class_diagram::Definition;
+    use crate::diagram::Options;
+    use crate::layout::geometry::inside;
+    use crate::layout::Block;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_class_boxes() {
+        let src = "classDiagram\nnamespace Zoo {\nclass Animal {\n+String name\n+int age\n+eat() void\n}\nclass Duck\n}\nAnimal <|-- Duck\nAnimal <|-- Fish\nclass Fish {\n-swim()\n}";
+        let diagram = parse(src.to_string()).unwrap();
+        let ClassLayout {
+            arrangement,
+            class_boxes,
+            ..
+        } = layout(&diagram, &config());
+
+        for class_box in &class_boxes {
+            let block = arrangement.node(class_box.node);
+            let parts = [
+                class_box.header,
+                class_box.fields.block,
+                class_box.methods.block,
+            ]
+            .map(|id| arrangement.node_part(class_box.node, id));
+            // header, fields and methods stacked over the whole box
+            assert!((parts[0].position.y - block.position.y).abs() < 1e-6);
+            for (upper, lower) in parts.iter().tuple_windows() {
+                assert!((upper.position.y + upper.height - lower.position.y).abs() < 1e-6);
+            }
+            let bottom = parts[2].position.y + parts[2].height;
+            assert!((bottom - block.position.y - block.height).abs() < 1e-6);
+            for part in &parts {
+                assert!((part.width - block.width).abs() < 1e-6);
+            }
+            for (compartment, part) in [&class_box.fields, &class_box.methods]
+                .iter()
+                .zip(&parts[1..])
+            {
+                let members: Vec<Block> = compartment
+                    .members
+                    .iter()
+                    .map(|m| arrangement.node_part(class_box.node, m.block))
+                    .collect();
+                for member in &members {
+                    assert!(inside(member, part));
+                }
+                for (upper, lower) in members.iter().tuple_windows() {
+                    assert!(upper.position.y + upper.height <= lower.position.y + 1e-6);
+                }
+            }
+        }
+        let animal = &class_boxes[0];
+        assert_eq!(
+            (2, 1),
+            (animal.fields.members.len(), animal.methods.members.len())
+        );
+
+        let (zoo, _, _) = arrangement.subgraph(0);
+        for class in &diagram.classes {
+            let block = arrangement.node(class.id);
+            assert_eq!(
+                class.namespace.is_some(),
+                inside(&block, &zoo),
+                "{}",
+                class.name
+            );
+        }
+
+        // relations end on the outline of their classes
+        for edge_id in arrangement.edges() {
+            let relation = &diagram.relations[edge_id];
+            let points = arrangement.edge_points(edge_id);
+            for (class, point) in [
+                (relation.from, points[0]),
+                (relation.to, points[points.len() - 1]),
+            ] {
+                let b = arrangement.node(class);
+                let on_vertical = (point.0 - b.position.x).abs() < 1e-6
+                    || (point.0 - b.position.x - b.width).abs() < 1e-6;
+                let on_horizontal = (point.1 - b.position.y).abs() < 1e-6
+                    || (point.1 - b.position.y - b.height).abs() < 1e-6;
+                assert!(on_vertical || on_horizontal);
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use svg::node::element::SVG;
+
+/// Settings shared by all diagram types.
+pub struct Options {
+    pub font_size: f64,
+    /// Width of a character relative to the font size, used to size text.
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    /// Only read by gantt charts, which mark this date as today. In
+    /// milliseconds since the Unix epoch; the clock is never read, so there
+    /// is no marker without it.
+    pub today: Option<crate::gantt::Timestamp>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            font_size: 10.0,
+            font_scale_factor: 1.2,
+            padding: 5.0,
+            today: None,
+        }
+    }
+}
+
+/// A type of diagram, from its source to an SVG document, through a layout
+/// where every element is placed.
+pub trait Diagram {
+    type Ast;
+    type Layout;
+
+    /// The words the first line of a source of this type starts with, such as
+    /// `flowchart`.
+    fn keywords(&self) -> &[&'static str];
+
+    fn parse(&self, src: &str) -> Result<Self::Ast, String>;
+
+    fn layout(&self, ast: &Self::Ast, options: &Options) -> Self::Layout;
+
+    fn to_svg(&self, layout: &Self::Layout) -> SVG;
+
+    fn render(&self, src: &str, options: &Options) -> Result<SVG, String> {
+        let ast = self.parse(src)?;
+        Ok(self.to_svg(&self.layout(&ast, options)))
+    }
+
+    /// Canonical source for `src`, for the diagram types with a formatter.
+    fn format(&self, _src: &str) -> Result<String, String> {
+        Err("Error: no formatter for this diagram type".to_string())
+    }
+}
+
+/// Defines the `Definition` of a diagram module whose `parser::parse` reads
+/// the `$ast` and whose `render::layout` places it as a `$layout` with the
+/// `SvgConfig` built by `$config` from the [`Options`], along with a `render`
+/// function taking the default options.
+macro_rules! diagram_type {
+    ($ast:ty, $layout:ty, [$($keyword:literal),+], |$options:ident| $config:expr) => {
+        pub struct Definition;
+
+        impl Definition {
+            /// The configuration diagrams of this type are drawn with.
+            pub fn config(&self, $options: &$crate::diagram::Options) -> SvgConfig {
+                $config
+            }
+        }
+
+        impl $crate::diagram::Diagram for Definition {
+            type Ast = $ast;
+            type Layout = $layout;
+
+            fn keywords(&self) -> &[&'static str] {
+                &[$($keyword),+]
+            }
+
+            fn parse(&self, src: &str) -> Result<$ast, String> {
+                parser::parse(src.to_string()).map_err(|e| format!("Error: {e:?}"))
+            }
+
+            fn layout(&self, ast: &$ast, options: &$crate::diagram::Options) -> $layout {
+                render::layout(ast, &self.config(options))
+            }
+
+            fn to_svg(&self, layout: &$layout) -> svg::node::element::SVG {
+                layout.to_svg()
+            }
+        }
+
+        pub fn render(src: &str) -> Result<svg::node::element::SVG, String> {
+            let options = $crate::diagram::Options::default();
+            $crate::diagram::Diagram::render(&Definition, src, &options)
+        }
+    };
+}
+pub(crate) use diagram_type;
+
+/// [`Diagram`] without its associated types, so that diagrams of any type can
+/// be kept together.
+pub trait AnyDiagram {
+    fn keywords(&self) -> &[&'static str];
+
+    fn render(&self, src: &str, options: &Options) -> Result<SVG, String>;
+
+    fn format(&self, src: &str) -> Result<String, String>;
+}
+
+impl<D: Diagram> AnyDiagram for D {
+    fn keywords(&self) -> &[&'static str] {
+        Diagram::keywords(self)
+    }
+
+    fn render(&self, src: &str, options: &Options) -> Result<SVG, String> {
+        Diagram::render(self, src, options)
+    }
+
+    fn format(&self, src: &str) -> Result<String, String> {
+        Diagram::format(self, src)
+    }
+}
+
+/// The first line that is not blank, a comment, a directive or part of the
+/// front matter.
+pub fn first_line(src: &str) -> Option<&str> {
+    let mut lines = src.lines().map(str::trim).filter(|line| !line.is_empty());
+    let mut line = lines.next()?;
+    if line == "---" {
+        lines.by_ref().find(|&line| line == "---")?;
+        line = lines.next()?;
+    }
+    std::iter::once(line)
+        .chain(lines)
+        .find(|line| !line.starts_with("%%"))
+}
+
+/// The source without its front matter, which no diagram type reads yet.
+pub fn body(src: &str) -> &str {
+    let trimmed = src.trim_start();
+    let Some(rest) = trimmed.strip_prefix("---") else {
+        return src;
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n').skip(1) {
+        offset += line.len();
+        if line.trim() == "---" {
+            let start = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            return &rest[start + offset..];
+        }
+    }
+    src
+}
+
+/// The diagram types `render` picks from.
+pub struct Registry {
+    diagrams: Vec<Box<dyn AnyDiagram>>,
+    fallback: Option<Box<dyn AnyDiagram>>,
+}
+
+impl Registry {
+    /// A registry without any diagram type.
+    pub fn new() -> Self {
+        Registry {
+            diagrams: vec![],
+            fallback: None,
+        }
+    }
+
+    /// Adds a diagram type, which takes precedence over the ones registered
+    /// before for the same keywords.
+    pub fn register<D: Diagram + 'static>(&mut self, diagram: D) {
+        self.diagrams.push(Box::new(diagram));
+    }
+
+    /// Sets the diagram type of the sources whose first line names none of the
+    /// registered types.
+    pub fn register_fallback<D: Diagram + 'static>(&mut self, diagram: D) {
+        self.fallback = Some(Box::new(diagram));
+    }
+
+    /// The diagram type the first line of `src` names, or the fallback type.
+    pub fn detect(&self, src: &str) -> Option<&dyn AnyDiagram> {
+        let word = first_line(src)
+            .and_then(|line| line.split(|c: char| c.is_whitespace() || c == ':').next());
+        self.diagrams
+            .iter()
+            .rev()
+            .find(|diagram| word.is_some_and(|word| diagram.keywords().contains(&word)))
+            .or(self.fallback.as_ref())
+            .map(|diagram| diagram.as_ref())
+    }
+
+    pub fn render(&self, src: &str, options: &Options) -> Result<SVG, String> {
+        match self.detect(src) {
+            Some(diagram) => diagram.render(body(src), options),
+            None => Err("Error: unknown diagram type".to_string()),
+        }
+    }
+}
+
+impl Default for Registry {
+    /// All the diagram types of this crate.
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register(crate::c4::Definition);
+        registry.register(crate::class_diagram::Definition);
+        registry.register(crate::er_diagram::Definition);
+        registry.register(crate::flowchart::Definition);
+        registry.register(crate::gantt::Definition);
+        registry.register(crate::git_graph::Definition);
+        registry.register(crate::journey::Definition);
+        registry.register(crate::mindmap::Definition);
+        registry.register(crate::pie::Definition);
+        registry.register(crate::quadrant::Definition);
+        registry.register(crate::requirement::Definition);
+        registry.register(crate::sankey::Definition);
+        registry.register(crate::sequence_diagram::Definition);
+        registry.register(crate::state_diagram::Definition);
+        registry.register(crate::timeline::Definition);
+        registry.register(crate::xychart::Definition);
+        // Sequence diagrams came first and did without a header.
+        registry.register_fallback(crate::sequence_diagram::Definition);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_line() {
+        let src = "---\ntitle: Flows\n---\n\n%%{init: {}}%%\n  %% comment\n  flowchart LR\nA --> B";
+        assert_eq!(Some("flowchart LR"), first_line(src));
+        assert_eq!(None, first_line("\n%% only a comment\n"));
+        assert_eq!(
+            "%%{init: {}}%%\nflowchart LR",
+            body("---\ntitle: Flows\n---\n%%{init: {}}%%\nflowchart LR")
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        let registry = Registry::default();
+        let keywords = |src: &str| registry.detect(src).map(|diagram| diagram.keywords()[0]);
+        assert_eq!(Some("flowchart"), keywords("graph TD\nA --> B"));
+        assert_eq!(Some("stateDiagram"), keywords("stateDiagram-v2\n[*] --> A"));
+        assert_eq!(Some("pie"), keywords("pie title Pets\n\"Dogs\" : 3"));
+        // sources without a header are sequence diagrams
+        assert_eq!(Some("sequenceDiagram"), keywords("Alice->Bob: hi"));
+        assert!(Registry::new().detect("Alice->Bob: hi").is_none());
+    }
+
+    /// A diagram type defined outside of the crate, drawing its source as the
+    /// title of an empty document.
+    struct Caption;
+
+    impl Diagram for Caption {
+        type Ast = String;
+        type Layout = String;
+
+        fn keywords(&self) -> &[&'static str] {
+            &["caption"]
+        }
+
+        fn parse(&self, src: &str) -> Result<String, String> {
+            Ok(src.lines().nth(1).unwrap_or_default().to_string())
+        }
+
+        fn layout(&self, ast: &String, _options: &Options) -> String {
+            ast.clone()
+        }
+
+        fn to_svg(&self, layout: &String) -> SVG {
+            let title = svg::node::element::Title::new().add(svg::node::Text::new(layout.as_str()));
+            crate::theme::document(10.0, 10.0).add(title)
+        }
+    }
+
+    #[test]
+    fn test_render_with() {
+        let options = Options::default();
+        let src = "caption\nHello";
+        assert!(crate::render(src, &options).is_err());
+
+        let mut registry = Registry::default();
+        registry.register(Caption);
+        let svg = crate::render_with(&registry, src, &options)
+            .unwrap()
+            .to_string();
+        assert!(svg.contains("Hello"), "{svg}");
+        // the fallback still applies to sources without a header
+        assert!(crate::render_with(&registry, "A->>B: hi", &options).is_ok());
+        assert_eq!(
+            Err("Error: unknown diagram type".to_string()),
+            crate::render_with(&Registry::new(), "A->>B: hi", &options).map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,16 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(ErDiagram, ErLayout, ["erDiagram"], |options| SvgConfig {
+    max_label_length: 20,
+    max_attribute_length: 30,
+    font_scale_factor: options.font_scale_factor,
+    font_size: options.font_size,
+    padding: options.padding,
+    corner_radius: 2.0,
+    rank_gutter: 50.0,
+    node_gutter: 30.0,
+});
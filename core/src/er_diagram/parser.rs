@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::parser::choice::choice;
+use combine::{
+    attempt, eof, many, many1, none_of, one_of, optional, sep_by1, skip_many, ParseError, Parser,
+    Stream,
+};
+
+use crate::parsing::{blank, blank1, comment_parser, keyword, quoted, skipped_parser};
+
+pub use crate::flowchart::Direction;
+
+pub type EntityId = usize;
+pub type RelationshipId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Key {
+    Primary,
+    Foreign,
+    Unique,
+}
+
+impl Key {
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Key::Primary => "PK",
+            Key::Foreign => "FK",
+            Key::Unique => "UK",
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    pub kind: String,
+    pub name: String,
+    pub keys: Vec<Key>,
+    pub comment: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entity {
+    pub id: EntityId,
+    pub name: String,
+    /// Shown instead of the name, from `NAME[Label]`.
+    pub label: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Entity {
+    pub fn title(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// How many instances of an entity take part in a relationship, drawn as a
+/// crow's foot line end.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cardinality {
+    ZeroOrOne,
+    ExactlyOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relationship {
+    pub id: RelationshipId,
+    pub from: EntityId,
+    pub to: EntityId,
+    pub from_cardinality: Cardinality,
+    pub to_cardinality: Cardinality,
+    /// Identifying relationships are drawn with a solid, others with a dashed line.
+    pub identifying: bool,
+    pub label: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErDiagram {
+    pub direction: Direction,
+    pub entities: Vec<Entity>,
+    pub relationships: Vec<Relationship>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header,
+    Direction(Direction),
+    Entity {
+        name: String,
+        label: Option<String>,
+        attributes: Vec<Attribute>,
+    },
+    Relationship {
+        from: String,
+        from_cardinality: Cardinality,
+        identifying: bool,
+        to_cardinality: Cardinality,
+        to: String,
+        label: Option<String>,
+    },
+}
+
+pub fn parse(src: String) -> Result<ErDiagram, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => Ok(build_er_diagram(statements)),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_er_diagram(statements: Vec<Statement>) -> ErDiagram {
+    let mut diagram = ErDiagram {
+        direction: Direction::TopToBottom,
+        entities: vec![],
+        relationships: vec![],
+    };
+    let mut ids: HashMap<String, EntityId> = HashMap::new();
+    let mut entity = |diagram: &mut ErDiagram, name: &str| {
+        *ids.entry(name.to_string()).or_insert_with(|| {
+            diagram.entities.push(Entity {
+                id: diagram.entities.len(),
+                name: name.to_string(),
+                label: None,
+                attributes: vec![],
+            });
+            diagram.entities.len() - 1
+        })
+    };
+
+    for statement in statements {
+        match statement {
+            Statement::Empty | Statement::Header => {}
+            Statement::Direction(direction) => diagram.direction = direction,
+            Statement::Entity {
+                name,
+                label,
+                attributes,
+            } => {
+                let id = entity(&mut diagram, &name);
+                let entity = &mut diagram.entities[id];
+                if label.is_some() {
+                    entity.label = label;
+                }
+                entity.attributes.extend(attributes);
+            }
+            Statement::Relationship {
+                from,
+                from_cardinality,
+                identifying,
+                to_cardinality,
+                to,
+                label,
+            } => {
+                let from = entity(&mut diagram, &from);
+                let to = entity(&mut diagram, &to);
+                diagram.relationships.push(Relationship {
+                    id: diagram.relationships.len(),
+                    from,
+                    to,
+                    from_cardinality,
+                    to_cardinality,
+                    identifying,
+                    label,
+                });
+            }
+        }
+    }
+    diagram
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    keyword("erDiagram").map(|_| Statement::Header)
+}
+
+fn direction_statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let direction = choice((
+        attempt(string("TB")).map(|_| Direction::TopToBottom),
+        attempt(string("TD")).map(|_| Direction::TopToBottom),
+        string("BT").map(|_| Direction::BottomToTop),
+        string("LR").map(|_| Direction::LeftToRight),
+        string("RL").map(|_| Direction::RightToLeft),
+    ));
+    (keyword("direction"), blank1(), direction).map(|(_, _, d)| Statement::Direction(d))
+}
+
+fn entity_name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((quoted(), many1(choice((alpha_num(), one_of("_-".chars()))))))
+}
+
+fn key_parser<Input>() -> impl Parser<Input, Output = Key>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        keyword("PK").map(|_| Key::Primary),
+        keyword("FK").map(|_| Key::Foreign),
+        keyword("UK").map(|_| Key::Unique),
+    ))
+}
+
+/// `type name [PK, FK] ["comment"]`, a line in the body of an entity.
+fn attribute_parser<Input>() -> impl Parser<Input, Output = Attribute>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let kind = many1(none_of(" \t\n\"{}".chars()));
+    let name = many1(none_of(" \t\n\",{}".chars()));
+    let keys = attempt((
+        blank1(),
+        sep_by1(key_parser(), attempt((blank(), char(','), blank()))),
+    ))
+    .map(|(_, keys)| keys);
+    let comment = attempt((blank1(), quoted())).map(|(_, comment)| comment);
+    (kind, blank1(), name, optional(keys), optional(comment)).map(
+        |(kind, _, name, keys, comment)| Attribute {
+            kind,
+            name,
+            keys: keys.unwrap_or_default(),
+            comment,
+        },
+    )
+}
+
+fn entity_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let label = (
+        char('['),
+        choice((quoted(), many1(none_of("]\n".chars())))),
+        char(']'),
+    )
+        .map(|(_, label, _)| label);
+    let whitespace = || skip_many(one_of(" \t\n".chars()));
+    let line = (
+        attempt((
+            whitespace(),
+            choice((comment_parser().map(|_| None), attribute_parser().map(Some))),
+        )),
+        blank(),
+        optional(comment_parser()),
+    )
+        .map(|((_, attribute), _, _)| attribute);
+    let body = (char('{'), many(line), whitespace(), char('}'))
+        .map(|(_, lines, _, _): (_, Vec<_>, _, _)| lines.into_iter().flatten().collect());
+    (
+        entity_name_parser(),
+        optional(label),
+        blank(),
+        optional(body),
+    )
+        .map(|(name, label, _, attributes)| Statement::Entity {
+            name,
+            label,
+            attributes: attributes.unwrap_or_default(),
+        })
+}
+
+fn cardinality_parser<Input>(right: bool) -> impl Parser<Input, Output = Cardinality>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let (zero_or_more, one_or_more) = if right { ("o{", "|{") } else { ("}o", "}|") };
+    choice((
+        attempt(choice((string("|o"), string("o|")))).map(|_| Cardinality::ZeroOrOne),
+        attempt(string("||")).map(|_| Cardinality::ExactlyOne),
+        attempt(string(zero_or_more)).map(|_| Cardinality::ZeroOrMore),
+        attempt(string(one_or_more)).map(|_| Cardinality::OneOrMore),
+    ))
+}
+
+fn relationship_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let line = choice((
+        attempt(string("--")).map(|_| true),
+        string("..").map(|_| false),
+    ));
+    let label = optional(attempt((
+        blank(),
+        char(':'),
+        blank(),
+        choice((quoted(), many(none_of("\n%".chars())))),
+    )))
+    .map(|label| label.map(|(_, _, _, text): (_, _, _, String)| text.trim().to_string()));
+    attempt((
+        entity_name_parser(),
+        blank(),
+        cardinality_parser(false),
+        line,
+        cardinality_parser(true),
+        blank(),
+        entity_name_parser(),
+    ))
+    .and(label)
+    .map(
+        |((from, _, from_cardinality, identifying, to_cardinality, _, to), label)| {
+            Statement::Relationship {
+                from,
+                from_cardinality,
+                identifying,
+                to_cardinality,
+                to,
+                label,
+            }
+        },
+    )
+}
+
+/// Styling and accessibility statements don't affect the layout and are skipped.
+fn ignored_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&["classDef", "class", "style", "accTitle", "accDescr"])
+        .map(|_| Statement::Empty)
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            direction_statement_parser(),
+            ignored_parser(),
+            relationship_parser(),
+            entity_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cardinality_parser() {
+        assert_eq!(
+            Ok((Cardinality::ZeroOrOne, "")),
+            cardinality_parser(false).parse("|o")
+        );
+        assert_eq!(
+            Ok((Cardinality::OneOrMore, "")),
+            cardinality_parser(false).parse("}|")
+        );
+        assert_eq!(
+            Ok((Cardinality::ZeroOrOne, "")),
+            cardinality_parser(true).parse("|o")
+        );
+        assert_eq!(
+            Ok((Cardinality::ExactlyOne, "")),
+            cardinality_parser(true).parse("||")
+        );
+        assert_eq!(
+            Ok((Cardinality::ZeroOrMore, "")),
+            cardinality_parser(true).parse("o{")
+        );
+        assert!(cardinality_parser(true).parse("}o").is_err());
+    }
+
+    #[test]
+    fn test_attribute_parser() {
+        assert_eq!(
+            Ok((
+                Attribute {
+                    kind: "decimal(10,2)".to_string(),
+                    name: "price".to_string(),
+                    keys: vec![],
+                    comment: None,
+                },
+                ""
+            )),
+            attribute_parser().parse("decimal(10,2) price")
+        );
+        assert_eq!(
+            Ok((
+                Attribute {
+                    kind: "string".to_string(),
+                    name: "customer_id".to_string(),
+                    keys: vec![Key::Foreign, Key::Unique],
+                    comment: Some("one account each".to_string()),
+                },
+                ""
+            )),
+            attribute_parser().parse("string customer_id FK, UK \"one account each\"")
+        );
+    }
+
+    #[test]
+    fn test_relationship_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Relationship {
+                    from: "CUSTOMER".to_string(),
+                    from_cardinality: Cardinality::ExactlyOne,
+                    identifying: true,
+                    to_cardinality: Cardinality::ZeroOrMore,
+                    to: "ORDER".to_string(),
+                    label: Some("places".to_string()),
+                },
+                "\n"
+            )),
+            relationship_parser().parse("CUSTOMER ||--o{ ORDER : places\n")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Relationship {
+                    from: "ORDER".to_string(),
+                    from_cardinality: Cardinality::OneOrMore,
+                    identifying: false,
+                    to_cardinality: Cardinality::ZeroOrOne,
+                    to: "LINE-ITEM".to_string(),
+                    label: Some("has many".to_string()),
+                },
+                "\n"
+            )),
+            relationship_parser().parse("ORDER }|..o| LINE-ITEM : \"has many\"\n")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"erDiagram
+            direction LR
+            CUSTOMER ||--o{ ORDER : places
+            CUSTOMER {
+                string name PK
+                %% contact details
+                string email UK "login"
+            }
+            ORDER ||--|{ LINE-ITEM : contains
+            p[Person] {
+                int age
+            }
+        "#;
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(Direction::LeftToRight, diagram.direction);
+        assert_eq!(4, diagram.entities.len());
+        let customer = &diagram.entities[0];
+        assert_eq!(2, customer.attributes.len());
+        assert_eq!(vec![Key::Primary], customer.attributes[0].keys);
+        assert_eq!(Some("login".to_string()), customer.attributes[1].comment);
+        assert_eq!("Person", diagram.entities[3].title());
+        assert_eq!(2, diagram.relationships.len());
+        assert_eq!(
+            Cardinality::OneOrMore,
+            diagram.relationships[1].to_cardinality
+        );
+    }
+
+    #[test]
+    fn test_parse_unterminated_entity() {
+        assert!(parse("erDiagram\nA {\nint id\n".to_string()).is_err());
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+
+use svg::node::element::{Circle, Definitions, Group, Line, Marker, Path, SVG};
+use svg::Node as _;
+
+use cassowary::strength::{MEDIUM, REQUIRED};
+use cassowary::WeightedRelation::*;
+use itertools::Itertools;
+
+use crate::er_diagram::*;
+use crate::flowchart::{
+    self, Arrangement, Edge, EdgeEnd, Flowchart, Node, NodeId, NodeShape, NodeSize, Stroke,
+};
+use crate::layout::{BlockId, Layout, Orientation};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub max_attribute_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+}
+
+/// Length of a crow's foot line end along the relationship.
+const FOOT_LENGTH: f64 = 22.0;
+const FOOT_WIDTH: f64 = 16.0;
+
+/// The attributes of an entity as a table with a column for the type, the
+/// name, the keys and the comment. Columns without any content are left out.
+struct AttributeTable {
+    columns: Vec<BlockId>,
+    rows: Vec<BlockId>,
+    cells: Vec<(BlockId, Vec<String>)>,
+}
+
+struct EntityBox {
+    node: NodeId,
+    header: BlockId,
+    header_lines: Vec<String>,
+    table: AttributeTable,
+}
+
+fn attribute_cells(attribute: &Attribute) -> [Option<String>; 4] {
+    let keys = attribute.keys.iter().map(Key::marker).join(", ");
+    [
+        Some(attribute.kind.clone()),
+        Some(attribute.name.clone()),
+        Some(keys).filter(|keys| !keys.is_empty()),
+        attribute.comment.clone(),
+    ]
+}
+
+fn add_attribute_table(
+    layout: &mut Layout,
+    entity: &Entity,
+    block: BlockId,
+    config: &SvgConfig,
+) -> AttributeTable {
+    let font_size = config.font_size * config.font_scale_factor;
+    let contents: Vec<[Option<String>; 4]> =
+        entity.attributes.iter().map(attribute_cells).collect();
+    let mut table = AttributeTable {
+        columns: vec![],
+        rows: vec![],
+        cells: vec![],
+    };
+
+    let used: Vec<usize> = (0..4)
+        .filter(|&c| contents.iter().any(|row| row[c].is_some()))
+        .collect();
+    table.columns = used.iter().map(|_| layout.add_block()).collect();
+
+    for row_content in &contents {
+        let row = layout.add_block();
+        table.rows.push(row);
+        layout.add_constraint(layout.b(row).left() | EQ(REQUIRED) | layout.b(block).left());
+        layout.add_constraint(layout.b(row).width | EQ(REQUIRED) | layout.b(block).width);
+        layout.add_constraint(layout.b(row).height | EQ(MEDIUM) | 0.0);
+        for (&c, &column) in used.iter().zip(&table.columns) {
+            let Some(text) = &row_content[c] else {
+                continue;
+            };
+            let (cell, lines) =
+                layout.add_text_block(text, config.max_attribute_length, config.padding, font_size);
+            layout.add_constraint(layout.b(cell).left() | EQ(REQUIRED) | layout.b(column).left());
+            layout.add_constraint(layout.b(cell).width | EQ(REQUIRED) | layout.b(column).width);
+            layout.add_constraint(layout.b(cell).top() | EQ(REQUIRED) | layout.b(row).top());
+            layout.add_constraint(layout.b(cell).height | EQ(REQUIRED) | layout.b(row).height);
+            table.cells.push((cell, lines));
+        }
+    }
+
+    layout.distribute(Orientation::Horizontal, 0.0, table.columns.iter());
+    if let (Some(&first), Some(&last)) = (table.columns.first(), table.columns.last()) {
+        layout.add_constraint(layout.b(first).left() | EQ(REQUIRED) | layout.b(block).left());
+        layout.add_constraint(layout.b(last).right() | EQ(REQUIRED) | layout.b(block).right());
+    }
+    table
+}
+
+fn add_entity_box(
+    layout: &mut Layout,
+    entity: &Entity,
+    block: BlockId,
+    config: &SvgConfig,
+) -> EntityBox {
+    let font_size = config.font_size * config.font_scale_factor;
+    let (header, header_lines) = layout.add_text_block(
+        entity.title(),
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let table = add_attribute_table(layout, entity, block, config);
+
+    let stack: Vec<BlockId> = std::iter::once(header)
+        .chain(table.rows.iter().copied())
+        .collect();
+    layout.add_constraint(layout.b(header).top() | EQ(REQUIRED) | layout.b(block).top());
+    layout.add_constraint(layout.b(header).left() | EQ(REQUIRED) | layout.b(block).left());
+    layout.add_constraint(layout.b(header).width | EQ(REQUIRED) | layout.b(block).width);
+    layout.distribute(Orientation::Vertical, 0.0, stack.iter());
+    let last = *stack.last().unwrap_or(&header);
+    layout.add_constraint(layout.b(last).bottom() | EQ(REQUIRED) | layout.b(block).bottom());
+    // as small as the table allows
+    layout.add_constraint(layout.b(block).width | EQ(MEDIUM) | 0.0);
+    layout.add_constraint(layout.b(block).height | EQ(MEDIUM) | 0.0);
+
+    EntityBox {
+        node: entity.id,
+        header,
+        header_lines,
+        table,
+    }
+}
+
+impl EntityBox {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.node(self.node);
+        let header = arrangement.node_part(self.node, self.header);
+        let mut group = Group::new()
+            .add(translated(&block).add(outline(&block, "white", 0.0)))
+            .add(translated(&header).add(outline(&header, NOTE_FILL, 0.0)));
+
+        let mut title = translated(&header);
+        for (i, line) in self.header_lines.iter().enumerate() {
+            let y = config.padding + ((i + 1) as f64) * header.line_height;
+            title = title.add(
+                text_line(line, y, &header, config.font_size, config.padding)
+                    .set("x", header.width * 0.5)
+                    .set("text-anchor", "middle"),
+            );
+        }
+        group = group.add(title);
+
+        let line = |x1: f64, y1: f64, x2: f64, y2: f64| {
+            stroked(
+                Line::new()
+                    .set("x1", x1)
+                    .set("y1", y1)
+                    .set("x2", x2)
+                    .set("y2", y2),
+                "none",
+            )
+        };
+        for &row in self.table.rows.iter().skip(1) {
+            let b = arrangement.node_part(self.node, row);
+            group = group.add(line(
+                b.position.x,
+                b.position.y,
+                b.position.x + b.width,
+                b.position.y,
+            ));
+        }
+        let body_top = header.position.y + header.height;
+        let bottom = block.position.y + block.height;
+        for &column in self.table.columns.iter().skip(1) {
+            let x = arrangement.node_part(self.node, column).position.x;
+            group = group.add(line(x, body_top, x, bottom));
+        }
+
+        for (cell, lines) in &self.table.cells {
+            let b = arrangement.node_part(self.node, *cell);
+            let mut texts = translated(&b);
+            for t in text_lines(lines, &b, config.font_size, config.padding) {
+                texts = texts.add(t);
+            }
+            group = group.add(texts);
+        }
+        group
+    }
+}
+
+fn marker_id(cardinality: Cardinality) -> &'static str {
+    match cardinality {
+        Cardinality::ZeroOrOne => "er-zero-or-one",
+        Cardinality::ExactlyOne => "er-exactly-one",
+        Cardinality::ZeroOrMore => "er-zero-or-more",
+        Cardinality::OneOrMore => "er-one-or-more",
+    }
+}
+
+/// Crow's foot line ends, drawn for a relationship reaching its entity from
+/// the left and reversed at the start of a path.
+fn cardinality_markers() -> Definitions {
+    let (w, h) = (FOOT_LENGTH, FOOT_WIDTH);
+    let mid = h * 0.5;
+    let marker = |cardinality: Cardinality| {
+        Marker::new()
+            .set("id", marker_id(cardinality))
+            .set("markerWidth", w)
+            .set("markerHeight", h)
+            .set("refX", w)
+            .set("refY", mid)
+            .set("orient", "auto-start-reverse")
+            .set("markerUnits", "userSpaceOnUse")
+    };
+    let bar = |x: f64| {
+        stroked(
+            Path::new().set("d", format!("M {x} 2 L {x} {}", h - 2.0)),
+            "none",
+        )
+    };
+    let ring = |cx: f64| {
+        stroked(
+            Circle::new().set("cx", cx).set("cy", mid).set("r", 4),
+            "white",
+        )
+    };
+    let foot = stroked(
+        Path::new().set("d", format!("M {w} 2 L 12 {mid} L {w} {}", h - 2.0)),
+        "none",
+    );
+    Definitions::new()
+        .add(marker(Cardinality::ZeroOrOne).add(ring(7.0)).add(bar(16.0)))
+        .add(
+            marker(Cardinality::ExactlyOne)
+                .add(bar(12.0))
+                .add(bar(16.0)),
+        )
+        .add(
+            marker(Cardinality::ZeroOrMore)
+                .add(ring(6.0))
+                .add(foot.clone()),
+        )
+        .add(marker(Cardinality::OneOrMore).add(bar(8.0)).add(foot))
+}
+
+fn relationship_path(relationship: &Relationship, d: String) -> Path {
+    let mut path = stroked(Path::new().set("d", d), "none")
+        .set(
+            "marker-start",
+            format!("url(#{})", marker_id(relationship.from_cardinality)),
+        )
+        .set(
+            "marker-end",
+            format!("url(#{})", marker_id(relationship.to_cardinality)),
+        );
+    if !relationship.identifying {
+        path.assign("stroke-dasharray", "5 3");
+    }
+    path
+}
+
+/// An entity relationship diagram with its entity tables placed.
+pub struct ErLayout {
+    diagram: ErDiagram,
+    config: SvgConfig,
+    arrangement: Arrangement,
+    entity_boxes: Vec<EntityBox>,
+}
+
+pub fn layout(diagram: &ErDiagram, config: &SvgConfig) -> ErLayout {
+    let flowchart = Flowchart {
+        direction: diagram.direction,
+        nodes: diagram
+            .entities
+            .iter()
+            .map(|entity| Node {
+                id: entity.id,
+                name: entity.name.clone(),
+                label: String::new(),
+                shape: NodeShape::Rectangle,
+            })
+            .collect(),
+        edges: diagram
+            .relationships
+            .iter()
+            .map(|relationship| Edge {
+                id: relationship.id,
+                from: relationship.from,
+                to: relationship.to,
+                label: relationship.label.clone(),
+                stroke: Stroke::Solid,
+                start: EdgeEnd::None,
+                end: EdgeEnd::None,
+                length: 1,
+            })
+            .collect(),
+        subgraphs: vec![],
+    };
+
+    let flowchart_config = flowchart::SvgConfig {
+        max_label_length: config.max_label_length,
+        font_size: config.font_size,
+        font_scale_factor: config.font_scale_factor,
+        padding: config.padding,
+        corner_radius: config.corner_radius,
+        rank_gutter: config.rank_gutter,
+        node_gutter: config.node_gutter,
+        subgraph_padding: 0.0,
+    };
+    let sizes: HashMap<_, _> = diagram
+        .entities
+        .iter()
+        .map(|e| (e.id, NodeSize::Free))
+        .collect();
+    let mut arrangement = Arrangement::new(&flowchart, &flowchart_config, &sizes);
+    let entity_boxes: Vec<EntityBox> = diagram
+        .entities
+        .iter()
+        .map(|entity| {
+            let block = arrangement.node_block(entity.id);
+            add_entity_box(arrangement.layout(), entity, block, config)
+        })
+        .collect();
+    arrangement.solve();
+    ErLayout {
+        diagram: diagram.clone(),
+        config: config.clone(),
+        arrangement,
+        entity_boxes,
+    }
+}
+
+impl ErLayout {
+    pub fn to_svg(&self) -> SVG {
+        let ErLayout {
+            diagram,
+            config,
+            arrangement,
+            entity_boxes,
+        } = self;
+
+        let mut doc =
+            document(arrangement.width(), arrangement.height()).add(cardinality_markers());
+        for edge_id in arrangement.edges() {
+            let d = arrangement
+                .edge_points(edge_id)
+                .iter()
+                .enumerate()
+                .map(|(i, (x, y))| format!("{} {x} {y}", if i == 0 { "M" } else { "L" }))
+                .join(" ");
+            doc = doc.add(relationship_path(&diagram.relationships[edge_id], d));
+        }
+        for edge_id in arrangement.loops() {
+            let relationship = &diagram.relationships[edge_id];
+            doc = doc.add(relationship_path(
+                relationship,
+                arrangement.loop_path(relationship.from),
+            ));
+        }
+
+        for entity_box in entity_boxes {
+            doc = doc.add(entity_box.to_svg(arrangement, config));
+        }
+        for relationship in &diagram.relationships {
+            if let Some((block, lines)) = arrangement.edge_label(relationship.id) {
+                doc = doc.add(label_box(lines, &block, config.font_size, config.padding));
+            }
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(diagram: &ErDiagram, config: &SvgConfig) -> SVG {
+    layout(diagram, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::er_diagram::Definition;
+    use crate::layout::geometry::overlap;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_entity_tables() {
+        let src = "erDiagram\nCUSTOMER ||--o{ ORDER : places\nCUSTOMER ||--o{ ADDRESS : has\nCUSTOMER {\nstring name PK\nstring email \"primary contact\"\n}\nORDER {\nint id PK\nint customer FK\n}";
+        let diagram = parse(src.to_string()).unwrap();
+        let ErLayout {
+            arrangement,
+            entity_boxes,
+            ..
+        } = layout(&diagram, &config());
+
+        // type, name, keys and comment, the last two only where used
+        let columns: Vec<usize> = entity_boxes.iter().map(|e| e.table.columns.len()).collect();
+        assert_eq!(vec![4, 3, 0], columns);
+
+        for entity_box in &entity_boxes {
+            let part = |id| arrangement.node_part(entity_box.node, id);
+            let block = arrangement.node(entity_box.node);
+            let header = part(entity_box.header);
+            let mut top = header.position.y + header.height;
+            for &row in &entity_box.table.rows {
+                let row = part(row);
+                assert!((row.position.y - top).abs() < 1e-6);
+                top += row.height;
+            }
+            assert!((block.position.y + block.height - top).abs() < 1e-6);
+
+            let mut left = block.position.x;
+            for &column in &entity_box.table.columns {
+                let column = part(column);
+                assert!((column.position.x - left).abs() < 1e-6);
+                left += column.width;
+                // every cell of the column is as wide as the column
+                let cells = entity_box
+                    .table
+                    .cells
+                    .iter()
+                    .map(|(cell, _)| part(*cell))
+                    .filter(|cell| (cell.position.x - column.position.x).abs() < 1e-6);
+                for cell in cells {
+                    assert!((cell.width - column.width).abs() < 1e-6);
+                }
+            }
+            if !entity_box.table.columns.is_empty() {
+                assert!((block.position.x + block.width - left).abs() < 1e-6);
+            }
+        }
+
+        for (a, b) in entity_boxes.iter().tuple_combinations() {
+            assert!(!overlap(
+                &arrangement.node(a.node),
+                &arrangement.node(b.node)
+            ));
+        }
+    }
+}
@@ -0,0 +1,459 @@
+//! Sugiyama-style layering of a flowchart: cycles are broken, nodes are assigned
+//! to ranks, long edges are split by bend points and the vertices of every rank
+//! are ordered to reduce crossings. Coordinates are left to the renderer.
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::flowchart::*;
+
+const ORDERING_SWEEPS: usize = 8;
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Vertex {
+    Node(NodeId),
+    /// The n-th bend point of an edge that spans more than one rank.
+    Bend(EdgeId, usize),
+    /// Stands in for a subgraph in a rank it spans without having members there.
+    Filler(SubgraphId, usize),
+}
+
+/// The subgraphs a vertex is drawn in, outermost first. Bends belong to the
+/// subgraphs shared by both ends of their edge.
+pub fn vertex_subgraphs(flowchart: &Flowchart, vertex: &Vertex) -> Vec<SubgraphId> {
+    match vertex {
+        Vertex::Node(id) => flowchart.subgraph_path(*id),
+        Vertex::Bend(edge_id, _) => {
+            let edge = &flowchart.edges[*edge_id];
+            flowchart
+                .subgraph_path(edge.from)
+                .into_iter()
+                .zip(flowchart.subgraph_path(edge.to))
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }
+        Vertex::Filler(subgraph_id, _) => flowchart.subgraph_ancestors(*subgraph_id),
+    }
+}
+
+/// The way an edge passes through the ranks.
+#[derive(Debug)]
+pub struct Route {
+    pub edge_id: EdgeId,
+    /// Vertices from the source to the target of the edge, bends included.
+    pub vertices: Vec<Vertex>,
+    /// Index into `vertices` of the bend carrying the edge label.
+    pub label_at: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Layering {
+    /// Vertices of every rank in their final order.
+    pub ranks: Vec<Vec<Vertex>>,
+    pub routes: Vec<Route>,
+    /// Edges connecting a node with itself, they don't take part in the layering.
+    pub loops: Vec<EdgeId>,
+}
+
+impl Layering {
+    pub fn rank_of(&self, vertex: Vertex) -> Option<usize> {
+        self.ranks.iter().position(|rank| rank.contains(&vertex))
+    }
+}
+
+/// An edge oriented along the ranks, reversed if it closed a cycle.
+struct Segmented {
+    edge_id: EdgeId,
+    upper: NodeId,
+    lower: NodeId,
+    reversed: bool,
+    min_length: usize,
+}
+
+pub fn layer(flowchart: &Flowchart) -> Layering {
+    let (oriented, loops) = break_cycles(flowchart);
+    let ranks_of_nodes = assign_ranks(flowchart, &oriented);
+
+    let rank_count = ranks_of_nodes.iter().max().map_or(0, |max| max + 1);
+    let mut ranks: Vec<Vec<Vertex>> = vec![vec![]; rank_count];
+    for node in &flowchart.nodes {
+        ranks[ranks_of_nodes[node.id]].push(Vertex::Node(node.id));
+    }
+
+    let mut routes = vec![];
+    for edge in &oriented {
+        let top = ranks_of_nodes[edge.upper];
+        let bottom = ranks_of_nodes[edge.lower];
+        let mut vertices = vec![Vertex::Node(edge.upper)];
+        for (n, rank) in (top + 1..bottom).enumerate() {
+            let bend = Vertex::Bend(edge.edge_id, n);
+            ranks[rank].push(bend);
+            vertices.push(bend);
+        }
+        vertices.push(Vertex::Node(edge.lower));
+
+        let label_at = flowchart.edges[edge.edge_id]
+            .label
+            .as_ref()
+            .filter(|_| vertices.len() > 2)
+            .map(|_| vertices.len() / 2);
+        if edge.reversed {
+            vertices.reverse();
+        }
+        let label_at = label_at.map(|i| {
+            if edge.reversed {
+                vertices.len() - 1 - i
+            } else {
+                i
+            }
+        });
+        routes.push(Route {
+            edge_id: edge.edge_id,
+            vertices,
+            label_at,
+        });
+    }
+
+    let filler_links = add_fillers(flowchart, &mut ranks);
+    order_ranks(&mut ranks, &oriented, &routes, &filler_links);
+    group_subgraphs(flowchart, &mut ranks);
+
+    Layering {
+        ranks,
+        routes,
+        loops,
+    }
+}
+
+/// Reverses the edges closing a cycle, found by a depth first search in
+/// declaration order, so the remaining graph is acyclic.
+fn break_cycles(flowchart: &Flowchart) -> (Vec<Segmented>, Vec<EdgeId>) {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        New,
+        OnStack,
+        Done,
+    }
+
+    let mut reversed = vec![false; flowchart.edges.len()];
+    let mut state = vec![Visit::New; flowchart.nodes.len()];
+    for root in &flowchart.nodes {
+        if state[root.id] != Visit::New {
+            continue;
+        }
+        // (node, index of the next outgoing edge to look at)
+        let mut stack = vec![(root.id, 0)];
+        state[root.id] = Visit::OnStack;
+        while let Some((node, next_edge)) = stack.pop() {
+            let outgoing = flowchart
+                .edges
+                .iter()
+                .skip(next_edge)
+                .find(|e| e.from == node && e.to != node);
+            match outgoing {
+                Some(edge) => {
+                    stack.push((node, edge.id + 1));
+                    match state[edge.to] {
+                        Visit::New => {
+                            state[edge.to] = Visit::OnStack;
+                            stack.push((edge.to, 0));
+                        }
+                        Visit::OnStack => reversed[edge.id] = true,
+                        Visit::Done => {}
+                    }
+                }
+                None => state[node] = Visit::Done,
+            }
+        }
+    }
+
+    let mut oriented = vec![];
+    let mut loops = vec![];
+    for edge in &flowchart.edges {
+        if edge.from == edge.to {
+            loops.push(edge.id);
+            continue;
+        }
+        let (upper, lower) = if reversed[edge.id] {
+            (edge.to, edge.from)
+        } else {
+            (edge.from, edge.to)
+        };
+        // labelled edges need a bend in between to carry the label
+        let min_length = if edge.label.is_some() {
+            edge.length.max(2)
+        } else {
+            edge.length.max(1)
+        };
+        oriented.push(Segmented {
+            edge_id: edge.id,
+            upper,
+            lower,
+            reversed: reversed[edge.id],
+            min_length,
+        });
+    }
+    (oriented, loops)
+}
+
+/// Longest path ranking, afterwards nodes without predecessors are moved
+/// down as close to their successors as possible to shorten their edges.
+fn assign_ranks(flowchart: &Flowchart, oriented: &[Segmented]) -> Vec<usize> {
+    let node_count = flowchart.nodes.len();
+    let mut in_degree = vec![0; node_count];
+    for edge in oriented {
+        in_degree[edge.lower] += 1;
+    }
+    let mut ranks = vec![0; node_count];
+    let mut ready: Vec<NodeId> = (0..node_count)
+        .filter(|&n| in_degree[n] == 0)
+        .rev()
+        .collect();
+    while let Some(node) = ready.pop() {
+        for edge in oriented.iter().filter(|e| e.upper == node) {
+            ranks[edge.lower] = ranks[edge.lower].max(ranks[node] + edge.min_length);
+            in_degree[edge.lower] -= 1;
+            if in_degree[edge.lower] == 0 {
+                ready.push(edge.lower);
+            }
+        }
+    }
+
+    for node in 0..node_count {
+        let has_predecessor = oriented.iter().any(|e| e.lower == node);
+        let closest_successor = oriented
+            .iter()
+            .filter(|e| e.upper == node)
+            .map(|e| ranks[e.lower] - e.min_length)
+            .min();
+        if let (false, Some(rank)) = (has_predecessor, closest_successor) {
+            ranks[node] = rank;
+        }
+    }
+    ranks
+}
+
+/// Barycenter heuristic, sweeping down and up the ranks and keeping the
+/// ordering with the fewest crossings.
+fn order_ranks(
+    ranks: &mut [Vec<Vertex>],
+    oriented: &[Segmented],
+    routes: &[Route],
+    filler_links: &[(Vertex, Vertex)],
+) {
+    let mut below: HashMap<Vertex, Vec<Vertex>> = HashMap::new();
+    let mut above: HashMap<Vertex, Vec<Vertex>> = HashMap::new();
+    let mut links = filler_links.to_vec();
+    for (edge, route) in oriented.iter().zip(routes) {
+        let mut vertices = route.vertices.clone();
+        if edge.reversed {
+            vertices.reverse();
+        }
+        links.extend(vertices.into_iter().tuple_windows::<(Vertex, Vertex)>());
+    }
+    for (upper, lower) in links {
+        below.entry(upper).or_default().push(lower);
+        above.entry(lower).or_default().push(upper);
+    }
+
+    let mut best = ranks.to_vec();
+    let mut best_crossings = crossings(ranks, &below);
+    for sweep in 0..ORDERING_SWEEPS {
+        if sweep % 2 == 0 {
+            for r in 1..ranks.len() {
+                let (fixed, free) = ranks.split_at_mut(r);
+                sort_by_barycenter(&mut free[0], &fixed[r - 1], &above);
+            }
+        } else {
+            for r in (0..ranks.len().saturating_sub(1)).rev() {
+                let (free, fixed) = ranks.split_at_mut(r + 1);
+                sort_by_barycenter(&mut free[r], &fixed[0], &below);
+            }
+        }
+        let count = crossings(ranks, &below);
+        if count < best_crossings {
+            best_crossings = count;
+            best = ranks.to_vec();
+        }
+    }
+    ranks.clone_from_slice(&best);
+}
+
+fn sort_by_barycenter(
+    rank: &mut [Vertex],
+    fixed: &[Vertex],
+    neighbours: &HashMap<Vertex, Vec<Vertex>>,
+) {
+    let position = |v: &Vertex| fixed.iter().position(|f| f == v);
+    let barycenters: HashMap<Vertex, f64> = rank
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let positions: Vec<usize> = neighbours
+                .get(v)
+                .map(|ns| ns.iter().filter_map(position).collect())
+                .unwrap_or_default();
+            let barycenter = if positions.is_empty() {
+                i as f64
+            } else {
+                positions.iter().sum::<usize>() as f64 / positions.len() as f64
+            };
+            (*v, barycenter)
+        })
+        .collect();
+    rank.sort_by(|a, b| barycenters[a].total_cmp(&barycenters[b]));
+}
+
+fn crossings(ranks: &[Vec<Vertex>], below: &HashMap<Vertex, Vec<Vertex>>) -> usize {
+    let mut count = 0;
+    for (upper, lower) in ranks.iter().tuple_windows() {
+        let segments: Vec<(usize, usize)> = upper
+            .iter()
+            .enumerate()
+            .flat_map(|(i, v)| {
+                below
+                    .get(v)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|w| lower.iter().position(|l| l == w))
+                    .map(move |j| (i, j))
+            })
+            .collect();
+        for ((a1, b1), (a2, b2)) in segments.iter().tuple_combinations() {
+            if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// A subgraph box covers every rank from its first to its last member. Ranks in
+/// between without members get a filler, chained to the members of the
+/// neighbouring ranks so the ordering keeps it in line with them.
+fn add_fillers(flowchart: &Flowchart, ranks: &mut [Vec<Vertex>]) -> Vec<(Vertex, Vertex)> {
+    let mut links = vec![];
+    // nested subgraphs first, their fillers count as members of the parents
+    for subgraph in flowchart.subgraphs.iter().rev() {
+        let is_member = |v: &Vertex| vertex_subgraphs(flowchart, v).contains(&subgraph.id);
+        let member_ranks: Vec<usize> = ranks
+            .iter()
+            .positions(|rank| rank.iter().any(is_member))
+            .collect();
+        let (Some(&first), Some(&last)) = (member_ranks.first(), member_ranks.last()) else {
+            continue;
+        };
+        let mut previous: Option<Vertex> = None;
+        for (r, rank) in ranks.iter_mut().enumerate().take(last + 1).skip(first) {
+            let representative = match rank.iter().find(|v| is_member(v)) {
+                Some(member) => *member,
+                None => {
+                    let filler = Vertex::Filler(subgraph.id, r);
+                    rank.push(filler);
+                    filler
+                }
+            };
+            if let Some(previous) = previous {
+                links.push((previous, representative));
+            }
+            previous = Some(representative);
+        }
+    }
+    links
+}
+
+/// Members of a subgraph have to be next to each other within every rank so
+/// the subgraph can be drawn as a box around them.
+fn group_subgraphs(flowchart: &Flowchart, ranks: &mut [Vec<Vertex>]) {
+    if flowchart.subgraphs.is_empty() {
+        return;
+    }
+    for rank in ranks.iter_mut() {
+        let paths: Vec<Vec<SubgraphId>> = rank
+            .iter()
+            .map(|v| vertex_subgraphs(flowchart, v))
+            .collect();
+        let mean_position = |subgraph: SubgraphId| {
+            let members: Vec<usize> = paths.iter().positions(|p| p.contains(&subgraph)).collect();
+            members.iter().sum::<usize>() as f64 / members.len() as f64
+        };
+        let keys: Vec<Vec<f64>> = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let mut key: Vec<f64> = path.iter().map(|s| mean_position(*s)).collect();
+                key.push(i as f64);
+                key
+            })
+            .collect();
+        let order: Vec<usize> = (0..rank.len())
+            .sorted_by(|a, b| {
+                keys[*a]
+                    .iter()
+                    .zip(&keys[*b])
+                    .map(|(x, y)| x.total_cmp(y))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(keys[*a].len().cmp(&keys[*b].len()))
+            })
+            .collect();
+        let reordered: Vec<Vertex> = order.iter().map(|i| rank[*i]).collect();
+        rank.copy_from_slice(&reordered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layering(src: &str) -> (Flowchart, Layering) {
+        let flowchart = parse(src.to_string()).unwrap();
+        let layering = layer(&flowchart);
+        (flowchart, layering)
+    }
+
+    fn node(flowchart: &Flowchart, name: &str) -> Vertex {
+        Vertex::Node(flowchart.nodes.iter().find(|n| n.name == name).unwrap().id)
+    }
+
+    #[test]
+    fn test_ranks() {
+        let (flowchart, layering) = layering("flowchart TD\nA --> B\nB --> C\nA --> C\nC --> A");
+        let rank = |name| layering.rank_of(node(&flowchart, name)).unwrap();
+        assert_eq!((0, 1, 2), (rank("A"), rank("B"), rank("C")));
+        for route in &layering.routes {
+            let ranks: Vec<usize> = route
+                .vertices
+                .iter()
+                .map(|v| layering.rank_of(*v).unwrap())
+                .collect();
+            // one rank at a time, downwards but for the edge closing the cycle
+            let step = if flowchart.edges[route.edge_id].to == 0 {
+                -1
+            } else {
+                1
+            };
+            for (upper, lower) in ranks.iter().tuple_windows() {
+                assert_eq!(step, *lower as i64 - *upper as i64);
+            }
+        }
+        // the long edges bend in the middle rank
+        assert_eq!(
+            vec![Vertex::Node(1), Vertex::Bend(2, 0), Vertex::Bend(3, 0)],
+            layering.ranks[1]
+        );
+    }
+
+    #[test]
+    fn test_crossing_reduction() {
+        // in declaration order the edges of A and B cross
+        let (_, layering) = layering("flowchart TD\nA --> D\nB --> C\nA --> E\nB --> F");
+        let mut below: HashMap<Vertex, Vec<Vertex>> = HashMap::new();
+        for route in &layering.routes {
+            for (upper, lower) in route.vertices.iter().tuple_windows() {
+                below.entry(*upper).or_default().push(*lower);
+            }
+        }
+        assert_eq!(0, crossings(&layering.ranks, &below));
+    }
+}
@@ -0,0 +1,22 @@
+pub mod layering;
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(
+    Flowchart,
+    FlowchartLayout,
+    ["flowchart", "graph"],
+    |options| SvgConfig {
+        max_label_length: 20,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        corner_radius: 2.0,
+        rank_gutter: 40.0,
+        node_gutter: 20.0,
+        subgraph_padding: 10.0,
+    }
+);
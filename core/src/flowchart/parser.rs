@@ -0,0 +1,853 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::parser::choice::choice;
+use combine::parser::repeat::take_until;
+use combine::{
+    attempt, eof, many, many1, none_of, not_followed_by, one_of, optional, sep_by1, skip_many,
+    skip_many1, ParseError, Parser, Stream,
+};
+
+use crate::parsing::{blank, blank1, comment_parser, keyword, skipped_parser};
+
+pub type NodeId = usize;
+pub type EdgeId = usize;
+pub type SubgraphId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    TopToBottom,
+    BottomToTop,
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeShape {
+    Rectangle,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    ParallelogramAlt,
+    Trapezoid,
+    TrapezoidAlt,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: NodeId,
+    pub name: String,
+    pub label: String,
+    pub shape: NodeShape,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stroke {
+    Solid,
+    Dotted,
+    Thick,
+    Invisible,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgeEnd {
+    None,
+    Arrow,
+    Circle,
+    Cross,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub id: EdgeId,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub label: Option<String>,
+    pub stroke: Stroke,
+    pub start: EdgeEnd,
+    pub end: EdgeEnd,
+    /// Minimal number of ranks the edge spans, `-->` is 1, `--->` is 2 and so on.
+    pub length: usize,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subgraph {
+    pub id: SubgraphId,
+    pub name: String,
+    pub title: String,
+    pub direction: Option<Direction>,
+    pub parent: Option<SubgraphId>,
+    pub nodes: Vec<NodeId>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flowchart {
+    pub direction: Direction,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    pub subgraphs: Vec<Subgraph>,
+}
+
+impl Flowchart {
+    /// The subgraphs containing `node_id`, outermost first.
+    pub fn subgraph_path(&self, node_id: NodeId) -> Vec<SubgraphId> {
+        self.subgraphs
+            .iter()
+            .find(|s| s.nodes.contains(&node_id))
+            .map_or(vec![], |s| self.subgraph_ancestors(s.id))
+    }
+
+    /// `subgraph_id` and the subgraphs it is nested in, outermost first.
+    pub fn subgraph_ancestors(&self, subgraph_id: SubgraphId) -> Vec<SubgraphId> {
+        let mut path = vec![];
+        let mut current = Some(subgraph_id);
+        while let Some(id) = current {
+            path.push(id);
+            current = self.subgraphs[id].parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeRef {
+    pub name: String,
+    pub shape: Option<(NodeShape, String)>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Link {
+    pub stroke: Stroke,
+    pub start: EdgeEnd,
+    pub end: EdgeEnd,
+    pub label: Option<String>,
+    pub length: usize,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header(Direction),
+    Direction(Direction),
+    Subgraph(String),
+    End,
+    Chain(Vec<NodeRef>, Vec<(Link, Vec<NodeRef>)>),
+}
+
+pub fn parse(src: String) -> Result<Flowchart, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_flowchart(statements),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_flowchart(statements: Vec<Statement>) -> Result<Flowchart, StringStreamError> {
+    let mut flowchart = Flowchart {
+        direction: Direction::TopToBottom,
+        nodes: vec![],
+        edges: vec![],
+        subgraphs: vec![],
+    };
+    let mut node_ids: HashMap<String, NodeId> = HashMap::new();
+    let mut open_subgraphs: Vec<SubgraphId> = vec![];
+
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Header(direction) => flowchart.direction = direction,
+            Statement::Direction(direction) => match open_subgraphs.last() {
+                Some(&id) => flowchart.subgraphs[id].direction = Some(direction),
+                None => flowchart.direction = direction,
+            },
+            Statement::Subgraph(header) => {
+                let id = flowchart.subgraphs.len();
+                let (name, title) = subgraph_name_and_title(&header, id);
+                flowchart.subgraphs.push(Subgraph {
+                    id,
+                    name,
+                    title,
+                    direction: None,
+                    parent: open_subgraphs.last().copied(),
+                    nodes: vec![],
+                });
+                open_subgraphs.push(id);
+            }
+            Statement::End => {
+                open_subgraphs
+                    .pop()
+                    .ok_or(StringStreamError::UnexpectedParse)?;
+            }
+            Statement::Chain(first, links) => {
+                let mut previous =
+                    declare_nodes(&mut flowchart, &mut node_ids, &open_subgraphs, first);
+                for (link, group) in links {
+                    let next = declare_nodes(&mut flowchart, &mut node_ids, &open_subgraphs, group);
+                    for &from in &previous {
+                        for &to in &next {
+                            flowchart.edges.push(Edge {
+                                id: flowchart.edges.len(),
+                                from,
+                                to,
+                                label: link.label.clone(),
+                                stroke: link.stroke,
+                                start: link.start,
+                                end: link.end,
+                                length: link.length,
+                            });
+                        }
+                    }
+                    previous = next;
+                }
+            }
+        }
+    }
+
+    if open_subgraphs.is_empty() {
+        Ok(flowchart)
+    } else {
+        Err(StringStreamError::UnexpectedParse)
+    }
+}
+
+/// Registers the referenced nodes, updating label and shape if given, and makes
+/// nodes that are not part of any subgraph yet members of the innermost open one.
+fn declare_nodes(
+    flowchart: &mut Flowchart,
+    node_ids: &mut HashMap<String, NodeId>,
+    open_subgraphs: &[SubgraphId],
+    refs: Vec<NodeRef>,
+) -> Vec<NodeId> {
+    let mut ids = vec![];
+    for node_ref in refs {
+        let id = *node_ids.entry(node_ref.name.clone()).or_insert_with(|| {
+            flowchart.nodes.push(Node {
+                id: flowchart.nodes.len(),
+                name: node_ref.name.clone(),
+                label: node_ref.name.clone(),
+                shape: NodeShape::Rectangle,
+            });
+            flowchart.nodes.len() - 1
+        });
+        if let Some((shape, label)) = node_ref.shape {
+            flowchart.nodes[id].shape = shape;
+            flowchart.nodes[id].label = label;
+        }
+        if let Some(&subgraph) = open_subgraphs.last() {
+            if !flowchart.subgraphs.iter().any(|s| s.nodes.contains(&id)) {
+                flowchart.subgraphs[subgraph].nodes.push(id);
+            }
+        }
+        ids.push(id);
+    }
+    ids
+}
+
+fn subgraph_name_and_title(header: &str, id: SubgraphId) -> (String, String) {
+    let header = header.trim();
+    match header.split_once('[') {
+        Some((name, title)) if header.ends_with(']') => (
+            name.trim().to_string(),
+            label_text(title.trim_end_matches(']')),
+        ),
+        _ if header.is_empty() => (format!("subgraph{id}"), String::new()),
+        _ => (header.to_string(), label_text(header)),
+    }
+}
+
+/// Strips the quotes around a label and turns `<br>` into line breaks.
+fn label_text(raw: &str) -> String {
+    let trimmed = raw.trim();
+    let unquoted = trimmed
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(trimmed);
+    unquoted
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+}
+
+fn direction_parser<Input>() -> impl Parser<Input, Output = Direction>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        attempt(string("TB")).map(|_| Direction::TopToBottom),
+        attempt(string("TD")).map(|_| Direction::TopToBottom),
+        string("BT").map(|_| Direction::BottomToTop),
+        string("LR").map(|_| Direction::LeftToRight),
+        string("RL").map(|_| Direction::RightToLeft),
+    ))
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        choice((keyword("flowchart"), keyword("graph"))),
+        optional(attempt((blank1(), direction_parser()))),
+    )
+        .map(|(_, direction)| {
+            Statement::Header(direction.map_or(Direction::TopToBottom, |(_, d)| d))
+        })
+}
+
+fn direction_statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword("direction"), blank1(), direction_parser())
+        .map(|(_, _, direction)| Statement::Direction(direction))
+}
+
+fn subgraph_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword("subgraph"), blank(), many(none_of("\n;".chars())))
+        .map(|(_, _, header): (_, _, String)| Statement::Subgraph(header))
+}
+
+/// Styling and interaction statements don't affect the layout and are skipped.
+fn ignored_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&[
+        "classDef",
+        "class",
+        "style",
+        "linkStyle",
+        "click",
+        "accTitle",
+        "accDescr",
+    ])
+    .map(|_| Statement::Empty)
+}
+
+fn node_name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(choice((alpha_num(), char('_'))))
+}
+
+/// Text between `open` and `close` on a single line.
+fn enclosed<Input>(
+    open: &'static str,
+    close: &'static str,
+    shape: NodeShape,
+) -> impl Parser<Input, Output = (NodeShape, String)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((
+        string(open),
+        take_until(attempt(choice((string(close), string("\n"))))),
+        string(close),
+    ))
+    .map(move |(_, text, _): (_, String, _)| (shape, label_text(&text)))
+}
+
+/// `[/text/]`, `[\text\]`, `[/text\]` and `[\text/]` only differ in their slants.
+fn slanted<Input>() -> impl Parser<Input, Output = (NodeShape, String)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let slant = || choice((char('/'), char('\\')));
+    attempt((
+        char('['),
+        slant(),
+        take_until(attempt(choice((string("/]"), string("\\]"), string("\n"))))),
+        slant(),
+        char(']'),
+    ))
+    .map(|(_, open, text, close, _): (_, char, String, char, _)| {
+        let shape = match (open, close) {
+            ('/', '/') => NodeShape::Parallelogram,
+            ('\\', '\\') => NodeShape::ParallelogramAlt,
+            ('/', _) => NodeShape::Trapezoid,
+            _ => NodeShape::TrapezoidAlt,
+        };
+        (shape, label_text(&text))
+    })
+}
+
+fn shape_parser<Input>() -> impl Parser<Input, Output = (NodeShape, String)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        enclosed("(((", ")))", NodeShape::DoubleCircle),
+        enclosed("((", "))", NodeShape::Circle),
+        enclosed("([", "])", NodeShape::Stadium),
+        enclosed("(", ")", NodeShape::Round),
+        enclosed("[[", "]]", NodeShape::Subroutine),
+        enclosed("[(", ")]", NodeShape::Cylinder),
+        slanted(),
+        enclosed("[", "]", NodeShape::Rectangle),
+        enclosed("{{", "}}", NodeShape::Hexagon),
+        enclosed("{", "}", NodeShape::Rhombus),
+        enclosed(">", "]", NodeShape::Asymmetric),
+    ))
+}
+
+fn node_ref_parser<Input>() -> impl Parser<Input, Output = NodeRef>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let class = attempt((
+        string(":::"),
+        skip_many1(choice((alpha_num(), one_of("_-".chars())))),
+    ));
+    (
+        node_name_parser(),
+        optional(shape_parser()),
+        optional(class),
+    )
+        .map(|(name, shape, _)| NodeRef { name, shape })
+}
+
+fn node_group_parser<Input>() -> impl Parser<Input, Output = Vec<NodeRef>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    sep_by1(node_ref_parser(), attempt((blank(), char('&'), blank())))
+}
+
+fn arrow_head<Input>() -> impl Parser<Input, Output = EdgeEnd>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        char('>').map(|_| EdgeEnd::Arrow),
+        char('o').map(|_| EdgeEnd::Circle),
+        char('x').map(|_| EdgeEnd::Cross),
+    ))
+}
+
+fn arrow_tail<Input>() -> impl Parser<Input, Output = EdgeEnd>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        char('<').map(|_| EdgeEnd::Arrow),
+        char('o').map(|_| EdgeEnd::Circle),
+        char('x').map(|_| EdgeEnd::Cross),
+    ))
+}
+
+/// Text of a label written inside the link, e.g. the `yes` of `-- yes -->`.
+fn inline_label<Input>(until: &'static str) -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    take_until(attempt(choice((string(until), string("\n")))))
+}
+
+fn link_body_parser<Input>() -> impl Parser<Input, Output = (Stroke, EdgeEnd, usize, Option<String>)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let end = || optional(arrow_head()).map(|end| end.unwrap_or(EdgeEnd::None));
+    let solid_labelled = (
+        string("--"),
+        blank1(),
+        inline_label("--"),
+        string("--"),
+        skip_many(char('-')),
+        end(),
+    )
+        .map(|(_, _, label, _, _, end): (_, _, String, _, _, _)| {
+            (Stroke::Solid, end, 1, Some(label_text(&label)))
+        });
+    let dotted_labelled = (
+        string("-."),
+        blank(),
+        inline_label(".-"),
+        string(".-"),
+        end(),
+    )
+        .map(|(_, _, label, _, end): (_, _, String, _, _)| {
+            (Stroke::Dotted, end, 1, Some(label_text(&label)))
+        });
+    let thick_labelled = (
+        string("=="),
+        blank1(),
+        inline_label("=="),
+        string("=="),
+        skip_many(char('=')),
+        end(),
+    )
+        .map(|(_, _, label, _, _, end): (_, _, String, _, _, _)| {
+            (Stroke::Thick, end, 1, Some(label_text(&label)))
+        });
+    let dotted = (char('-'), many1(char('.')), char('-'), end())
+        .map(|(_, dots, _, end): (_, String, _, _)| (Stroke::Dotted, end, dots.len(), None));
+    let solid_with_head = (string("--"), many(char('-')), arrow_head())
+        .map(|(_, extra, end): (_, String, _)| (Stroke::Solid, end, extra.len() + 1, None));
+    let solid_open = (string("---"), many(char('-')))
+        .map(|(_, extra): (_, String)| (Stroke::Solid, EdgeEnd::None, extra.len() + 1, None));
+    let thick_with_head =
+        (string("=="), many(char('=')), char('>')).map(|(_, extra, _): (_, String, _)| {
+            (Stroke::Thick, EdgeEnd::Arrow, extra.len() + 1, None)
+        });
+    let thick_open = (string("==="), many(char('=')))
+        .map(|(_, extra): (_, String)| (Stroke::Thick, EdgeEnd::None, extra.len() + 1, None));
+    let invisible =
+        (string("~~~"), skip_many(char('~'))).map(|_| (Stroke::Invisible, EdgeEnd::None, 1, None));
+
+    choice((
+        attempt(solid_labelled),
+        attempt(dotted_labelled),
+        attempt(thick_labelled),
+        attempt(dotted),
+        attempt(solid_with_head),
+        attempt(solid_open),
+        attempt(thick_with_head),
+        attempt(thick_open),
+        attempt(invisible),
+    ))
+}
+
+fn link_parser<Input>() -> impl Parser<Input, Output = Link>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let start = optional(attempt((arrow_tail(), not_followed_by(alpha_num()))));
+    let pipe_label = optional(attempt((
+        blank(),
+        char('|'),
+        take_until(one_of("|\n".chars())),
+        char('|'),
+    )));
+    (start, link_body_parser(), pipe_label).map(
+        |(start, (stroke, end, length, inline), pipe): (_, _, Option<(_, _, String, _)>)| Link {
+            stroke,
+            start: start.map_or(EdgeEnd::None, |(tail, _)| tail),
+            end,
+            label: pipe.map(|(_, _, label, _)| label_text(&label)).or(inline),
+            length,
+        },
+    )
+}
+
+fn chain_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        node_group_parser(),
+        many(attempt((
+            blank(),
+            link_parser(),
+            blank(),
+            node_group_parser(),
+        ))),
+    )
+        .map(|(first, links): (_, Vec<(_, Link, _, Vec<NodeRef>)>)| {
+            Statement::Chain(
+                first,
+                links
+                    .into_iter()
+                    .map(|(_, link, _, group)| (link, group))
+                    .collect(),
+            )
+        })
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            direction_statement_parser(),
+            subgraph_parser(),
+            keyword("end").map(|_| Statement::End),
+            ignored_parser(),
+            chain_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        one_of(";\n".chars()),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, shape: Option<(NodeShape, &str)>) -> NodeRef {
+        NodeRef {
+            name: name.to_string(),
+            shape: shape.map(|(s, l)| (s, l.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_header_parser() {
+        assert_eq!(
+            Ok((Statement::Header(Direction::LeftToRight), "\n")),
+            header_parser().parse("graph LR\n")
+        );
+        assert_eq!(
+            Ok((Statement::Header(Direction::TopToBottom), "\n")),
+            header_parser().parse("flowchart TD\n")
+        );
+        assert_eq!(
+            Ok((Statement::Header(Direction::TopToBottom), ";")),
+            header_parser().parse("graph;")
+        );
+    }
+
+    #[test]
+    fn test_shape_parser() {
+        let cases = [
+            ("[a]", NodeShape::Rectangle),
+            ("(a)", NodeShape::Round),
+            ("([a])", NodeShape::Stadium),
+            ("[[a]]", NodeShape::Subroutine),
+            ("[(a)]", NodeShape::Cylinder),
+            ("((a))", NodeShape::Circle),
+            ("(((a)))", NodeShape::DoubleCircle),
+            (">a]", NodeShape::Asymmetric),
+            ("{a}", NodeShape::Rhombus),
+            ("{{a}}", NodeShape::Hexagon),
+            ("[/a/]", NodeShape::Parallelogram),
+            ("[\\a\\]", NodeShape::ParallelogramAlt),
+            ("[/a\\]", NodeShape::Trapezoid),
+            ("[\\a/]", NodeShape::TrapezoidAlt),
+        ];
+        for (input, shape) in cases {
+            assert_eq!(
+                Ok(((shape, "a".to_string()), "")),
+                shape_parser().parse(input),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_quoted_label() {
+        assert_eq!(
+            Ok((node("A", Some((NodeShape::Rectangle, "Hello (world)"))), "")),
+            node_ref_parser().parse("A[\"Hello (world)\"]")
+        );
+    }
+
+    #[test]
+    fn test_link_parser() {
+        let link = |stroke, start, end, label: Option<&str>, length| Link {
+            stroke,
+            start,
+            end,
+            label: label.map(|l| l.to_string()),
+            length,
+        };
+        assert_eq!(
+            Ok((
+                link(Stroke::Solid, EdgeEnd::None, EdgeEnd::Arrow, None, 1),
+                ""
+            )),
+            link_parser().parse("-->")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Solid, EdgeEnd::None, EdgeEnd::None, None, 2),
+                ""
+            )),
+            link_parser().parse("----")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Dotted, EdgeEnd::None, EdgeEnd::Arrow, None, 1),
+                ""
+            )),
+            link_parser().parse("-.->")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Thick, EdgeEnd::None, EdgeEnd::Arrow, None, 1),
+                ""
+            )),
+            link_parser().parse("==>")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Solid, EdgeEnd::Arrow, EdgeEnd::Arrow, None, 1),
+                ""
+            )),
+            link_parser().parse("<-->")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Solid, EdgeEnd::Circle, EdgeEnd::Cross, None, 1),
+                ""
+            )),
+            link_parser().parse("o--x")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Solid, EdgeEnd::None, EdgeEnd::Arrow, Some("yes"), 1),
+                ""
+            )),
+            link_parser().parse("-->|yes|")
+        );
+        assert_eq!(
+            Ok((
+                link(
+                    Stroke::Solid,
+                    EdgeEnd::None,
+                    EdgeEnd::Arrow,
+                    Some("no way"),
+                    1
+                ),
+                ""
+            )),
+            link_parser().parse("-- no way -->")
+        );
+        assert_eq!(
+            Ok((
+                link(
+                    Stroke::Dotted,
+                    EdgeEnd::None,
+                    EdgeEnd::Arrow,
+                    Some("maybe"),
+                    1
+                ),
+                ""
+            )),
+            link_parser().parse("-. maybe .->")
+        );
+        assert_eq!(
+            Ok((
+                link(Stroke::Invisible, EdgeEnd::None, EdgeEnd::None, None, 1),
+                ""
+            )),
+            link_parser().parse("~~~")
+        );
+    }
+
+    #[test]
+    fn test_chain_parser() {
+        let arrow = Link {
+            stroke: Stroke::Solid,
+            start: EdgeEnd::None,
+            end: EdgeEnd::Arrow,
+            label: None,
+            length: 1,
+        };
+        let expected = Statement::Chain(
+            vec![node("A", Some((NodeShape::Round, "Start")))],
+            vec![(arrow, vec![node("B", None), node("C", None)])],
+        );
+        assert_eq!(
+            Ok((expected, "\n")),
+            chain_parser().parse("A(Start) --> B & C\n")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"flowchart LR
+            %% a comment
+            A[Start] --> B{Is it?}
+            B -->|Yes| C
+            subgraph one [The first]
+              C --> D
+            end
+        "#;
+        let flowchart = parse(input.to_string()).unwrap();
+        assert_eq!(Direction::LeftToRight, flowchart.direction);
+        assert_eq!(
+            vec!["A", "B", "C", "D"],
+            flowchart
+                .nodes
+                .iter()
+                .map(|n| n.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(NodeShape::Rhombus, flowchart.nodes[1].shape);
+        assert_eq!("Is it?", flowchart.nodes[1].label);
+        assert_eq!(3, flowchart.edges.len());
+        assert_eq!(Some("Yes".to_string()), flowchart.edges[1].label);
+        assert_eq!(
+            vec![Subgraph {
+                id: 0,
+                name: "one".to_string(),
+                title: "The first".to_string(),
+                direction: None,
+                parent: None,
+                nodes: vec![2, 3],
+            }],
+            flowchart.subgraphs
+        );
+    }
+
+    #[test]
+    fn test_parse_semicolons() {
+        let flowchart = parse("graph TD;A-->B;B-->C;".to_string()).unwrap();
+        assert_eq!(3, flowchart.nodes.len());
+        assert_eq!(2, flowchart.edges.len());
+    }
+
+    #[test]
+    fn test_unbalanced_subgraph() {
+        assert!(parse("graph TD\nsubgraph a\nA-->B\n".to_string()).is_err());
+        assert!(parse("graph TD\nend\n".to_string()).is_err());
+    }
+}
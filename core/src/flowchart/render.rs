@@ -0,0 +1,1080 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use svg::node::element::{Circle, Definitions, Group, Line, Marker, Path, Polygon, SVG};
+use svg::Node as _;
+
+use cassowary::strength::{self, REQUIRED, STRONG, WEAK};
+use cassowary::Expression;
+use cassowary::WeightedRelation::*;
+
+use crate::flowchart::layering::{layer, vertex_subgraphs, Layering, Vertex};
+use crate::flowchart::*;
+use crate::layout::{AlignmentAnchor, Block, BlockId, Layout, LayoutBlock, Orientation};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+    pub subgraph_padding: f64,
+}
+
+const LOOP_SIZE: f64 = 20.0;
+
+/// Ranks are stacked along the main axis, the vertices of a rank are placed
+/// next to each other along the cross axis.
+#[derive(Clone, Copy)]
+struct Axes {
+    ranks_vertical: bool,
+}
+
+impl Axes {
+    fn main_orientation(&self) -> Orientation {
+        if self.ranks_vertical {
+            Orientation::Vertical
+        } else {
+            Orientation::Horizontal
+        }
+    }
+
+    fn cross_orientation(&self) -> Orientation {
+        if self.ranks_vertical {
+            Orientation::Horizontal
+        } else {
+            Orientation::Vertical
+        }
+    }
+
+    fn main_start(&self, b: &LayoutBlock) -> Expression {
+        if self.ranks_vertical {
+            b.top().into()
+        } else {
+            b.left().into()
+        }
+    }
+
+    fn main_end(&self, b: &LayoutBlock) -> Expression {
+        if self.ranks_vertical {
+            b.bottom()
+        } else {
+            b.right()
+        }
+    }
+
+    fn cross_start(&self, b: &LayoutBlock) -> Expression {
+        if self.ranks_vertical {
+            b.left().into()
+        } else {
+            b.top().into()
+        }
+    }
+
+    fn cross_end(&self, b: &LayoutBlock) -> Expression {
+        if self.ranks_vertical {
+            b.right()
+        } else {
+            b.bottom()
+        }
+    }
+
+    fn cross_center(&self, b: &LayoutBlock) -> Expression {
+        if self.ranks_vertical {
+            b.left() + b.width * 0.5
+        } else {
+            b.top() + b.height * 0.5
+        }
+    }
+}
+
+struct NodeBox {
+    shape: NodeShape,
+    block: BlockId,
+    text_block: BlockId,
+    lines: Vec<String>,
+}
+
+struct EdgeLabel {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+struct Cluster {
+    block: BlockId,
+    title_block: BlockId,
+    title_lines: Vec<String>,
+}
+
+/// Minimal size of a node's outline around its label.
+fn add_shape_constraints(layout: &mut Layout, shape: NodeShape, outer: BlockId, inner: BlockId) {
+    let o = layout.b(outer);
+    let i = layout.b(inner);
+    let (ow, oh, iw, ih) = (o.width, o.height, i.width, i.height);
+
+    let (min_width, min_height): (Expression, Expression) = match shape {
+        NodeShape::Rectangle | NodeShape::Round => (iw.into(), ih.into()),
+        NodeShape::Stadium | NodeShape::Parallelogram | NodeShape::ParallelogramAlt => {
+            (iw + ih, ih.into())
+        }
+        NodeShape::Trapezoid | NodeShape::TrapezoidAlt => (iw + ih, ih.into()),
+        NodeShape::Subroutine => (iw + 16.0, ih.into()),
+        NodeShape::Cylinder => (iw.into(), ih + 16.0),
+        NodeShape::Asymmetric | NodeShape::Hexagon => (iw + ih * 0.5, ih.into()),
+        // a diamond of width w + 2h and height h + w/2 just contains a w x h box
+        NodeShape::Rhombus => (iw + ih * 2.0, ih + iw * 0.5),
+        NodeShape::Circle | NodeShape::DoubleCircle => (iw + ih * 0.5, ih + ih * 0.5),
+    };
+    layout.add_constraint(ow | GE(REQUIRED) | min_width.clone());
+    layout.add_constraint(ow | EQ(STRONG) | min_width);
+    layout.add_constraint(oh | GE(REQUIRED) | min_height.clone());
+    layout.add_constraint(oh | EQ(STRONG) | min_height);
+    if let NodeShape::Circle | NodeShape::DoubleCircle = shape {
+        layout.add_constraint(ow | EQ(REQUIRED) | oh);
+    }
+
+    for orientation in [Orientation::Horizontal, Orientation::Vertical] {
+        layout.align(orientation, AlignmentAnchor::Middle, [outer, inner].iter());
+    }
+}
+
+impl NodeBox {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let outer = arrangement.block(self.block);
+        let (w, h) = (outer.width, outer.height);
+        let polygon = |points: Vec<(f64, f64)>| {
+            let points = points.iter().map(|(x, y)| format!("{x},{y}")).join(" ");
+            stroked(Polygon::new().set("points", points), "transparent")
+        };
+
+        let mut shape = translated(&outer);
+        shape = match self.shape {
+            NodeShape::Rectangle => shape.add(outline(&outer, "transparent", 0.0)),
+            NodeShape::Round => {
+                shape.add(outline(&outer, "transparent", config.corner_radius * 2.5))
+            }
+            NodeShape::Stadium => shape.add(outline(&outer, "transparent", h * 0.5)),
+            NodeShape::Subroutine => shape
+                .add(outline(&outer, "transparent", 0.0))
+                .add(stroked(
+                    Line::new()
+                        .set("x1", 8)
+                        .set("y1", 0)
+                        .set("x2", 8)
+                        .set("y2", h),
+                    "none",
+                ))
+                .add(stroked(
+                    Line::new()
+                        .set("x1", w - 8.0)
+                        .set("y1", 0)
+                        .set("x2", w - 8.0)
+                        .set("y2", h),
+                    "none",
+                )),
+            NodeShape::Cylinder => {
+                let ry = 8.0;
+                let rx = w * 0.5;
+                let body = format!(
+                    "M 0 {ry} A {rx} {ry} 0 0 0 {w} {ry} A {rx} {ry} 0 0 0 0 {ry} L 0 {bottom} A {rx} {ry} 0 0 0 {w} {bottom} L {w} {ry}",
+                    bottom = h - ry
+                );
+                shape.add(stroked(Path::new().set("d", body), "transparent"))
+            }
+            NodeShape::Circle => shape.add(stroked(
+                Circle::new()
+                    .set("cx", w * 0.5)
+                    .set("cy", h * 0.5)
+                    .set("r", w * 0.5),
+                "transparent",
+            )),
+            NodeShape::DoubleCircle => shape
+                .add(stroked(
+                    Circle::new()
+                        .set("cx", w * 0.5)
+                        .set("cy", h * 0.5)
+                        .set("r", w * 0.5),
+                    "transparent",
+                ))
+                .add(stroked(
+                    Circle::new()
+                        .set("cx", w * 0.5)
+                        .set("cy", h * 0.5)
+                        .set("r", w * 0.5 - 4.0),
+                    "transparent",
+                )),
+            NodeShape::Asymmetric => shape.add(polygon(vec![
+                (0.0, 0.0),
+                (w, 0.0),
+                (w, h),
+                (0.0, h),
+                (h * 0.25, h * 0.5),
+            ])),
+            NodeShape::Rhombus => shape.add(polygon(vec![
+                (w * 0.5, 0.0),
+                (w, h * 0.5),
+                (w * 0.5, h),
+                (0.0, h * 0.5),
+            ])),
+            NodeShape::Hexagon => {
+                let s = h * 0.25;
+                shape.add(polygon(vec![
+                    (s, 0.0),
+                    (w - s, 0.0),
+                    (w, h * 0.5),
+                    (w - s, h),
+                    (s, h),
+                    (0.0, h * 0.5),
+                ]))
+            }
+            NodeShape::Parallelogram => {
+                let s = h * 0.5;
+                shape.add(polygon(vec![(s, 0.0), (w, 0.0), (w - s, h), (0.0, h)]))
+            }
+            NodeShape::ParallelogramAlt => {
+                let s = h * 0.5;
+                shape.add(polygon(vec![(0.0, 0.0), (w - s, 0.0), (w, h), (s, h)]))
+            }
+            NodeShape::Trapezoid => {
+                let s = h * 0.5;
+                shape.add(polygon(vec![(s, 0.0), (w - s, 0.0), (w, h), (0.0, h)]))
+            }
+            NodeShape::TrapezoidAlt => {
+                let s = h * 0.5;
+                shape.add(polygon(vec![(0.0, 0.0), (w, 0.0), (w - s, h), (s, h)]))
+            }
+        };
+
+        let text_block = arrangement.block(self.text_block);
+        let mut text = translated(&text_block);
+        for t in text_lines(&self.lines, &text_block, config.font_size, config.padding) {
+            text = text.add(t);
+        }
+        Group::new().add(shape).add(text)
+    }
+
+    /// The point where a line from the center of the node towards `(x, y)`
+    /// leaves the outline.
+    fn boundary_towards(&self, arrangement: &Arrangement, x: f64, y: f64) -> (f64, f64) {
+        let b = arrangement.block(self.block);
+        let (cx, cy) = arrangement.center(self.block);
+        let (dx, dy) = (x - cx, y - cy);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+        let (hw, hh) = (b.width * 0.5, b.height * 0.5);
+        let t = match self.shape {
+            NodeShape::Rhombus => 1.0 / (dx.abs() / hw + dy.abs() / hh),
+            NodeShape::Circle | NodeShape::DoubleCircle => hw / dx.hypot(dy),
+            _ => (hw / dx.abs()).min(hh / dy.abs()),
+        };
+        (cx + dx * t, cy + dy * t)
+    }
+}
+
+impl EdgeLabel {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.block(self.block);
+        label_box(&self.lines, &block, config.font_size, config.padding)
+    }
+}
+
+impl Cluster {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.block(self.block);
+        let title = arrangement.block(self.title_block);
+        let mut title_group = translated(&title);
+        for t in text_lines(&self.title_lines, &title, config.font_size, config.padding) {
+            title_group = title_group.add(t);
+        }
+        Group::new()
+            .add(translated(&block).add(outline(&block, CLUSTER_FILL, config.corner_radius)))
+            .add(title_group)
+    }
+}
+
+/// Markers for every kind of edge end, referenced by [`edge_path`].
+pub fn edge_markers() -> Definitions {
+    let marker = |id: &str, ref_x: f64| {
+        Marker::new()
+            .set("id", id)
+            .set("markerWidth", 10)
+            .set("markerHeight", 10)
+            .set("refX", ref_x)
+            .set("refY", 5)
+            .set("orient", "auto")
+            .set("markerUnits", "userSpaceOnUse")
+    };
+    Definitions::new()
+        .add(
+            marker("flowchart-arrow-end", 10.0)
+                .add(Polygon::new().set("points", "0 1.5, 10 5, 0 8.5")),
+        )
+        .add(
+            marker("flowchart-arrow-start", 0.0)
+                .add(Polygon::new().set("points", "10 1.5, 10 8.5, 0 5")),
+        )
+        .add(marker("flowchart-circle", 5.0).add(stroked(
+            Circle::new().set("cx", 5).set("cy", 5).set("r", 4),
+            "white",
+        )))
+        .add(marker("flowchart-cross", 5.0).add(stroked(
+            Path::new().set("d", "M 1 1 L 9 9 M 9 1 L 1 9"),
+            "none",
+        )))
+}
+
+fn marker_url(end: EdgeEnd, at_start: bool) -> Option<&'static str> {
+    match (end, at_start) {
+        (EdgeEnd::None, _) => None,
+        (EdgeEnd::Arrow, false) => Some("url(#flowchart-arrow-end)"),
+        (EdgeEnd::Arrow, true) => Some("url(#flowchart-arrow-start)"),
+        (EdgeEnd::Circle, _) => Some("url(#flowchart-circle)"),
+        (EdgeEnd::Cross, _) => Some("url(#flowchart-cross)"),
+    }
+}
+
+/// A polyline through `points` styled after the stroke and ends of `edge`.
+pub fn edge_path(edge: &Edge, points: &[(f64, f64)]) -> Path {
+    let d = points
+        .iter()
+        .enumerate()
+        .map(|(i, (x, y))| format!("{} {x} {y}", if i == 0 { "M" } else { "L" }))
+        .join(" ");
+    let mut path = stroked(Path::new().set("d", d), "none");
+    match edge.stroke {
+        Stroke::Solid | Stroke::Invisible => {}
+        Stroke::Dotted => path.assign("stroke-dasharray", "3 3"),
+        Stroke::Thick => path.assign("stroke-width", 3),
+    }
+    if let Some(url) = marker_url(edge.start, true) {
+        path.assign("marker-start", url);
+    }
+    if let Some(url) = marker_url(edge.end, false) {
+        path.assign("marker-end", url);
+    }
+    path
+}
+
+/// Which side of a node, along the cross axis, an attached block goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Before,
+    After,
+}
+
+/// How a node is sized if not by its shape and label.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeSize {
+    Fixed(f64, f64),
+    /// Left to constraints the caller adds on [`Arrangement::node_block`].
+    Free,
+}
+
+/// The geometry of a flowchart: nodes, edge bends and labels placed in their
+/// ranks and subgraph boxes around their members. Other graph like diagrams
+/// build a [`Flowchart`] and reuse the arrangement with their own decorations.
+pub struct Arrangement {
+    layout: Layout<'static>,
+    direction: Direction,
+    axes: Axes,
+    layering: Layering,
+    vertex_blocks: HashMap<Vertex, BlockId>,
+    path_of: HashMap<Vertex, Vec<SubgraphId>>,
+    node_boxes: Vec<NodeBox>,
+    labels: HashMap<EdgeId, EdgeLabel>,
+    clusters: Vec<Cluster>,
+    rank_blocks: Vec<BlockId>,
+    node_gutter: f64,
+    subgraph_padding: f64,
+}
+
+impl Arrangement {
+    /// Nodes missing from `sizes` fit their shape around the label.
+    pub fn new(
+        flowchart: &Flowchart,
+        config: &SvgConfig,
+        sizes: &HashMap<NodeId, NodeSize>,
+    ) -> Arrangement {
+        let axes = Axes {
+            ranks_vertical: matches!(
+                flowchart.direction,
+                Direction::TopToBottom | Direction::BottomToTop
+            ),
+        };
+        let layering = layer(flowchart);
+        let mut layout = Layout::new();
+        let font_size = config.font_size * config.font_scale_factor;
+
+        let mut node_boxes = vec![];
+        let mut vertex_blocks: HashMap<Vertex, BlockId> = HashMap::new();
+        for node in &flowchart.nodes {
+            let (text_block, lines) = layout.add_text_block(
+                &node.label,
+                config.max_label_length,
+                config.padding,
+                font_size,
+            );
+            let block = layout.add_block();
+            match sizes.get(&node.id) {
+                Some(&NodeSize::Fixed(width, height)) => {
+                    layout.add_constraint(layout.b(block).width | EQ(REQUIRED) | width);
+                    layout.add_constraint(layout.b(block).height | EQ(REQUIRED) | height);
+                }
+                Some(NodeSize::Free) => {}
+                None => add_shape_constraints(&mut layout, node.shape, block, text_block),
+            }
+            vertex_blocks.insert(Vertex::Node(node.id), block);
+            node_boxes.push(NodeBox {
+                shape: node.shape,
+                block,
+                text_block,
+                lines,
+            });
+        }
+
+        let mut labels: HashMap<EdgeId, EdgeLabel> = HashMap::new();
+        for route in &layering.routes {
+            for (i, vertex) in route.vertices.iter().enumerate() {
+                if let Vertex::Node(_) = vertex {
+                    continue;
+                }
+                let block = match (route.label_at, &flowchart.edges[route.edge_id].label) {
+                    (Some(at), Some(label)) if at == i => {
+                        let (block, lines) = layout.add_text_block(
+                            label,
+                            config.max_label_length,
+                            config.padding,
+                            font_size,
+                        );
+                        labels.insert(route.edge_id, EdgeLabel { block, lines });
+                        block
+                    }
+                    _ => {
+                        let block = layout.add_block();
+                        layout.add_constraint(layout.b(block).width | EQ(REQUIRED) | 0.0);
+                        layout.add_constraint(layout.b(block).height | EQ(REQUIRED) | 0.0);
+                        block
+                    }
+                };
+                vertex_blocks.insert(*vertex, block);
+            }
+        }
+
+        for vertex in layering.ranks.iter().flatten() {
+            if let Vertex::Filler(..) = vertex {
+                let block = layout.add_block();
+                layout.add_constraint(layout.b(block).width | EQ(REQUIRED) | 0.0);
+                layout.add_constraint(layout.b(block).height | EQ(REQUIRED) | 0.0);
+                vertex_blocks.insert(*vertex, block);
+            }
+        }
+
+        let mut rank_blocks = vec![];
+        for rank in &layering.ranks {
+            let rank_block = layout.add_block();
+            for vertex in rank {
+                let block = vertex_blocks[vertex];
+                layout.add_constraint(
+                    axes.main_start(layout.b(block))
+                        | GE(REQUIRED)
+                        | axes.main_start(layout.b(rank_block)),
+                );
+                layout.add_constraint(
+                    axes.main_end(layout.b(block))
+                        | LE(REQUIRED)
+                        | axes.main_end(layout.b(rank_block)),
+                );
+                layout.align(
+                    axes.main_orientation(),
+                    AlignmentAnchor::Middle,
+                    [rank_block, block].iter(),
+                );
+            }
+            let blocks: Vec<BlockId> = rank.iter().map(|v| vertex_blocks[v]).collect();
+            layout.distribute(axes.cross_orientation(), config.node_gutter, blocks.iter());
+            rank_blocks.push(rank_block);
+        }
+        layout.distribute(
+            axes.main_orientation(),
+            config.rank_gutter,
+            rank_blocks.iter(),
+        );
+
+        // prefer straight edges, long edges even more so, but never at the expense
+        // of the natural size of nodes and labels
+        for route in &layering.routes {
+            for (a, b) in route.vertices.iter().tuple_windows() {
+                let strength = match (a, b) {
+                    (Vertex::Bend(..), Vertex::Bend(..)) => WEAK,
+                    _ => strength::create(0.0, 0.0, 0.5, 1.0),
+                };
+                layout.add_constraint(
+                    axes.cross_center(layout.b(vertex_blocks[a]))
+                        | EQ(strength)
+                        | axes.cross_center(layout.b(vertex_blocks[b])),
+                );
+            }
+        }
+
+        let mut path_of: HashMap<Vertex, Vec<SubgraphId>> = HashMap::new();
+        for vertex in layering.ranks.iter().flatten() {
+            path_of.insert(*vertex, vertex_subgraphs(flowchart, vertex));
+        }
+
+        let mut arrangement = Arrangement {
+            layout,
+            direction: flowchart.direction,
+            axes,
+            layering,
+            vertex_blocks,
+            path_of,
+            node_boxes,
+            labels,
+            clusters: vec![],
+            rank_blocks,
+            node_gutter: config.node_gutter,
+            subgraph_padding: config.subgraph_padding,
+        };
+        arrangement.add_clusters(flowchart, config);
+        arrangement
+    }
+
+    /// A box around the members of every subgraph, including nested subgraphs and
+    /// the bends of edges running inside, that keeps all other vertices outside.
+    fn add_clusters(&mut self, flowchart: &Flowchart, config: &SvgConfig) {
+        let font_size = config.font_size * config.font_scale_factor;
+        let pad = self.subgraph_padding;
+        let axes = self.axes;
+
+        for subgraph in &flowchart.subgraphs {
+            let layout = &mut self.layout;
+            let block = layout.add_block();
+            let (title_block, title_lines) = layout.add_text_block(
+                &subgraph.title,
+                config.max_label_length,
+                config.padding,
+                font_size,
+            );
+            layout
+                .add_constraint(layout.b(title_block).top() | EQ(REQUIRED) | layout.b(block).top());
+            layout.add_constraint(
+                layout.b(title_block).left() | EQ(REQUIRED) | layout.b(block).left(),
+            );
+            layout.add_constraint(
+                layout.b(block).right() | GE(REQUIRED) | layout.b(title_block).right(),
+            );
+            self.clusters.push(Cluster {
+                block,
+                title_block,
+                title_lines,
+            });
+        }
+
+        for subgraph in &flowchart.subgraphs {
+            let mut member_ranks = vec![];
+            for r in 0..self.layering.ranks.len() {
+                for v in 0..self.layering.ranks[r].len() {
+                    let vertex = self.layering.ranks[r][v];
+                    if self.path_of[&vertex].contains(&subgraph.id) {
+                        self.enclose(subgraph.id, self.vertex_blocks[&vertex]);
+                        member_ranks.push(r);
+                    }
+                }
+            }
+            for child in flowchart
+                .subgraphs
+                .iter()
+                .filter(|s| s.parent == Some(subgraph.id))
+            {
+                self.enclose(subgraph.id, self.clusters[child.id].block);
+            }
+
+            // neighbouring ranks stay clear of the box
+            let layout = &mut self.layout;
+            let block = self.clusters[subgraph.id].block;
+            if let (Some(&first), Some(&last)) =
+                (member_ranks.iter().min(), member_ranks.iter().max())
+            {
+                if let Some(before) = first.checked_sub(1) {
+                    layout.add_constraint(
+                        axes.main_start(layout.b(block))
+                            | GE(REQUIRED)
+                            | (axes.main_end(layout.b(self.rank_blocks[before])) + pad),
+                    );
+                }
+                if let Some(after) = self.rank_blocks.get(last + 1) {
+                    layout.add_constraint(
+                        axes.main_end(layout.b(block))
+                            | LE(REQUIRED)
+                            | (axes.main_start(layout.b(*after)) - pad),
+                    );
+                }
+            }
+        }
+
+        // vertices next to each other in a rank but in different subgraphs are
+        // separated by the outermost boxes that tell them apart
+        for r in 0..self.layering.ranks.len() {
+            for (a, b) in self.layering.ranks[r].clone().into_iter().tuple_windows() {
+                let (a_block, b_block) = (self.vertex_blocks[&a], self.vertex_blocks[&b]);
+                self.separate(&a, a_block, &b, b_block, false);
+            }
+        }
+    }
+
+    fn enclose(&mut self, subgraph_id: SubgraphId, inner: BlockId) {
+        let pad = self.subgraph_padding;
+        let cluster = &self.clusters[subgraph_id];
+        let layout = &mut self.layout;
+        let (o, t, i) = (
+            layout.b(cluster.block),
+            layout.b(cluster.title_block),
+            layout.b(inner),
+        );
+        let constraints = [
+            i.left() | GE(REQUIRED) | (o.left() + pad),
+            i.right() | LE(REQUIRED) | (o.right() - pad),
+            i.top() | GE(REQUIRED) | (t.bottom() + pad),
+            i.bottom() | LE(REQUIRED) | (o.bottom() - pad),
+        ];
+        for constraint in constraints {
+            layout.add_constraint(constraint);
+        }
+    }
+
+    /// Keeps `before` ahead of `after` along the cross axis, or the outermost
+    /// subgraph boxes telling them apart. Vertices sharing all their subgraphs
+    /// are only separated if `same_subgraphs` is set.
+    fn separate(
+        &mut self,
+        before: &Vertex,
+        before_block: BlockId,
+        after: &Vertex,
+        after_block: BlockId,
+        same_subgraphs: bool,
+    ) {
+        let (path_a, path_b) = (&self.path_of[before], &self.path_of[after]);
+        let shared = path_a
+            .iter()
+            .zip(path_b)
+            .take_while(|(x, y)| x == y)
+            .count();
+        if path_a.len() == shared && path_b.len() == shared && !same_subgraphs {
+            return;
+        }
+        let left = path_a
+            .get(shared)
+            .map_or(before_block, |s| self.clusters[*s].block);
+        let right = path_b
+            .get(shared)
+            .map_or(after_block, |s| self.clusters[*s].block);
+        let axes = self.axes;
+        let layout = &mut self.layout;
+        layout.add_constraint(
+            (axes.cross_end(layout.b(left)) + self.node_gutter)
+                | LE(REQUIRED)
+                | axes.cross_start(layout.b(right)),
+        );
+    }
+
+    /// Places `block` right next to a node, within the node's rank and
+    /// subgraphs, pushing the other vertices of the rank away.
+    pub fn attach(&mut self, node_id: NodeId, block: BlockId, side: Side) {
+        let vertex = Vertex::Node(node_id);
+        let node_block = self.vertex_blocks[&vertex];
+        let axes = self.axes;
+        let gutter = self.node_gutter;
+        let layout = &mut self.layout;
+        let (node, attached) = (layout.b(node_block), layout.b(block));
+        let (from, to) = match side {
+            Side::Before => (axes.cross_end(attached) + gutter, axes.cross_start(node)),
+            Side::After => (axes.cross_end(node) + gutter, axes.cross_start(attached)),
+        };
+        layout.add_constraint(from.clone() | LE(REQUIRED) | to.clone());
+        layout.add_constraint(from | EQ(STRONG) | to);
+        layout.align(
+            axes.main_orientation(),
+            AlignmentAnchor::Middle,
+            [node_block, block].iter(),
+        );
+
+        let Some(r) = self.layering.rank_of(vertex) else {
+            return;
+        };
+        let rank_block = self.rank_blocks[r];
+        layout.add_constraint(
+            axes.main_start(layout.b(block)) | GE(REQUIRED) | axes.main_start(layout.b(rank_block)),
+        );
+        layout.add_constraint(
+            axes.main_end(layout.b(block)) | LE(REQUIRED) | axes.main_end(layout.b(rank_block)),
+        );
+
+        for subgraph_id in self.path_of[&vertex].clone() {
+            self.enclose(subgraph_id, block);
+        }
+        let rank = &self.layering.ranks[r];
+        let position = rank.iter().position(|v| *v == vertex).unwrap();
+        let neighbour = match side {
+            Side::Before => position.checked_sub(1).map(|p| rank[p]),
+            Side::After => rank.get(position + 1).copied(),
+        };
+        if let Some(neighbour) = neighbour {
+            let neighbour_block = self.vertex_blocks[&neighbour];
+            match side {
+                Side::Before => self.separate(&neighbour, neighbour_block, &vertex, block, true),
+                Side::After => self.separate(&vertex, block, &neighbour, neighbour_block, true),
+            }
+        }
+    }
+
+    /// Places `block` right next to the box of a subgraph, centered on it along
+    /// the main axis, within the subgraphs around it and clear of the vertices
+    /// beside the box in every rank it spans.
+    pub fn attach_to_subgraph(&mut self, subgraph_id: SubgraphId, block: BlockId, side: Side) {
+        let cluster_block = self.clusters[subgraph_id].block;
+        let axes = self.axes;
+        let gutter = self.node_gutter;
+        let layout = &mut self.layout;
+        let (cluster, attached) = (layout.b(cluster_block), layout.b(block));
+        let (from, to) = match side {
+            Side::Before => (axes.cross_end(attached) + gutter, axes.cross_start(cluster)),
+            Side::After => (axes.cross_end(cluster) + gutter, axes.cross_start(attached)),
+        };
+        layout.add_constraint(from.clone() | LE(REQUIRED) | to.clone());
+        layout.add_constraint(from | EQ(STRONG) | to);
+        layout.align(
+            axes.main_orientation(),
+            AlignmentAnchor::Middle,
+            [cluster_block, block].iter(),
+        );
+
+        let Some(ancestors) = self.path_of.values().find_map(|path| {
+            let depth = path.iter().position(|s| *s == subgraph_id)?;
+            Some(path[..depth].to_vec())
+        }) else {
+            return;
+        };
+        for ancestor in &ancestors {
+            self.enclose(*ancestor, block);
+        }
+
+        let depth = ancestors.len();
+        for rank in self.layering.ranks.clone() {
+            let inside = |v: &Vertex| self.path_of[v].get(depth) == Some(&subgraph_id);
+            let (Some(first), Some(last)) =
+                (rank.iter().position(inside), rank.iter().rposition(inside))
+            else {
+                continue;
+            };
+            let neighbour = match side {
+                Side::Before => first.checked_sub(1).map(|p| rank[p]),
+                Side::After => rank.get(last + 1).copied(),
+            };
+            // vertices outside the subgraphs around the block are kept away by those
+            let Some(neighbour) = neighbour.filter(|v| self.path_of[v].starts_with(&ancestors))
+            else {
+                continue;
+            };
+            let other = self.path_of[&neighbour]
+                .get(depth)
+                .map_or(self.vertex_blocks[&neighbour], |s| self.clusters[*s].block);
+            let layout = &mut self.layout;
+            let (attached, other) = (layout.b(block), layout.b(other));
+            layout.add_constraint(match side {
+                Side::Before => {
+                    (axes.cross_end(other) + gutter) | LE(REQUIRED) | axes.cross_start(attached)
+                }
+                Side::After => {
+                    (axes.cross_end(attached) + gutter) | LE(REQUIRED) | axes.cross_start(other)
+                }
+            });
+        }
+    }
+
+    pub fn layout(&mut self) -> &mut Layout<'static> {
+        &mut self.layout
+    }
+
+    pub fn solve(&mut self) {
+        self.layout.solve();
+    }
+
+    pub fn width(&self) -> f64 {
+        self.layout.width()
+    }
+
+    pub fn height(&self) -> f64 {
+        self.layout.height()
+    }
+
+    /// A solved block, mirrored along the main axis for `BT` and `RL` charts.
+    pub fn block(&self, id: BlockId) -> Block {
+        let solved = self.layout.b(id).solved();
+        let mut placed = Block {
+            position: solved.position,
+            width: solved.width,
+            height: solved.height,
+            line_height: solved.line_height,
+            direction: solved.direction,
+        };
+        match self.direction {
+            Direction::BottomToTop => {
+                placed.position.y = self.layout.height() - solved.position.y - solved.height;
+            }
+            Direction::RightToLeft => {
+                placed.position.x = self.layout.width() - solved.position.x - solved.width;
+            }
+            Direction::TopToBottom | Direction::LeftToRight => {}
+        }
+        placed
+    }
+
+    fn center(&self, id: BlockId) -> (f64, f64) {
+        let b = self.block(id);
+        (b.position.x + b.width * 0.5, b.position.y + b.height * 0.5)
+    }
+
+    pub fn node_block(&self, node_id: NodeId) -> BlockId {
+        self.node_boxes[node_id].block
+    }
+
+    pub fn node(&self, node_id: NodeId) -> Block {
+        self.block(self.node_boxes[node_id].block)
+    }
+
+    /// A block laid out inside a node, moved along with the node but not
+    /// mirrored within it, so compartments keep their order in every direction.
+    pub fn node_part(&self, node_id: NodeId, id: BlockId) -> Block {
+        let node = self.layout.b(self.node_boxes[node_id].block).solved();
+        let placed = self.node(node_id);
+        let solved = self.layout.b(id).solved();
+        let mut part = Block {
+            position: solved.position,
+            width: solved.width,
+            height: solved.height,
+            line_height: solved.line_height,
+            direction: solved.direction,
+        };
+        part.position.x += placed.position.x - node.position.x;
+        part.position.y += placed.position.y - node.position.y;
+        part
+    }
+
+    /// The wrapped label of a node and the block it is centered in.
+    pub fn node_text(&self, node_id: NodeId) -> (Block, &[String]) {
+        let node_box = &self.node_boxes[node_id];
+        (self.block(node_box.text_block), &node_box.lines)
+    }
+
+    pub fn edge_label(&self, edge_id: EdgeId) -> Option<(Block, &[String])> {
+        self.labels
+            .get(&edge_id)
+            .map(|label| (self.block(label.block), label.lines.as_slice()))
+    }
+
+    /// The box of a subgraph, the block of its title and the wrapped title.
+    pub fn subgraph(&self, subgraph_id: SubgraphId) -> (Block, Block, &[String]) {
+        let cluster = &self.clusters[subgraph_id];
+        (
+            self.block(cluster.block),
+            self.block(cluster.title_block),
+            &cluster.title_lines,
+        )
+    }
+
+    /// Edges routed through the ranks, self loops are left out.
+    pub fn edges(&self) -> impl Iterator<Item = EdgeId> + '_ {
+        self.layering.routes.iter().map(|route| route.edge_id)
+    }
+
+    pub fn loops(&self) -> impl Iterator<Item = EdgeId> + '_ {
+        self.layering.loops.iter().copied()
+    }
+
+    /// The polyline of a routed edge from its source to its target, both ends
+    /// clipped to the outline of the nodes.
+    pub fn edge_points(&self, edge_id: EdgeId) -> Vec<(f64, f64)> {
+        let Some(route) = self.layering.routes.iter().find(|r| r.edge_id == edge_id) else {
+            return vec![];
+        };
+        let mut points: Vec<(f64, f64)> = route
+            .vertices
+            .iter()
+            .map(|v| self.center(self.vertex_blocks[v]))
+            .collect();
+        let last = points.len() - 1;
+        if let (Vertex::Node(from), Vertex::Node(to)) = (route.vertices[0], route.vertices[last]) {
+            let (x, y) = points[1];
+            points[0] = self.node_boxes[from].boundary_towards(self, x, y);
+            let (x, y) = points[last - 1];
+            points[last] = self.node_boxes[to].boundary_towards(self, x, y);
+        }
+        points
+    }
+
+    /// Path data of a loop on the right of a node.
+    pub fn loop_path(&self, node_id: NodeId) -> String {
+        let node = self.node(node_id);
+        let (right, cy) = (
+            node.position.x + node.width,
+            node.position.y + node.height * 0.5,
+        );
+        format!(
+            "M {right} {} C {} {} {} {} {right} {}",
+            cy - LOOP_SIZE * 0.25,
+            right + LOOP_SIZE,
+            cy - LOOP_SIZE,
+            right + LOOP_SIZE,
+            cy + LOOP_SIZE,
+            cy + LOOP_SIZE * 0.25,
+        )
+    }
+}
+
+/// A flowchart with its nodes, edges and subgraphs placed.
+pub struct FlowchartLayout {
+    flowchart: Flowchart,
+    config: SvgConfig,
+    arrangement: Arrangement,
+}
+
+pub fn layout(flowchart: &Flowchart, config: &SvgConfig) -> FlowchartLayout {
+    let mut arrangement = Arrangement::new(flowchart, config, &HashMap::new());
+    arrangement.solve();
+    FlowchartLayout {
+        flowchart: flowchart.clone(),
+        config: config.clone(),
+        arrangement,
+    }
+}
+
+impl FlowchartLayout {
+    pub fn to_svg(&self) -> SVG {
+        let FlowchartLayout {
+            flowchart,
+            config,
+            arrangement,
+        } = self;
+        let mut doc = document(arrangement.width(), arrangement.height()).add(edge_markers());
+        for cluster in &arrangement.clusters {
+            doc = doc.add(cluster.to_svg(arrangement, config));
+        }
+        for node_box in &arrangement.node_boxes {
+            doc = doc.add(node_box.to_svg(arrangement, config));
+        }
+
+        for edge_id in arrangement.edges() {
+            let edge = &flowchart.edges[edge_id];
+            if edge.stroke != Stroke::Invisible {
+                doc = doc.add(edge_path(edge, &arrangement.edge_points(edge_id)));
+            }
+        }
+        for edge_id in arrangement.loops() {
+            let edge = &flowchart.edges[edge_id];
+            let mut path = edge_path(edge, &[]);
+            path.assign("d", arrangement.loop_path(edge.from));
+            doc = doc.add(path);
+        }
+
+        for label in arrangement.labels.values() {
+            doc = doc.add(label.to_svg(arrangement, config));
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(flowchart: &Flowchart, config: &SvgConfig) -> SVG {
+    layout(flowchart, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::flowchart::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_arrangement() {
+        let config = config();
+        for direction in ["TD", "BT", "LR", "RL"] {
+            let flowchart = parse(format!(
+                "flowchart {direction}\nA --> B\nA --> C\nA --> D\nsubgraph S\nB --> E\nC --> E\nend\nD --> E"
+            ))
+            .unwrap();
+            let mut arrangement = Arrangement::new(&flowchart, &config, &HashMap::new());
+            arrangement.solve();
+
+            // extents along and across the ranks, in the direction of the chart
+            let extents = |node_id: NodeId| {
+                let b = arrangement.node(node_id);
+                let (x, y) = (b.position.x, b.position.y);
+                match flowchart.direction {
+                    Direction::TopToBottom => ((y, y + b.height), (x, x + b.width)),
+                    Direction::BottomToTop => ((-y - b.height, -y), (x, x + b.width)),
+                    Direction::LeftToRight => ((x, x + b.width), (y, y + b.height)),
+                    Direction::RightToLeft => ((-x - b.width, -x), (y, y + b.height)),
+                }
+            };
+            let ranks: Vec<Vec<NodeId>> = arrangement
+                .layering
+                .ranks
+                .iter()
+                .map(|rank| {
+                    rank.iter()
+                        .filter_map(|v| match v {
+                            Vertex::Node(id) => Some(*id),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .collect();
+
+            for (upper, lower) in ranks.iter().tuple_windows() {
+                let end = upper
+                    .iter()
+                    .map(|id| extents(*id).0 .1)
+                    .fold(f64::MIN, f64::max);
+                let start = lower
+                    .iter()
+                    .map(|id| extents(*id).0 .0)
+                    .fold(f64::MAX, f64::min);
+                assert!(end + config.rank_gutter <= start + 1e-6, "{direction}");
+            }
+            for rank in &ranks {
+                let across = rank
+                    .iter()
+                    .map(|id| extents(*id).1)
+                    .sorted_by(|a, b| a.0.total_cmp(&b.0));
+                for (a, b) in across.tuple_windows() {
+                    assert!(a.1 + config.node_gutter <= b.0 + 1e-6, "{direction}");
+                }
+            }
+
+            assert_eq!(3, flowchart.subgraphs[0].nodes.len());
+            let (cluster, _, _) = arrangement.subgraph(0);
+            for node_id in &flowchart.subgraphs[0].nodes {
+                let node = arrangement.node(*node_id);
+                assert!(cluster.position.x <= node.position.x, "{direction}");
+                assert!(cluster.position.y <= node.position.y, "{direction}");
+                assert!(
+                    node.position.x + node.width <= cluster.position.x + cluster.width,
+                    "{direction}"
+                );
+                assert!(
+                    node.position.y + node.height <= cluster.position.y + cluster.height,
+                    "{direction}"
+                );
+            }
+        }
+    }
+}
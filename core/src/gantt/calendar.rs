@@ -0,0 +1,409 @@
+//! Date arithmetic for gantt charts. Dates carry no time zone and are counted
+//! in milliseconds from 1970-01-01 00:00, the way they are written.
+
+pub type Timestamp = i64;
+
+pub const SECOND: Timestamp = 1000;
+pub const MINUTE: Timestamp = 60 * SECOND;
+pub const HOUR: Timestamp = 60 * MINUTE;
+pub const DAY: Timestamp = 24 * HOUR;
+pub const WEEK: Timestamp = 7 * DAY;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Year, month and day of the date `days` after 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Start of the day `t` falls on.
+pub fn floor_day(t: Timestamp) -> Timestamp {
+    t.div_euclid(DAY) * DAY
+}
+
+/// Day of the week, 0 for Sunday.
+pub fn weekday(t: Timestamp) -> usize {
+    // 1970-01-01 was a Thursday
+    (t.div_euclid(DAY) + 4).rem_euclid(7) as usize
+}
+
+/// The weekday called `name`, in any case, 0 for Sunday.
+pub fn weekday_from_name(name: &str) -> Option<usize> {
+    WEEKDAYS
+        .iter()
+        .position(|day| day.eq_ignore_ascii_case(name))
+}
+
+/// `t` moved by whole months, keeping the time of day and clamping the day
+/// to the length of the target month.
+pub fn add_months(t: Timestamp, months: i64) -> Timestamp {
+    let day_start = floor_day(t);
+    let (year, month, day) = civil_from_days(day_start.div_euclid(DAY));
+    let index = year * 12 + month as i64 - 1 + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    let last = (days_from_civil(year + (month / 12) as i64, month % 12 + 1, 1)
+        - days_from_civil(year, month, 1)) as u32;
+    days_from_civil(year, month, day.min(last)) * DAY + (t - day_start)
+}
+
+/// Reads `text` in a moment.js style `format` such as `YYYY-MM-DD HH:mm`.
+/// `X` and `x` stand for unix seconds and milliseconds.
+pub fn parse_date(text: &str, format: &str) -> Option<Timestamp> {
+    let text: Vec<char> = text.trim().chars().collect();
+    let format: Vec<char> = format.trim().chars().collect();
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut millis) = (0, 0, 0, 0);
+    let mut unix = None;
+    let (mut i, mut f) = (0, 0);
+
+    let number = |i: &mut usize, max_digits: usize, signed: bool| -> Option<i64> {
+        let negative = signed && text.get(*i) == Some(&'-');
+        let start = *i + negative as usize;
+        let digits = text[start.min(text.len())..]
+            .iter()
+            .take(max_digits)
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let value: String = text[start..start + digits].iter().collect();
+        *i = start + digits;
+        let value: i64 = value.parse().ok()?;
+        Some(if negative { -value } else { value })
+    };
+
+    while f < format.len() {
+        let token_length = format[f..].iter().take_while(|&&c| c == format[f]).count();
+        match (format[f], token_length) {
+            ('Y', 4) => year = number(&mut i, 4, false)?,
+            ('Y', 2) => year = 2000 + number(&mut i, 2, false)?,
+            ('M', 1 | 2) => month = number(&mut i, 2, false)?,
+            ('D', 1 | 2) => day = number(&mut i, 2, false)?,
+            ('H', 1 | 2) => hour = number(&mut i, 2, false)?,
+            ('m', 1 | 2) => minute = number(&mut i, 2, false)?,
+            ('s', 1 | 2) => second = number(&mut i, 2, false)?,
+            ('S', 1..=3) => millis = number(&mut i, 3, false)?,
+            ('X', 1) => unix = Some(number(&mut i, 18, true)? * SECOND),
+            ('x', 1) => unix = Some(number(&mut i, 18, true)?),
+            (c, _) => {
+                if text.get(i) != Some(&c) {
+                    return None;
+                }
+                i += 1;
+                f += 1;
+                continue;
+            }
+        }
+        f += token_length;
+    }
+    if i != text.len() {
+        return None;
+    }
+    if let Some(t) = unix {
+        return Some(t);
+    }
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    Some(
+        days_from_civil(year, month as u32, day as u32) * DAY
+            + hour * HOUR
+            + minute * MINUTE
+            + second * SECOND
+            + millis,
+    )
+}
+
+/// Writes `t` in a d3 style `format` such as `%Y-%m-%d`.
+pub fn format_date(t: Timestamp, format: &str) -> String {
+    let (year, month, day) = civil_from_days(t.div_euclid(DAY));
+    let time = t - floor_day(t);
+    let (hour, minute) = (time / HOUR, time % HOUR / MINUTE);
+    let (second, millis) = (time % MINUTE / SECOND, time % SECOND);
+    let day_of_year = t.div_euclid(DAY) - days_from_civil(year, 1, 1) + 1;
+
+    let mut formatted = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => formatted += &year.to_string(),
+            Some('y') => formatted += &format!("{:02}", year.rem_euclid(100)),
+            Some('m') => formatted += &format!("{month:02}"),
+            Some('d') => formatted += &format!("{day:02}"),
+            Some('e') => formatted += &format!("{day:>2}"),
+            Some('j') => formatted += &format!("{day_of_year:03}"),
+            Some('H') => formatted += &format!("{hour:02}"),
+            Some('I') => formatted += &format!("{:02}", (hour + 11) % 12 + 1),
+            Some('p') => formatted += if hour < 12 { "AM" } else { "PM" },
+            Some('M') => formatted += &format!("{minute:02}"),
+            Some('S') => formatted += &format!("{second:02}"),
+            Some('L') => formatted += &format!("{millis:03}"),
+            Some('b') => formatted += &MONTHS[month as usize - 1][..3],
+            Some('B') => formatted += MONTHS[month as usize - 1],
+            Some('a') => formatted += &WEEKDAYS[weekday(t)][..3],
+            Some('A') => formatted += WEEKDAYS[weekday(t)],
+            Some('%') => formatted.push('%'),
+            Some(other) => {
+                formatted.push('%');
+                formatted.push(other);
+            }
+            None => formatted.push('%'),
+        }
+    }
+    formatted
+}
+
+/// A task length such as `3d`, `1.5h` or `2w`.
+pub fn parse_duration(text: &str) -> Option<Timestamp> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+    let value: f64 = text[..split].parse().ok()?;
+    let unit = match &text[split..] {
+        "ms" => 1,
+        "s" => SECOND,
+        "m" => MINUTE,
+        "h" => HOUR,
+        "d" => DAY,
+        "w" => WEEK,
+        _ => return None,
+    };
+    Some((value * unit as f64).round() as Timestamp)
+}
+
+/// Distance between two ticks of the time axis.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interval {
+    Fixed(Timestamp),
+    Months(i64),
+}
+
+/// A `tickInterval` such as `1day` or `2week`.
+pub fn parse_interval(text: &str) -> Option<Interval> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let count: i64 = text[..split].parse().ok().filter(|&n| n > 0)?;
+    let interval = match &text[split..] {
+        "millisecond" => Interval::Fixed(count),
+        "second" => Interval::Fixed(count * SECOND),
+        "minute" => Interval::Fixed(count * MINUTE),
+        "hour" => Interval::Fixed(count * HOUR),
+        "day" => Interval::Fixed(count * DAY),
+        "week" => Interval::Fixed(count * WEEK),
+        "month" => Interval::Months(count),
+        _ => return None,
+    };
+    Some(interval)
+}
+
+/// The smallest of the usual intervals that puts at most `max_ticks` ticks
+/// between `start` and `end`.
+pub fn auto_interval(start: Timestamp, end: Timestamp, max_ticks: i64) -> Interval {
+    let span = (end - start).max(1);
+    let fixed = [
+        SECOND,
+        5 * SECOND,
+        15 * SECOND,
+        30 * SECOND,
+        MINUTE,
+        5 * MINUTE,
+        15 * MINUTE,
+        30 * MINUTE,
+        HOUR,
+        3 * HOUR,
+        6 * HOUR,
+        12 * HOUR,
+        DAY,
+        2 * DAY,
+        WEEK,
+    ];
+    fixed
+        .iter()
+        .find(|&&step| span / step <= max_ticks)
+        .map(|&step| Interval::Fixed(step))
+        .or_else(|| {
+            [1, 3, 6, 12, 24, 60, 120]
+                .iter()
+                .find(|&&months| span / (months * 30 * DAY) <= max_ticks)
+                .map(|&months| Interval::Months(months))
+        })
+        .unwrap_or(Interval::Months(span / (max_ticks * 30 * DAY) + 1))
+}
+
+/// The ticks from `start` to `end`, on whole multiples of the interval:
+/// weeks start on Sundays and months on their first day.
+pub fn ticks(start: Timestamp, end: Timestamp, interval: Interval) -> Vec<Timestamp> {
+    let mut ticks = vec![];
+    match interval {
+        Interval::Fixed(step) => {
+            let mut t = if step % WEEK == 0 {
+                floor_day(start) - weekday(start) as Timestamp * DAY
+            } else {
+                start.div_euclid(step) * step
+            };
+            if t < start {
+                t += step;
+            }
+            while t <= end {
+                ticks.push(t);
+                t += step;
+            }
+        }
+        Interval::Months(months) => {
+            let (year, month, _) = civil_from_days(start.div_euclid(DAY));
+            let mut t = days_from_civil(year, month, 1) * DAY;
+            if t < start {
+                t = add_months(t, 1);
+            }
+            while t <= end {
+                ticks.push(t);
+                t = add_months(t, months);
+            }
+        }
+    }
+    ticks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Timestamp {
+        parse_date(text, "YYYY-MM-DD").unwrap()
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(Some(0), parse_date("1970-01-01", "YYYY-MM-DD"));
+        assert_eq!(Some(19723 * DAY), parse_date("2024-01-01", "YYYY-MM-DD"));
+        assert_eq!(
+            Some(19723 * DAY + 13 * HOUR + 30 * MINUTE),
+            parse_date("01/01/24 13:30", "DD/MM/YY HH:mm")
+        );
+        assert_eq!(Some(1_700_000_000_000), parse_date("1700000000", "X"));
+        assert_eq!(None, parse_date("2024-13-01", "YYYY-MM-DD"));
+        assert_eq!(None, parse_date("2024-01-01 extra", "YYYY-MM-DD"));
+    }
+
+    #[test]
+    fn test_format_date() {
+        let t = date("2024-02-29") + 15 * HOUR + 5 * MINUTE;
+        assert_eq!("2024-02-29", format_date(t, "%Y-%m-%d"));
+        assert_eq!("Thu 29 Feb, 03:05 PM", format_date(t, "%a %e %b, %I:%M %p"));
+        assert_eq!("060", format_date(t, "%j"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(3 * DAY), parse_duration("3d"));
+        assert_eq!(Some(90 * MINUTE), parse_duration("1.5h"));
+        assert_eq!(Some(2 * WEEK), parse_duration("2w"));
+        assert_eq!(None, parse_duration("3 days"));
+    }
+
+    #[test]
+    fn test_ticks() {
+        assert_eq!(
+            vec![date("2024-01-07"), date("2024-01-14")],
+            ticks(
+                date("2024-01-03"),
+                date("2024-01-20"),
+                Interval::Fixed(WEEK)
+            )
+        );
+        assert_eq!(
+            vec![date("2024-02-01"), date("2024-03-01")],
+            ticks(date("2024-01-15"), date("2024-03-10"), Interval::Months(1))
+        );
+        assert_eq!(Some(Interval::Months(2)), parse_interval("2month"));
+        assert_eq!(date("2024-02-29"), add_months(date("2024-01-31"), 1));
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(0, days_from_civil(1970, 1, 1));
+        assert_eq!(-1, days_from_civil(1969, 12, 31));
+        for days in [-719468, -1, 0, 11016, 19782, 19783, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days, days_from_civil(year, month, day));
+        }
+        assert_eq!(
+            (2024, 2, 29),
+            civil_from_days(days_from_civil(2024, 3, 1) - 1)
+        );
+        assert_eq!(
+            (1900, 2, 28),
+            civil_from_days(days_from_civil(1900, 3, 1) - 1)
+        );
+        assert_eq!(4, weekday(0));
+        assert_eq!(Some(0), weekday_from_name("sunday"));
+        assert_eq!(
+            date("2024-01-01") + 13 * HOUR,
+            add_months(date("2023-11-01") + 13 * HOUR, 2)
+        );
+    }
+
+    #[test]
+    fn test_auto_interval() {
+        assert_eq!(Interval::Fixed(DAY), auto_interval(0, 7 * DAY, 7));
+        assert_eq!(Interval::Fixed(2 * DAY), auto_interval(0, 8 * DAY, 7));
+        assert_eq!(Interval::Months(1), auto_interval(0, 180 * DAY, 7));
+        // every tick of the chosen interval fits
+        let (start, end) = (date("2024-01-03"), date("2024-09-20"));
+        assert!(ticks(start, end, auto_interval(start, end, 7)).len() <= 8);
+    }
+}
@@ -0,0 +1,19 @@
+pub mod calendar;
+pub mod parser;
+pub mod render;
+
+pub use calendar::{format_date, parse_date, Interval, Timestamp};
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Gantt, GanttLayout, ["gantt"], |options| SvgConfig {
+    max_label_length: 30,
+    font_scale_factor: options.font_scale_factor,
+    font_size: options.font_size,
+    padding: options.padding,
+    corner_radius: 2.0,
+    chart_width: 600.0,
+    bar_height: 20.0,
+    bar_gap: 4.0,
+    today: options.today,
+});
@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::char;
+use combine::parser::choice::choice;
+use combine::{eof, many, many1, none_of, optional, ParseError, Parser, Stream};
+
+use crate::gantt::calendar::*;
+use crate::parsing::{blank, blank1, comment_parser, keyword, skipped_parser};
+
+pub type TaskId = usize;
+pub type SectionId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Status {
+    Done,
+    Active,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Task {
+    pub id: TaskId,
+    /// The id other tasks refer to in `after` and `until`.
+    pub name: Option<String>,
+    pub label: String,
+    pub section: Option<SectionId>,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub status: Option<Status>,
+    pub crit: bool,
+    pub milestone: bool,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub id: SectionId,
+    pub title: String,
+}
+
+/// Days skipped when a duration is added to a start date.
+#[derive(PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Excludes {
+    pub weekends: bool,
+    pub weekdays: Vec<usize>,
+    pub dates: Vec<Timestamp>,
+    /// Days worked even though they match one of the above.
+    pub includes: Vec<Timestamp>,
+}
+
+impl Excludes {
+    pub fn is_empty(&self) -> bool {
+        !self.weekends && self.weekdays.is_empty() && self.dates.is_empty()
+    }
+
+    /// Whether the day `t` falls on is skipped.
+    pub fn contains(&self, t: Timestamp) -> bool {
+        let day = floor_day(t);
+        if self.includes.contains(&day) {
+            return false;
+        }
+        let weekday = weekday(day);
+        (self.weekends && (weekday == 0 || weekday == 6))
+            || self.weekdays.contains(&weekday)
+            || self.dates.contains(&day)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gantt {
+    pub title: Option<String>,
+    pub date_format: String,
+    /// A d3 time format for the labels of the time axis.
+    pub axis_format: Option<String>,
+    pub tick_interval: Option<Interval>,
+    pub excludes: Excludes,
+    /// False after `todayMarker off`.
+    pub today_marker: bool,
+    pub sections: Vec<Section>,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header,
+    Title(String),
+    DateFormat(String),
+    AxisFormat(String),
+    TickInterval(String),
+    Excludes(String),
+    Includes(String),
+    TodayMarker(String),
+    InclusiveEndDates,
+    Section(String),
+    /// `label : metadata`, the metadata is resolved once the date format is known.
+    Task(String, String),
+}
+
+#[derive(PartialEq, Debug)]
+enum Start {
+    Previous,
+    Date(Timestamp),
+    After(Vec<String>),
+}
+
+#[derive(PartialEq, Debug)]
+enum End {
+    Date(Timestamp),
+    Duration(Timestamp),
+    Until(Vec<String>),
+}
+
+/// A task as written, before its dates are resolved.
+#[derive(PartialEq, Debug)]
+struct Planned {
+    name: Option<String>,
+    label: String,
+    section: Option<SectionId>,
+    status: Option<Status>,
+    crit: bool,
+    milestone: bool,
+    start: Start,
+    end: End,
+}
+
+pub fn parse(src: String) -> Result<Gantt, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_gantt(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the comma separated metadata of a task:
+/// `[tags,] [id,] [start,] end`, where the start is a date or `after ids`
+/// and the end a date, a duration or `until ids`.
+fn plan_task(
+    label: String,
+    metadata: &str,
+    section: Option<SectionId>,
+    date_format: &str,
+    inclusive_end_dates: bool,
+) -> Option<Planned> {
+    let mut items: Vec<&str> = metadata.split(',').map(str::trim).collect();
+    let mut planned = Planned {
+        name: None,
+        label,
+        section,
+        status: None,
+        crit: false,
+        milestone: false,
+        start: Start::Previous,
+        end: End::Duration(0),
+    };
+    while let Some(&tag) = items.first() {
+        match tag {
+            "done" => planned.status = Some(Status::Done),
+            "active" => planned.status = Some(Status::Active),
+            "crit" => planned.crit = true,
+            "milestone" => planned.milestone = true,
+            _ => break,
+        }
+        items.remove(0);
+    }
+    let ids = |text: &str| text.split_whitespace().map(str::to_string).collect();
+
+    let (name, start, end) = match items[..] {
+        [end] => (None, None, end),
+        [start, end] => (None, Some(start), end),
+        [name, start, end] => (Some(name), Some(start), end),
+        _ => return None,
+    };
+    planned.name = name.map(str::to_string);
+    planned.start = match start {
+        None => Start::Previous,
+        Some(start) => match start.strip_prefix("after ") {
+            Some(after) => Start::After(ids(after)),
+            None => Start::Date(parse_date(start, date_format)?),
+        },
+    };
+    planned.end = match end.strip_prefix("until ") {
+        Some(until) => End::Until(ids(until)),
+        None => match parse_date(end, date_format) {
+            Some(date) if inclusive_end_dates => End::Date(date + DAY),
+            Some(date) => End::Date(date),
+            None => End::Duration(parse_duration(end)?),
+        },
+    };
+    Some(planned)
+}
+
+/// The end of a task lasting `duration` from `start`, made longer by a day
+/// for every excluded day it spans.
+fn add_duration(start: Timestamp, duration: Timestamp, excludes: &Excludes) -> Timestamp {
+    let mut end = start + duration;
+    if excludes.is_empty() {
+        return end;
+    }
+    let mut day = start;
+    while day < end {
+        if excludes.contains(day) {
+            end += DAY;
+        }
+        day += DAY;
+    }
+    end
+}
+
+/// Dates of the tasks, resolved in passes as tasks may refer to tasks further
+/// down. Fails on unknown ids and on cycles.
+fn schedule(planned: &[Planned], excludes: &Excludes) -> Option<Vec<(Timestamp, Timestamp)>> {
+    let names: HashMap<&str, TaskId> = planned
+        .iter()
+        .enumerate()
+        .filter_map(|(id, task)| task.name.as_deref().map(|name| (name, id)))
+        .collect();
+    let mut dates: Vec<Option<(Timestamp, Timestamp)>> = vec![None; planned.len()];
+
+    loop {
+        let mut progress = false;
+        for (id, task) in planned.iter().enumerate() {
+            if dates[id].is_some() {
+                continue;
+            }
+            let resolved = |refs: &[String]| -> Option<Option<Vec<(Timestamp, Timestamp)>>> {
+                let mut found = vec![];
+                for name in refs {
+                    match dates[*names.get(name.as_str())?] {
+                        Some(d) => found.push(d),
+                        None => return Some(None),
+                    }
+                }
+                Some(Some(found))
+            };
+            let start = match &task.start {
+                Start::Date(date) => Some(*date),
+                Start::Previous => match id.checked_sub(1) {
+                    Some(previous) => dates[previous].map(|(_, end)| end),
+                    None => return None,
+                },
+                Start::After(refs) => resolved(refs)?.and_then(|d| d.iter().map(|(_, e)| *e).max()),
+            };
+            let Some(start) = start else {
+                continue;
+            };
+            let end = match &task.end {
+                End::Date(date) => Some(*date),
+                End::Duration(duration) => Some(add_duration(start, *duration, excludes)),
+                End::Until(refs) => resolved(refs)?.and_then(|d| d.iter().map(|(s, _)| *s).min()),
+            };
+            if let Some(end) = end {
+                dates[id] = Some((start, end));
+                progress = true;
+            }
+        }
+        if dates.iter().all(Option::is_some) {
+            return Some(dates.into_iter().flatten().collect());
+        }
+        if !progress {
+            return None;
+        }
+    }
+}
+
+fn build_gantt(statements: Vec<Statement>) -> Result<Gantt, StringStreamError> {
+    let mut gantt = Gantt {
+        title: None,
+        date_format: "YYYY-MM-DD".to_string(),
+        axis_format: None,
+        tick_interval: None,
+        excludes: Excludes::default(),
+        today_marker: true,
+        sections: vec![],
+        tasks: vec![],
+    };
+    let mut tasks = vec![];
+    let mut excludes = vec![];
+    let mut includes = vec![];
+    let mut inclusive_end_dates = false;
+
+    for statement in statements {
+        match statement {
+            Statement::Empty | Statement::Header => {}
+            Statement::Title(title) => gantt.title = Some(title),
+            Statement::DateFormat(format) => gantt.date_format = format,
+            Statement::AxisFormat(format) => gantt.axis_format = Some(format),
+            Statement::TickInterval(interval) => {
+                gantt.tick_interval =
+                    Some(parse_interval(&interval).ok_or(StringStreamError::UnexpectedParse)?);
+            }
+            Statement::Excludes(days) => excludes.push(days),
+            Statement::Includes(days) => includes.push(days),
+            Statement::TodayMarker(style) => gantt.today_marker = style != "off",
+            Statement::InclusiveEndDates => inclusive_end_dates = true,
+            Statement::Section(title) => gantt.sections.push(Section {
+                id: gantt.sections.len(),
+                title,
+            }),
+            Statement::Task(label, metadata) => {
+                tasks.push((label, metadata, gantt.sections.last().map(|s| s.id)))
+            }
+        }
+    }
+
+    let days = |lists: &[String]| {
+        lists
+            .iter()
+            .flat_map(|list| list.split([',', ' ']))
+            .map(str::trim)
+            .filter(|day| !day.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    for day in days(&excludes) {
+        if day.eq_ignore_ascii_case("weekends") {
+            gantt.excludes.weekends = true;
+        } else if let Some(weekday) = weekday_from_name(&day) {
+            gantt.excludes.weekdays.push(weekday);
+        } else {
+            let date =
+                parse_date(&day, &gantt.date_format).ok_or(StringStreamError::UnexpectedParse)?;
+            gantt.excludes.dates.push(floor_day(date));
+        }
+    }
+    for day in days(&includes) {
+        let date =
+            parse_date(&day, &gantt.date_format).ok_or(StringStreamError::UnexpectedParse)?;
+        gantt.excludes.includes.push(floor_day(date));
+    }
+
+    let planned = tasks
+        .into_iter()
+        .map(|(label, metadata, section)| {
+            plan_task(
+                label,
+                &metadata,
+                section,
+                &gantt.date_format,
+                inclusive_end_dates,
+            )
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(StringStreamError::UnexpectedParse)?;
+    let dates = schedule(&planned, &gantt.excludes).ok_or(StringStreamError::UnexpectedParse)?;
+    gantt.tasks = planned
+        .into_iter()
+        .zip(dates)
+        .enumerate()
+        .map(|(id, (task, (start, end)))| Task {
+            id,
+            name: task.name,
+            label: task.label,
+            section: task.section,
+            start,
+            end,
+            status: task.status,
+            crit: task.crit,
+            milestone: task.milestone,
+        })
+        .collect();
+    Ok(gantt)
+}
+
+/// The rest of the line after a keyword, without surrounding blanks.
+fn argument<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (blank1(), many1(none_of("\n".chars()))).map(|(_, text): (_, String)| text.trim().to_string())
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    keyword("gantt").map(|_| Statement::Header)
+}
+
+fn setting_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (keyword("title"), argument()).map(|(_, text)| Statement::Title(text)),
+        (keyword("dateFormat"), argument()).map(|(_, text)| Statement::DateFormat(text)),
+        (keyword("axisFormat"), argument()).map(|(_, text)| Statement::AxisFormat(text)),
+        (keyword("tickInterval"), argument()).map(|(_, text)| Statement::TickInterval(text)),
+        (keyword("excludes"), argument()).map(|(_, text)| Statement::Excludes(text)),
+        (keyword("includes"), argument()).map(|(_, text)| Statement::Includes(text)),
+        (keyword("todayMarker"), argument()).map(|(_, text)| Statement::TodayMarker(text)),
+        keyword("inclusiveEndDates").map(|_| Statement::InclusiveEndDates),
+        (keyword("section"), argument()).map(|(_, text)| Statement::Section(text)),
+    ))
+}
+
+fn task_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        many1(none_of(":\n".chars())),
+        char(':'),
+        many(none_of("\n".chars())),
+    )
+        .map(|(label, _, metadata): (String, _, String)| {
+            Statement::Task(label.trim().to_string(), metadata.trim().to_string())
+        })
+}
+
+/// Interaction, accessibility and display statements without an effect on
+/// the schedule are skipped.
+fn ignored_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&[
+        "click", "accTitle", "accDescr", "topAxis", "weekday", "weekend",
+    ])
+    .map(|_| Statement::Empty)
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            comment_parser().map(|_| Statement::Empty),
+            header_parser(),
+            setting_parser(),
+            ignored_parser(),
+            task_parser(),
+        ))),
+        char('\n'),
+    )
+        .map(|(_, statement, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Timestamp {
+        parse_date(text, "YYYY-MM-DD").unwrap()
+    }
+
+    #[test]
+    fn test_task_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Task(
+                    "Write docs".to_string(),
+                    "done, a1, 2024-01-01, 3d".to_string()
+                ),
+                "\n"
+            )),
+            task_parser().parse("Write docs : done, a1, 2024-01-01, 3d\n")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"gantt
+            title Release
+            dateFormat YYYY-MM-DD
+            axisFormat %d %b
+            excludes weekends
+            section Build
+            Design        :done, des, 2024-01-01, 2024-01-03
+            Implement     :active, impl, after des, 5d
+            section Ship
+            Review        :crit, rev, after impl, 2d
+            Release       :milestone, rel, after impl rev, 0d
+            Docs          :2024-01-08, until rel
+        "#;
+        let gantt = parse(input.to_string()).unwrap();
+        assert_eq!(Some("Release".to_string()), gantt.title);
+        assert_eq!(Some("%d %b".to_string()), gantt.axis_format);
+        assert!(gantt.excludes.weekends);
+        assert_eq!(2, gantt.sections.len());
+        let tasks = &gantt.tasks;
+        assert_eq!(Some(Status::Done), tasks[0].status);
+        assert_eq!(
+            (date("2024-01-01"), date("2024-01-03")),
+            (tasks[0].start, tasks[0].end)
+        );
+        // five working days from Wednesday spread over a weekend
+        assert_eq!(
+            (date("2024-01-03"), date("2024-01-10")),
+            (tasks[1].start, tasks[1].end)
+        );
+        assert!(tasks[2].crit);
+        assert_eq!(Some(1), tasks[2].section);
+        assert_eq!(date("2024-01-10"), tasks[2].start);
+        assert!(tasks[3].milestone);
+        assert_eq!(date("2024-01-12"), tasks[3].start);
+        assert_eq!(
+            (date("2024-01-08"), date("2024-01-12")),
+            (tasks[4].start, tasks[4].end)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_schedule() {
+        assert!(parse("gantt\nA :a, after b, 1d\nB :b, after a, 1d\n".to_string()).is_err());
+        assert!(parse("gantt\nA :a, after missing, 1d\n".to_string()).is_err());
+        assert!(parse("gantt\nA :a, 2024-01-01, soon\n".to_string()).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use svg::node::element::{Group, Line, Polygon, Rectangle, SVG};
+
+use cassowary::strength::{MEDIUM, REQUIRED, STRONG};
+use cassowary::WeightedRelation::*;
+
+use crate::gantt::calendar::{self, auto_interval};
+use crate::gantt::*;
+use crate::layout::{AlignmentAnchor, BlockId, Layout, Orientation};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    /// Width of the time axis.
+    pub chart_width: f64,
+    pub bar_height: f64,
+    /// Least space above and below every bar.
+    pub bar_gap: f64,
+    /// Marked with a line when inside the schedule; the clock is never read.
+    pub today: Option<Timestamp>,
+}
+
+const TASK_FILL: &str = "#8A90DD";
+const ACTIVE_FILL: &str = "#BFC7FF";
+const DONE_FILL: &str = "lightgrey";
+const CRIT_FILL: &str = "#FF8888";
+const CRIT_STROKE: &str = "#FF0000";
+const GRID_COLOR: &str = "#DDD";
+const TODAY_COLOR: &str = "#FF0000";
+/// Minimal distance between the ticks of the time axis.
+const TICK_SPACING: f64 = 80.0;
+
+struct TaskBar {
+    row: BlockId,
+    bar: BlockId,
+    label: BlockId,
+    lines: Vec<String>,
+}
+
+struct SectionBand {
+    block: BlockId,
+    title: BlockId,
+    lines: Vec<String>,
+}
+
+struct Tick {
+    at: Timestamp,
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+fn fill(task: &Task) -> &'static str {
+    match (task.status, task.crit) {
+        (Some(Status::Done), _) => DONE_FILL,
+        (Some(Status::Active), _) => ACTIVE_FILL,
+        (None, true) => CRIT_FILL,
+        (None, false) => TASK_FILL,
+    }
+}
+
+fn text_group(layout: &Layout, block: BlockId, lines: &[String], config: &SvgConfig) -> Group {
+    let b = layout.b(block).solved();
+    let mut group = translated(b);
+    for t in text_lines(lines, b, config.font_size, config.padding) {
+        group = group.add(t);
+    }
+    group
+}
+
+/// A gantt chart with its bars, sections and ticks placed on the time axis.
+pub struct GanttLayout {
+    gantt: Gantt,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    chart: BlockId,
+    title: Option<(BlockId, Vec<String>)>,
+    bars: Vec<TaskBar>,
+    sections: Vec<SectionBand>,
+    ticks: Vec<Tick>,
+    first: Timestamp,
+    last: Timestamp,
+    /// Width of a millisecond on the time axis.
+    scale: f64,
+}
+
+impl GanttLayout {
+    /// Distance of `t` from the left of the chart.
+    fn x_of(&self, t: Timestamp) -> f64 {
+        (t - self.first) as f64 * self.scale
+    }
+
+    /// Where the today marker goes, when there is one inside the schedule.
+    fn today_x(&self) -> Option<f64> {
+        let today = self.config.today.filter(|_| self.gantt.today_marker)?;
+        if !(self.first..=self.last).contains(&today) {
+            return None;
+        }
+        Some(self.layout.b(self.chart).solved().position.x + self.x_of(today))
+    }
+}
+
+pub fn layout(gantt: &Gantt, config: &SvgConfig) -> GanttLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+
+    let first = gantt.tasks.iter().map(|t| t.start).min().unwrap_or(0);
+    let last = gantt.tasks.iter().map(|t| t.end).max().unwrap_or(first);
+    let span = (last - first).max(1) as f64;
+    let scale = config.chart_width / span;
+    let x_of = |t: Timestamp| (t - first) as f64 * scale;
+
+    let title = gantt.title.as_ref().map(|title| {
+        layout.add_text_block(
+            title,
+            config.max_label_length * 3,
+            config.padding,
+            font_size,
+        )
+    });
+    let sidebar = layout.add_block();
+    let chart = layout.add_block();
+    layout.add_constraint(layout.b(sidebar).left() | EQ(REQUIRED) | 0.0);
+    layout.add_constraint(layout.b(sidebar).width | EQ(STRONG) | 0.0);
+    layout.add_constraint(
+        layout.b(chart).left() | EQ(REQUIRED) | (layout.b(sidebar).right() + config.padding),
+    );
+    layout.add_constraint(layout.b(chart).width | EQ(REQUIRED) | config.chart_width);
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::Start,
+        [sidebar, chart].iter(),
+    );
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::End,
+        [sidebar, chart].iter(),
+    );
+    match &title {
+        Some((block, _)) => {
+            layout.add_constraint(layout.b(*block).top() | EQ(REQUIRED) | 0.0);
+            layout.add_constraint(layout.b(*block).left() | GE(REQUIRED) | 0.0);
+            layout.add_constraint(
+                (layout.b(*block).left() + layout.b(*block).width * 0.5)
+                    | EQ(STRONG)
+                    | (layout.b(chart).left() + layout.b(chart).width * 0.5),
+            );
+            layout.add_constraint(
+                layout.b(chart).top() | EQ(REQUIRED) | (layout.b(*block).bottom() + config.padding),
+            );
+        }
+        None => layout.add_constraint(layout.b(chart).top() | EQ(REQUIRED) | 0.0),
+    }
+
+    let bars: Vec<TaskBar> = gantt
+        .tasks
+        .iter()
+        .map(|task| {
+            let row = layout.add_block();
+            let bar = layout.add_block();
+            let (label, lines) = layout.add_text_block(
+                &task.label,
+                config.max_label_length,
+                config.padding,
+                font_size,
+            );
+            layout.add_constraint(layout.b(row).left() | EQ(REQUIRED) | layout.b(sidebar).left());
+            layout.add_constraint(layout.b(row).right() | EQ(REQUIRED) | layout.b(chart).right());
+            // rows grow for labels wrapped over several lines
+            let row_height = config.bar_height + 2.0 * config.bar_gap;
+            layout.add_constraint(layout.b(row).height | GE(REQUIRED) | row_height);
+            layout.add_constraint(layout.b(row).height | GE(REQUIRED) | layout.b(label).height);
+            layout.add_constraint(layout.b(row).height | EQ(MEDIUM) | row_height);
+            layout.align(
+                Orientation::Vertical,
+                AlignmentAnchor::Middle,
+                [row, bar].iter(),
+            );
+            layout.add_constraint(layout.b(bar).height | EQ(REQUIRED) | config.bar_height);
+            if task.milestone {
+                let middle = x_of(task.start) + (x_of(task.end) - x_of(task.start)) * 0.5;
+                layout.add_constraint(
+                    layout.b(bar).left()
+                        | EQ(REQUIRED)
+                        | (layout.b(chart).left() + middle - config.bar_height * 0.5),
+                );
+                layout.add_constraint(layout.b(bar).width | EQ(REQUIRED) | config.bar_height);
+            } else {
+                layout.add_constraint(
+                    layout.b(bar).left()
+                        | EQ(REQUIRED)
+                        | (layout.b(chart).left() + x_of(task.start)),
+                );
+                layout.add_constraint(
+                    layout.b(bar).width | EQ(REQUIRED) | (x_of(task.end) - x_of(task.start)),
+                );
+            }
+            layout.align(
+                Orientation::Vertical,
+                AlignmentAnchor::Middle,
+                [bar, label].iter(),
+            );
+            TaskBar {
+                row,
+                bar,
+                label,
+                lines,
+            }
+        })
+        .collect();
+
+    let rows: Vec<BlockId> = bars.iter().map(|b| b.row).collect();
+    layout.distribute(Orientation::Vertical, 0.0, rows.iter());
+    match (rows.first(), rows.last()) {
+        (Some(&first_row), Some(&last_row)) => {
+            layout.add_constraint(layout.b(first_row).top() | EQ(REQUIRED) | layout.b(chart).top());
+            layout.add_constraint(
+                layout.b(last_row).bottom() | EQ(REQUIRED) | layout.b(chart).bottom(),
+            );
+        }
+        _ => layout.add_constraint(layout.b(chart).height | EQ(REQUIRED) | 0.0),
+    }
+
+    let mut sections = vec![];
+    for section in &gantt.sections {
+        let in_section: Vec<&TaskBar> = gantt
+            .tasks
+            .iter()
+            .zip(&bars)
+            .filter(|(task, _)| task.section == Some(section.id))
+            .map(|(_, bar)| bar)
+            .collect();
+        let (Some(first_bar), Some(last_bar)) = (in_section.first(), in_section.last()) else {
+            continue;
+        };
+        let block = layout.add_block();
+        let (title, lines) = layout.add_text_block(
+            &section.title,
+            config.max_label_length,
+            config.padding,
+            font_size,
+        );
+        layout.add_constraint(layout.b(block).top() | EQ(REQUIRED) | layout.b(first_bar.row).top());
+        layout.add_constraint(
+            layout.b(block).bottom() | EQ(REQUIRED) | layout.b(last_bar.row).bottom(),
+        );
+        layout.add_constraint(layout.b(title).left() | EQ(REQUIRED) | layout.b(sidebar).left());
+        layout.add_constraint(layout.b(title).right() | LE(REQUIRED) | layout.b(sidebar).right());
+        layout.align(
+            Orientation::Vertical,
+            AlignmentAnchor::Middle,
+            [block, title].iter(),
+        );
+        sections.push(SectionBand {
+            block,
+            title,
+            lines,
+        });
+    }
+
+    let axis_format = gantt.axis_format.as_deref().unwrap_or("%Y-%m-%d");
+    let max_ticks = (config.chart_width / TICK_SPACING).max(1.0) as i64;
+    let interval = gantt
+        .tick_interval
+        .unwrap_or_else(|| auto_interval(first, last, max_ticks));
+    let axis = layout.add_block();
+    layout.add_constraint(layout.b(axis).top() | EQ(REQUIRED) | layout.b(chart).bottom());
+    let ticks: Vec<Tick> = calendar::ticks(first, last, interval)
+        .into_iter()
+        .map(|at| {
+            let (block, lines) = layout.add_text_block(
+                &format_date(at, axis_format),
+                config.max_label_length,
+                config.padding,
+                config.font_size,
+            );
+            layout.add_constraint(layout.b(block).top() | EQ(REQUIRED) | layout.b(axis).top());
+            layout.add_constraint(
+                (layout.b(block).left() + layout.b(block).width * 0.5)
+                    | EQ(REQUIRED)
+                    | (layout.b(chart).left() + x_of(at)),
+            );
+            layout
+                .add_constraint(layout.b(axis).bottom() | GE(REQUIRED) | layout.b(block).bottom());
+            Tick { at, block, lines }
+        })
+        .collect();
+    layout.solve();
+
+    // labels go inside their bar when they fit, after it otherwise
+    for (task, bar) in gantt.tasks.iter().zip(&bars) {
+        let (label, bar_block) = (layout.b(bar.label).solved(), layout.b(bar.bar).solved());
+        let fits = !task.milestone && label.width <= bar_block.width && bar.lines.len() == 1;
+        if fits {
+            layout.align(
+                Orientation::Horizontal,
+                AlignmentAnchor::Middle,
+                [bar.bar, bar.label].iter(),
+            );
+        } else {
+            layout.add_constraint(
+                layout.b(bar.label).left() | EQ(REQUIRED) | layout.b(bar.bar).right(),
+            );
+        }
+    }
+    layout.solve();
+
+    GanttLayout {
+        gantt: gantt.clone(),
+        config: config.clone(),
+        layout,
+        chart,
+        title,
+        bars,
+        sections,
+        ticks,
+        first,
+        last,
+        scale,
+    }
+}
+
+impl GanttLayout {
+    pub fn to_svg(&self) -> SVG {
+        let GanttLayout {
+            gantt,
+            config,
+            layout,
+            chart,
+            title,
+            bars,
+            sections,
+            ticks,
+            ..
+        } = self;
+
+        let chart_block = layout.b(*chart).solved();
+        let (chart_left, chart_top) = (chart_block.position.x, chart_block.position.y);
+        let chart_bottom = chart_top + chart_block.height;
+        let mut doc = document(layout.width(), layout.height());
+
+        for (i, section) in sections.iter().enumerate() {
+            let b = layout.b(section.block).solved();
+            doc = doc.add(
+                Rectangle::new()
+                    .set("x", 0)
+                    .set("y", b.position.y)
+                    .set("width", layout.width())
+                    .set("height", b.height)
+                    .set("fill", if i % 2 == 0 { CLUSTER_FILL } else { "white" }),
+            );
+        }
+
+        for tick in ticks {
+            let x = chart_left + self.x_of(tick.at);
+            doc = doc
+                .add(
+                    Line::new()
+                        .set("x1", x)
+                        .set("y1", chart_top)
+                        .set("x2", x)
+                        .set("y2", chart_bottom)
+                        .set("stroke", GRID_COLOR),
+                )
+                .add(text_group(layout, tick.block, &tick.lines, config));
+        }
+
+        for section in sections {
+            doc = doc.add(text_group(layout, section.title, &section.lines, config));
+        }
+
+        for (task, bar) in gantt.tasks.iter().zip(bars) {
+            let b = layout.b(bar.bar).solved();
+            let stroke = if task.crit { CRIT_STROKE } else { LINE_COLOR };
+            let shape = if task.milestone {
+                let (w, h) = (b.width, b.height);
+                let points = format!(
+                    "{},0 {w},{} {},{h} 0,{}",
+                    w * 0.5,
+                    h * 0.5,
+                    w * 0.5,
+                    h * 0.5
+                );
+                translated(b).add(
+                    stroked(Polygon::new().set("points", points), fill(task)).set("stroke", stroke),
+                )
+            } else {
+                translated(b)
+                    .add(outline(b, fill(task), config.corner_radius).set("stroke", stroke))
+            };
+            doc = doc
+                .add(shape)
+                .add(text_group(layout, bar.label, &bar.lines, config));
+        }
+
+        if let Some(x) = self.today_x() {
+            doc = doc.add(
+                Line::new()
+                    .set("x1", x)
+                    .set("y1", chart_top)
+                    .set("x2", x)
+                    .set("y2", chart_bottom)
+                    .set("stroke", TODAY_COLOR)
+                    .set("stroke-width", 2),
+            );
+        }
+
+        if let Some((block, lines)) = title {
+            doc = doc.add(text_group(layout, *block, lines, config));
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(gantt: &Gantt, config: &SvgConfig) -> SVG {
+    layout(gantt, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::gantt::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_bars() {
+        let input = "gantt
+dateFormat YYYY-MM-DD
+section Build
+A long design phase :des, 2024-01-01, 10d
+Implement with a label much too long to fit :impl, after des, 2d
+section Ship
+Release :milestone, rel, after impl, 0d";
+        let gantt = parse(input.to_string()).unwrap();
+        let config = config();
+        let schedule = layout(&gantt, &config);
+        let layout = &schedule.layout;
+        let chart = layout.b(schedule.chart).solved();
+        let b = |id: BlockId| layout.b(id).solved();
+
+        // the schedule spans the whole chart
+        assert_eq!(config.chart_width, schedule.x_of(schedule.last));
+        let mut top = chart.position.y;
+        for (task, bar) in gantt.tasks.iter().zip(&schedule.bars) {
+            let (row, block) = (b(bar.row), b(bar.bar));
+            assert!((row.position.y - top).abs() < 1e-6);
+            top += row.height;
+            let middle = |b: &crate::layout::Block| b.position.y + b.height * 0.5;
+            assert!((middle(row) - middle(block)).abs() < 1e-6);
+
+            let (start, end) = (schedule.x_of(task.start), schedule.x_of(task.end));
+            if task.milestone {
+                let center = block.position.x + block.width * 0.5 - chart.position.x;
+                assert!((center - start).abs() < 1e-6);
+            } else {
+                assert!((block.position.x - chart.position.x - start).abs() < 1e-6);
+                assert!((block.width - (end - start)).abs() < 1e-6);
+            }
+        }
+        assert!((chart.position.y + chart.height - top).abs() < 1e-6);
+
+        // the first label fits inside its bar, the others follow theirs
+        let (label, bar) = (b(schedule.bars[0].label), b(schedule.bars[0].bar));
+        assert!(bar.position.x <= label.position.x);
+        assert!(label.position.x + label.width <= bar.position.x + bar.width);
+        for bar in &schedule.bars[1..] {
+            let (label, block) = (b(bar.label), b(bar.bar));
+            assert!((label.position.x - block.position.x - block.width).abs() < 1e-6);
+        }
+
+        for (section, rows) in schedule.sections.iter().zip([0..2, 2..3]) {
+            let band = b(section.block);
+            let (first, last) = (
+                b(schedule.bars[rows.start].row),
+                b(schedule.bars[rows.end - 1].row),
+            );
+            assert!((band.position.y - first.position.y).abs() < 1e-6);
+            assert!((band.position.y + band.height - last.position.y - last.height).abs() < 1e-6);
+        }
+
+        for tick in &schedule.ticks {
+            let block = b(tick.block);
+            let center = block.position.x + block.width * 0.5 - chart.position.x;
+            assert!((center - schedule.x_of(tick.at)).abs() < 1e-6);
+            assert!(block.position.y >= chart.position.y + chart.height - 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_today() {
+        let src = "gantt\ndateFormat YYYY-MM-DD\nsection A\nTask : 2024-01-01, 10d";
+        let date = |date: &str| parse_date(date, "YYYY-MM-DD");
+        let marker = |src: &str, today: Option<Timestamp>| {
+            let config = SvgConfig { today, ..config() };
+            let schedule = layout(&parse(src.to_string()).unwrap(), &config);
+            let chart = schedule.layout.b(schedule.chart).solved().clone();
+            (schedule.today_x(), chart, schedule.to_svg().to_string())
+        };
+
+        // four of the ten days have gone by
+        let (x, chart, svg) = marker(src, date("2024-01-05"));
+        let expected = chart.position.x + chart.width * 0.4;
+        assert!((x.unwrap() - expected).abs() < 1e-6);
+        assert!(svg.contains(&format!("x1=\"{}\"", x.unwrap())));
+
+        assert_eq!(None, marker(src, None).0);
+        assert_eq!(None, marker(src, date("2025-01-05")).0);
+        let off = src.replace("section", "todayMarker off\nsection");
+        assert_eq!(None, marker(&off, date("2024-01-05")).0);
+    }
+}
@@ -0,0 +1,18 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(GitGraph, GitGraphLayout, ["gitGraph"], |options| {
+    SvgConfig {
+        max_label_length: 30,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        corner_radius: 10.0,
+        lane_gap: 60.0,
+        commit_gap: 50.0,
+        commit_radius: 8.0,
+    }
+});
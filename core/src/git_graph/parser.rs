@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{char, digit};
+use combine::parser::choice::choice;
+use combine::{attempt, eof, many, many1, none_of, optional, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, blank1, comment_parser, keyword};
+
+pub type BranchId = usize;
+pub type CommitId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphOrientation {
+    LeftToRight,
+    TopToBottom,
+    BottomToTop,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommitKind {
+    Normal,
+    Reverse,
+    Highlight,
+    Merge,
+    CherryPick,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Commit {
+    pub id: CommitId,
+    /// The id given in the source, or a generated one.
+    pub name: String,
+    pub kind: CommitKind,
+    pub tags: Vec<String>,
+    pub message: Option<String>,
+    pub branch: BranchId,
+    /// The previous commit of the branch first, then the merged one.
+    pub parents: Vec<CommitId>,
+    pub cherry_picked: Option<CommitId>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Branch {
+    pub id: BranchId,
+    pub name: String,
+    pub order: Option<i64>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GitGraph {
+    pub orientation: GraphOrientation,
+    pub branches: Vec<Branch>,
+    pub commits: Vec<Commit>,
+}
+
+impl GitGraph {
+    /// The lane of every branch: branches with an `order` are sorted by it,
+    /// the others keep the position they were created at.
+    pub fn lanes(&self) -> Vec<usize> {
+        let mut sorted: Vec<&Branch> = self.branches.iter().collect();
+        sorted.sort_by_key(|branch| (branch.order.unwrap_or(branch.id as i64), branch.id));
+        let mut lanes = vec![0; self.branches.len()];
+        for (lane, branch) in sorted.iter().enumerate() {
+            lanes[branch.id] = lane;
+        }
+        lanes
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Attribute {
+    Id(String),
+    Type(CommitKind),
+    Tag(String),
+    Message(String),
+    Parent(String),
+    Order(i64),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header(Option<GraphOrientation>),
+    Commit(Vec<Attribute>),
+    Branch(String, Vec<Attribute>),
+    Checkout(String),
+    Merge(String, Vec<Attribute>),
+    CherryPick(Vec<Attribute>),
+}
+
+pub fn parse(src: String) -> Result<GitGraph, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_graph(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// A short id for commits without one, stable from one run to the next.
+fn generated_name(seq: usize, branch: &str) -> String {
+    let hash = format!("{seq}{branch}")
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{seq}-{:07x}", hash & 0xfffffff)
+}
+
+struct Builder {
+    graph: GitGraph,
+    names: HashMap<String, CommitId>,
+    branch_names: HashMap<String, BranchId>,
+    heads: Vec<Option<CommitId>>,
+    current: BranchId,
+}
+
+impl Builder {
+    fn add_commit(
+        &mut self,
+        kind: CommitKind,
+        attributes: Vec<Attribute>,
+        parents: Vec<CommitId>,
+        cherry_picked: Option<CommitId>,
+    ) -> Result<(), StringStreamError> {
+        let id = self.graph.commits.len();
+        let mut commit = Commit {
+            id,
+            name: generated_name(id, &self.graph.branches[self.current].name),
+            kind,
+            tags: vec![],
+            message: None,
+            branch: self.current,
+            parents,
+            cherry_picked,
+        };
+        for attribute in attributes {
+            match attribute {
+                Attribute::Id(name) => commit.name = name,
+                Attribute::Type(kind) => commit.kind = kind,
+                Attribute::Tag(tag) => commit.tags.push(tag),
+                Attribute::Message(message) => commit.message = Some(message),
+                Attribute::Parent(_) | Attribute::Order(_) => {}
+            }
+        }
+        if self.names.insert(commit.name.clone(), id).is_some() {
+            return Err(StringStreamError::UnexpectedParse);
+        }
+        self.heads[self.current] = Some(id);
+        self.graph.commits.push(commit);
+        Ok(())
+    }
+
+    fn branch(&self, name: &str) -> Result<BranchId, StringStreamError> {
+        self.branch_names
+            .get(name)
+            .copied()
+            .ok_or(StringStreamError::UnexpectedParse)
+    }
+}
+
+fn build_graph(statements: Vec<Statement>) -> Result<GitGraph, StringStreamError> {
+    let mut builder = Builder {
+        graph: GitGraph {
+            orientation: GraphOrientation::LeftToRight,
+            branches: vec![Branch {
+                id: 0,
+                name: "main".to_string(),
+                order: None,
+            }],
+            commits: vec![],
+        },
+        names: HashMap::new(),
+        branch_names: HashMap::from([("main".to_string(), 0)]),
+        heads: vec![None],
+        current: 0,
+    };
+
+    for statement in statements {
+        let head = builder.heads[builder.current];
+        match statement {
+            Statement::Empty => {}
+            Statement::Header(orientation) => {
+                builder.graph.orientation = orientation.unwrap_or(builder.graph.orientation)
+            }
+            Statement::Commit(attributes) => builder.add_commit(
+                CommitKind::Normal,
+                attributes,
+                head.into_iter().collect(),
+                None,
+            )?,
+            Statement::Branch(name, attributes) => {
+                if builder.branch_names.contains_key(&name) {
+                    return Err(StringStreamError::UnexpectedParse);
+                }
+                let id = builder.graph.branches.len();
+                let order = attributes.iter().find_map(|attribute| match attribute {
+                    Attribute::Order(order) => Some(*order),
+                    _ => None,
+                });
+                builder.branch_names.insert(name.clone(), id);
+                builder.graph.branches.push(Branch { id, name, order });
+                builder.heads.push(head);
+                builder.current = id;
+            }
+            Statement::Checkout(name) => builder.current = builder.branch(&name)?,
+            Statement::Merge(name, attributes) => {
+                let other = builder.branch(&name)?;
+                let merged = builder.heads[other];
+                match (head, merged) {
+                    (Some(head), Some(merged)) if other != builder.current && head != merged => {
+                        builder.add_commit(
+                            CommitKind::Merge,
+                            attributes,
+                            vec![head, merged],
+                            None,
+                        )?
+                    }
+                    _ => return Err(StringStreamError::UnexpectedParse),
+                }
+            }
+            Statement::CherryPick(attributes) => {
+                let mut source = None;
+                let mut parent = None;
+                let mut rest = vec![];
+                for attribute in attributes {
+                    match attribute {
+                        Attribute::Id(name) => source = builder.names.get(&name).copied(),
+                        Attribute::Parent(name) => parent = builder.names.get(&name).copied(),
+                        attribute => rest.push(attribute),
+                    }
+                }
+                let source = source.ok_or(StringStreamError::UnexpectedParse)?;
+                let picked = &builder.graph.commits[source];
+                // A merge can only be picked along one of its parents.
+                let valid_parent = match picked.parents.len() {
+                    0 | 1 => true,
+                    _ => parent.is_some_and(|p| picked.parents.contains(&p)),
+                };
+                if picked.branch == builder.current || head.is_none() || !valid_parent {
+                    return Err(StringStreamError::UnexpectedParse);
+                }
+                if !rest.iter().any(|a| matches!(a, Attribute::Tag(_))) {
+                    rest.push(Attribute::Tag(format!("cherry-pick:{}", picked.name)));
+                }
+                builder.add_commit(
+                    CommitKind::CherryPick,
+                    rest,
+                    head.into_iter().collect(),
+                    Some(source),
+                )?
+            }
+        }
+    }
+    Ok(builder.graph)
+}
+
+/// A quoted string or a name such as `feature/login`.
+fn name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, name, _)| name),
+        many1(none_of(" \t\n\":%".chars())),
+    ))
+}
+
+fn kind_parser<Input>() -> impl Parser<Input, Output = CommitKind>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        keyword("NORMAL").map(|_| CommitKind::Normal),
+        keyword("REVERSE").map(|_| CommitKind::Reverse),
+        keyword("HIGHLIGHT").map(|_| CommitKind::Highlight),
+    ))
+}
+
+/// `key: value`
+fn attribute_parser<Input>() -> impl Parser<Input, Output = Attribute>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let colon = || (blank(), char(':'), blank());
+    choice((
+        (keyword("id"), colon(), name_parser()).map(|(_, _, id)| Attribute::Id(id)),
+        (keyword("type"), colon(), kind_parser()).map(|(_, _, kind)| Attribute::Type(kind)),
+        (keyword("tag"), colon(), name_parser()).map(|(_, _, tag)| Attribute::Tag(tag)),
+        (keyword("msg"), colon(), name_parser()).map(|(_, _, message)| Attribute::Message(message)),
+        (keyword("parent"), colon(), name_parser()).map(|(_, _, parent)| Attribute::Parent(parent)),
+        (
+            keyword("order"),
+            colon(),
+            optional(char('-')),
+            many1(digit()),
+        )
+            .map(|(_, _, sign, digits): (_, _, _, String)| {
+                let order: i64 = digits.parse().unwrap_or(0);
+                Attribute::Order(if sign.is_some() { -order } else { order })
+            }),
+    ))
+}
+
+fn attributes_parser<Input>() -> impl Parser<Input, Output = Vec<Attribute>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many(attempt((blank1(), attribute_parser())).map(|(_, attribute)| attribute))
+}
+
+/// `gitGraph [LR: | TB: | BT:]`
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("gitGraph"),
+        optional(attempt((
+            blank(),
+            choice((
+                keyword("LR").map(|_| GraphOrientation::LeftToRight),
+                keyword("TB").map(|_| GraphOrientation::TopToBottom),
+                keyword("BT").map(|_| GraphOrientation::BottomToTop),
+            )),
+            blank(),
+            char(':'),
+        ))),
+    )
+        .map(|(_, orientation)| Statement::Header(orientation.map(|(_, o, _, _)| o)))
+}
+
+fn command_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (keyword("commit"), attributes_parser()).map(|(_, a)| Statement::Commit(a)),
+        (
+            keyword("branch"),
+            blank1(),
+            name_parser(),
+            attributes_parser(),
+        )
+            .map(|(_, _, name, a)| Statement::Branch(name, a)),
+        (
+            choice((keyword("checkout"), keyword("switch"))),
+            blank1(),
+            name_parser(),
+        )
+            .map(|(_, _, name)| Statement::Checkout(name)),
+        (
+            keyword("merge"),
+            blank1(),
+            name_parser(),
+            attributes_parser(),
+        )
+            .map(|(_, _, name, a)| Statement::Merge(name, a)),
+        (keyword("cherry-pick"), attributes_parser()).map(|(_, a)| Statement::CherryPick(a)),
+    ))
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            command_parser(),
+            accessibility_parser().map(|_| Statement::Empty),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Commit(vec![
+                    Attribute::Id("init".to_string()),
+                    Attribute::Type(CommitKind::Highlight),
+                    Attribute::Tag("v1.0".to_string()),
+                ]),
+                ""
+            )),
+            command_parser().parse("commit id: \"init\" type: HIGHLIGHT tag: \"v1.0\"")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Branch("feature/login".to_string(), vec![Attribute::Order(2)]),
+                ""
+            )),
+            command_parser().parse("branch feature/login order: 2")
+        );
+        assert_eq!(
+            Ok((Statement::Checkout("develop".to_string()), "")),
+            command_parser().parse("switch develop")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"gitGraph TB:
+            commit id: "A"
+            branch develop
+            commit id: "B"
+            checkout main
+            commit id: "C"
+            merge develop tag: "v1"
+            branch hotfix order: -1
+            cherry-pick id: "B"
+        "#;
+        let graph = parse(input.to_string()).unwrap();
+        assert_eq!(GraphOrientation::TopToBottom, graph.orientation);
+        assert_eq!(5, graph.commits.len());
+        assert_eq!(vec![2, 1], graph.commits[3].parents);
+        assert_eq!(CommitKind::Merge, graph.commits[3].kind);
+        assert_eq!(vec!["v1".to_string()], graph.commits[3].tags);
+        assert_eq!(Some(1), graph.commits[4].cherry_picked);
+        assert_eq!(vec!["cherry-pick:B".to_string()], graph.commits[4].tags);
+        assert_eq!(vec![1, 2, 0], graph.lanes());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "gitGraph\ncheckout develop",
+            "gitGraph\ncommit\nmerge main",
+            "gitGraph\ncommit id: \"A\"\ncommit id: \"A\"",
+            "gitGraph\ncommit id: \"A\"\ncherry-pick id: \"A\"",
+        ] {
+            assert!(parse(input.to_string()).is_err(), "{input}");
+        }
+    }
+}
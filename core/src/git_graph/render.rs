@@ -0,0 +1,527 @@
+use svg::node::element::{Circle, Group, Line, Path, Rectangle, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::*;
+
+use crate::git_graph::*;
+use crate::layout::{BlockId, Layout};
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    /// Distance between the lanes of two branches.
+    pub lane_gap: f64,
+    /// Minimum distance between two consecutive commits.
+    pub commit_gap: f64,
+    pub commit_radius: f64,
+}
+
+struct Text {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+impl Text {
+    fn new(
+        layout: &mut Layout,
+        content: &str,
+        max_label_length: usize,
+        config: &SvgConfig,
+    ) -> Text {
+        let font_size = config.font_size * config.font_scale_factor;
+        let (block, lines) =
+            layout.add_text_block(content, max_label_length, config.padding, font_size);
+        Text { block, lines }
+    }
+}
+
+/// Turns positions along the history and across the lanes into coordinates.
+fn point(orientation: GraphOrientation, along: f64, across: f64) -> (f64, f64) {
+    match orientation {
+        GraphOrientation::LeftToRight => (along, across),
+        _ => (across, along),
+    }
+}
+
+/// A path from `from` to `to` that changes lanes at `cross`, a position
+/// along the graph between the two commits.
+fn edge_path(
+    orientation: GraphOrientation,
+    from: (f64, f64),
+    to: (f64, f64),
+    cross: f64,
+    radius: f64,
+) -> String {
+    let ((a1, c1), (a2, c2)) = (from, to);
+    let p = |along, across| {
+        let (x, y) = point(orientation, along, across);
+        format!("{x} {y}")
+    };
+    if c1 == c2 {
+        return format!("M {} L {}", p(a1, c1), p(a2, c2));
+    }
+    let r = radius
+        .min((c2 - c1).abs() * 0.5)
+        .min((cross - a1).abs())
+        .min((a2 - cross).abs());
+    let (da, dc) = ((a2 - a1).signum() * r, (c2 - c1).signum() * r);
+    format!(
+        "M {} L {} Q {} {} L {} Q {} {} L {}",
+        p(a1, c1),
+        p(cross - da, c1),
+        p(cross, c1),
+        p(cross, c1 + dc),
+        p(cross, c2 - dc),
+        p(cross, c2),
+        p(cross + da, c2),
+        p(a2, c2)
+    )
+}
+
+fn commit_glyph(kind: CommitKind, x: f64, y: f64, r: f64, color: &str) -> Group {
+    let circle =
+        |r: f64, fill: &str| stroked(Circle::new().set("cx", x).set("cy", y).set("r", r), fill);
+    let group = Group::new();
+    match kind {
+        CommitKind::Normal | CommitKind::CherryPick => group.add(circle(r, color)),
+        CommitKind::Merge => group.add(circle(r, color)).add(circle(r * 0.5, LINE_COLOR)),
+        CommitKind::Highlight => group.add(stroked(
+            Rectangle::new()
+                .set("x", x - r)
+                .set("y", y - r)
+                .set("width", 2.0 * r)
+                .set("height", 2.0 * r),
+            color,
+        )),
+        CommitKind::Reverse => {
+            let d = r * 0.6;
+            let cross = |x1: f64, y1: f64, x2: f64, y2: f64| {
+                Line::new()
+                    .set("x1", x1)
+                    .set("y1", y1)
+                    .set("x2", x2)
+                    .set("y2", y2)
+                    .set("stroke", LINE_COLOR)
+                    .set("stroke-width", 2)
+            };
+            group
+                .add(circle(r, color))
+                .add(cross(x - d, y - d, x + d, y + d))
+                .add(cross(x - d, y + d, x + d, y - d))
+        }
+    }
+}
+
+/// A git graph with its commits and labels placed along the branch lanes.
+pub struct GitGraphLayout {
+    graph: GitGraph,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    /// The lanes across and the commits along.
+    area: BlockId,
+    branch_labels: Vec<Text>,
+    commit_labels: Vec<Text>,
+    tags: Vec<Option<Text>>,
+}
+
+pub fn layout(graph: &GitGraph, config: &SvgConfig) -> GitGraphLayout {
+    let mut layout = Layout::new();
+    let orientation = graph.orientation;
+    let horizontal = orientation == GraphOrientation::LeftToRight;
+    let lanes = graph.lanes();
+    let r = config.commit_radius;
+
+    // The lanes are stacked across the graph block, commits follow one
+    // another along it.
+    let area = layout.add_block();
+    let (area_start, area_across, area_length, area_width) = match horizontal {
+        true => (
+            layout.b(area).left(),
+            layout.b(area).top(),
+            layout.b(area).width,
+            layout.b(area).height,
+        ),
+        false => (
+            layout.b(area).top(),
+            layout.b(area).left(),
+            layout.b(area).height,
+            layout.b(area).width,
+        ),
+    };
+    layout.add_constraint(area_width | EQ(REQUIRED) | (config.lane_gap * lanes.len() as f64));
+    layout.add_constraint(area_length | EQ(WEAK) | 0.0);
+    let lane_center = |lane: usize| area_across + config.lane_gap * (lane as f64 + 0.5);
+
+    let branch_labels: Vec<Text> = graph
+        .branches
+        .iter()
+        .map(|branch| {
+            let text = Text::new(&mut layout, &branch.name, config.max_label_length, config);
+            let (b, a) = (layout.b(text.block), layout.b(area));
+            let center = lane_center(lanes[branch.id]);
+            let constraints = match orientation {
+                GraphOrientation::LeftToRight => [
+                    b.right() | EQ(REQUIRED) | (a.left() - config.padding),
+                    (b.top() + b.height * 0.5) | EQ(REQUIRED) | center,
+                ],
+                GraphOrientation::TopToBottom => [
+                    b.bottom() | EQ(REQUIRED) | (a.top() - config.padding),
+                    (b.left() + b.width * 0.5) | EQ(REQUIRED) | center,
+                ],
+                GraphOrientation::BottomToTop => [
+                    b.top() | EQ(REQUIRED) | (a.bottom() + config.padding),
+                    (b.left() + b.width * 0.5) | EQ(REQUIRED) | center,
+                ],
+            };
+            for constraint in constraints {
+                layout.add_constraint(constraint);
+            }
+            text
+        })
+        .collect();
+
+    // Every commit is labelled with its id, beside it in its lane. The
+    // labels set the pace along the graph so that none of them overlap.
+    let commit_labels: Vec<Text> = graph
+        .commits
+        .iter()
+        .map(|commit| {
+            let text = Text::new(
+                &mut layout,
+                &commit.name,
+                config.max_label_length / 2,
+                config,
+            );
+            let b = layout.b(text.block);
+            let side = lane_center(lanes[commit.branch]) + r + config.padding;
+            match horizontal {
+                true => layout.add_constraint(b.top() | EQ(REQUIRED) | side),
+                false => layout.add_constraint(b.left() | EQ(REQUIRED) | side),
+            }
+            text
+        })
+        .collect();
+    let center = |layout: &Layout, text: &Text| {
+        let b = layout.b(text.block);
+        match horizontal {
+            true => b.left() + b.width * 0.5,
+            false => b.top() + b.height * 0.5,
+        }
+    };
+    let mut sequence: Vec<&Text> = commit_labels.iter().collect();
+    if orientation == GraphOrientation::BottomToTop {
+        sequence.reverse();
+    }
+    for (previous, next) in sequence.iter().zip(sequence.iter().skip(1)) {
+        let (previous_block, next_block) = (layout.b(previous.block), layout.b(next.block));
+        let constraint = match horizontal {
+            true => (previous_block.right() + config.padding) | LE(REQUIRED) | next_block.left(),
+            false => previous_block.bottom() | LE(REQUIRED) | next_block.top(),
+        };
+        layout.add_constraint(constraint);
+        layout.add_constraint(
+            (center(&layout, previous) + config.commit_gap) | LE(REQUIRED) | center(&layout, next),
+        );
+        layout.add_constraint(
+            (center(&layout, previous) + config.commit_gap) | EQ(WEAK) | center(&layout, next),
+        );
+    }
+    if let (Some(first), Some(last)) = (sequence.first(), sequence.last()) {
+        layout.add_constraint(
+            center(&layout, first) | EQ(REQUIRED) | (area_start + config.commit_gap * 0.5),
+        );
+        layout.add_constraint(
+            (center(&layout, last) + config.commit_gap * 0.5)
+                | EQ(REQUIRED)
+                | (area_start + area_length),
+        );
+    }
+
+    // Tags go on the other side of the commit.
+    let tags: Vec<Option<Text>> = graph
+        .commits
+        .iter()
+        .zip(commit_labels.iter())
+        .map(|(commit, label)| {
+            if commit.tags.is_empty() {
+                return None;
+            }
+            let text = Text::new(
+                &mut layout,
+                &commit.tags.join(", "),
+                config.max_label_length,
+                config,
+            );
+            let b = layout.b(text.block);
+            let side = lane_center(lanes[commit.branch]) - r - config.padding;
+            let commit_center = center(&layout, label);
+            let constraints = match horizontal {
+                true => [
+                    b.bottom() | EQ(REQUIRED) | side,
+                    (b.left() + b.width * 0.5) | EQ(STRONG) | commit_center,
+                ],
+                false => [
+                    b.right() | EQ(REQUIRED) | side,
+                    (b.top() + b.height * 0.5) | EQ(STRONG) | commit_center,
+                ],
+            };
+            for constraint in constraints {
+                layout.add_constraint(constraint);
+            }
+            Some(text)
+        })
+        .collect();
+    layout.solve();
+
+    GitGraphLayout {
+        graph: graph.clone(),
+        config: config.clone(),
+        layout,
+        area,
+        branch_labels,
+        commit_labels,
+        tags,
+    }
+}
+
+impl GitGraphLayout {
+    pub fn to_svg(&self) -> SVG {
+        let GitGraphLayout {
+            graph,
+            config,
+            layout,
+            area,
+            branch_labels,
+            commit_labels,
+            tags,
+        } = self;
+        let orientation = graph.orientation;
+        let horizontal = orientation == GraphOrientation::LeftToRight;
+        let lanes = graph.lanes();
+        let r = config.commit_radius;
+
+        let a = layout.b(*area).solved();
+        let (start, across, length) = match horizontal {
+            true => (a.position.x, a.position.y, a.width),
+            false => (a.position.y, a.position.x, a.height),
+        };
+        let lane_at = |lane: usize| across + config.lane_gap * (lane as f64 + 0.5);
+        // Where each commit label starts and ends along the graph.
+        let extents: Vec<(f64, f64)> = commit_labels
+            .iter()
+            .map(|text| {
+                let b = layout.b(text.block).solved();
+                match horizontal {
+                    true => (b.position.x, b.position.x + b.width),
+                    false => (b.position.y, b.position.y + b.height),
+                }
+            })
+            .collect();
+        let along: Vec<f64> = extents.iter().map(|(a, b)| (a + b) * 0.5).collect();
+        let position = |commit: &Commit| (along[commit.id], lane_at(lanes[commit.branch]));
+        let color = |branch: BranchId| PALETTE[lanes[branch] % PALETTE.len()];
+
+        let mut doc = document(
+            layout.width() + config.padding,
+            layout.height() + config.padding,
+        );
+
+        for branch in &graph.branches {
+            let center = lane_at(lanes[branch.id]);
+            let (x1, y1) = point(orientation, start, center);
+            let (x2, y2) = point(orientation, start + length, center);
+            doc = doc.add(
+                Line::new()
+                    .set("x1", x1)
+                    .set("y1", y1)
+                    .set("x2", x2)
+                    .set("y2", y2)
+                    .set("stroke", LINE_COLOR)
+                    .set("stroke-opacity", 0.3)
+                    .set("stroke-dasharray", "2 4"),
+            );
+        }
+
+        // Edges are drawn over the commit labels so none of them is hidden.
+        for text in commit_labels {
+            let b = layout.b(text.block).solved();
+            doc = doc.add(label_box(&text.lines, b, config.font_size, config.padding));
+        }
+
+        // Edges change lanes in the gap between the label of a commit and the
+        // label of the one before it, which no other label runs into. A new
+        // branch takes its own colour, a merge the colour of the merged branch.
+        for commit in graph.commits.iter().filter(|commit| commit.id > 0) {
+            let ((previous_start, previous_end), (start, end)) =
+                (extents[commit.id - 1], extents[commit.id]);
+            let cross = match orientation {
+                GraphOrientation::BottomToTop => (end + previous_start) * 0.5,
+                _ => (previous_end + start) * 0.5,
+            };
+            for (i, &parent) in commit.parents.iter().enumerate() {
+                let parent = &graph.commits[parent];
+                let branch = if i == 0 { commit.branch } else { parent.branch };
+                doc = doc.add(
+                    Path::new()
+                        .set(
+                            "d",
+                            edge_path(
+                                orientation,
+                                position(parent),
+                                position(commit),
+                                cross,
+                                config.corner_radius,
+                            ),
+                        )
+                        .set("fill", "none")
+                        .set("stroke", color(branch))
+                        .set("stroke-width", 4),
+                );
+            }
+        }
+
+        for commit in &graph.commits {
+            let (x, y) = point(orientation, along[commit.id], lane_at(lanes[commit.branch]));
+            doc = doc.add(commit_glyph(commit.kind, x, y, r, color(commit.branch)));
+        }
+
+        for (branch, text) in graph.branches.iter().zip(branch_labels.iter()) {
+            let b = layout.b(text.block).solved();
+            let group = translated(b).add(outline(b, color(branch.id), config.corner_radius));
+            doc = doc.add(
+                text_lines(&text.lines, b, config.font_size, config.padding)
+                    .fold(group, Group::add),
+            );
+        }
+        for text in tags.iter().flatten() {
+            let b = layout.b(text.block).solved();
+            let group = translated(b).add(outline(b, NOTE_FILL, config.corner_radius));
+            doc = doc.add(
+                text_lines(&text.lines, b, config.font_size, config.padding)
+                    .fold(group, Group::add),
+            );
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(graph: &GitGraph, config: &SvgConfig) -> SVG {
+    layout(graph, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::git_graph::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_lanes() {
+        let graph = parse(
+            "gitGraph\ncommit\nbranch a order: 2\ncommit\nbranch b order: 1\ncommit\nbranch c\ncommit"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(vec![0, 2, 1, 3], graph.lanes());
+    }
+
+    #[test]
+    fn test_placement() {
+        let config = config();
+        for orientation in ["LR", "TB", "BT"] {
+            let graph = parse(format!(
+                "gitGraph {orientation}:\ncommit id: \"A\"\nbranch develop\ncommit id: \"B\" tag: \"v0\"\ncheckout main\ncommit id: \"C\"\nmerge develop id: \"D\""
+            ))
+            .unwrap();
+            let horizontal = graph.orientation == GraphOrientation::LeftToRight;
+            let lanes = graph.lanes();
+            let placement = layout(&graph, &config);
+            let layout = &placement.layout;
+            // along the history and across the lanes
+            let extents = |id: BlockId| {
+                let b = layout.b(id).solved();
+                let (x, y) = (
+                    (b.position.x, b.position.x + b.width),
+                    (b.position.y, b.position.y + b.height),
+                );
+                if horizontal {
+                    (x, y)
+                } else {
+                    (y, x)
+                }
+            };
+            let (area_along, area_across) = extents(placement.area);
+            let lane_center = |lane: usize| area_across.0 + config.lane_gap * (lane as f64 + 0.5);
+            assert!((area_across.1 - area_across.0 - 2.0 * config.lane_gap).abs() < 1e-6);
+
+            let mut centers = vec![];
+            for (commit, text) in graph.commits.iter().zip(&placement.commit_labels) {
+                let (along, across) = extents(text.block);
+                assert!(
+                    area_along.0 <= along.0 && along.1 <= area_along.1,
+                    "{orientation}"
+                );
+                let side =
+                    lane_center(lanes[commit.branch]) + config.commit_radius + config.padding;
+                assert!((across.0 - side).abs() < 1e-6, "{orientation}");
+                centers.push((along.0 + along.1) * 0.5);
+            }
+            if graph.orientation == GraphOrientation::BottomToTop {
+                centers.reverse();
+            }
+            for (previous, next) in centers.iter().zip(&centers[1..]) {
+                assert!(previous + config.commit_gap <= next + 1e-6, "{orientation}");
+            }
+
+            for (branch, text) in graph.branches.iter().zip(&placement.branch_labels) {
+                let (along, across) = extents(text.block);
+                let center = (across.0 + across.1) * 0.5;
+                assert!(
+                    (center - lane_center(lanes[branch.id])).abs() < 1e-6,
+                    "{orientation}"
+                );
+                match graph.orientation {
+                    GraphOrientation::BottomToTop => {
+                        assert!(along.0 >= area_along.1, "{orientation}")
+                    }
+                    _ => assert!(along.1 <= area_along.0, "{orientation}"),
+                }
+            }
+
+            let tag = placement.tags[1].as_ref().unwrap();
+            let (_, across) = extents(tag.block);
+            let side = lane_center(lanes[1]) - config.commit_radius - config.padding;
+            assert!((across.1 - side).abs() < 1e-6, "{orientation}");
+            assert_eq!(1, placement.tags.iter().flatten().count());
+        }
+    }
+
+    #[test]
+    fn test_edge_path() {
+        let o = GraphOrientation::LeftToRight;
+        assert_eq!(
+            "M 0 10 L 50 10",
+            edge_path(o, (0.0, 10.0), (50.0, 10.0), 25.0, 5.0)
+        );
+        // along the first lane, around the corner at the crossing and on
+        assert_eq!(
+            "M 0 10 L 20 10 Q 25 10 25 15 L 25 65 Q 25 70 30 70 L 50 70",
+            edge_path(o, (0.0, 10.0), (50.0, 70.0), 25.0, 5.0)
+        );
+        let o = GraphOrientation::TopToBottom;
+        assert_eq!(
+            "M 70 0 L 70 20 Q 70 25 65 25 L 15 25 Q 10 25 10 30 L 10 50",
+            edge_path(o, (0.0, 70.0), (50.0, 10.0), 25.0, 5.0)
+        );
+    }
+}
@@ -0,0 +1,17 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Journey, JourneyLayout, ["journey"], |options| SvgConfig {
+    max_label_length: 20,
+    font_scale_factor: options.font_scale_factor,
+    font_size: options.font_size,
+    padding: options.padding,
+    corner_radius: 5.0,
+    task_width: 120.0,
+    gutter: 20.0,
+    mood_height: 80.0,
+    face_radius: 15.0,
+});
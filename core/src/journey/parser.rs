@@ -0,0 +1,224 @@
+use combine::error::StringStreamError;
+use combine::parser::char::{char, digit};
+use combine::parser::choice::choice;
+use combine::{
+    eof, many, many1, none_of, not_followed_by, one_of, optional, sep_by1, ParseError, Parser,
+    Stream,
+};
+
+use crate::parsing::{accessibility_parser, blank, comment_parser, keyword, keyword_line};
+
+pub type SectionId = usize;
+pub type TaskId = usize;
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub id: SectionId,
+    pub name: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Task {
+    pub id: TaskId,
+    pub section: Option<SectionId>,
+    pub label: String,
+    /// How the actors feel about the task, from 1 to 5.
+    pub score: u8,
+    pub actors: Vec<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Journey {
+    pub title: Option<String>,
+    pub sections: Vec<Section>,
+    pub tasks: Vec<Task>,
+}
+
+impl Journey {
+    /// Every actor once, in order of appearance.
+    pub fn actors(&self) -> Vec<&String> {
+        let mut actors: Vec<&String> = vec![];
+        for actor in self.tasks.iter().flat_map(|task| task.actors.iter()) {
+            if !actors.contains(&actor) {
+                actors.push(actor);
+            }
+        }
+        actors
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Title(String),
+    Section(String),
+    Task {
+        label: String,
+        score: u8,
+        actors: Vec<String>,
+    },
+}
+
+pub fn parse(src: String) -> Result<Journey, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => Ok(build_journey(statements)),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_journey(statements: Vec<Statement>) -> Journey {
+    let mut journey = Journey {
+        title: None,
+        sections: vec![],
+        tasks: vec![],
+    };
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Title(title) => journey.title = Some(title),
+            Statement::Section(name) => journey.sections.push(Section {
+                id: journey.sections.len(),
+                name,
+            }),
+            Statement::Task {
+                label,
+                score,
+                actors,
+            } => journey.tasks.push(Task {
+                id: journey.tasks.len(),
+                section: journey.sections.last().map(|section| section.id),
+                label,
+                score,
+                actors,
+            }),
+        }
+    }
+    journey
+}
+
+/// `label: score[: actor, actor]`
+fn task_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        none_of(":\n%".chars()),
+        many(none_of(":\n".chars())),
+        char(':'),
+        blank(),
+        one_of("12345".chars()),
+        not_followed_by(digit()),
+        blank(),
+        optional(
+            (
+                char(':'),
+                blank(),
+                sep_by1(
+                    many1(none_of(",\n".chars())).map(|actor: String| actor.trim().to_string()),
+                    char(','),
+                ),
+            )
+                .map(|(_, _, actors)| actors),
+        ),
+    )
+        .map(
+            |(first, rest, _, _, score, _, _, actors): (char, String, _, _, char, _, _, _)| {
+                Statement::Task {
+                    label: format!("{first}{rest}").trim().to_string(),
+                    score: score as u8 - b'0',
+                    actors: actors.unwrap_or_default(),
+                }
+            },
+        )
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            keyword("journey").map(|_| Statement::Empty),
+            keyword_line("title").map(Statement::Title),
+            keyword_line("section").map(Statement::Section),
+            accessibility_parser().map(|_| Statement::Empty),
+            task_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Task {
+                    label: "Go upstairs".to_string(),
+                    score: 3,
+                    actors: vec!["Me".to_string(), "Cat".to_string()],
+                },
+                ""
+            )),
+            task_parser().parse("Go upstairs: 3: Me, Cat")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Task {
+                    label: "Sit down".to_string(),
+                    score: 5,
+                    actors: vec![],
+                },
+                ""
+            )),
+            task_parser().parse("Sit down: 5")
+        );
+        assert!(task_parser().parse("Sit down: 6: Me").is_err());
+        assert!(task_parser().parse("Sit down: 10: Me").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"journey
+            title My working day
+            section Go to work
+              Make tea: 5: Me
+              Go upstairs: 3: Me, Cat
+            section Go home
+              %% at last
+              Go downstairs: 5: Me
+        "#;
+        let journey = parse(input.to_string()).unwrap();
+        assert_eq!(Some("My working day".to_string()), journey.title);
+        assert_eq!(2, journey.sections.len());
+        assert_eq!(Some(1), journey.tasks[2].section);
+        assert_eq!(
+            vec![&"Me".to_string(), &"Cat".to_string()],
+            journey.actors()
+        );
+    }
+}
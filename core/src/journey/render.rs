@@ -0,0 +1,403 @@
+use svg::node::element::{Circle, Group, Line, Path, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG};
+use cassowary::WeightedRelation::*;
+
+use crate::journey::*;
+use crate::layout::{AlignmentAnchor, BlockId, Layout, Orientation};
+use crate::pie::Legend;
+use crate::theme::*;
+
+const FACE_FILL: &str = "#FFF8DC";
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    /// Minimum width of a task.
+    pub task_width: f64,
+    /// Space between tasks, and between the tasks and the other parts.
+    pub gutter: f64,
+    /// Distance between the faces of the best and the worst scores.
+    pub mood_height: f64,
+    pub face_radius: f64,
+}
+
+struct Text {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+fn add_text(
+    layout: &mut Layout,
+    content: &str,
+    max_label_length: usize,
+    config: &SvgConfig,
+) -> Text {
+    let font_size = config.font_size * config.font_scale_factor;
+    let (block, lines) =
+        layout.add_text_block(content, max_label_length, config.padding, font_size);
+    Text { block, lines }
+}
+
+fn text_box(layout: &Layout, text: &Text, fill: &str, config: &SvgConfig) -> Group {
+    let b = layout.b(text.block).solved();
+    let group = translated(b).add(outline(b, fill, config.corner_radius));
+    text_lines(&text.lines, b, config.font_size, config.padding).fold(group, Group::add)
+}
+
+/// A face smiling for scores above 3 and frowning below.
+fn face(score: u8, x: f64, y: f64, r: f64) -> Group {
+    let eye = |dx: f64| {
+        Circle::new()
+            .set("cx", x + dx)
+            .set("cy", y - r * 0.25)
+            .set("r", r * 0.1)
+            .set("fill", LINE_COLOR)
+    };
+    let (left, right) = (x - r * 0.5, x + r * 0.5);
+    let mouth = match score {
+        4.. => format!(
+            "M {left} {} Q {x} {} {right} {}",
+            y + r * 0.25,
+            y + r * 0.75,
+            y + r * 0.25
+        ),
+        3 => format!("M {left} {} L {right} {}", y + r * 0.4, y + r * 0.4),
+        _ => format!(
+            "M {left} {} Q {x} {} {right} {}",
+            y + r * 0.55,
+            y + r * 0.1,
+            y + r * 0.55
+        ),
+    };
+    Group::new()
+        .add(stroked(
+            Circle::new().set("cx", x).set("cy", y).set("r", r),
+            FACE_FILL,
+        ))
+        .add(eye(-r * 0.35))
+        .add(eye(r * 0.35))
+        .add(stroked(Path::new().set("d", mouth), "none"))
+}
+
+/// How far below its task the face of a score hangs, to its center.
+fn face_offset(score: u8, config: &SvgConfig) -> f64 {
+    config.gutter + config.face_radius + config.mood_height * (5 - score.clamp(1, 5)) as f64 / 4.0
+}
+
+/// A user journey with its tasks, sections and legend placed.
+pub struct JourneyLayout {
+    journey: Journey,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    title: Option<Text>,
+    legend: Legend,
+    tasks: Vec<Text>,
+    sections: Vec<Text>,
+}
+
+pub fn layout(journey: &Journey, config: &SvgConfig) -> JourneyLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+    let actors = journey.actors();
+
+    let title = journey
+        .title
+        .as_ref()
+        .map(|title| add_text(&mut layout, title, config.max_label_length * 3, config));
+
+    let legend_labels: Vec<String> = actors.iter().map(|actor| actor.to_string()).collect();
+    let legend = Legend::new(
+        &mut layout,
+        &legend_labels,
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let legend_block = legend.block();
+
+    // Tasks are laid out in a row right of the legend of the actors.
+    let tasks: Vec<Text> = journey
+        .tasks
+        .iter()
+        .map(|task| {
+            let text = add_text(&mut layout, &task.label, config.max_label_length, config);
+            layout.add_constraint(layout.b(text.block).width | GE(REQUIRED) | config.task_width);
+            text
+        })
+        .collect();
+    let task_blocks: Vec<BlockId> = tasks.iter().map(|task| task.block).collect();
+    layout.distribute(Orientation::Horizontal, config.gutter, task_blocks.iter());
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::Start,
+        task_blocks.iter(),
+    );
+    for (previous, next) in task_blocks.iter().zip(task_blocks.iter().skip(1)) {
+        layout.add_constraint(layout.b(*previous).height | EQ(REQUIRED) | layout.b(*next).height);
+        layout.add_constraint(
+            (layout.b(*previous).right() + config.gutter) | EQ(STRONG) | layout.b(*next).left(),
+        );
+    }
+    layout.add_constraint(layout.b(legend_block).left() | EQ(REQUIRED) | config.padding);
+    if let (Some(&first), Some(&last)) = (task_blocks.first(), task_blocks.last()) {
+        layout.add_constraint(
+            layout.b(first).left()
+                | EQ(REQUIRED)
+                | (layout.b(legend_block).right() + config.gutter),
+        );
+        layout.add_constraint(layout.b(legend_block).top() | EQ(REQUIRED) | layout.b(first).top());
+
+        // The faces hang below the tasks, higher for better scores.
+        let mood = layout.add_block();
+        layout.add_constraint(
+            layout.b(mood).top() | EQ(REQUIRED) | (layout.b(first).bottom() + config.gutter),
+        );
+        layout.add_constraint(
+            layout.b(mood).height
+                | EQ(REQUIRED)
+                | (config.mood_height + 2.0 * config.face_radius + config.gutter),
+        );
+        layout.add_constraint(layout.b(mood).left() | EQ(REQUIRED) | layout.b(first).left());
+        layout.add_constraint(
+            layout.b(mood).right() | EQ(REQUIRED) | (layout.b(last).right() + config.gutter),
+        );
+    }
+
+    // Sections span the tasks they hold.
+    let sections: Vec<Text> = journey
+        .sections
+        .iter()
+        .map(|section| {
+            let text = add_text(&mut layout, &section.name, config.max_label_length, config);
+            let b = text.block;
+            let members: Vec<BlockId> = journey
+                .tasks
+                .iter()
+                .filter(|task| task.section == Some(section.id))
+                .map(|task| tasks[task.id].block)
+                .collect();
+            match (members.first(), members.last()) {
+                (Some(&first), Some(&last)) => {
+                    layout
+                        .add_constraint(layout.b(b).left() | EQ(REQUIRED) | layout.b(first).left());
+                    layout.add_constraint(
+                        layout.b(b).right() | GE(REQUIRED) | layout.b(last).right(),
+                    );
+                    layout
+                        .add_constraint(layout.b(b).right() | EQ(STRONG) | layout.b(last).right());
+                    layout.add_constraint(
+                        (layout.b(b).bottom() + config.gutter * 0.5)
+                            | EQ(REQUIRED)
+                            | layout.b(first).top(),
+                    );
+                }
+                _ => layout.add_constraint(layout.b(b).width | EQ(REQUIRED) | 0.0),
+            }
+            text
+        })
+        .collect();
+    let section_blocks: Vec<BlockId> = sections.iter().map(|section| section.block).collect();
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::End,
+        section_blocks.iter(),
+    );
+
+    if let Some(title) = &title {
+        let b = title.block;
+        layout.add_constraint(layout.b(b).top() | EQ(REQUIRED) | 0.0);
+        layout.add_constraint(layout.b(b).left() | EQ(STRONG) | config.padding);
+        for &other in section_blocks
+            .iter()
+            .chain(task_blocks.iter())
+            .chain([legend_block].iter())
+        {
+            layout.add_constraint(
+                layout.b(other).top() | GE(REQUIRED) | (layout.b(b).bottom() + config.gutter),
+            );
+        }
+    }
+    layout.solve();
+
+    JourneyLayout {
+        journey: journey.clone(),
+        config: config.clone(),
+        layout,
+        title,
+        legend,
+        tasks,
+        sections,
+    }
+}
+
+impl JourneyLayout {
+    pub fn to_svg(&self) -> SVG {
+        let JourneyLayout {
+            journey,
+            config,
+            layout,
+            title,
+            legend,
+            tasks,
+            sections,
+        } = self;
+        let actors = journey.actors();
+
+        let mut doc = document(
+            layout.width() + config.padding,
+            layout.height() + config.padding,
+        );
+        let r = config.face_radius;
+
+        for (task, text) in journey.tasks.iter().zip(tasks.iter()) {
+            let b = layout.b(text.block).solved();
+            let x = b.position.x + b.width * 0.5;
+            let top = b.position.y + b.height;
+            let y = top + face_offset(task.score, config);
+            doc = doc
+                .add(
+                    Line::new()
+                        .set("x1", x)
+                        .set("y1", top)
+                        .set("x2", x)
+                        .set("y2", y - r)
+                        .set("stroke", LINE_COLOR)
+                        .set("stroke-dasharray", "4 4"),
+                )
+                .add(face(task.score, x, y, r));
+
+            let color = match task.section {
+                Some(section) => PALETTE[section % PALETTE.len()],
+                None => CLUSTER_FILL,
+            };
+            doc = doc.add(text_box(layout, text, color, config));
+            for (i, actor) in task.actors.iter().enumerate() {
+                let index = actors.iter().position(|a| *a == actor).unwrap_or(0);
+                doc = doc.add(stroked(
+                    Circle::new()
+                        .set(
+                            "cx",
+                            b.position.x + config.corner_radius + r * 0.5 + i as f64 * r,
+                        )
+                        .set("cy", b.position.y)
+                        .set("r", r * 0.35),
+                    PALETTE[index % PALETTE.len()],
+                ));
+            }
+        }
+
+        // The journey runs from left to right below the faces.
+        if let (Some(first), Some(last)) = (tasks.first(), tasks.last()) {
+            let (first, last) = (
+                layout.b(first.block).solved(),
+                layout.b(last.block).solved(),
+            );
+            let y = first.position.y
+                + first.height
+                + 2.0 * config.gutter
+                + config.mood_height
+                + 2.0 * r;
+            doc = doc.add(
+                Line::new()
+                    .set("x1", first.position.x)
+                    .set("y1", y)
+                    .set("x2", last.position.x + last.width)
+                    .set("y2", y)
+                    .set("stroke", LINE_COLOR)
+                    .set("stroke-width", 2)
+                    .set("marker-end", "url(#end-arrow)"),
+            );
+        }
+
+        for (section, text) in journey.sections.iter().zip(sections.iter()) {
+            if !journey.tasks.iter().any(|t| t.section == Some(section.id)) {
+                continue;
+            }
+            doc = doc.add(text_box(
+                layout,
+                text,
+                PALETTE[section.id % PALETTE.len()],
+                config,
+            ));
+        }
+        doc = doc.add(legend.to_svg(layout, config.font_size, config.padding));
+        if let Some(title) = &title {
+            let b = layout.b(title.block).solved();
+            doc = doc.add(
+                text_lines(&title.lines, b, config.font_size, config.padding)
+                    .fold(translated(b), Group::add),
+            );
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(journey: &Journey, config: &SvgConfig) -> SVG {
+    layout(journey, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::journey::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_tasks() {
+        let input = "journey
+title My working day
+section Go to work
+Make tea: 5: Me
+Go upstairs: 3: Me, Cat
+section Go home
+Go downstairs with a label long enough to wrap: 1: Me";
+        let journey = parse(input.to_string()).unwrap();
+        let config = config();
+        let chart = layout(&journey, &config);
+        let b = |id: BlockId| chart.layout.b(id).solved();
+
+        let legend = b(chart.legend.block());
+        let tasks: Vec<_> = chart.tasks.iter().map(|t| b(t.block)).collect();
+        assert!(
+            (legend.position.x + legend.width + config.gutter - tasks[0].position.x).abs() < 1e-6
+        );
+        assert_eq!(legend.position.y, tasks[0].position.y);
+        for (previous, next) in tasks.iter().zip(&tasks[1..]) {
+            assert!(previous.position.x + previous.width + config.gutter <= next.position.x + 1e-6);
+            assert_eq!(previous.position.y, next.position.y);
+            assert!((previous.height - next.height).abs() < 1e-6);
+        }
+        for task in &tasks {
+            assert!(task.width >= config.task_width);
+        }
+
+        for (section, members) in chart.sections.iter().zip([0..2, 2..3]) {
+            let section = b(section.block);
+            let (first, last) = (&tasks[members.start], &tasks[members.end - 1]);
+            assert!((section.position.x - first.position.x).abs() < 1e-6);
+            assert!(section.position.x + section.width >= last.position.x + last.width - 1e-6);
+            assert!(section.position.y + section.height < first.position.y);
+        }
+        let title = b(chart.title.as_ref().unwrap().block);
+        assert!(title.position.y + title.height + config.gutter <= legend.position.y + 1e-6);
+
+        // happier faces hang higher, all of them between the tasks and the axis
+        let offsets: Vec<f64> = (0..=6).map(|score| face_offset(score, &config)).collect();
+        assert_eq!(config.gutter + config.face_radius, offsets[5]);
+        assert_eq!(offsets[1] - offsets[5], config.mood_height);
+        assert_eq!((offsets[0], offsets[6]), (offsets[1], offsets[5]));
+        for (worse, better) in offsets[1..=5].iter().zip(&offsets[2..=5]) {
+            assert!(better < worse);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use euclid::Vector2D;
 
 use itertools::Itertools;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use cassowary::strength::{MEDIUM, REQUIRED, STRONG, WEAK};
 use cassowary::WeightedRelation::*;
@@ -13,6 +13,8 @@ use rusttype::{point, Font, Scale};
 
 use textwrap::{wrap, LineEnding, Options};
 
+use unicode_bidi::{BidiInfo, Level};
+
 #[derive(Debug, Clone, Copy)]
 pub enum Orientation {
     Horizontal,
@@ -28,6 +30,43 @@ pub enum AlignmentAnchor {
 
 pub type Alignment = (Orientation, AlignmentAnchor);
 
+/// The font text is measured with, and drawn with when rasterising.
+pub(crate) const FONT: &[u8] = include_bytes!("../../resources/fonts/Roboto-Regular.ttf");
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+}
+
+/// Base direction of a text as determined by the Unicode bidi algorithm,
+/// i.e. the direction of its first strong character.
+pub fn paragraph_direction(text: &str) -> TextDirection {
+    let bidi = BidiInfo::new(text, None);
+    match bidi.paragraphs.first() {
+        Some(para) if para.level.is_rtl() => TextDirection::RightToLeft,
+        _ => TextDirection::LeftToRight,
+    }
+}
+
+/// The characters of a wrapped line in the order they are displayed within a
+/// paragraph of the given direction, for outputs that place glyphs from left
+/// to right without reordering them. Lines are broken in logical order first,
+/// as the bidi algorithm has it.
+pub fn visual_order(line: &str, direction: TextDirection) -> String {
+    let level = match direction {
+        TextDirection::LeftToRight => Level::ltr(),
+        TextDirection::RightToLeft => Level::rtl(),
+    };
+    let bidi = BidiInfo::new(line, Some(level));
+    bidi.paragraphs
+        .iter()
+        .map(|para| bidi.reorder_line(para, para.range.clone()))
+        .collect()
+}
+
 pub struct Layout<'a> {
     solver: Solver,
     vars: HashMap<Variable, VariableId>,
@@ -49,10 +88,8 @@ impl Layout<'_> {
         vars.insert(right_var, VariableId::LayoutRight);
         vars.insert(bottom_var, VariableId::LayoutBottom);
 
-        // Read the font data.
-        let font = include_bytes!("../../resources/fonts/Roboto-Regular.ttf") as &[u8];
-        // Parse it into the font type.
-        let roboto_regular = Font::try_from_bytes(font).expect("Error constructing Font");
+        // Parse the font data into the font type.
+        let roboto_regular = Font::try_from_bytes(FONT).expect("Error constructing Font");
 
         let scale = Scale::uniform(12.0);
         let v_metrics = roboto_regular.v_metrics(scale);
@@ -98,46 +135,26 @@ impl Layout<'_> {
         self.constraints_accu
             .push(self.bottom_var | GE(REQUIRED) | block.bottom());
 
-        let mut text_width = 0;
-
         let lines = wrap(content, max_length);
         // The font size to use
         let scale = Scale::uniform(font_size as f32);
-        let v_metrics = self.font.v_metrics(scale);
 
         let mut height = 2.0 * padding;
+        let mut text_width: f64 = 0.0;
         for line in &lines {
-            // layout the glyphs in a line with 20 pixels padding
-            let glyphs: Vec<_> = self
-                .font
-                .layout(line, scale, point(0.0, 0.0 + v_metrics.ascent))
-                .collect();
-            // work out the layout size
             height += self.glyphs_height;
-            let glyphs_width = {
-                let min_x = glyphs
-                    .first()
-                    .map(|g| g.pixel_bounding_box().unwrap().min.x)
-                    .unwrap();
-                let max_x = glyphs
-                    .last()
-                    .map(|g| g.pixel_bounding_box().unwrap().max.x)
-                    .unwrap();
-                let line_width = (max_x - min_x) as u32;
-                if line_width > text_width {
-                    text_width = line_width;
-                }
-                line_width
-            };
+            text_width = text_width.max(self.line_width(line, scale));
         }
+        block.block.direction = paragraph_direction(content);
 
-        let width: f64 = (text_width as f64) + (2.0 * padding);
+        let width: f64 = text_width.ceil() + (2.0 * padding);
         self.constraints_accu
             .push(block.width | GE(REQUIRED) | width);
         self.constraints_accu.push(block.width | EQ(WEAK) | width);
 
         self.constraints_accu
             .push(block.height | GE(STRONG) | height);
+        self.constraints_accu.push(block.height | EQ(WEAK) | height);
 
         self.blocks.push(block);
         let liness: Vec<String> = lines
@@ -148,6 +165,21 @@ impl Layout<'_> {
         (id, liness)
     }
 
+    /// Width of a single line of text, which does not depend on the order its
+    /// runs are displayed in.
+    fn line_width(&self, line: &str, scale: Scale) -> f64 {
+        self.font
+            .layout(line, scale, point(0.0, 0.0))
+            .last()
+            .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.0) as f64
+    }
+
+    /// Width of a single line of text drawn at `font_size`.
+    pub fn text_width(&self, line: &str, font_size: f64) -> f64 {
+        self.line_width(line, Scale::uniform(font_size as f32))
+    }
+
     pub fn b(&self, id: BlockId) -> &LayoutBlock {
         &self.blocks[id]
     }
@@ -299,12 +331,23 @@ type Scalar = f64;
 type Position = euclid::Vector2D<Scalar, ScreenSpace>;
 type Size = euclid::Vector2D<Scalar, ScreenSpace>;
 
-#[derive(Default)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub position: Position,
     pub width: Scalar,
     pub height: Scalar,
     pub line_height: Scalar,
+    pub direction: TextDirection,
+}
+
+/// The solved blocks of a diagram, keyed by the id of the element they hold.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolvedLayout {
+    pub width: Scalar,
+    pub height: Scalar,
+    pub blocks: BTreeMap<String, Block>,
 }
 
 pub struct LayoutBlock {
@@ -373,3 +416,68 @@ impl LayoutBlock {
         }
     }
 }
+
+/// Rectangle predicates for asserting on solved layouts.
+#[cfg(test)]
+pub(crate) mod geometry {
+    use super::Block;
+
+    const EPSILON: f64 = 1e-6;
+
+    pub(crate) fn inside(inner: &Block, outer: &Block) -> bool {
+        outer.position.x <= inner.position.x + EPSILON
+            && outer.position.y <= inner.position.y + EPSILON
+            && inner.position.x + inner.width <= outer.position.x + outer.width + EPSILON
+            && inner.position.y + inner.height <= outer.position.y + outer.height + EPSILON
+    }
+
+    pub(crate) fn overlap(a: &Block, b: &Block) -> bool {
+        a.position.x < b.position.x + b.width
+            && b.position.x < a.position.x + a.width
+            && a.position.y < b.position.y + b.height
+            && b.position.y < a.position.y + a.height
+    }
+
+    pub(crate) fn on_outline((x, y): (f64, f64), b: &Block) -> bool {
+        let (right, bottom) = (b.position.x + b.width, b.position.y + b.height);
+        let within_x = b.position.x - EPSILON <= x && x <= right + EPSILON;
+        let within_y = b.position.y - EPSILON <= y && y <= bottom + EPSILON;
+        (within_y && ((x - b.position.x).abs() < EPSILON || (x - right).abs() < EPSILON))
+            || (within_x && ((y - b.position.y).abs() < EPSILON || (y - bottom).abs() < EPSILON))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_directions() {
+        assert_eq!(
+            TextDirection::RightToLeft,
+            paragraph_direction("مرحبا Alice")
+        );
+        assert_eq!(
+            TextDirection::LeftToRight,
+            paragraph_direction("Alice مرحبا")
+        );
+
+        // a line of a right-to-left paragraph that starts with a Latin word
+        // still ends on the right
+        let line = "Alice مرحبا";
+        assert_eq!(
+            "Alice ابحرم",
+            visual_order(line, TextDirection::LeftToRight)
+        );
+        assert_eq!(
+            "ابحرم Alice",
+            visual_order(line, TextDirection::RightToLeft)
+        );
+
+        // lines are wrapped in logical order and keep the paragraph direction
+        let mut layout = Layout::new();
+        let (id, lines) = layout.add_text_block("مرحبا Alice مرحبا Bob", 12, 5.0, 12.0);
+        assert_eq!(vec!["مرحبا Alice", "مرحبا Bob"], lines);
+        assert_eq!(TextDirection::RightToLeft, layout.b(id).solved().direction);
+    }
+}
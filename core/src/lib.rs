@@ -1,3 +1,77 @@
+pub mod c4;
+pub mod class_diagram;
+pub mod diagram;
+pub mod er_diagram;
+pub mod flowchart;
+pub mod gantt;
+pub mod git_graph;
+pub mod journey;
 pub mod layout;
+pub mod mindmap;
+mod parsing;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod pie;
+pub mod quadrant;
+#[cfg(feature = "png")]
+pub mod raster;
+pub mod requirement;
+pub mod sankey;
+pub mod scene;
 pub mod sequence_diagram;
+pub mod state_diagram;
+pub mod theme;
+pub mod timeline;
+pub mod xychart;
 
+pub use diagram::{Diagram, Options, Registry};
+use svg::node::element::SVG;
+
+/// The diagram type of `src` among the types of this crate.
+fn detect<'a>(registry: &'a Registry, src: &str) -> Result<&'a dyn diagram::AnyDiagram, String> {
+    registry
+        .detect(src)
+        .ok_or_else(|| "Error: unknown diagram type".to_string())
+}
+
+/// Renders a diagram of any of the types of this crate.
+pub fn render(src: &str, options: &Options) -> Result<SVG, String> {
+    render_with(&Registry::default(), src, options)
+}
+
+/// Renders a diagram of any of the types of `registry`, which may hold types
+/// defined outside of this crate.
+pub fn render_with(registry: &Registry, src: &str, options: &Options) -> Result<SVG, String> {
+    registry.render(src, options)
+}
+
+/// Renders a sequence diagram to PDF, the only diagram type with a PDF
+/// output so far.
+#[cfg(feature = "pdf")]
+pub fn render_pdf(src: &str, options: &Options, pdf: &pdf::PdfOptions) -> Result<Vec<u8>, String> {
+    let sequence = &sequence_diagram::Definition;
+    if detect(&Registry::default(), src)?.keywords() != Diagram::keywords(sequence) {
+        return Err("Error: only sequence diagrams can be rendered to PDF".to_string());
+    }
+    let ast = sequence.parse(diagram::body(src))?;
+    let scene = sequence.layout(&ast, options).scene();
+    scene::RenderBackend::render(&pdf::PdfBackend { options: pdf }, &scene)
+}
+
+/// Draws a sequence diagram with characters, to be printed to a terminal.
+pub fn render_text(src: &str, config: &sequence_diagram::TextConfig) -> Result<String, String> {
+    let sequence = &sequence_diagram::Definition;
+    if detect(&Registry::default(), src)?.keywords() != Diagram::keywords(sequence) {
+        return Err("Error: only sequence diagrams can be rendered to text".to_string());
+    }
+    let ast = sequence.parse(diagram::body(src))?;
+    Ok(sequence_diagram::to_text(&ast, config))
+}
+
+/// Canonical source for a diagram, with its front matter kept as written.
+pub fn format(src: &str) -> Result<String, String> {
+    let body = diagram::body(src);
+    let front_matter = &src[..src.len() - body.len()];
+    let formatted = detect(&Registry::default(), src)?.format(body)?;
+    Ok(format!("{front_matter}{formatted}"))
+}
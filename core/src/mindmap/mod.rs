@@ -0,0 +1,17 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Mindmap, MindmapLayout, ["mindmap"], |options| SvgConfig {
+    max_label_length: 20,
+    font_scale_factor: options.font_scale_factor,
+    font_size: options.font_size,
+    padding: options.padding,
+    corner_radius: 5.0,
+    tree_layout: TreeLayout::Balanced,
+    rank_gutter: 40.0,
+    node_gutter: 10.0,
+    ring_gap: 150.0,
+});
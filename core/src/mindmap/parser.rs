@@ -0,0 +1,343 @@
+use combine::error::StringStreamError;
+use combine::parser::char::{char, string};
+use combine::parser::choice::choice;
+use combine::{
+    attempt, eof, many, many1, none_of, not_followed_by, one_of, optional, skip_many, ParseError,
+    Parser, Stream,
+};
+
+use crate::parsing::{comment_parser, keyword};
+
+pub type NodeId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    /// Plain text, without delimiters.
+    Default,
+    /// `[text]`
+    Square,
+    /// `(text)`
+    Rounded,
+    /// `((text))`
+    Circle,
+    /// `{{text}}`
+    Hexagon,
+    /// `)text(`
+    Cloud,
+    /// `))text((`
+    Bang,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: NodeId,
+    /// The id written in front of the shape, if any.
+    pub name: Option<String>,
+    pub label: String,
+    pub shape: Shape,
+    pub icon: Option<String>,
+    pub classes: Vec<String>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+}
+
+/// A tree of nodes, the root being the first one.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mindmap {
+    pub nodes: Vec<Node>,
+}
+
+impl Mindmap {
+    pub fn depth(&self, id: NodeId) -> usize {
+        let mut depth = 0;
+        let mut node = &self.nodes[id];
+        while let Some(parent) = node.parent {
+            depth += 1;
+            node = &self.nodes[parent];
+        }
+        depth
+    }
+
+    /// Number of leaves below `id`, or 1 for a leaf.
+    pub fn leaves(&self, id: NodeId) -> usize {
+        match self.nodes[id].children.len() {
+            0 => 1,
+            _ => self.nodes[id]
+                .children
+                .iter()
+                .map(|&child| self.leaves(child))
+                .sum(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header,
+    Node {
+        name: Option<String>,
+        label: String,
+        shape: Shape,
+    },
+    Icon(String),
+    Class(Vec<String>),
+}
+
+pub fn parse(src: String) -> Result<Mindmap, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_mindmap(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// Drops the quotes of `"text"` and the backquotes of markdown strings, and
+/// turns `<br>` into line breaks.
+fn label_text(text: &str) -> String {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("\"`")
+        .and_then(|t| t.strip_suffix("`\""))
+        .or_else(|| text.strip_prefix('"').and_then(|t| t.strip_suffix('"')))
+        .unwrap_or(text);
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+}
+
+fn build_mindmap(statements: Vec<(usize, Statement)>) -> Result<Mindmap, StringStreamError> {
+    let mut mindmap = Mindmap { nodes: vec![] };
+    // The nodes a new line can be nested in, with their indentation.
+    let mut stack: Vec<(usize, NodeId)> = vec![];
+    for (indent, statement) in statements {
+        let (name, label, shape) = match statement {
+            Statement::Empty => continue,
+            Statement::Header if mindmap.nodes.is_empty() => continue,
+            Statement::Header => (None, "mindmap".to_string(), Shape::Default),
+            Statement::Icon(icon) => {
+                let node = mindmap
+                    .nodes
+                    .last_mut()
+                    .ok_or(StringStreamError::UnexpectedParse)?;
+                node.icon = Some(icon);
+                continue;
+            }
+            Statement::Class(classes) => {
+                let node = mindmap
+                    .nodes
+                    .last_mut()
+                    .ok_or(StringStreamError::UnexpectedParse)?;
+                node.classes.extend(classes);
+                continue;
+            }
+            Statement::Node { name, label, shape } => (name, label, shape),
+        };
+        while matches!(stack.last(), Some(&(parent_indent, _)) if parent_indent >= indent) {
+            stack.pop();
+        }
+        let parent = stack.last().map(|&(_, parent)| parent);
+        // There is a single root.
+        if parent.is_none() && !mindmap.nodes.is_empty() {
+            return Err(StringStreamError::UnexpectedParse);
+        }
+        let id = mindmap.nodes.len();
+        if let Some(parent) = parent {
+            mindmap.nodes[parent].children.push(id);
+        }
+        mindmap.nodes.push(Node {
+            id,
+            name,
+            label: label_text(&label),
+            shape,
+            icon: None,
+            classes: vec![],
+            parent,
+            children: vec![],
+        });
+        stack.push((indent, id));
+    }
+    if mindmap.nodes.is_empty() {
+        return Err(StringStreamError::UnexpectedParse);
+    }
+    Ok(mindmap)
+}
+
+/// Delimiters of the shapes, tried in order.
+const SHAPES: [(&str, &str, Shape); 6] = [
+    ("((", "))", Shape::Circle),
+    ("(", ")", Shape::Rounded),
+    ("[", "]", Shape::Square),
+    ("{{", "}}", Shape::Hexagon),
+    ("))", "((", Shape::Bang),
+    (")", "(", Shape::Cloud),
+];
+
+/// Reads `[id]shape` where the shape runs to the end of the line, or plain
+/// text.
+fn node_statement(line: &str) -> Statement {
+    let line = line.trim();
+    let split = line.find(['[', '(', ')', '{']).unwrap_or(line.len());
+    let (prefix, rest) = line.split_at(split);
+    let shape = SHAPES.iter().find_map(|&(open, close, shape)| {
+        rest.strip_prefix(open)
+            .and_then(|text| text.strip_suffix(close))
+            .map(|text| (shape, text))
+    });
+    match shape {
+        Some((shape, label)) => Statement::Node {
+            name: Some(prefix.trim().to_string()).filter(|name| !name.is_empty()),
+            label: label.to_string(),
+            shape,
+        },
+        None => Statement::Node {
+            name: None,
+            label: line.to_string(),
+            shape: Shape::Default,
+        },
+    }
+}
+
+fn node_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        not_followed_by(one_of(":%".chars())),
+        many1(none_of("\n".chars())),
+    )
+        .map(|(_, line): (_, String)| node_statement(&line))
+}
+
+/// `::icon(fa fa-book)`
+fn icon_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        attempt(string("::icon(")),
+        many1(none_of(")\n".chars())),
+        char(')'),
+    )
+        .map(|(_, icon, _): (_, String, _)| Statement::Icon(icon.trim().to_string()))
+}
+
+/// `:::class1 class2`
+fn class_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (attempt(string(":::")), many1(none_of("\n".chars()))).map(|(_, classes): (_, String)| {
+        Statement::Class(classes.split_whitespace().map(str::to_string).collect())
+    })
+}
+
+/// A statement and the width of its indentation.
+fn statement_parser<Input>() -> impl Parser<Input, Output = (usize, Statement)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        many(one_of(" \t".chars())),
+        optional(choice((
+            (keyword("mindmap"), skip_many(one_of(" \t".chars()))).map(|_| Statement::Header),
+            icon_parser(),
+            class_parser(),
+            node_parser(),
+        ))),
+        skip_many(one_of(" \t".chars())),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(indent, statement, _, _, _): (String, _, _, _, _)| {
+            (indent.len(), statement.unwrap_or(Statement::Empty))
+        })
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<(usize, Statement)>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_parser() {
+        let node = |name: Option<&str>, label: &str, shape| {
+            Ok((
+                Statement::Node {
+                    name: name.map(str::to_string),
+                    label: label.to_string(),
+                    shape,
+                },
+                "",
+            ))
+        };
+        assert_eq!(
+            node(Some("root"), "mind map", Shape::Circle),
+            node_parser().parse("root((mind map))")
+        );
+        assert_eq!(
+            node(Some("id"), "I am (a) cloud", Shape::Cloud),
+            node_parser().parse("id)I am (a) cloud(")
+        );
+        assert_eq!(
+            node(None, "bang", Shape::Bang),
+            node_parser().parse("))bang((")
+        );
+        assert_eq!(
+            node(None, "hexagon", Shape::Hexagon),
+            node_parser().parse("{{hexagon}}")
+        );
+        assert_eq!(
+            node(None, "Long history", Shape::Default),
+            node_parser().parse("Long history")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"mindmap
+  root((mindmap))
+    Origins
+      Long history
+      ::icon(fa fa-book)
+      Popularisation
+    %% a comment
+    Research[On effectiveness<br/>and features]
+    :::urgent large
+      Tabbed
+"#;
+        let mindmap = parse(input.to_string()).unwrap();
+        assert_eq!(6, mindmap.nodes.len());
+        assert_eq!(vec![1, 4], mindmap.nodes[0].children);
+        assert_eq!(vec![2, 3], mindmap.nodes[1].children);
+        assert_eq!(Some("fa fa-book".to_string()), mindmap.nodes[2].icon);
+        assert_eq!("On effectiveness\nand features", mindmap.nodes[4].label);
+        assert_eq!(vec!["urgent", "large"], mindmap.nodes[4].classes);
+        assert_eq!(Some(4), mindmap.nodes[5].parent);
+        assert_eq!(2, mindmap.depth(5));
+        assert_eq!(3, mindmap.leaves(0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("mindmap\n  a\n  b".to_string()).is_err());
+        assert!(parse("mindmap\n  ::icon(fa fa-book)".to_string()).is_err());
+    }
+}
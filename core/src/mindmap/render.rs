@@ -0,0 +1,510 @@
+use std::f64::consts::{PI, TAU};
+
+use svg::node::element::{Circle, Group, Line, Path, Polygon, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{BlockId, Layout};
+use crate::mindmap::*;
+use crate::theme::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeLayout {
+    /// The branches of the root are split between its left and right.
+    Balanced,
+    /// The branches go all around the root, deeper nodes further out.
+    Radial,
+}
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub tree_layout: TreeLayout,
+    /// Horizontal space between a node and its children in a balanced tree.
+    pub rank_gutter: f64,
+    /// Vertical space between siblings in a balanced tree.
+    pub node_gutter: f64,
+    /// Distance between the rings of a radial tree.
+    pub ring_gap: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// The name of an icon such as `fa fa-book`, shown as text.
+fn icon_name(icon: &str) -> &str {
+    let name = icon.split_whitespace().last().unwrap_or(icon);
+    name.split_once('-').map(|(_, name)| name).unwrap_or(name)
+}
+
+/// The side of the root every node is on: the branches of the root go to the
+/// side with the fewest leaves so far, the first one to the right.
+fn sides(mindmap: &Mindmap) -> Vec<Side> {
+    let mut sides = vec![Side::Right; mindmap.nodes.len()];
+    let (mut left, mut right) = (0, 0);
+    for &branch in &mindmap.nodes[0].children {
+        let leaves = mindmap.leaves(branch);
+        let side = if right <= left {
+            right += leaves;
+            Side::Right
+        } else {
+            left += leaves;
+            Side::Left
+        };
+        let mut stack = vec![branch];
+        while let Some(id) = stack.pop() {
+            sides[id] = side;
+            stack.extend(&mindmap.nodes[id].children);
+        }
+    }
+    sides
+}
+
+/// The index of the branch of the root every node belongs to, the root
+/// itself being 0.
+fn branches(mindmap: &Mindmap) -> Vec<usize> {
+    mindmap
+        .nodes
+        .iter()
+        .map(|node| {
+            let mut id = node.id;
+            while let Some(parent) = mindmap.nodes[id].parent.filter(|&p| p != 0) {
+                id = parent;
+            }
+            mindmap.nodes[0]
+                .children
+                .iter()
+                .position(|&branch| branch == id)
+                .map_or(0, |i| i + 1)
+        })
+        .collect()
+}
+
+/// Angle of every node of a radial tree: each node gets a share of the
+/// angle of its parent in proportion to its number of leaves.
+fn angles(mindmap: &Mindmap) -> Vec<f64> {
+    let mut angles = vec![0.0; mindmap.nodes.len()];
+    let mut stack = vec![(0, -PI / 2.0, TAU - PI / 2.0)];
+    while let Some((id, start, end)) = stack.pop() {
+        angles[id] = (start + end) * 0.5;
+        let total = mindmap.leaves(id) as f64;
+        let mut from = start;
+        for &child in &mindmap.nodes[id].children {
+            let to = from + (end - start) * mindmap.leaves(child) as f64 / total;
+            stack.push((child, from, to));
+            from = to;
+        }
+    }
+    angles
+}
+
+fn shape_outline(shape: Shape, w: f64, h: f64, fill: &str, corner_radius: f64) -> Group {
+    let group = Group::new();
+    match shape {
+        Shape::Default => group.add(
+            Line::new()
+                .set("x1", 0)
+                .set("y1", h)
+                .set("x2", w)
+                .set("y2", h)
+                .set("stroke", fill)
+                .set("stroke-width", 3),
+        ),
+        Shape::Square | Shape::Rounded => {
+            let radius = if shape == Shape::Rounded {
+                corner_radius
+            } else {
+                0.0
+            };
+            group.add(stroked(
+                svg::node::element::Rectangle::new()
+                    .set("width", w)
+                    .set("height", h)
+                    .set("rx", radius),
+                fill,
+            ))
+        }
+        Shape::Circle => group.add(stroked(
+            Circle::new()
+                .set("cx", w * 0.5)
+                .set("cy", h * 0.5)
+                .set("r", w.max(h) * 0.5),
+            fill,
+        )),
+        Shape::Hexagon => {
+            let d = h * 0.3;
+            group.add(stroked(
+                Polygon::new().set(
+                    "points",
+                    format!(
+                        "0,{} {d},0 {},0 {w},{} {},{h} {d},{h}",
+                        h * 0.5,
+                        w - d,
+                        h * 0.5,
+                        w - d
+                    ),
+                ),
+                fill,
+            ))
+        }
+        Shape::Cloud => {
+            // Bulges around a rectangle inset by their height, going round
+            // clockwise.
+            let inset = h * 0.15;
+            let (right, bottom) = (w - inset, h - inset);
+            let bumps = (((right - inset) / (bottom - inset)).round() as usize).max(1);
+            let step = (right - inset) / bumps as f64;
+            let mut points = vec![(inset, inset)];
+            points.extend((1..=bumps).map(|i| (inset + step * i as f64, inset)));
+            points.push((right, bottom));
+            points.extend((1..=bumps).map(|i| (right - step * i as f64, bottom)));
+            points.push((inset, inset));
+            let mut d = format!("M {inset} {inset}");
+            for (&(x0, y0), &(x1, y1)) in points.iter().zip(points.iter().skip(1)) {
+                let chord = (x1 - x0).hypot(y1 - y0);
+                let radius = (chord * chord * 0.25 + inset * inset) / (2.0 * inset);
+                d += &format!(" A {radius} {radius} 0 0 1 {x1} {y1}");
+            }
+            group.add(stroked(Path::new().set("d", d + " Z"), fill))
+        }
+        Shape::Bang => {
+            let (cx, cy) = (w * 0.5, h * 0.5);
+            let spikes = 16;
+            let points: Vec<String> = (0..2 * spikes)
+                .map(|i| {
+                    let angle = PI * i as f64 / spikes as f64;
+                    let scale = if i % 2 == 0 { 0.5 } else { 0.4 };
+                    let (x, y) = (cx + w * scale * angle.cos(), cy + h * scale * angle.sin());
+                    format!("{x},{y}")
+                })
+                .collect();
+            group.add(stroked(
+                Polygon::new().set("points", points.join(" ")),
+                fill,
+            ))
+        }
+    }
+}
+
+/// A mindmap with its nodes placed around the root.
+pub struct MindmapLayout {
+    mindmap: Mindmap,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    /// The text block of every node, its wrapped label and the padding
+    /// around it.
+    texts: Vec<(BlockId, Vec<String>, f64)>,
+}
+
+pub fn layout(mindmap: &Mindmap, config: &SvgConfig) -> MindmapLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+    let sides = sides(mindmap);
+
+    // Shapes that are not rectangles need room around their text.
+    let texts: Vec<(BlockId, Vec<String>, f64)> = mindmap
+        .nodes
+        .iter()
+        .map(|node| {
+            let padding = match node.shape {
+                Shape::Default | Shape::Square | Shape::Rounded => config.padding,
+                Shape::Bang => config.padding * 3.0,
+                _ => config.padding * 2.0,
+            };
+            let content = match &node.icon {
+                Some(icon) => format!("[{}]\n{}", icon_name(icon), node.label),
+                None => node.label.clone(),
+            };
+            let (block, lines) =
+                layout.add_text_block(&content, config.max_label_length, padding, font_size);
+            if node.shape == Shape::Circle {
+                layout
+                    .add_constraint(layout.b(block).width | EQ(REQUIRED) | layout.b(block).height);
+            }
+            (block, lines, padding)
+        })
+        .collect();
+    let block = |id: NodeId| texts[id].0;
+    let middle = |layout: &Layout, id: NodeId| {
+        let b = layout.b(block(id));
+        b.top() + b.height * 0.5
+    };
+
+    match config.tree_layout {
+        TreeLayout::Balanced => {
+            // Every node has a block around its subtree, and the subtrees of
+            // siblings are stacked.
+            let subtrees: Vec<BlockId> = mindmap.nodes.iter().map(|_| layout.add_block()).collect();
+            for node in &mindmap.nodes {
+                let (b, sub) = (block(node.id), subtrees[node.id]);
+                layout.add_constraint(layout.b(sub).top() | LE(REQUIRED) | layout.b(b).top());
+                layout.add_constraint(layout.b(sub).bottom() | GE(REQUIRED) | layout.b(b).bottom());
+                layout.add_constraint(layout.b(sub).left() | EQ(REQUIRED) | layout.b(b).left());
+                layout.add_constraint(layout.b(sub).width | EQ(REQUIRED) | layout.b(b).width);
+                layout.add_constraint(layout.b(sub).height | EQ(WEAK) | 0.0);
+
+                if let Some(parent) = node.parent {
+                    let p = block(parent);
+                    let constraint = match sides[node.id] {
+                        Side::Right => {
+                            layout.b(b).left()
+                                | EQ(REQUIRED)
+                                | (layout.b(p).right() + config.rank_gutter)
+                        }
+                        Side::Left => {
+                            layout.b(b).right()
+                                | EQ(REQUIRED)
+                                | (layout.b(p).left() - config.rank_gutter)
+                        }
+                    };
+                    layout.add_constraint(constraint);
+                }
+
+                for side in [Side::Right, Side::Left] {
+                    let children: Vec<BlockId> = node
+                        .children
+                        .iter()
+                        .filter(|&&child| sides[child] == side)
+                        .map(|&child| subtrees[child])
+                        .collect();
+                    layout.distribute(
+                        crate::layout::Orientation::Vertical,
+                        config.node_gutter,
+                        children.iter(),
+                    );
+                    if let (Some(&first), Some(&last)) = (children.first(), children.last()) {
+                        layout.add_constraint(
+                            layout.b(sub).top() | LE(REQUIRED) | layout.b(first).top(),
+                        );
+                        layout.add_constraint(
+                            layout.b(sub).bottom() | GE(REQUIRED) | layout.b(last).bottom(),
+                        );
+                        layout.add_constraint(
+                            middle(&layout, node.id)
+                                | EQ(STRONG)
+                                | ((layout.b(first).top() + layout.b(last).bottom()) * 0.5),
+                        );
+                    }
+                }
+            }
+        }
+        TreeLayout::Radial => {
+            let origin = layout.add_block();
+            layout.add_constraint(layout.b(origin).left() | EQ(WEAK) | 0.0);
+            layout.add_constraint(layout.b(origin).top() | EQ(WEAK) | 0.0);
+            let angles = angles(mindmap);
+            for node in &mindmap.nodes {
+                let b = block(node.id);
+                let radius = config.ring_gap * mindmap.depth(node.id) as f64;
+                let angle = angles[node.id];
+                layout.add_constraint(
+                    (layout.b(b).left() + layout.b(b).width * 0.5)
+                        | EQ(REQUIRED)
+                        | (layout.b(origin).left() + radius * angle.cos()),
+                );
+                layout.add_constraint(
+                    middle(&layout, node.id)
+                        | EQ(REQUIRED)
+                        | (layout.b(origin).top() + radius * angle.sin()),
+                );
+            }
+        }
+    }
+    layout.add_constraint(layout.b(block(0)).left() | EQ(WEAK) | 0.0);
+    layout.add_constraint(layout.b(block(0)).top() | EQ(WEAK) | 0.0);
+    layout.solve();
+    MindmapLayout {
+        mindmap: mindmap.clone(),
+        config: config.clone(),
+        layout,
+        texts,
+    }
+}
+
+impl MindmapLayout {
+    pub fn to_svg(&self) -> SVG {
+        let MindmapLayout {
+            mindmap,
+            config,
+            layout,
+            texts,
+        } = self;
+        let sides = sides(mindmap);
+        let branches = branches(mindmap);
+        let block = |id: NodeId| texts[id].0;
+
+        let mut doc = document(
+            layout.width() + config.padding,
+            layout.height() + config.padding,
+        );
+        let color = |id: NodeId| PALETTE[branches[id] % PALETTE.len()];
+
+        // Connectors leave the side of the parent facing the child, or its
+        // center in a radial tree where they are hidden behind the nodes.
+        for node in &mindmap.nodes {
+            let Some(parent) = node.parent else {
+                continue;
+            };
+            let (p, c) = (
+                layout.b(block(parent)).solved(),
+                layout.b(block(node.id)).solved(),
+            );
+            let (py, cy) = (p.position.y + p.height * 0.5, c.position.y + c.height * 0.5);
+            let (px, cx) = match (config.tree_layout, sides[node.id]) {
+                (TreeLayout::Radial, _) => {
+                    (p.position.x + p.width * 0.5, c.position.x + c.width * 0.5)
+                }
+                (_, Side::Right) => (p.position.x + p.width, c.position.x),
+                (_, Side::Left) => (p.position.x, c.position.x + c.width),
+            };
+            let mx = (px + cx) * 0.5;
+            let d = match config.tree_layout {
+                TreeLayout::Balanced => format!("M {px} {py} C {mx} {py} {mx} {cy} {cx} {cy}"),
+                TreeLayout::Radial => format!("M {px} {py} L {cx} {cy}"),
+            };
+            let depth = mindmap.depth(node.id) as f64;
+            doc = doc.add(
+                Path::new()
+                    .set("d", d)
+                    .set("fill", "none")
+                    .set("stroke", color(node.id))
+                    .set("stroke-width", (6.0 - 1.5 * depth).max(1.0)),
+            );
+        }
+
+        for node in &mindmap.nodes {
+            let (b, lines, padding) = &texts[node.id];
+            let b = layout.b(*b).solved();
+            let mut group = translated(b).add(shape_outline(
+                node.shape,
+                b.width,
+                b.height,
+                color(node.id),
+                config.corner_radius,
+            ));
+            if !node.classes.is_empty() {
+                group = group.set("class", node.classes.join(" "));
+            }
+            doc = doc.add(text_lines(lines, b, config.font_size, *padding).fold(group, Group::add));
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(mindmap: &Mindmap, config: &SvgConfig) -> SVG {
+    layout(mindmap, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::layout::Block;
+    use crate::mindmap::Definition;
+
+    fn config(tree_layout: TreeLayout) -> SvgConfig {
+        SvgConfig {
+            tree_layout,
+            ..Definition.config(&Options::default())
+        }
+    }
+
+    fn mindmap() -> Mindmap {
+        parse("mindmap\n  root((root))\n    A\n      A1\n      A2\n      A3\n    B\n      B1\n    C\n      C1\n      C2".to_string())
+            .unwrap()
+    }
+
+    fn center(b: &Block) -> (f64, f64) {
+        (b.position.x + b.width * 0.5, b.position.y + b.height * 0.5)
+    }
+
+    #[test]
+    fn test_balanced() {
+        let mindmap = mindmap();
+        let sides = sides(&mindmap);
+        // A has three leaves on the right, B and C as many on the left
+        let names = |side| {
+            mindmap.nodes[1..]
+                .iter()
+                .filter(|node| sides[node.id] == side)
+                .map(|node| node.label.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["A", "A1", "A2", "A3"], names(Side::Right));
+        assert_eq!(vec!["B", "B1", "C", "C1", "C2"], names(Side::Left));
+
+        let config = config(TreeLayout::Balanced);
+        let MindmapLayout { layout, texts, .. } = layout(&mindmap, &config);
+        let b = |id: NodeId| layout.b(texts[id].0).solved();
+        for node in &mindmap.nodes {
+            let Some(parent) = node.parent else {
+                continue;
+            };
+            let (parent, child) = (b(parent), b(node.id));
+            match sides[node.id] {
+                Side::Right => assert!(
+                    (child.position.x - parent.position.x - parent.width - config.rank_gutter)
+                        .abs()
+                        < 1e-6
+                ),
+                Side::Left => assert!(
+                    (parent.position.x - child.position.x - child.width - config.rank_gutter).abs()
+                        < 1e-6
+                ),
+            }
+            // siblings on a side stacked top to bottom, around their parent
+            let siblings: Vec<&Block> = mindmap.nodes[node.parent.unwrap()]
+                .children
+                .iter()
+                .filter(|&&id| sides[id] == sides[node.id])
+                .map(|&id| b(id))
+                .collect();
+            for (upper, lower) in siblings.iter().zip(&siblings[1..]) {
+                assert!(
+                    upper.position.y + upper.height + config.node_gutter <= lower.position.y + 1e-6
+                );
+            }
+        }
+        let a = mindmap.nodes[1]
+            .children
+            .iter()
+            .map(|&id| b(id))
+            .collect::<Vec<_>>();
+        let middle = (a[0].position.y + a[2].position.y + a[2].height) * 0.5;
+        assert!((center(b(1)).1 - middle).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_radial() {
+        let mindmap = mindmap();
+        let angles = angles(&mindmap);
+        // every leaf gets a sixth of the circle, clockwise from the top
+        let leaves = [2, 3, 4, 6, 8, 9];
+        for (i, leaf) in leaves.iter().enumerate() {
+            let expected = -PI / 2.0 + TAU / 6.0 * (i as f64 + 0.5);
+            assert!((angles[*leaf] - expected).abs() < 1e-9);
+        }
+        // and a branch points at the middle of its leaves
+        assert!((angles[1] - angles[3]).abs() < 1e-9);
+
+        let config = config(TreeLayout::Radial);
+        let MindmapLayout { layout, texts, .. } = layout(&mindmap, &config);
+        let root = center(layout.b(texts[0].0).solved());
+        for node in &mindmap.nodes[1..] {
+            let (x, y) = center(layout.b(texts[node.id].0).solved());
+            let radius = config.ring_gap * mindmap.depth(node.id) as f64;
+            assert!(((x - root.0).hypot(y - root.1) - radius).abs() < 1e-6);
+            let turn = ((y - root.1).atan2(x - root.0) - angles[node.id]).rem_euclid(TAU);
+            assert!(turn.min(TAU - turn) < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Parsers shared by the diagram types.
+
+use combine::error::StreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::stream::StreamErrorFor;
+use combine::{
+    attempt, many, many1, none_of, not_followed_by, one_of, skip_many, skip_many1, ParseError,
+    Parser, Stream,
+};
+
+/// Spaces and tabs
+pub(crate) fn blank<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skip_many(one_of(" \t".chars()))
+}
+
+pub(crate) fn blank1<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skip_many1(one_of(" \t".chars()))
+}
+
+/// A whole word, without consuming anything when the input only starts like
+/// it.
+pub(crate) fn keyword<Input>(word: &'static str) -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((string(word), not_followed_by(alpha_num()))).map(|_| ())
+}
+
+/// The rest of the line after a keyword such as `title` or `section`.
+pub(crate) fn keyword_line<Input>(word: &'static str) -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword(word), blank1(), many1(none_of("\n".chars())))
+        .map(|(_, _, text): (_, _, String)| text.trim().to_string())
+}
+
+pub(crate) fn comment_parser<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (string("%%"), skip_many(none_of("\n".chars()))).map(|_| ())
+}
+
+pub(crate) fn quoted<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, text, _)| text)
+}
+
+/// A line starting with one of `words`, which is skipped as a whole.
+pub(crate) fn skipped_parser<Input>(
+    words: &'static [&'static str],
+) -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let word = many1(alpha_num()).and_then(move |word: String| {
+        if words.contains(&word.as_str()) {
+            Ok(())
+        } else {
+            Err(StreamErrorFor::<Input>::expected_static_message("keyword"))
+        }
+    });
+    (attempt(word), skip_many(none_of("\n".chars()))).map(|_| ())
+}
+
+/// Accessibility statements are skipped.
+pub(crate) fn accessibility_parser<Input>() -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&["accTitle", "accDescr"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use combine::error::StringStreamError;
+
+    #[test]
+    fn test_keyword() {
+        assert_eq!(Ok(((), " x")), keyword("title").parse("title x"));
+        assert_eq!(
+            Err(StringStreamError::UnexpectedParse),
+            keyword("title").parse("titles")
+        );
+        assert_eq!(
+            Ok(("a b".to_string(), "\n")),
+            keyword_line("title").parse("title  a b \n")
+        );
+    }
+
+    #[test]
+    fn test_skipped() {
+        let words = &["classDef", "class"];
+        assert_eq!(
+            Ok(((), "\nA")),
+            skipped_parser(words).parse("class A big\nA")
+        );
+        assert_eq!(
+            Ok(((), "")),
+            skipped_parser(words).parse("classDef big fill:#f00")
+        );
+        assert!(skipped_parser(words).parse("classes").is_err());
+        assert_eq!(Ok(((), "")), accessibility_parser().parse("accTitle: Flow"));
+    }
+}
@@ -0,0 +1,522 @@
+//! Vector PDF output, drawn from the scene of a diagram rather than from the
+//! SVG documents, so text stays selectable.
+use std::iter::once;
+
+use printpdf::path::{PaintMode, WindingOrder};
+use printpdf::{
+    Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+    Polygon, Pt, Rgb,
+};
+
+use crate::layout::{visual_order, TextDirection, FONT};
+use crate::scene::{Item, RenderBackend, Scene, ARROW_TIP_LENGTH};
+use crate::theme::{LINE_COLOR, TEXT_COLOR};
+
+/// Half the height of the arrow tips, which are as long as in the SVG output.
+const ARROW_TIP_HALF_WIDTH: f64 = 3.5;
+
+/// Points per unit of the layouts, which are CSS pixels.
+const PT_PER_UNIT: f64 = 0.75;
+
+pub const A4: (f32, f32) = (210.0, 297.0);
+pub const LETTER: (f32, f32) = (215.9, 279.4);
+
+pub struct PdfOptions {
+    /// Width and height of the pages in millimetres, `None` for a single page
+    /// the size of the diagram.
+    pub page: Option<(f32, f32)>,
+    /// Margin around the diagram in millimetres.
+    pub margin: f32,
+    /// Spreads diagrams taller than a page over several pages instead of
+    /// shrinking them, for the diagram types that support it.
+    pub split: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page: Some(A4),
+            margin: 10.0,
+            split: false,
+        }
+    }
+}
+
+impl PdfOptions {
+    /// How much a diagram of the given size is shrunk to fit the pages, it
+    /// is never enlarged.
+    pub fn scale(&self, width: f64, height: f64) -> f64 {
+        let Some((page_width, page_height)) = self.page else {
+            return 1.0;
+        };
+        let fit = |page: f32, size: f64| {
+            let available = Pt::from(Mm(page - 2.0 * self.margin)).0 as f64;
+            available / (size * PT_PER_UNIT)
+        };
+        let scale = fit(page_width, width).min(1.0);
+        if self.split {
+            scale
+        } else {
+            scale.min(fit(page_height, height))
+        }
+    }
+
+    /// The height of a page in layout units once shrunk by `scale`, without
+    /// the margins.
+    pub fn page_height(&self, scale: f64) -> Option<f64> {
+        self.page.map(|(_, height)| {
+            Pt::from(Mm(height - 2.0 * self.margin)).0 as f64 / (PT_PER_UNIT * scale)
+        })
+    }
+}
+
+/// A PDF document whose pages are drawn with the coordinates of a layout.
+pub struct Canvas {
+    doc: PdfDocumentReference,
+    font: IndirectFontRef,
+    layer: PdfLayerReference,
+    width: f64,
+    height: f64,
+    scale: f64,
+    margin: f64,
+    /// Layout ordinate drawn at the top of the page
+    pub top: f64,
+}
+
+impl Canvas {
+    /// A document with a first page for a diagram of the given size.
+    pub fn new(width: f64, height: f64, options: &PdfOptions) -> Result<Canvas, String> {
+        let scale = options.scale(width, height);
+        let margin = Pt::from(Mm(options.margin)).0 as f64;
+        let (page_width, page_height) = options.page.unwrap_or((
+            Mm::from(Pt((width * PT_PER_UNIT) as f32)).0 + 2.0 * options.margin,
+            Mm::from(Pt((height * PT_PER_UNIT) as f32)).0 + 2.0 * options.margin,
+        ));
+        let (doc, page, layer) =
+            PdfDocument::new("diagram", Mm(page_width), Mm(page_height), "diagram");
+        let font = doc
+            .add_external_font(FONT)
+            .map_err(|e| format!("Error: {e}"))?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Canvas {
+            doc,
+            font,
+            layer,
+            width: Pt::from(Mm(page_width)).0 as f64,
+            height: Pt::from(Mm(page_height)).0 as f64,
+            scale,
+            margin,
+            top: 0.0,
+        })
+    }
+
+    pub fn add_page(&mut self) {
+        let (width, height) = (
+            Mm::from(Pt(self.width as f32)),
+            Mm::from(Pt(self.height as f32)),
+        );
+        let (page, layer) = self.doc.add_page(width, height, "diagram");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+    }
+
+    pub fn save(self) -> Result<Vec<u8>, String> {
+        self.doc.save_to_bytes().map_err(|e| format!("Error: {e}"))
+    }
+
+    fn point(&self, x: f64, y: f64) -> Point {
+        let x = self.margin + x * self.scale * PT_PER_UNIT;
+        let y = self.height - self.margin - (y - self.top) * self.scale * PT_PER_UNIT;
+        Point {
+            x: Pt(x as f32),
+            y: Pt(y as f32),
+        }
+    }
+
+    fn colors(&self, fill: &str, stroke: &str) {
+        self.layer.set_outline_color(color(stroke));
+        self.layer
+            .set_outline_thickness((self.scale * PT_PER_UNIT) as f32);
+        self.layer.set_fill_color(color(fill));
+    }
+
+    /// A line through the points, which is closed and filled with `fill` when
+    /// given.
+    pub fn path(&self, points: &[(f64, f64)], fill: Option<&str>, stroke: &str) {
+        let points = points
+            .iter()
+            .map(|&(x, y)| (self.point(x, y), false))
+            .collect();
+        match fill {
+            Some(fill) => {
+                self.colors(fill, stroke);
+                self.layer.add_polygon(Polygon {
+                    rings: vec![points],
+                    mode: if fill == "transparent" {
+                        PaintMode::Stroke
+                    } else {
+                        PaintMode::FillStroke
+                    },
+                    winding_order: WindingOrder::NonZero,
+                });
+            }
+            None => {
+                self.colors(LINE_COLOR, stroke);
+                self.layer.add_line(Line {
+                    points,
+                    is_closed: false,
+                });
+            }
+        }
+    }
+
+    /// A rectangle filled with `fill`.
+    pub fn rect(&self, (x, y): (f64, f64), (right, bottom): (f64, f64), fill: &str, stroke: &str) {
+        self.path(
+            &[(x, y), (right, y), (right, bottom), (x, bottom)],
+            Some(fill),
+            stroke,
+        );
+    }
+
+    /// A line of text whose baseline starts at `x`, or ends there when it is
+    /// right-to-left and `width` is its width.
+    pub fn text(
+        &self,
+        content: &str,
+        x: f64,
+        y: f64,
+        width: f64,
+        direction: TextDirection,
+        size: f64,
+    ) {
+        let (content, x) = match direction {
+            TextDirection::LeftToRight => (content.to_string(), x),
+            TextDirection::RightToLeft => (visual_order(content, direction), x - width),
+        };
+        let point = self.point(x, y);
+        self.layer.set_fill_color(color(TEXT_COLOR));
+        self.layer.use_text(
+            content,
+            (size * self.scale * PT_PER_UNIT) as f32,
+            Mm::from(point.x),
+            Mm::from(point.y),
+            &self.font,
+        );
+    }
+}
+
+/// Renders scenes to PDF documents. With `split` in the options, a scene
+/// taller than a page goes on several pages, cut between the items that
+/// cannot be cut such as text, and each page starts with the header band.
+pub struct PdfBackend<'a> {
+    pub options: &'a PdfOptions,
+}
+
+impl RenderBackend for PdfBackend<'_> {
+    type Output = Result<Vec<u8>, String>;
+
+    fn render(&self, scene: &Scene) -> Result<Vec<u8>, String> {
+        let options = self.options;
+        let (body_top, body_bottom) = (scene.header, scene.height - scene.footer);
+        let cuttable: Vec<bool> = scene
+            .items
+            .iter()
+            .map(|item| cuttable(scene, item))
+            .collect();
+        let mut canvas = Canvas::new(scene.width, scene.height, options)?;
+        let available = options
+            .page_height(options.scale(scene.width, scene.height))
+            .map(|height| height - scene.header - scene.footer);
+        let cuts = match available {
+            Some(available) if options.split && available > 0.0 => {
+                page_breaks(scene, &cuttable, available)
+            }
+            _ => vec![],
+        };
+
+        // items that cannot be cut go on the page holding their middle
+        let page_of = |y: f64| cuts.iter().filter(|&&cut| cut <= y).count();
+        let starts = once(body_top).chain(cuts.iter().copied());
+        let ends = cuts.iter().copied().chain(once(body_bottom));
+        for (page, (start, end)) in starts.zip(ends).enumerate() {
+            if page > 0 {
+                canvas.add_page();
+            }
+            canvas.top = 0.0;
+            for item in &scene.items {
+                if extent(item).1 <= body_top {
+                    draw(&canvas, item, None);
+                }
+            }
+            // the part of the body between `start` and `end` goes right below
+            // the header, the footer after the last part
+            canvas.top = start - body_top;
+            for (item, &cuttable) in scene.items.iter().zip(&cuttable) {
+                let (top, bottom) = extent(item);
+                if bottom <= body_top {
+                    continue;
+                } else if top >= body_bottom {
+                    if page == cuts.len() {
+                        draw(&canvas, item, None);
+                    }
+                } else if cuttable {
+                    draw(&canvas, item, Some((start, end)));
+                } else if page_of((top + bottom) / 2.0) == page {
+                    draw(&canvas, item, None);
+                }
+            }
+        }
+        canvas.save()
+    }
+}
+
+/// The top and the bottom of an item, text reaching a font size above its
+/// baseline.
+fn extent(item: &Item) -> (f64, f64) {
+    match item {
+        Item::Rect { y, height, .. } => (*y, y + height),
+        Item::Line { from, to } | Item::Arrow { from, to } => (from.1.min(to.1), from.1.max(to.1)),
+        Item::Text {
+            position,
+            font_size,
+            ..
+        } => (position.1 - font_size, position.1),
+    }
+}
+
+/// Lines and rectangles without text, such as lifelines and activations, can
+/// be cut between pages, every other item goes on a single page.
+fn cuttable(scene: &Scene, item: &Item) -> bool {
+    match item {
+        Item::Line { .. } => true,
+        Item::Rect {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } => !scene.items.iter().any(|other| match other {
+            Item::Text { position, .. } => {
+                (*x..x + width).contains(&position.0) && (*y..y + height).contains(&position.1)
+            }
+            _ => false,
+        }),
+        Item::Arrow { .. } | Item::Text { .. } => false,
+    }
+}
+
+/// Where to cut the body of a scene so that each part is at most `available`
+/// high, halfway between the items that cannot be cut.
+fn page_breaks(scene: &Scene, cuttable: &[bool], available: f64) -> Vec<f64> {
+    let (body_top, body_bottom) = (scene.header, scene.height - scene.footer);
+    let mut rows: Vec<(f64, f64)> = scene
+        .items
+        .iter()
+        .zip(cuttable)
+        .filter(|(_, &cuttable)| !cuttable)
+        .map(|(item, _)| extent(item))
+        .filter(|&(top, bottom)| bottom > body_top && top < body_bottom)
+        .collect();
+    rows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // the footer has to fit on the last page too
+    rows.push((body_bottom, body_bottom));
+
+    let mut cuts = vec![];
+    let (mut start, mut previous_bottom) = (body_top, body_top);
+    for (top, bottom) in rows {
+        if bottom - start > available && top > start {
+            let cut = if previous_bottom < top {
+                (previous_bottom + top) / 2.0
+            } else {
+                top
+            };
+            cuts.push(cut);
+            start = cut;
+        }
+        previous_bottom = previous_bottom.max(bottom);
+    }
+    cuts
+}
+
+/// Draws `item`, or only its part between two ordinates.
+fn draw(canvas: &Canvas, item: &Item, clip: Option<(f64, f64)>) {
+    let (top, bottom) = extent(item);
+    let (top, bottom) = clip.map_or((top, bottom), |(start, end)| {
+        (top.max(start), bottom.min(end))
+    });
+    match item {
+        Item::Rect {
+            x,
+            width,
+            fill,
+            stroke,
+            ..
+        } if top < bottom => canvas.rect((*x, top), (x + width, bottom), fill, stroke),
+        // horizontal lines go whole on the page they are on
+        Item::Line { from, to }
+            if from.1 == to.1 && clip.is_none_or(|(start, end)| start <= top && top < end) =>
+        {
+            canvas.path(&[*from, *to], None, LINE_COLOR)
+        }
+        Item::Line { from, to } if top < bottom => {
+            let at = |y: f64| (from.0 + (to.0 - from.0) * (y - from.1) / (to.1 - from.1), y);
+            canvas.path(&[at(top), at(bottom)], None, LINE_COLOR);
+        }
+        Item::Arrow { from, to } => {
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            let length = dx.hypot(dy);
+            if length == 0.0 {
+                return;
+            }
+            let (ux, uy) = (dx / length, dy / length);
+            let tip = ARROW_TIP_LENGTH.min(length);
+            let base = (to.0 - ux * tip, to.1 - uy * tip);
+            let half = ARROW_TIP_HALF_WIDTH;
+            canvas.path(&[*from, base], None, LINE_COLOR);
+            let corners = [
+                (base.0 - uy * half, base.1 + ux * half),
+                *to,
+                (base.0 + uy * half, base.1 - ux * half),
+            ];
+            canvas.path(&corners, Some(LINE_COLOR), LINE_COLOR);
+        }
+        Item::Text {
+            content,
+            position,
+            width,
+            font_size,
+            direction,
+        } => canvas.text(
+            content, position.0, position.1, *width, *direction, *font_size,
+        ),
+        _ => {}
+    }
+}
+
+/// The colours of the theme, which are either names or `#RGB` and `#RRGGBB`
+/// codes.
+fn color(name: &str) -> Color {
+    let rgb = |r: f32, g: f32, b: f32| Color::Rgb(Rgb::new(r, g, b, None));
+    let hex = |digits: &str| u8::from_str_radix(digits, 16).unwrap_or(0) as f32 / 255.0;
+    match name {
+        "white" | "transparent" => rgb(1.0, 1.0, 1.0),
+        "blue" => rgb(0.0, 0.0, 1.0),
+        "gray" => rgb(0.5, 0.5, 0.5),
+        _ => match name.strip_prefix('#') {
+            Some(code) if code.len() == 3 => {
+                let channel = |i: usize| hex(&code[i..i + 1].repeat(2));
+                rgb(channel(0), channel(1), channel(2))
+            }
+            Some(code) if code.len() == 6 => {
+                let channel = |i: usize| hex(&code[i..i + 2]);
+                rgb(channel(0), channel(2), channel(4))
+            }
+            _ => rgb(0.0, 0.0, 0.0),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use printpdf::lopdf;
+
+    use super::*;
+    use crate::diagram::Options;
+
+    fn pdf(src: &str, options: &PdfOptions) -> lopdf::Document {
+        let pdf = crate::render_pdf(src, &Options::default(), options).unwrap();
+        lopdf::Document::load_mem(&pdf).unwrap()
+    }
+
+    #[test]
+    fn test_to_pdf() {
+        let src: String = (0..40)
+            .map(|i| format!("Alice->>John: message {i}\n"))
+            .collect();
+        let single = pdf(&src, &PdfOptions::default());
+        assert_eq!(1, single.get_pages().len());
+        // the font goes along, with the map from its glyphs back to text
+        let font = single.objects.values().find_map(|object| {
+            let dict = object.as_dict().ok()?;
+            dict.has(b"ToUnicode").then_some(dict)
+        });
+        assert!(font.is_some());
+        assert!(single
+            .objects
+            .values()
+            .any(|object| object.as_dict().is_ok_and(|dict| dict.has(b"FontFile2"))));
+
+        let options = PdfOptions {
+            split: true,
+            ..PdfOptions::default()
+        };
+        let split = pdf(&src, &options);
+        let pages = split.get_pages().len() as u32;
+        assert!(pages > 1);
+        let heads = |page| {
+            let text = split.extract_text(&[page]).unwrap();
+            text.lines().take(2).map(str::to_string).collect::<Vec<_>>()
+        };
+        for page in 2..=pages {
+            assert_eq!(heads(1), heads(page));
+        }
+        // every message on exactly one page, the labels being the same but
+        // for the numbers which do not come out of the text
+        let text: Vec<String> = (1..=pages)
+            .map(|page| split.extract_text(&[page]).unwrap())
+            .collect();
+        let label = text[0].lines().nth(2).unwrap();
+        let labels = text
+            .iter()
+            .flat_map(|page| page.lines())
+            .filter(|line| *line == label);
+        assert_eq!(40, labels.count());
+    }
+
+    #[test]
+    fn test_page_breaks() {
+        let text = |y: f64| Item::Text {
+            content: "x".to_string(),
+            position: (0.0, y),
+            width: 5.0,
+            font_size: 10.0,
+            direction: TextDirection::LeftToRight,
+        };
+        let rect = |y: f64, height: f64| Item::Rect {
+            x: 20.0,
+            y,
+            width: 10.0,
+            height,
+            fill: "gray".to_string(),
+            stroke: "black".to_string(),
+            corner_radius: 0.0,
+        };
+        let scene = Scene {
+            width: 30.0,
+            height: 100.0,
+            font_scale_factor: 1.0,
+            header: 10.0,
+            footer: 10.0,
+            items: vec![
+                Item::Line {
+                    from: (25.0, 10.0),
+                    to: (25.0, 90.0),
+                },
+                rect(15.0, 70.0),
+                text(30.0),
+                text(50.0),
+                text(70.0),
+            ],
+        };
+        let cuttable: Vec<bool> = scene
+            .items
+            .iter()
+            .map(|item| cuttable(&scene, item))
+            .collect();
+        assert_eq!(vec![true, true, false, false, false], cuttable);
+        // halfway between the text runs, through the lifeline and activation,
+        // and before the end of the body so the footer fits
+        assert_eq!(vec![35.0, 55.0, 80.0], page_breaks(&scene, &cuttable, 25.0));
+        assert_eq!(Vec::<f64>::new(), page_breaks(&scene, &cuttable, 80.0));
+    }
+}
@@ -0,0 +1,14 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Pie, PieLayout, ["pie"], |options| SvgConfig {
+    max_label_length: 30,
+    font_scale_factor: options.font_scale_factor,
+    font_size: options.font_size,
+    padding: options.padding,
+    radius: 100.0,
+    legend_gutter: 30.0,
+});
@@ -0,0 +1,218 @@
+use combine::error::StringStreamError;
+use combine::parser::char::{char, digit};
+use combine::parser::choice::choice;
+use combine::{attempt, eof, many, many1, none_of, optional, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, blank1, comment_parser, keyword};
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Slice {
+    pub label: String,
+    pub value: f64,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pie {
+    pub title: Option<String>,
+    /// Whether the legend shows the values next to the labels.
+    pub show_data: bool,
+    pub slices: Vec<Slice>,
+}
+
+impl Pie {
+    pub fn total(&self) -> f64 {
+        self.slices.iter().map(|slice| slice.value).sum()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header {
+        show_data: bool,
+        title: Option<String>,
+    },
+    ShowData,
+    Title(String),
+    Slice(Slice),
+}
+
+pub fn parse(src: String) -> Result<Pie, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => Ok(build_pie(statements)),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_pie(statements: Vec<Statement>) -> Pie {
+    let mut pie = Pie {
+        title: None,
+        show_data: false,
+        slices: vec![],
+    };
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Header { show_data, title } => {
+                pie.show_data |= show_data;
+                pie.title = title.or(pie.title);
+            }
+            Statement::ShowData => pie.show_data = true,
+            Statement::Title(title) => pie.title = Some(title),
+            Statement::Slice(slice) => pie.slices.push(slice),
+        }
+    }
+    pie
+}
+
+fn title_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword("title"), blank1(), many1(none_of("\n".chars())))
+        .map(|(_, _, title): (_, _, String)| title.trim().to_string())
+}
+
+/// `pie [showData] [title text]`
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("pie"),
+        optional(attempt((blank1(), keyword("showData")))),
+        optional(attempt((blank1(), title_parser()))),
+    )
+        .map(|(_, show_data, title)| Statement::Header {
+            show_data: show_data.is_some(),
+            title: title.map(|(_, title)| title),
+        })
+}
+
+/// A non negative decimal number such as `42` or `42.5`.
+fn value_parser<Input>() -> impl Parser<Input, Output = f64>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (many1(digit()), optional((char('.'), many1(digit())))).map(
+        |(whole, fraction): (String, Option<(_, String)>)| {
+            let fraction = fraction.map(|(_, f)| format!(".{f}")).unwrap_or_default();
+            format!("{whole}{fraction}").parse().unwrap_or(0.0)
+        },
+    )
+}
+
+fn slice_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        char('"'),
+        many(none_of("\"\n".chars())),
+        char('"'),
+        blank(),
+        char(':'),
+        blank(),
+        value_parser(),
+    )
+        .map(|(_, label, _, _, _, _, value)| Statement::Slice(Slice { label, value }))
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            keyword("showData").map(|_| Statement::ShowData),
+            title_parser().map(Statement::Title),
+            accessibility_parser().map(|_| Statement::Empty),
+            slice_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Header {
+                    show_data: true,
+                    title: Some("Key elements".to_string()),
+                },
+                ""
+            )),
+            header_parser().parse("pie showData title Key elements")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Header {
+                    show_data: false,
+                    title: None,
+                },
+                ""
+            )),
+            header_parser().parse("pie")
+        );
+    }
+
+    #[test]
+    fn test_slice_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Slice(Slice {
+                    label: "Calcium".to_string(),
+                    value: 42.96,
+                }),
+                ""
+            )),
+            slice_parser().parse("\"Calcium\" : 42.96")
+        );
+        assert!(slice_parser().parse("\"Calcium\" : -1").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"pie
+            title Pets adopted
+            %% by volunteers
+            "Dogs" : 386
+            "Cats" : 85.5
+            showData
+        "#;
+        let pie = parse(input.to_string()).unwrap();
+        assert_eq!(Some("Pets adopted".to_string()), pie.title);
+        assert!(pie.show_data);
+        assert_eq!(2, pie.slices.len());
+        assert_eq!(471.5, pie.total());
+    }
+}
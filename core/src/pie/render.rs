@@ -0,0 +1,340 @@
+use std::f64::consts::{PI, TAU};
+
+use svg::node::element::{Circle, Group, Path, SVG};
+
+use cassowary::strength::{MEDIUM, REQUIRED, STRONG};
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{AlignmentAnchor, BlockId, Layout, Orientation};
+use crate::pie::*;
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub radius: f64,
+    /// Space between the chart and its legend.
+    pub legend_gutter: f64,
+}
+
+struct LegendEntry {
+    swatch: BlockId,
+    text: BlockId,
+    lines: Vec<String>,
+    color: &'static str,
+}
+
+/// A colour swatch and a label per series of a chart, stacked from top to
+/// bottom. Used by the other chart types as well.
+pub struct Legend {
+    block: BlockId,
+    entries: Vec<LegendEntry>,
+}
+
+impl Legend {
+    pub fn new(
+        layout: &mut Layout,
+        labels: &[String],
+        max_label_length: usize,
+        padding: f64,
+        font_size: f64,
+    ) -> Legend {
+        let block = layout.add_block();
+        let swatch_size = layout.glyphs_height;
+        let entries: Vec<LegendEntry> = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let swatch = layout.add_block();
+                let (text, lines) =
+                    layout.add_text_block(label, max_label_length, padding, font_size);
+                layout.add_constraint(layout.b(swatch).width | EQ(REQUIRED) | swatch_size);
+                layout.add_constraint(layout.b(swatch).height | EQ(REQUIRED) | swatch_size);
+                layout.add_constraint(
+                    layout.b(swatch).left() | EQ(REQUIRED) | layout.b(block).left(),
+                );
+                layout.add_constraint(
+                    layout.b(text).left() | EQ(REQUIRED) | layout.b(swatch).right(),
+                );
+                layout.add_constraint(
+                    layout.b(text).right() | LE(REQUIRED) | layout.b(block).right(),
+                );
+                layout.align(
+                    Orientation::Vertical,
+                    AlignmentAnchor::Middle,
+                    [swatch, text].iter(),
+                );
+                LegendEntry {
+                    swatch,
+                    text,
+                    lines,
+                    color: PALETTE[i % PALETTE.len()],
+                }
+            })
+            .collect();
+
+        let texts: Vec<BlockId> = entries.iter().map(|entry| entry.text).collect();
+        for (previous, next) in texts.iter().zip(texts.iter().skip(1)) {
+            layout.add_constraint(
+                layout.b(*next).top() | EQ(REQUIRED) | layout.b(*previous).bottom(),
+            );
+        }
+        match (texts.first(), texts.last()) {
+            (Some(&first), Some(&last)) => {
+                layout.add_constraint(layout.b(first).top() | EQ(REQUIRED) | layout.b(block).top());
+                layout.add_constraint(
+                    layout.b(last).bottom() | EQ(REQUIRED) | layout.b(block).bottom(),
+                );
+            }
+            _ => layout.add_constraint(layout.b(block).height | EQ(REQUIRED) | 0.0),
+        }
+        layout.add_constraint(layout.b(block).width | EQ(MEDIUM) | 0.0);
+        Legend { block, entries }
+    }
+
+    pub fn block(&self) -> BlockId {
+        self.block
+    }
+
+    pub fn to_svg(&self, layout: &Layout, font_size: f64, padding: f64) -> Group {
+        let mut group = Group::new();
+        for entry in &self.entries {
+            let swatch = layout.b(entry.swatch).solved();
+            let text = layout.b(entry.text).solved();
+            let mut label = translated(text);
+            for t in text_lines(&entry.lines, text, font_size, padding) {
+                label = label.add(t);
+            }
+            group = group
+                .add(translated(swatch).add(outline(swatch, entry.color, 0.0)))
+                .add(label);
+        }
+        group
+    }
+}
+
+/// A value without a trailing `.0` for whole numbers.
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
+
+fn slice_path(cx: f64, cy: f64, r: f64, from: f64, to: f64) -> String {
+    let (x0, y0) = (cx + r * from.cos(), cy + r * from.sin());
+    let (x1, y1) = (cx + r * to.cos(), cy + r * to.sin());
+    let large_arc = if to - from > PI { 1 } else { 0 };
+    format!("M {cx} {cy} L {x0} {y0} A {r} {r} 0 {large_arc} 1 {x1} {y1} Z")
+}
+
+/// Index, share of the total, start and end angle of every slice with a
+/// positive value, clockwise from the top.
+fn sectors(pie: &Pie) -> Vec<(usize, f64, f64, f64)> {
+    let total = pie.total();
+    if total <= 0.0 {
+        return vec![];
+    }
+    let mut angle = -PI / 2.0;
+    let mut sectors = vec![];
+    for (i, slice) in pie.slices.iter().enumerate() {
+        if slice.value <= 0.0 {
+            continue;
+        }
+        let share = slice.value / total;
+        let to = angle + share * TAU;
+        sectors.push((i, share, angle, to));
+        angle = to;
+    }
+    sectors
+}
+
+/// A pie chart with its disc, legend and title placed.
+pub struct PieLayout {
+    pie: Pie,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    title: Option<(BlockId, Vec<String>)>,
+    disc: BlockId,
+    legend: Legend,
+}
+
+pub fn layout(pie: &Pie, config: &SvgConfig) -> PieLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+
+    let title = pie.title.as_ref().map(|title| {
+        layout.add_text_block(
+            title,
+            config.max_label_length * 3,
+            config.padding,
+            font_size,
+        )
+    });
+    let disc = layout.add_block();
+    layout.add_constraint(layout.b(disc).width | EQ(REQUIRED) | (2.0 * config.radius));
+    layout.add_constraint(layout.b(disc).height | EQ(REQUIRED) | (2.0 * config.radius));
+    layout.add_constraint(layout.b(disc).left() | GE(REQUIRED) | config.padding);
+    layout.add_constraint(layout.b(disc).left() | EQ(STRONG) | config.padding);
+
+    let labels: Vec<String> = pie
+        .slices
+        .iter()
+        .map(|slice| match pie.show_data {
+            true => format!("{} [{}]", slice.label, format_value(slice.value)),
+            false => slice.label.clone(),
+        })
+        .collect();
+    let legend = Legend::new(
+        &mut layout,
+        &labels,
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let legend_block = legend.block();
+    layout.add_constraint(
+        layout.b(legend_block).left()
+            | EQ(REQUIRED)
+            | (layout.b(disc).right() + config.legend_gutter),
+    );
+    layout.add_constraint(layout.b(legend_block).top() | GE(REQUIRED) | layout.b(disc).top());
+    layout.add_constraint(
+        (layout.b(legend_block).top() + layout.b(legend_block).height * 0.5)
+            | EQ(STRONG)
+            | (layout.b(disc).top() + config.radius),
+    );
+
+    match &title {
+        Some((block, _)) => {
+            layout.add_constraint(layout.b(*block).top() | EQ(REQUIRED) | 0.0);
+            layout.add_constraint(layout.b(*block).left() | GE(REQUIRED) | 0.0);
+            layout.add_constraint(
+                (layout.b(*block).left() + layout.b(*block).width * 0.5)
+                    | EQ(STRONG)
+                    | ((layout.b(disc).left() + layout.b(legend_block).right()) * 0.5),
+            );
+            layout.add_constraint(
+                layout.b(disc).top() | EQ(REQUIRED) | (layout.b(*block).bottom() + config.padding),
+            );
+        }
+        None => layout.add_constraint(layout.b(disc).top() | EQ(REQUIRED) | config.padding),
+    }
+    layout.solve();
+    PieLayout {
+        pie: pie.clone(),
+        config: config.clone(),
+        layout,
+        title,
+        disc,
+        legend,
+    }
+}
+
+impl PieLayout {
+    pub fn to_svg(&self) -> SVG {
+        let PieLayout {
+            pie,
+            config,
+            layout,
+            title,
+            disc,
+            legend,
+        } = self;
+        let b = layout.b(*disc).solved();
+        let (cx, cy, r) = (
+            b.position.x + config.radius,
+            b.position.y + config.radius,
+            config.radius,
+        );
+        let mut doc = document(
+            layout.width() + config.padding,
+            layout.height() + config.padding,
+        );
+
+        let mut percentages = vec![];
+        for (i, share, angle, to) in sectors(pie) {
+            let color = PALETTE[i % PALETTE.len()];
+            doc = if share >= 1.0 {
+                doc.add(stroked(
+                    Circle::new().set("cx", cx).set("cy", cy).set("r", r),
+                    color,
+                ))
+            } else {
+                doc.add(stroked(
+                    Path::new().set("d", slice_path(cx, cy, r, angle, to)),
+                    color,
+                ))
+            };
+            let middle = (angle + to) * 0.5;
+            percentages.push(text_at(
+                &format!("{:.0}%", share * 100.0),
+                cx + r * 0.7 * middle.cos(),
+                cy + r * 0.7 * middle.sin(),
+                config.font_size,
+            ));
+        }
+        if pie.total() <= 0.0 {
+            doc = doc.add(stroked(
+                Circle::new().set("cx", cx).set("cy", cy).set("r", r),
+                "none",
+            ));
+        }
+        for percentage in percentages {
+            doc = doc.add(percentage);
+        }
+
+        doc = doc.add(legend.to_svg(layout, config.font_size, config.padding));
+        if let Some((block, lines)) = &title {
+            let b = layout.b(*block).solved();
+            let mut group = translated(b);
+            for t in text_lines(lines, b, config.font_size, config.padding) {
+                group = group.add(t);
+            }
+            doc = doc.add(group);
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(pie: &Pie, config: &SvgConfig) -> SVG {
+    layout(pie, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sectors() {
+        let zero = parse("pie\n\"Zero\" : 0".to_string()).unwrap();
+        assert!(sectors(&zero).is_empty());
+
+        let pie = parse("pie\n\"Dogs\" : 3\n\"None\" : 0\n\"Cats\" : 1\n\"Rats\" : 4".to_string())
+            .unwrap();
+        let sectors = sectors(&pie);
+        assert_eq!(
+            vec![0, 2, 3],
+            sectors.iter().map(|s| s.0).collect::<Vec<_>>()
+        );
+        // from the top, each slice where the previous one ends, all around
+        assert_eq!(-PI / 2.0, sectors[0].2);
+        for (previous, next) in sectors.iter().zip(&sectors[1..]) {
+            assert_eq!(previous.3, next.2);
+        }
+        assert!((sectors[2].3 - sectors[0].2 - TAU).abs() < 1e-9);
+        for (i, share, from, to) in sectors {
+            assert!((to - from - pie.slices[i].value / 8.0 * TAU).abs() < 1e-9);
+            assert_eq!(pie.slices[i].value / 8.0, share);
+        }
+
+        // slices over half of the disc take the large arc
+        assert!(slice_path(0.0, 0.0, 1.0, 0.0, PI + 0.1).contains(" 0 1 1 "));
+        assert!(slice_path(0.0, 0.0, 1.0, 0.0, PI - 0.1).contains(" 0 0 1 "));
+    }
+}
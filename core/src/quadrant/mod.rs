@@ -0,0 +1,19 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(
+    QuadrantChart,
+    QuadrantLayout,
+    ["quadrantChart"],
+    |options| SvgConfig {
+        max_label_length: 30,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        plot_size: 400.0,
+        point_radius: 5.0,
+    }
+);
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{char, digit, string};
+use combine::parser::choice::choice;
+use combine::{attempt, eof, many, many1, none_of, optional, satisfy, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, blank1, comment_parser, keyword, keyword_line};
+
+pub type PointId = usize;
+
+/// The labels at the low and high ends of an axis.
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Axis {
+    pub low: String,
+    pub high: Option<String>,
+}
+
+/// How a point is drawn, as set on the point or by its class.
+#[derive(PartialEq, Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PointStyle {
+    pub radius: Option<f64>,
+    pub color: Option<String>,
+    pub stroke_color: Option<String>,
+    pub stroke_width: Option<f64>,
+}
+
+impl PointStyle {
+    /// Settings of `self`, completed by those of `other`.
+    fn or(self, other: &PointStyle) -> PointStyle {
+        PointStyle {
+            radius: self.radius.or(other.radius),
+            color: self.color.or_else(|| other.color.clone()),
+            stroke_color: self.stroke_color.or_else(|| other.stroke_color.clone()),
+            stroke_width: self.stroke_width.or(other.stroke_width),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub id: PointId,
+    pub label: String,
+    /// Both coordinates go from 0 to 1.
+    pub x: f64,
+    pub y: f64,
+    pub class: Option<String>,
+    pub style: PointStyle,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuadrantChart {
+    pub title: Option<String>,
+    pub x_axis: Option<Axis>,
+    pub y_axis: Option<Axis>,
+    /// Labels of the quadrants, counter clockwise from the top right one.
+    pub quadrants: [Option<String>; 4],
+    pub points: Vec<Point>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Title(String),
+    XAxis(Axis),
+    YAxis(Axis),
+    /// The number of the quadrant, from 1 to 4, and its label.
+    Quadrant(usize, String),
+    Point {
+        label: String,
+        x: f64,
+        y: f64,
+        class: Option<String>,
+        style: String,
+    },
+    ClassDef {
+        name: String,
+        style: String,
+    },
+}
+
+pub fn parse(src: String) -> Result<QuadrantChart, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_chart(statements),
+        Err(e) => Err(e),
+    }
+}
+
+/// `radius: 10, color: #ff3300, stroke-color: #ff0000, stroke-width: 5px`
+fn point_style(text: &str) -> Result<PointStyle, StringStreamError> {
+    let mut style = PointStyle::default();
+    let size = |value: &str| {
+        value
+            .trim_end_matches("px")
+            .parse::<f64>()
+            .map_err(|_| StringStreamError::UnexpectedParse)
+    };
+    for setting in text.split(',').filter(|s| !s.trim().is_empty()) {
+        let (key, value) = setting
+            .split_once(':')
+            .ok_or(StringStreamError::UnexpectedParse)?;
+        let value = value.trim();
+        match key.trim() {
+            "radius" => style.radius = Some(size(value)?),
+            "color" => style.color = Some(value.to_string()),
+            "stroke-color" => style.stroke_color = Some(value.to_string()),
+            "stroke-width" => style.stroke_width = Some(size(value)?),
+            _ => return Err(StringStreamError::UnexpectedParse),
+        }
+    }
+    Ok(style)
+}
+
+fn build_chart(statements: Vec<Statement>) -> Result<QuadrantChart, StringStreamError> {
+    let mut chart = QuadrantChart {
+        title: None,
+        x_axis: None,
+        y_axis: None,
+        quadrants: [None, None, None, None],
+        points: vec![],
+    };
+    let mut classes: HashMap<String, PointStyle> = HashMap::new();
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Title(title) => chart.title = Some(title),
+            Statement::XAxis(axis) => chart.x_axis = Some(axis),
+            Statement::YAxis(axis) => chart.y_axis = Some(axis),
+            Statement::Quadrant(number, label) => chart.quadrants[number - 1] = Some(label),
+            Statement::Point {
+                label,
+                x,
+                y,
+                class,
+                style,
+            } => {
+                if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                    return Err(StringStreamError::UnexpectedParse);
+                }
+                chart.points.push(Point {
+                    id: chart.points.len(),
+                    label,
+                    x,
+                    y,
+                    class,
+                    style: point_style(&style)?,
+                });
+            }
+            Statement::ClassDef { name, style } => {
+                classes.insert(name, point_style(&style)?);
+            }
+        }
+    }
+    // Classes may be defined after the points using them.
+    for point in &mut chart.points {
+        if let Some(class) = &point.class {
+            let class_style = classes
+                .get(class)
+                .ok_or(StringStreamError::UnexpectedParse)?;
+            point.style = std::mem::take(&mut point.style).or(class_style);
+        }
+    }
+    Ok(chart)
+}
+
+/// Drops the quotes around a label.
+fn unquoted(text: &str) -> String {
+    let text = text.trim();
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+        .to_string()
+}
+
+/// `low --> high` or `low`
+fn axis(text: String) -> Axis {
+    match text.split_once("-->") {
+        Some((low, high)) => Axis {
+            low: unquoted(low),
+            high: Some(unquoted(high)).filter(|high| !high.is_empty()),
+        },
+        None => Axis {
+            low: unquoted(&text),
+            high: None,
+        },
+    }
+}
+
+/// A decimal number such as `0`, `1`, `0.45` or `.45`.
+fn number_parser<Input>() -> impl Parser<Input, Output = f64>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        optional(char('-')),
+        choice((
+            (many1(digit()), optional((char('.'), many(digit())))).map(
+                |(whole, fraction): (String, Option<(_, String)>)| {
+                    let fraction = fraction.map(|(_, f)| f).unwrap_or_default();
+                    format!("{whole}.{fraction}0")
+                },
+            ),
+            (char('.'), many1(digit())).map(|(_, fraction): (_, String)| format!("0.{fraction}")),
+        )),
+    )
+        .map(|(sign, number): (Option<char>, String)| {
+            let value: f64 = number.parse().unwrap_or(0.0);
+            if sign.is_some() {
+                -value
+            } else {
+                value
+            }
+        })
+}
+
+/// `Label[:::class]: [x, y] [style]`
+fn point_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        many1(none_of(":\n%".chars())),
+        optional(
+            (
+                attempt(string(":::")),
+                many1(satisfy(|c: char| {
+                    c.is_alphanumeric() || c == '_' || c == '-'
+                })),
+            )
+                .map(|(_, class)| class),
+        ),
+        char(':'),
+        blank(),
+        char('['),
+        blank(),
+        number_parser(),
+        blank(),
+        char(','),
+        blank(),
+        number_parser(),
+        blank(),
+        char(']'),
+        many(none_of("\n%".chars())),
+    )
+        .map(
+            |(label, class, _, _, _, _, x, _, _, _, y, _, _, style): (
+                String,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                _,
+                String,
+            )| {
+                Statement::Point {
+                    label: unquoted(&label),
+                    x,
+                    y,
+                    class,
+                    style: style.trim().to_string(),
+                }
+            },
+        )
+}
+
+/// `classDef name radius: 10, color: #ff3300`
+fn class_def_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("classDef"),
+        blank1(),
+        many1(satisfy(|c: char| {
+            c.is_alphanumeric() || c == '_' || c == '-'
+        })),
+        blank1(),
+        many1(none_of("\n%".chars())),
+    )
+        .map(
+            |(_, _, name, _, style): (_, _, String, _, String)| Statement::ClassDef {
+                name,
+                style: style.trim().to_string(),
+            },
+        )
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            keyword("quadrantChart").map(|_| Statement::Empty),
+            keyword_line("title").map(Statement::Title),
+            keyword_line("x-axis").map(|text| Statement::XAxis(axis(text))),
+            keyword_line("y-axis").map(|text| Statement::YAxis(axis(text))),
+            keyword_line("quadrant-1").map(|text| Statement::Quadrant(1, unquoted(&text))),
+            keyword_line("quadrant-2").map(|text| Statement::Quadrant(2, unquoted(&text))),
+            keyword_line("quadrant-3").map(|text| Statement::Quadrant(3, unquoted(&text))),
+            keyword_line("quadrant-4").map(|text| Statement::Quadrant(4, unquoted(&text))),
+            class_def_parser(),
+            accessibility_parser().map(|_| Statement::Empty),
+            point_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Point {
+                    label: "Campaign A".to_string(),
+                    x: 0.3,
+                    y: 0.6,
+                    class: None,
+                    style: String::new(),
+                },
+                ""
+            )),
+            point_parser().parse("Campaign A: [0.3, 0.6]")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Point {
+                    label: "Point B".to_string(),
+                    x: 0.8,
+                    y: 0.0,
+                    class: Some("class1".to_string()),
+                    style: "radius: 10, color: #ff3300".to_string(),
+                },
+                ""
+            )),
+            point_parser().parse("Point B:::class1: [.8, 0] radius: 10, color: #ff3300")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"quadrantChart
+    title Reach and engagement of campaigns
+    x-axis Low Reach --> High Reach
+    y-axis Low Engagement
+    quadrant-1 We should expand
+    quadrant-3 Re-evaluate
+    Campaign A: [0.3, 0.6]
+    Campaign B:::big: [0.45, 0.23] color: #ff3300
+    classDef big radius: 12, stroke-width: 2px, color: #109060
+"#;
+        let chart = parse(input.to_string()).unwrap();
+        assert_eq!(
+            Some(Axis {
+                low: "Low Reach".to_string(),
+                high: Some("High Reach".to_string()),
+            }),
+            chart.x_axis
+        );
+        assert_eq!(None, chart.y_axis.unwrap().high);
+        assert_eq!(Some("Re-evaluate".to_string()), chart.quadrants[2]);
+        assert_eq!(None, chart.quadrants[1]);
+        assert_eq!(
+            PointStyle {
+                radius: Some(12.0),
+                color: Some("#ff3300".to_string()),
+                stroke_color: None,
+                stroke_width: Some(2.0),
+            },
+            chart.points[1].style
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("quadrantChart\nA: [1.5, 0.5]".to_string()).is_err());
+        assert!(parse("quadrantChart\nA:::missing: [0.5, 0.5]".to_string()).is_err());
+        assert!(parse("quadrantChart\nA: [0.5, 0.5] size: 3".to_string()).is_err());
+    }
+}
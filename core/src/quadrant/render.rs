@@ -0,0 +1,383 @@
+use svg::node::element::{Circle, Group, Line, Rectangle, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG};
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{Block, BlockId, Layout};
+use crate::quadrant::*;
+use crate::theme::*;
+
+/// Fills of the quadrants, counter clockwise from the top right one.
+const QUADRANT_FILLS: [&str; 4] = ["#E3EEF9", "#EEF6E4", "#FBEAEA", "#FEF3E2"];
+const POINT_FILL: &str = "#5B7FBF";
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    /// Width and height of the square the points are drawn in.
+    pub plot_size: f64,
+    pub point_radius: f64,
+}
+
+struct Text {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+fn add_text(
+    layout: &mut Layout,
+    content: &str,
+    max_label_length: usize,
+    config: &SvgConfig,
+) -> Text {
+    let font_size = config.font_size * config.font_scale_factor;
+    let (block, lines) =
+        layout.add_text_block(content, max_label_length, config.padding, font_size);
+    layout.add_constraint(layout.b(block).left() | GE(REQUIRED) | 0.0);
+    Text { block, lines }
+}
+
+impl Text {
+    fn to_svg(&self, layout: &Layout, config: &SvgConfig) -> Group {
+        let b = layout.b(self.block).solved();
+        text_lines(&self.lines, b, config.font_size, config.padding).fold(translated(b), Group::add)
+    }
+}
+
+/// A quadrant chart with its plot and labels placed.
+pub struct QuadrantLayout {
+    chart: QuadrantChart,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    plot: BlockId,
+    quadrants: Vec<Text>,
+    axis_labels: Vec<Text>,
+    title: Option<Text>,
+}
+
+pub fn layout(chart: &QuadrantChart, config: &SvgConfig) -> QuadrantLayout {
+    let mut layout = Layout::new();
+    let size = config.plot_size;
+    let half = size * 0.5;
+
+    let plot = layout.add_block();
+    layout.add_constraint(layout.b(plot).width | EQ(REQUIRED) | size);
+    layout.add_constraint(layout.b(plot).height | EQ(REQUIRED) | size);
+    let center_x = |layout: &Layout, b: BlockId| layout.b(b).left() + layout.b(b).width * 0.5;
+    let center_y = |layout: &Layout, b: BlockId| layout.b(b).top() + layout.b(b).height * 0.5;
+
+    // Quadrant labels are centered at the top of their quadrant.
+    let quadrants: Vec<Text> = chart
+        .quadrants
+        .iter()
+        .enumerate()
+        .filter_map(|(i, label)| label.as_ref().map(|label| (i, label)))
+        .map(|(i, label)| {
+            let text = add_text(&mut layout, label, config.max_label_length, config);
+            let b = text.block;
+            let x = match i {
+                0 | 3 => size * 0.75,
+                _ => size * 0.25,
+            };
+            let y = match i {
+                0 | 1 => 0.0,
+                _ => half,
+            };
+            let constraints = [
+                center_x(&layout, b) | EQ(REQUIRED) | (layout.b(plot).left() + x),
+                layout.b(b).top() | EQ(REQUIRED) | (layout.b(plot).top() + y + config.padding),
+            ];
+            for constraint in constraints {
+                layout.add_constraint(constraint);
+            }
+            text
+        })
+        .collect();
+
+    // Axis labels are centered on the half of the axis they stand for, or on
+    // the whole axis when there is only one.
+    let mut axis_labels = vec![];
+    if let Some(axis) = &chart.x_axis {
+        let ends = [
+            (&axis.low, 0.25),
+            (&axis.high.clone().unwrap_or_default(), 0.75),
+        ];
+        for (label, fraction) in ends {
+            if label.is_empty() {
+                continue;
+            }
+            let fraction = if axis.high.is_none() { 0.5 } else { fraction };
+            let text = add_text(&mut layout, label, config.max_label_length, config);
+            let b = text.block;
+            let constraints = [
+                center_x(&layout, b) | EQ(STRONG) | (layout.b(plot).left() + size * fraction),
+                layout.b(b).top() | EQ(REQUIRED) | (layout.b(plot).bottom() + config.padding),
+            ];
+            for constraint in constraints {
+                layout.add_constraint(constraint);
+            }
+            axis_labels.push(text);
+        }
+    }
+    if let Some(axis) = &chart.y_axis {
+        let ends = [
+            (&axis.low, 0.75),
+            (&axis.high.clone().unwrap_or_default(), 0.25),
+        ];
+        for (label, fraction) in ends {
+            if label.is_empty() {
+                continue;
+            }
+            let fraction = if axis.high.is_none() { 0.5 } else { fraction };
+            let text = add_text(&mut layout, label, config.max_label_length / 2, config);
+            let b = text.block;
+            let constraints = [
+                center_y(&layout, b) | EQ(STRONG) | (layout.b(plot).top() + size * fraction),
+                layout.b(b).right() | EQ(REQUIRED) | (layout.b(plot).left() - config.padding),
+            ];
+            for constraint in constraints {
+                layout.add_constraint(constraint);
+            }
+            axis_labels.push(text);
+        }
+    }
+
+    let title = chart
+        .title
+        .as_ref()
+        .map(|title| add_text(&mut layout, title, config.max_label_length * 3, config));
+    if let Some(title) = &title {
+        let b = title.block;
+        let constraints = [
+            layout.b(b).top() | EQ(REQUIRED) | 0.0,
+            center_x(&layout, b) | EQ(STRONG) | center_x(&layout, plot),
+            layout.b(plot).top() | GE(REQUIRED) | (layout.b(b).bottom() + config.padding),
+        ];
+        for constraint in constraints {
+            layout.add_constraint(constraint);
+        }
+    }
+    // Room for the labels of the points on the edges.
+    layout.add_constraint(layout.b(plot).left() | GE(REQUIRED) | (config.padding * 4.0));
+    layout.add_constraint(layout.b(plot).top() | GE(REQUIRED) | (config.padding * 4.0));
+    layout.solve();
+    QuadrantLayout {
+        chart: chart.clone(),
+        config: config.clone(),
+        layout,
+        plot,
+        quadrants,
+        axis_labels,
+        title,
+    }
+}
+
+/// Top left corner of a quadrant, counted counter clockwise from the top right
+/// one.
+fn quadrant_corner(plot: &Block, i: usize) -> (f64, f64) {
+    let (half_width, half_height) = (plot.width * 0.5, plot.height * 0.5);
+    let x = if i == 0 || i == 3 { half_width } else { 0.0 };
+    let y = if i < 2 { 0.0 } else { half_height };
+    (plot.position.x + x, plot.position.y + y)
+}
+
+/// Center of a point, its coordinates being fractions of the plot from its
+/// bottom left corner.
+fn point_center(plot: &Block, point: &Point) -> (f64, f64) {
+    (
+        plot.position.x + point.x * plot.width,
+        plot.position.y + (1.0 - point.y) * plot.height,
+    )
+}
+
+impl QuadrantLayout {
+    pub fn to_svg(&self) -> SVG {
+        let QuadrantLayout {
+            chart,
+            config,
+            layout,
+            plot,
+            quadrants,
+            axis_labels,
+            title,
+        } = self;
+        let size = config.plot_size;
+        let half = size * 0.5;
+
+        let p = layout.b(*plot).solved();
+        let (x0, y0) = (p.position.x, p.position.y);
+        let mut doc = document(
+            layout.width() + config.padding * 4.0,
+            layout.height() + config.padding * 4.0,
+        );
+
+        for (i, fill) in QUADRANT_FILLS.iter().enumerate() {
+            let (x, y) = quadrant_corner(p, i);
+            doc = doc.add(
+                Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", half)
+                    .set("height", half)
+                    .set("fill", *fill),
+            );
+        }
+        let line = |x1: f64, y1: f64, x2: f64, y2: f64| {
+            Line::new()
+                .set("x1", x1)
+                .set("y1", y1)
+                .set("x2", x2)
+                .set("y2", y2)
+                .set("stroke", LINE_COLOR)
+        };
+        doc = doc
+            .add(line(x0 + half, y0, x0 + half, y0 + size))
+            .add(line(x0, y0 + half, x0 + size, y0 + half))
+            .add(stroked(
+                Rectangle::new()
+                    .set("x", x0)
+                    .set("y", y0)
+                    .set("width", size)
+                    .set("height", size),
+                "none",
+            ));
+
+        for text in quadrants.iter().chain(axis_labels.iter()) {
+            doc = doc.add(text.to_svg(layout, config));
+        }
+        if let Some(title) = &title {
+            doc = doc.add(title.to_svg(layout, config));
+        }
+
+        for point in &chart.points {
+            let (x, y) = point_center(p, point);
+            let radius = point.style.radius.unwrap_or(config.point_radius);
+            let fill = point.style.color.as_deref().unwrap_or(POINT_FILL);
+            doc = doc
+                .add(
+                    Circle::new()
+                        .set("cx", x)
+                        .set("cy", y)
+                        .set("r", radius)
+                        .set("fill", fill)
+                        .set(
+                            "stroke",
+                            point.style.stroke_color.as_deref().unwrap_or(fill),
+                        )
+                        .set("stroke-width", point.style.stroke_width.unwrap_or(1.0)),
+                )
+                .add(text_at(
+                    &point.label,
+                    x,
+                    y + radius + config.font_size,
+                    config.font_size,
+                ));
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(chart: &QuadrantChart, config: &SvgConfig) -> SVG {
+    layout(chart, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::quadrant::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    fn center(b: &Block) -> (f64, f64) {
+        (b.position.x + b.width * 0.5, b.position.y + b.height * 0.5)
+    }
+
+    #[test]
+    fn test_plot() {
+        let input = r#"quadrantChart
+    title Reach and engagement of campaigns
+    x-axis Low Reach --> High Reach
+    y-axis Low Engagement
+    quadrant-1 We should expand
+    quadrant-3 Re-evaluate
+    Campaign A: [0.3, 0.6]
+    Campaign B: [0.45, 0.23]
+    Campaign C: [1, 0]
+"#;
+        let chart = parse(input.to_string()).unwrap();
+        let config = config();
+        let QuadrantLayout {
+            layout,
+            plot,
+            quadrants,
+            axis_labels,
+            title,
+            ..
+        } = layout(&chart, &config);
+        let p = layout.b(plot).solved();
+        assert_eq!((config.plot_size, config.plot_size), (p.width, p.height));
+        let half = config.plot_size * 0.5;
+
+        // the quadrants tile the plot, the first one on the top right
+        let corners: Vec<(f64, f64)> = (0..4).map(|i| quadrant_corner(p, i)).collect();
+        let (x0, y0) = (p.position.x, p.position.y);
+        assert_eq!(
+            vec![
+                (x0 + half, y0),
+                (x0, y0),
+                (x0, y0 + half),
+                (x0 + half, y0 + half)
+            ],
+            corners
+        );
+
+        // labels centered at the top of their quadrant
+        assert_eq!(2, quadrants.len());
+        for (text, i) in quadrants.iter().zip([0, 2]) {
+            let b = layout.b(text.block).solved();
+            let (x, y) = corners[i];
+            assert!(x <= b.position.x && b.position.x + b.width <= x + half);
+            assert!(b.position.y + b.height <= y + half);
+            assert!((center(b).0 - x - half * 0.5).abs() < 1e-6);
+            assert!((b.position.y - y - config.padding).abs() < 1e-6);
+        }
+
+        // both ends of the x axis under their half, the only end of the y
+        // axis left of its middle
+        assert_eq!(3, axis_labels.len());
+        let bottom = y0 + config.plot_size;
+        for (text, fraction) in axis_labels[..2].iter().zip([0.25, 0.75]) {
+            let b = layout.b(text.block).solved();
+            assert!((center(b).0 - x0 - config.plot_size * fraction).abs() < 1e-6);
+            assert!((b.position.y - bottom - config.padding).abs() < 1e-6);
+        }
+        let b = layout.b(axis_labels[2].block).solved();
+        assert!((center(b).1 - y0 - half).abs() < 1e-6);
+        assert!((b.position.x + b.width + config.padding - x0).abs() < 1e-6);
+
+        let title = layout.b(title.unwrap().block).solved();
+        assert!((center(title).0 - x0 - half).abs() < 1e-6);
+        assert!(title.position.y + title.height + config.padding <= y0 + 1e-6);
+
+        let centers: Vec<(f64, f64)> = chart
+            .points
+            .iter()
+            .map(|point| point_center(p, point))
+            .collect();
+        assert_eq!(
+            vec![
+                (x0 + 120.0, y0 + 160.0),
+                (x0 + 180.0, y0 + 308.0),
+                (x0 + 400.0, y0 + 400.0)
+            ],
+            centers
+        );
+    }
+}
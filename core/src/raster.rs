@@ -0,0 +1,111 @@
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{self, Tree};
+use svg::node::element::SVG;
+
+use crate::layout::FONT;
+
+/// Pixels per inch of the coordinates of the SVG documents.
+const SVG_DPI: f32 = 96.0;
+
+/// How an SVG document is turned into a PNG image.
+pub struct PngOptions {
+    /// Zoom on top of the one coming from the resolution.
+    pub scale: f32,
+    /// Pixels per inch, which is also recorded in the image.
+    pub dpi: f32,
+    /// RGBA color behind the diagram, transparent when `None`.
+    pub background: Option<[u8; 4]>,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            scale: 1.0,
+            dpi: SVG_DPI,
+            background: Some([255, 255, 255, 255]),
+        }
+    }
+}
+
+/// Rasterises `doc` with the font its text was measured with, whatever the
+/// fonts of the system are.
+pub fn to_png(doc: &SVG, options: &PngOptions) -> Result<Vec<u8>, String> {
+    let mut usvg_options = usvg::Options {
+        dpi: options.dpi,
+        font_family: "Roboto".to_string(),
+        ..usvg::Options::default()
+    };
+    usvg_options.fontdb_mut().load_font_data(FONT.to_vec());
+    let tree =
+        Tree::from_str(&doc.to_string(), &usvg_options).map_err(|e| format!("Error: {e}"))?;
+
+    let zoom = options.scale * options.dpi / SVG_DPI;
+    let mut pixmap = tree
+        .size()
+        .to_int_size()
+        .scale_by(zoom)
+        .and_then(|size| Pixmap::new(size.width(), size.height()))
+        .ok_or("Error: empty image")?;
+    if let Some([r, g, b, a]) = options.background {
+        pixmap.fill(Color::from_rgba8(r, g, b, a));
+    }
+    resvg::render(
+        &tree,
+        Transform::from_scale(zoom, zoom),
+        &mut pixmap.as_mut(),
+    );
+    encode(&pixmap, options.dpi).map_err(|e| format!("Error: {e}"))
+}
+
+fn encode(pixmap: &Pixmap, dpi: f32) -> Result<Vec<u8>, png::EncodingError> {
+    let data: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let mut out = vec![];
+    let mut encoder = png::Encoder::new(&mut out, pixmap.width(), pixmap.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels_per_meter = (dpi / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: pixels_per_meter,
+        yppu: pixels_per_meter,
+        unit: png::Unit::Meter,
+    }));
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(png: &[u8]) -> (u32, u32, Option<png::PixelDimensions>) {
+        let reader = png::Decoder::new(png).read_info().unwrap();
+        let info = reader.info();
+        (info.width, info.height, info.pixel_dims)
+    }
+
+    #[test]
+    fn test_to_png() {
+        let doc = crate::render("Alice->>John: Hello", &crate::Options::default()).unwrap();
+        let (width, height, _) = info(&to_png(&doc, &PngOptions::default()).unwrap());
+        assert!(width > 0 && height > 0);
+
+        let options = PngOptions {
+            scale: 2.0,
+            dpi: 192.0,
+            background: None,
+        };
+        let (large_width, large_height, dims) = info(&to_png(&doc, &options).unwrap());
+        assert!(large_width.abs_diff(width * 4) <= 4);
+        assert!(large_height.abs_diff(height * 4) <= 4);
+        assert_eq!(Some(7559), dims.map(|dims| dims.xppu));
+    }
+}
@@ -0,0 +1,22 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(
+    RequirementDiagram,
+    RequirementLayout,
+    ["requirementDiagram"],
+    |options| {
+        SvgConfig {
+            max_label_length: 20,
+            max_field_length: 30,
+            font_scale_factor: options.font_scale_factor,
+            font_size: options.font_size,
+            padding: options.padding,
+            rank_gutter: 50.0,
+            node_gutter: 30.0,
+        }
+    }
+);
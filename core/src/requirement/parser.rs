@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::parser::choice::choice;
+use combine::{attempt, eof, many, many1, none_of, optional, satisfy, ParseError, Parser, Stream};
+
+use crate::flowchart::Direction;
+use crate::parsing::{blank, blank1, comment_parser, keyword};
+
+pub type NodeId = usize;
+pub type RelationId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RequirementKind {
+    Requirement,
+    Functional,
+    Interface,
+    Performance,
+    Physical,
+    DesignConstraint,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VerifyMethod {
+    Analysis,
+    Inspection,
+    Test,
+    Demonstration,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    Requirement {
+        kind: RequirementKind,
+        /// The `id` field, distinct from the name used by relations.
+        requirement_id: Option<String>,
+        text: Option<String>,
+        risk: Option<Risk>,
+        verify_method: Option<VerifyMethod>,
+    },
+    Element {
+        element_type: Option<String>,
+        docref: Option<String>,
+    },
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: NodeId,
+    pub name: String,
+    pub kind: NodeKind,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelationKind {
+    Contains,
+    Copies,
+    Derives,
+    Satisfies,
+    Verifies,
+    Refines,
+    Traces,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relation {
+    pub id: RelationId,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub kind: RelationKind,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequirementDiagram {
+    pub direction: Direction,
+    pub nodes: Vec<Node>,
+    pub relations: Vec<Relation>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Direction(Direction),
+    /// `requirement name {` and the other kinds of requirements.
+    Requirement(RequirementKind, String),
+    /// `element name {`
+    Element(String),
+    /// `key: value` within a requirement or an element.
+    Field(String, String),
+    End,
+    Relation {
+        from: String,
+        to: String,
+        kind: RelationKind,
+    },
+}
+
+pub fn parse(src: String) -> Result<RequirementDiagram, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_diagram(statements),
+        Err(e) => Err(e),
+    }
+}
+
+fn risk(value: &str) -> Result<Risk, StringStreamError> {
+    match value.to_lowercase().as_str() {
+        "low" => Ok(Risk::Low),
+        "medium" => Ok(Risk::Medium),
+        "high" => Ok(Risk::High),
+        _ => Err(StringStreamError::UnexpectedParse),
+    }
+}
+
+fn verify_method(value: &str) -> Result<VerifyMethod, StringStreamError> {
+    match value.to_lowercase().as_str() {
+        "analysis" => Ok(VerifyMethod::Analysis),
+        "inspection" => Ok(VerifyMethod::Inspection),
+        "test" => Ok(VerifyMethod::Test),
+        "demonstration" => Ok(VerifyMethod::Demonstration),
+        _ => Err(StringStreamError::UnexpectedParse),
+    }
+}
+
+/// Sets a field of the node being defined.
+fn set_field(node: &mut Node, key: &str, value: String) -> Result<(), StringStreamError> {
+    match (&mut node.kind, key.to_lowercase().as_str()) {
+        (NodeKind::Requirement { requirement_id, .. }, "id") => *requirement_id = Some(value),
+        (NodeKind::Requirement { text, .. }, "text") => *text = Some(value),
+        (NodeKind::Requirement { risk: r, .. }, "risk") => *r = Some(risk(&value)?),
+        (
+            NodeKind::Requirement {
+                verify_method: v, ..
+            },
+            "verifymethod",
+        ) => *v = Some(verify_method(&value)?),
+        (NodeKind::Element { element_type, .. }, "type") => *element_type = Some(value),
+        (NodeKind::Element { docref, .. }, "docref") => *docref = Some(value),
+        _ => return Err(StringStreamError::UnexpectedParse),
+    }
+    Ok(())
+}
+
+fn build_diagram(statements: Vec<Statement>) -> Result<RequirementDiagram, StringStreamError> {
+    let mut diagram = RequirementDiagram {
+        direction: Direction::TopToBottom,
+        nodes: vec![],
+        relations: vec![],
+    };
+    let mut names: HashMap<String, NodeId> = HashMap::new();
+    // Relations may refer to nodes defined further down.
+    let mut relations = vec![];
+    let mut open = false;
+    for statement in statements {
+        let (name, kind) = match statement {
+            Statement::Empty => continue,
+            Statement::Direction(direction) => {
+                diagram.direction = direction;
+                continue;
+            }
+            Statement::Field(key, value) => {
+                let node = diagram
+                    .nodes
+                    .last_mut()
+                    .filter(|_| open)
+                    .ok_or(StringStreamError::UnexpectedParse)?;
+                set_field(node, &key, value)?;
+                continue;
+            }
+            Statement::End if open => {
+                open = false;
+                continue;
+            }
+            Statement::End => return Err(StringStreamError::UnexpectedParse),
+            Statement::Relation { from, to, kind } => {
+                relations.push((from, to, kind));
+                continue;
+            }
+            Statement::Requirement(kind, name) => (
+                name,
+                NodeKind::Requirement {
+                    kind,
+                    requirement_id: None,
+                    text: None,
+                    risk: None,
+                    verify_method: None,
+                },
+            ),
+            Statement::Element(name) => (
+                name,
+                NodeKind::Element {
+                    element_type: None,
+                    docref: None,
+                },
+            ),
+        };
+        let id = diagram.nodes.len();
+        if open || names.insert(name.clone(), id).is_some() {
+            return Err(StringStreamError::UnexpectedParse);
+        }
+        diagram.nodes.push(Node { id, name, kind });
+        open = true;
+    }
+    if open {
+        return Err(StringStreamError::UnexpectedParse);
+    }
+    for (from, to, kind) in relations {
+        let (Some(&from), Some(&to)) = (names.get(&from), names.get(&to)) else {
+            return Err(StringStreamError::UnexpectedParse);
+        };
+        diagram.relations.push(Relation {
+            id: diagram.relations.len(),
+            from,
+            to,
+            kind,
+        });
+    }
+    Ok(diagram)
+}
+
+/// A quoted string or a word.
+fn name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, name, _)| name),
+        many1(satisfy(|c: char| {
+            c.is_alphanumeric() || c == '_' || c == '.'
+        })),
+    ))
+}
+
+fn direction_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("direction"),
+        blank1(),
+        choice((
+            keyword("TB").map(|_| Direction::TopToBottom),
+            keyword("BT").map(|_| Direction::BottomToTop),
+            keyword("LR").map(|_| Direction::LeftToRight),
+            keyword("RL").map(|_| Direction::RightToLeft),
+        )),
+    )
+        .map(|(_, _, direction)| Statement::Direction(direction))
+}
+
+fn requirement_kind_parser<Input>() -> impl Parser<Input, Output = RequirementKind>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        keyword("requirement").map(|_| RequirementKind::Requirement),
+        keyword("functionalRequirement").map(|_| RequirementKind::Functional),
+        keyword("interfaceRequirement").map(|_| RequirementKind::Interface),
+        keyword("performanceRequirement").map(|_| RequirementKind::Performance),
+        keyword("physicalRequirement").map(|_| RequirementKind::Physical),
+        keyword("designConstraint").map(|_| RequirementKind::DesignConstraint),
+    ))
+}
+
+/// `requirement name {` or `element name {`
+fn definition_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let kind = choice((
+        keyword("element").map(|_| None),
+        requirement_kind_parser().map(Some),
+    ));
+    attempt((kind, blank1(), name_parser(), blank(), char('{'))).map(|(kind, _, name, _, _)| {
+        match kind {
+            Some(kind) => Statement::Requirement(kind, name),
+            None => Statement::Element(name),
+        }
+    })
+}
+
+/// `key: value`, the value possibly quoted.
+fn field_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        attempt((many1(alpha_num()), blank(), char(':'))),
+        blank(),
+        many(none_of("\n".chars())),
+    )
+        .map(|((key, _, _), _, value): ((String, _, _), _, String)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Statement::Field(key, value.to_string())
+        })
+}
+
+fn relation_kind_parser<Input>() -> impl Parser<Input, Output = RelationKind>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        keyword("contains").map(|_| RelationKind::Contains),
+        keyword("copies").map(|_| RelationKind::Copies),
+        keyword("derives").map(|_| RelationKind::Derives),
+        keyword("satisfies").map(|_| RelationKind::Satisfies),
+        keyword("verifies").map(|_| RelationKind::Verifies),
+        keyword("refines").map(|_| RelationKind::Refines),
+        keyword("traces").map(|_| RelationKind::Traces),
+    ))
+}
+
+/// `a - satisfies -> b` or `b <- satisfies - a`
+fn relation_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let forward = (
+        char('-'),
+        blank(),
+        relation_kind_parser(),
+        blank(),
+        string("->"),
+    )
+        .map(|(_, _, kind, _, _)| (kind, false));
+    let backward = (
+        string("<-"),
+        blank(),
+        relation_kind_parser(),
+        blank(),
+        char('-'),
+    )
+        .map(|(_, _, kind, _, _)| (kind, true));
+    (
+        name_parser(),
+        blank(),
+        choice((attempt(forward), backward)),
+        blank(),
+        name_parser(),
+    )
+        .map(|(first, _, (kind, backward), _, second)| {
+            let (from, to) = if backward {
+                (second, first)
+            } else {
+                (first, second)
+            };
+            Statement::Relation { from, to, kind }
+        })
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            keyword("requirementDiagram").map(|_| Statement::Empty),
+            direction_parser(),
+            definition_parser(),
+            char('}').map(|_| Statement::End),
+            field_parser(),
+            relation_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relation_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Relation {
+                    from: "test_entity".to_string(),
+                    to: "test_req".to_string(),
+                    kind: RelationKind::Satisfies,
+                },
+                ""
+            )),
+            relation_parser().parse("test_entity - satisfies -> test_req")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Relation {
+                    from: "test_entity2".to_string(),
+                    to: "test_req".to_string(),
+                    kind: RelationKind::Copies,
+                },
+                ""
+            )),
+            relation_parser().parse("test_req <- copies - test_entity2")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"requirementDiagram
+
+    requirement test_req {
+    id: 1
+    text: "the test text."
+    risk: high
+    verifymethod: test
+    }
+
+    element test_entity {
+    type: simulation
+    }
+
+    test_entity - satisfies -> test_req
+"#;
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(
+            NodeKind::Requirement {
+                kind: RequirementKind::Requirement,
+                requirement_id: Some("1".to_string()),
+                text: Some("the test text.".to_string()),
+                risk: Some(Risk::High),
+                verify_method: Some(VerifyMethod::Test),
+            },
+            diagram.nodes[0].kind
+        );
+        assert_eq!(
+            NodeKind::Element {
+                element_type: Some("simulation".to_string()),
+                docref: None,
+            },
+            diagram.nodes[1].kind
+        );
+        assert_eq!(
+            vec![Relation {
+                id: 0,
+                from: 1,
+                to: 0,
+                kind: RelationKind::Satisfies,
+            }],
+            diagram.relations
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("requirementDiagram\na - traces -> b".to_string()).is_err());
+        assert!(parse("requirementDiagram\nrequirement a {\nrisk: huge\n}".to_string()).is_err());
+        assert!(parse("requirementDiagram\nelement a {\nrisk: low\n}".to_string()).is_err());
+        assert!(parse("requirementDiagram\nrequirement a {\nid: 1".to_string()).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use svg::node::element::{Group, Line, SVG};
+use svg::Node as _;
+
+use cassowary::strength::{MEDIUM, REQUIRED};
+use cassowary::WeightedRelation::*;
+
+use crate::flowchart::{
+    self, edge_markers, edge_path, Arrangement, Edge, EdgeEnd, Flowchart, NodeId, NodeShape,
+    NodeSize, Stroke,
+};
+use crate::layout::{BlockId, Layout, Orientation};
+use crate::requirement::*;
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub max_field_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+}
+
+impl RequirementKind {
+    fn stereotype(&self) -> &'static str {
+        match self {
+            RequirementKind::Requirement => "«Requirement»",
+            RequirementKind::Functional => "«Functional Requirement»",
+            RequirementKind::Interface => "«Interface Requirement»",
+            RequirementKind::Performance => "«Performance Requirement»",
+            RequirementKind::Physical => "«Physical Requirement»",
+            RequirementKind::DesignConstraint => "«Design Constraint»",
+        }
+    }
+}
+
+impl RelationKind {
+    fn label(&self) -> &'static str {
+        match self {
+            RelationKind::Contains => "«contains»",
+            RelationKind::Copies => "«copies»",
+            RelationKind::Derives => "«derives»",
+            RelationKind::Satisfies => "«satisfies»",
+            RelationKind::Verifies => "«verifies»",
+            RelationKind::Refines => "«refines»",
+            RelationKind::Traces => "«traces»",
+        }
+    }
+}
+
+/// The stereotype and the name of a node, followed by its fields.
+fn header_and_fields(node: &Node) -> (String, Vec<String>) {
+    let field = |name: &str, value: Option<String>| value.map(|value| format!("{name}: {value}"));
+    match &node.kind {
+        NodeKind::Requirement {
+            kind,
+            requirement_id,
+            text,
+            risk,
+            verify_method,
+        } => (
+            format!("{}\n{}", kind.stereotype(), node.name),
+            [
+                field("Id", requirement_id.clone()),
+                field("Text", text.clone()),
+                field("Risk", risk.map(|r| format!("{r:?}"))),
+                field("Verification", verify_method.map(|v| format!("{v:?}"))),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        ),
+        NodeKind::Element {
+            element_type,
+            docref,
+        } => (
+            format!("«Element»\n{}", node.name),
+            [
+                field("Type", element_type.clone()),
+                field("Doc Ref", docref.clone()),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        ),
+    }
+}
+
+struct NodeBox {
+    node: NodeId,
+    header: BlockId,
+    header_lines: Vec<String>,
+    fields: Option<(BlockId, Vec<String>)>,
+}
+
+fn add_node_box(layout: &mut Layout, node: &Node, block: BlockId, config: &SvgConfig) -> NodeBox {
+    let font_size = config.font_size * config.font_scale_factor;
+    let (header_text, field_texts) = header_and_fields(node);
+    let (header, header_lines) = layout.add_text_block(
+        &header_text,
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let fields = Some(field_texts.join("\n"))
+        .filter(|text| !text.is_empty())
+        .map(|text| {
+            layout.add_text_block(&text, config.max_field_length, config.padding, font_size)
+        });
+
+    let stack: Vec<BlockId> = std::iter::once(header)
+        .chain(fields.as_ref().map(|(b, _)| *b))
+        .collect();
+    for &b in &stack {
+        layout.add_constraint(layout.b(b).left() | EQ(REQUIRED) | layout.b(block).left());
+        layout.add_constraint(layout.b(b).width | EQ(REQUIRED) | layout.b(block).width);
+    }
+    layout.add_constraint(layout.b(header).top() | EQ(REQUIRED) | layout.b(block).top());
+    layout.distribute(Orientation::Vertical, 0.0, stack.iter());
+    let last = *stack.last().unwrap_or(&header);
+    layout.add_constraint(layout.b(last).bottom() | EQ(REQUIRED) | layout.b(block).bottom());
+    layout.add_constraint(layout.b(block).width | EQ(MEDIUM) | 0.0);
+    layout.add_constraint(layout.b(block).height | EQ(MEDIUM) | 0.0);
+
+    NodeBox {
+        node: node.id,
+        header,
+        header_lines,
+        fields,
+    }
+}
+
+impl NodeBox {
+    fn to_svg(&self, arrangement: &Arrangement, config: &SvgConfig) -> Group {
+        let block = arrangement.node(self.node);
+        let header = arrangement.node_part(self.node, self.header);
+        let mut group = Group::new().add(translated(&block).add(outline(&block, "white", 0.0)));
+        group = group.add(
+            text_lines(
+                &self.header_lines,
+                &header,
+                config.font_size,
+                config.padding,
+            )
+            .fold(
+                translated(&header).add(outline(&header, NOTE_FILL, 0.0)),
+                |group, t| group.add(t.set("x", header.width * 0.5).set("text-anchor", "middle")),
+            ),
+        );
+        let bottom = header.position.y + header.height;
+        group = group.add(stroked(
+            Line::new()
+                .set("x1", header.position.x)
+                .set("y1", bottom)
+                .set("x2", header.position.x + header.width)
+                .set("y2", bottom),
+            "none",
+        ));
+        if let Some((field, lines)) = &self.fields {
+            let b = arrangement.node_part(self.node, *field);
+            group = group.add(
+                text_lines(lines, &b, config.font_size, config.padding)
+                    .fold(translated(&b), Group::add),
+            );
+        }
+        group
+    }
+}
+
+fn relation_edge(relation: &Relation) -> Edge {
+    // The container end of `contains` carries a circle, every other relation
+    // is a dashed arrow.
+    let (stroke, start, end) = match relation.kind {
+        RelationKind::Contains => (Stroke::Solid, EdgeEnd::Circle, EdgeEnd::None),
+        _ => (Stroke::Dotted, EdgeEnd::None, EdgeEnd::Arrow),
+    };
+    Edge {
+        id: relation.id,
+        from: relation.from,
+        to: relation.to,
+        label: Some(relation.kind.label().to_string()),
+        stroke,
+        start,
+        end,
+        length: 1,
+    }
+}
+
+/// A requirement diagram with its requirement boxes and relations placed.
+pub struct RequirementLayout {
+    diagram: RequirementDiagram,
+    config: SvgConfig,
+    flowchart: Flowchart,
+    arrangement: Arrangement,
+    node_boxes: Vec<NodeBox>,
+}
+
+pub fn layout(diagram: &RequirementDiagram, config: &SvgConfig) -> RequirementLayout {
+    let flowchart = Flowchart {
+        direction: diagram.direction,
+        nodes: diagram
+            .nodes
+            .iter()
+            .map(|node| flowchart::Node {
+                id: node.id,
+                name: node.name.clone(),
+                label: String::new(),
+                shape: NodeShape::Rectangle,
+            })
+            .collect(),
+        edges: diagram.relations.iter().map(relation_edge).collect(),
+        subgraphs: vec![],
+    };
+
+    let flowchart_config = flowchart::SvgConfig {
+        max_label_length: config.max_label_length,
+        font_size: config.font_size,
+        font_scale_factor: config.font_scale_factor,
+        padding: config.padding,
+        corner_radius: 0.0,
+        rank_gutter: config.rank_gutter,
+        node_gutter: config.node_gutter,
+        subgraph_padding: 0.0,
+    };
+    let sizes: HashMap<_, _> = diagram
+        .nodes
+        .iter()
+        .map(|node| (node.id, NodeSize::Free))
+        .collect();
+    let mut arrangement = Arrangement::new(&flowchart, &flowchart_config, &sizes);
+    let node_boxes: Vec<NodeBox> = diagram
+        .nodes
+        .iter()
+        .map(|node| {
+            let block = arrangement.node_block(node.id);
+            add_node_box(arrangement.layout(), node, block, config)
+        })
+        .collect();
+    arrangement.solve();
+    RequirementLayout {
+        diagram: diagram.clone(),
+        config: config.clone(),
+        flowchart,
+        arrangement,
+        node_boxes,
+    }
+}
+
+impl RequirementLayout {
+    pub fn to_svg(&self) -> SVG {
+        let RequirementLayout {
+            diagram,
+            config,
+            flowchart,
+            arrangement,
+            node_boxes,
+        } = self;
+
+        let mut doc = document(arrangement.width(), arrangement.height()).add(edge_markers());
+        for edge_id in arrangement.edges() {
+            doc = doc.add(edge_path(
+                &flowchart.edges[edge_id],
+                &arrangement.edge_points(edge_id),
+            ));
+        }
+        for edge_id in arrangement.loops() {
+            let mut path = edge_path(&flowchart.edges[edge_id], &[]);
+            path.assign("d", arrangement.loop_path(flowchart.edges[edge_id].from));
+            doc = doc.add(path);
+        }
+        for node_box in node_boxes {
+            doc = doc.add(node_box.to_svg(arrangement, config));
+        }
+        for relation in &diagram.relations {
+            if let Some((block, lines)) = arrangement.edge_label(relation.id) {
+                doc = doc.add(label_box(lines, &block, config.font_size, config.padding));
+            }
+        }
+        doc
+    }
+}
+
+pub fn to_svg(diagram: &RequirementDiagram, config: &SvgConfig) -> SVG {
+    layout(diagram, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::layout::geometry::{on_outline, overlap};
+    use crate::requirement::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_node_boxes() {
+        let input = r#"requirementDiagram
+
+    requirement test_req {
+    id: 1
+    text: "the test text."
+    risk: high
+    verifymethod: test
+    }
+
+    functionalRequirement test_req2 {
+    id: 1.1
+    }
+
+    element test_entity {
+    type: simulation
+    }
+
+    element test_doc {
+    docref: reqs/test_doc
+    }
+
+    test_entity - satisfies -> test_req2
+    test_req - contains -> test_req2
+    test_doc - traces -> test_req
+"#;
+        let diagram = parse(input.to_string()).unwrap();
+        let RequirementLayout {
+            arrangement,
+            node_boxes,
+            ..
+        } = layout(&diagram, &config());
+
+        // the fields stacked under the header, both as wide as the box
+        for node_box in &node_boxes {
+            let block = arrangement.node(node_box.node);
+            let header = arrangement.node_part(node_box.node, node_box.header);
+            assert!((header.position.x - block.position.x).abs() < 1e-6);
+            assert!((header.position.y - block.position.y).abs() < 1e-6);
+            assert!((header.width - block.width).abs() < 1e-6);
+            let last = match &node_box.fields {
+                Some((fields, _)) => {
+                    let fields = arrangement.node_part(node_box.node, *fields);
+                    assert!((fields.position.x - block.position.x).abs() < 1e-6);
+                    assert!((fields.width - block.width).abs() < 1e-6);
+                    let top = header.position.y + header.height;
+                    assert!((fields.position.y - top).abs() < 1e-6);
+                    fields
+                }
+                None => header,
+            };
+            let bottom = last.position.y + last.height;
+            assert!((bottom - block.position.y - block.height).abs() < 1e-6);
+        }
+        let fields: Vec<usize> = node_boxes
+            .iter()
+            .map(|node_box| node_box.fields.as_ref().map_or(0, |(_, lines)| lines.len()))
+            .collect();
+        assert_eq!(vec![4, 1, 1, 1], fields);
+
+        for (i, a) in diagram.nodes.iter().enumerate() {
+            for b in &diagram.nodes[i + 1..] {
+                assert!(!overlap(&arrangement.node(a.id), &arrangement.node(b.id)));
+            }
+        }
+
+        // top to bottom, each relation goes down to the next rank
+        for relation in &diagram.relations {
+            let from = arrangement.node(relation.from);
+            let to = arrangement.node(relation.to);
+            assert!(
+                from.position.y + from.height < to.position.y,
+                "{relation:?}"
+            );
+            let points = arrangement.edge_points(relation.id);
+            assert!(on_outline(points[0], &from));
+            assert!(on_outline(points[points.len() - 1], &to));
+            let (label, lines) = arrangement.edge_label(relation.id).unwrap();
+            assert_eq!([relation.kind.label().to_string()], lines);
+            assert!(from.position.y + from.height <= label.position.y);
+            assert!(label.position.y + label.height <= to.position.y);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Sankey, SankeyLayout, ["sankey-beta", "sankey"], |options| {
+    SvgConfig {
+        max_label_length: 20,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        plot_height: 400.0,
+        node_width: 10.0,
+        column_gap: 150.0,
+        node_gutter: 10.0,
+        show_values: true,
+    }
+});
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{char, string};
+use combine::parser::choice::choice;
+use combine::{
+    attempt, eof, look_ahead, many, many1, none_of, optional, skip_many, ParseError, Parser, Stream,
+};
+
+use crate::parsing::{blank, keyword};
+
+pub type NodeId = usize;
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub id: NodeId,
+    pub name: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Link {
+    pub source: NodeId,
+    pub target: NodeId,
+    pub value: f64,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sankey {
+    pub nodes: Vec<Node>,
+    pub links: Vec<Link>,
+}
+
+impl Sankey {
+    /// The larger of the incoming and the outgoing total of a node.
+    pub fn node_value(&self, id: NodeId) -> f64 {
+        let incoming: f64 = self
+            .links
+            .iter()
+            .filter(|link| link.target == id)
+            .map(|link| link.value)
+            .sum();
+        let outgoing: f64 = self
+            .links
+            .iter()
+            .filter(|link| link.source == id)
+            .map(|link| link.value)
+            .sum();
+        // Empty sums are -0.0.
+        incoming.max(outgoing) + 0.0
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    /// A `source,target,value` row, with the value still unparsed.
+    Link(String, String, String),
+}
+
+pub fn parse(src: String) -> Result<Sankey, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_sankey(statements),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_sankey(statements: Vec<Statement>) -> Result<Sankey, StringStreamError> {
+    let mut sankey = Sankey {
+        nodes: vec![],
+        links: vec![],
+    };
+    let mut names: HashMap<String, NodeId> = HashMap::new();
+    for statement in statements {
+        let Statement::Link(source, target, value) = statement else {
+            continue;
+        };
+        let value: f64 = value
+            .parse()
+            .map_err(|_| StringStreamError::UnexpectedParse)?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(StringStreamError::UnexpectedParse);
+        }
+        let [source, target] = [source, target].map(|name| {
+            *names.entry(name.clone()).or_insert_with(|| {
+                sankey.nodes.push(Node {
+                    id: sankey.nodes.len(),
+                    name,
+                });
+                sankey.nodes.len() - 1
+            })
+        });
+        sankey.links.push(Link {
+            source,
+            target,
+            value,
+        });
+    }
+    if has_cycle(&sankey) {
+        return Err(StringStreamError::UnexpectedParse);
+    }
+    Ok(sankey)
+}
+
+/// Whether the links loop back to a node, which leaves flows without a column.
+fn has_cycle(sankey: &Sankey) -> bool {
+    let mut incoming = vec![0; sankey.nodes.len()];
+    for link in &sankey.links {
+        incoming[link.target] += 1;
+    }
+    let mut ready: Vec<NodeId> = (0..sankey.nodes.len())
+        .filter(|&id| incoming[id] == 0)
+        .collect();
+    let mut visited = 0;
+    while let Some(id) = ready.pop() {
+        visited += 1;
+        for link in sankey.links.iter().filter(|link| link.source == id) {
+            incoming[link.target] -= 1;
+            if incoming[link.target] == 0 {
+                ready.push(link.target);
+            }
+        }
+    }
+    visited < sankey.nodes.len()
+}
+
+/// A CSV field, either quoted with `""` standing for a quote or running up to
+/// the next comma.
+fn field_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let quoted = (
+        char('"'),
+        many(choice((
+            none_of("\"".chars()),
+            attempt(string("\"\"")).map(|_| '"'),
+        ))),
+        char('"'),
+        blank(),
+    )
+        .map(|(_, text, _, _): (_, String, _, _)| text);
+    let unquoted = many1(none_of(",\n".chars())).map(|text: String| text.trim().to_string());
+    (blank(), choice((quoted, unquoted))).map(|(_, field)| field)
+}
+
+fn link_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        field_parser(),
+        char(','),
+        field_parser(),
+        char(','),
+        field_parser(),
+    )
+        .map(|(source, _, target, _, value)| Statement::Link(source, target, value))
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            // The header must stand alone, a row may start with a node
+            // called `sankey`.
+            attempt((
+                choice((keyword("sankey-beta"), keyword("sankey"))),
+                blank(),
+                look_ahead(char('\n')),
+            ))
+            .map(|_| Statement::Empty),
+            (string("%%"), skip_many(none_of("\n".chars()))).map(|_| Statement::Empty),
+            link_parser(),
+        ))),
+        char('\n'),
+    )
+        .map(|(_, statement, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Link(
+                    "Bio-conversion".to_string(),
+                    "Liquid".to_string(),
+                    "0.597".to_string()
+                ),
+                ""
+            )),
+            link_parser().parse("Bio-conversion,Liquid,0.597")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Link(
+                    "Heating and cooling, \"homes\"".to_string(),
+                    "Losses".to_string(),
+                    "113.7".to_string()
+                ),
+                ""
+            )),
+            link_parser().parse("\"Heating and cooling, \"\"homes\"\"\" , Losses,113.7")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = "sankey-beta
+
+%% source,target,value
+Agricultural 'waste',Bio-conversion,124.729
+Bio-conversion,Liquid,0.597
+Bio-conversion,Losses,26.862";
+        let sankey = parse(input.to_string()).unwrap();
+        assert_eq!(
+            vec!["Agricultural 'waste'", "Bio-conversion", "Liquid", "Losses"],
+            sankey
+                .nodes
+                .iter()
+                .map(|n| n.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Link {
+                source: 1,
+                target: 3,
+                value: 26.862
+            },
+            sankey.links[2]
+        );
+        assert_eq!(124.729, sankey.node_value(1));
+
+        let empty = parse("sankey-beta\na,b,0".to_string()).unwrap();
+        assert!(empty
+            .nodes
+            .iter()
+            .all(|n| empty.node_value(n.id).is_sign_positive()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("sankey-beta\na,b,many".to_string()).is_err());
+        assert!(parse("sankey-beta\na,b,-1".to_string()).is_err());
+        assert!(parse("sankey-beta\na,b,1\nb,c,1\nc,a,1".to_string()).is_err());
+        assert!(parse("sankey-beta\na,b".to_string()).is_err());
+    }
+}
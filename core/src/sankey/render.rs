@@ -0,0 +1,334 @@
+use svg::node::element::{Group, Path, Rectangle, SVG};
+
+use cassowary::strength::REQUIRED;
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{BlockId, Layout};
+use crate::pie::format_value;
+use crate::sankey::*;
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    /// Height of the tallest column of nodes, gutters included.
+    pub plot_height: f64,
+    pub node_width: f64,
+    /// Horizontal space between two columns, taken by the bands.
+    pub column_gap: f64,
+    pub node_gutter: f64,
+    /// Whether the labels show the value of their node under its name.
+    pub show_values: bool,
+}
+
+/// The column of every node: the length of the longest path reaching it, with
+/// the sinks moved to the last column.
+fn columns(sankey: &Sankey) -> Vec<usize> {
+    let mut column = vec![0; sankey.nodes.len()];
+    // Links never form a cycle, so a pass per node settles every column.
+    for _ in 0..sankey.nodes.len() {
+        for link in &sankey.links {
+            column[link.target] = column[link.target].max(column[link.source] + 1);
+        }
+    }
+    let last = column.iter().copied().max().unwrap_or(0);
+    for node in &sankey.nodes {
+        if !sankey.links.iter().any(|link| link.source == node.id) {
+            column[node.id] = last;
+        }
+    }
+    column
+}
+
+struct NodeBox {
+    block: BlockId,
+    label: BlockId,
+    lines: Vec<String>,
+}
+
+/// A sankey diagram with its nodes placed in columns.
+pub struct SankeyLayout {
+    sankey: Sankey,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    boxes: Vec<NodeBox>,
+    /// Height of a unit of value.
+    scale: f64,
+}
+
+pub fn layout(sankey: &Sankey, config: &SvgConfig) -> SankeyLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+    let column_of = columns(sankey);
+    let column_count = column_of.iter().copied().max().map_or(0, |last| last + 1);
+    let stacks: Vec<Vec<NodeId>> = (0..column_count)
+        .map(|c| {
+            sankey
+                .nodes
+                .iter()
+                .filter(|node| column_of[node.id] == c)
+                .map(|node| node.id)
+                .collect()
+        })
+        .collect();
+
+    // One scale for all columns, so that the fullest one fits the plot.
+    let scale = stacks
+        .iter()
+        .filter_map(|stack| {
+            let total: f64 = stack.iter().map(|&id| sankey.node_value(id)).sum();
+            let room = config.plot_height - (stack.len() as f64 - 1.0) * config.node_gutter;
+            Some(room / total).filter(|scale| scale.is_finite())
+        })
+        .fold(f64::INFINITY, f64::min);
+    let scale = if scale.is_finite() { scale } else { 1.0 };
+
+    let mut boxes = vec![];
+    for node in &sankey.nodes {
+        let value = sankey.node_value(node.id);
+        let block = layout.add_block();
+        let text = if config.show_values {
+            // Sums of decimal values pick up noise.
+            format!(
+                "{}\n{}",
+                node.name,
+                format_value((value * 1000.0).round() / 1000.0)
+            )
+        } else {
+            node.name.clone()
+        };
+        let (label, lines) =
+            layout.add_text_block(&text, config.max_label_length, config.padding, font_size);
+        let column = column_of[node.id];
+        let left = column as f64 * (config.node_width + config.column_gap);
+        // Labels face the bands, except in the last column.
+        let label_side = if column + 1 == column_count && column > 0 {
+            layout.b(label).right() | EQ(REQUIRED) | layout.b(block).left()
+        } else {
+            layout.b(label).left() | EQ(REQUIRED) | layout.b(block).right()
+        };
+        let constraints = [
+            layout.b(block).left() | EQ(REQUIRED) | left,
+            layout.b(block).width | EQ(REQUIRED) | config.node_width,
+            layout.b(block).height | EQ(REQUIRED) | (value * scale),
+            label_side,
+            (layout.b(label).top() + layout.b(label).height * 0.5)
+                | EQ(REQUIRED)
+                | (layout.b(block).top() + layout.b(block).height * 0.5),
+        ];
+        for constraint in constraints {
+            layout.add_constraint(constraint);
+        }
+        boxes.push(NodeBox {
+            block,
+            label,
+            lines,
+        });
+    }
+    // Columns are stacked from their middle so they line up around the
+    // centre of the plot.
+    for stack in &stacks {
+        let blocks: Vec<BlockId> = stack.iter().map(|&id| boxes[id].block).collect();
+        let height: f64 = stack
+            .iter()
+            .map(|&id| sankey.node_value(id) * scale + config.node_gutter)
+            .sum::<f64>()
+            - config.node_gutter;
+        if let Some(&first) = blocks.first() {
+            layout.add_constraint(
+                layout.b(first).top() | EQ(REQUIRED) | ((config.plot_height - height) * 0.5),
+            );
+        }
+        for pair in blocks.windows(2) {
+            layout.add_constraint(
+                layout.b(pair[1]).top()
+                    | EQ(REQUIRED)
+                    | (layout.b(pair[0]).bottom() + config.node_gutter),
+            );
+        }
+    }
+    layout.solve();
+    SankeyLayout {
+        sankey: sankey.clone(),
+        config: config.clone(),
+        layout,
+        boxes,
+        scale,
+    }
+}
+
+/// Where the middle of a band leaves its source and enters its target.
+#[derive(Clone, Copy, Default)]
+struct Band {
+    from: (f64, f64),
+    to: (f64, f64),
+    width: f64,
+}
+
+/// The bands of the links, in their order.
+fn bands(sankey: &Sankey, layout: &Layout, boxes: &[NodeBox], scale: f64) -> Vec<Band> {
+    let solved = |id: NodeId| layout.b(boxes[id].block).solved();
+    let center_y = |id: NodeId| {
+        let b = solved(id);
+        b.position.y + b.height * 0.5
+    };
+
+    // Bands leave and enter their nodes in the order of the node at the other
+    // end, which keeps them from crossing next to the nodes.
+    let mut out_offset = vec![0.0; sankey.nodes.len()];
+    let mut in_offset = vec![0.0; sankey.nodes.len()];
+    let mut outgoing: Vec<(usize, &Link)> = sankey.links.iter().enumerate().collect();
+    outgoing.sort_by(|(_, a), (_, b)| center_y(a.target).total_cmp(&center_y(b.target)));
+    let mut sources = vec![0.0; sankey.links.len()];
+    for (index, link) in outgoing {
+        let width = link.value * scale;
+        sources[index] = solved(link.source).position.y + out_offset[link.source] + width * 0.5;
+        out_offset[link.source] += width;
+    }
+    let mut incoming: Vec<(usize, &Link)> = sankey.links.iter().enumerate().collect();
+    incoming.sort_by(|(_, a), (_, b)| center_y(a.source).total_cmp(&center_y(b.source)));
+    let mut bands = vec![Band::default(); sankey.links.len()];
+    for (index, link) in incoming {
+        let width = link.value * scale;
+        let target_y = solved(link.target).position.y + in_offset[link.target] + width * 0.5;
+        in_offset[link.target] += width;
+        let source = solved(link.source);
+        let x0 = source.position.x + source.width;
+        let x1 = solved(link.target).position.x;
+        bands[index] = Band {
+            from: (x0, sources[index]),
+            to: (x1, target_y),
+            width,
+        };
+    }
+    bands
+}
+
+impl SankeyLayout {
+    pub fn to_svg(&self) -> SVG {
+        let SankeyLayout {
+            sankey,
+            config,
+            layout,
+            boxes,
+            scale,
+        } = self;
+        let mut doc = document(layout.width(), layout.height());
+        let solved = |id: NodeId| layout.b(boxes[id].block).solved();
+
+        let bands = bands(sankey, layout, boxes, *scale);
+        for (link, band) in sankey.links.iter().zip(bands) {
+            let ((x0, y0), (x1, y1)) = (band.from, band.to);
+            let mid = (x0 + x1) * 0.5;
+            doc = doc.add(
+                Path::new()
+                    .set(
+                        "d",
+                        format!("M {x0} {y0} C {mid} {y0} {mid} {y1} {x1} {y1}"),
+                    )
+                    .set("fill", "none")
+                    .set("stroke", PALETTE[link.source % PALETTE.len()])
+                    .set("stroke-opacity", 0.5)
+                    .set("stroke-width", band.width),
+            );
+        }
+
+        for node in &sankey.nodes {
+            let b = solved(node.id);
+            doc = doc.add(
+                Rectangle::new()
+                    .set("x", b.position.x)
+                    .set("y", b.position.y)
+                    .set("width", b.width)
+                    .set("height", b.height)
+                    .set("fill", PALETTE[node.id % PALETTE.len()])
+                    .set("stroke", LINE_COLOR),
+            );
+        }
+        for node_box in boxes {
+            let b = layout.b(node_box.label).solved();
+            doc = doc.add(
+                text_lines(&node_box.lines, b, config.font_size, config.padding)
+                    .fold(translated(b), Group::add),
+            );
+        }
+        doc
+    }
+}
+
+pub fn to_svg(sankey: &Sankey, config: &SvgConfig) -> SVG {
+    layout(sankey, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::sankey::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_bands() {
+        let input = "sankey-beta
+Coal,Power,60
+Gas,Power,30
+Gas,Heat,20
+Power,Homes,50
+Power,Losses,40
+Sun,Homes,5";
+        let sankey = parse(input.to_string()).unwrap();
+        let config = config();
+        let id = |name: &str| sankey.nodes.iter().find(|n| n.name == name).unwrap().id;
+        let column_of = columns(&sankey);
+        // sources first, sinks last whatever their depth
+        assert_eq!(0, column_of[id("Sun")]);
+        assert_eq!(2, column_of[id("Heat")]);
+        assert_eq!(1, column_of[id("Power")]);
+
+        let SankeyLayout {
+            layout,
+            boxes,
+            scale,
+            ..
+        } = layout(&sankey, &config);
+        let solved = |id: NodeId| layout.b(boxes[id].block).solved();
+        for node in &sankey.nodes {
+            let b = solved(node.id);
+            assert!((b.height - sankey.node_value(node.id) * scale).abs() < 1e-6);
+            assert_eq!(config.node_width, b.width);
+        }
+        // the last column, Heat, Homes and Losses with gutters, is the fullest
+        let sinks = ["Heat", "Homes", "Losses"].map(|name| solved(id(name)));
+        let top = sinks[0].position.y;
+        let bottom = sinks[2].position.y + sinks[2].height;
+        assert!((bottom - top - config.plot_height).abs() < 1e-6);
+
+        let bands = bands(&sankey, &layout, &boxes, scale);
+        for (link, band) in sankey.links.iter().zip(&bands) {
+            let ((x0, y0), (x1, y1), width) = (band.from, band.to, band.width);
+            assert!((width - link.value * scale).abs() < 1e-9);
+            let (source, target) = (solved(link.source), solved(link.target));
+            assert!((x0 - source.position.x - source.width).abs() < 1e-6);
+            assert!((x1 - target.position.x).abs() < 1e-6);
+            assert!(source.position.y - 1e-6 <= y0 - width * 0.5);
+            assert!(y0 + width * 0.5 <= source.position.y + source.height + 1e-6);
+            assert!(target.position.y - 1e-6 <= y1 - width * 0.5);
+            assert!(y1 + width * 0.5 <= target.position.y + target.height + 1e-6);
+        }
+        // Power sends to Homes, which is above Losses, from its top
+        let power = solved(id("Power"));
+        let (to_homes, to_losses) = (bands[3], bands[4]);
+        assert!((to_homes.from.1 - to_homes.width * 0.5 - power.position.y).abs() < 1e-6);
+        assert!(
+            (to_homes.from.1 + to_homes.width * 0.5 - (to_losses.from.1 - to_losses.width * 0.5))
+                .abs()
+                < 1e-6
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Laid out diagrams as shapes and text runs at absolute positions, which the
+//! backends turn into documents of their format.
+use svg::node::element::{Line, Rectangle, Text, SVG};
+
+use crate::layout::TextDirection;
+use crate::theme::{document, escaped, LINE_COLOR, TEXT_COLOR};
+
+/// Length of the arrow tips along their line.
+pub const ARROW_TIP_LENGTH: f64 = 10.0;
+
+pub type Point = (f64, f64);
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Item {
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: String,
+        stroke: String,
+        corner_radius: f64,
+    },
+    Line {
+        from: Point,
+        to: Point,
+    },
+    /// A line with an arrow tip at `to`
+    Arrow {
+        from: Point,
+        to: Point,
+    },
+    /// A line of text whose baseline starts at `position`, or ends there when
+    /// it is right-to-left, `width` being its width at `font_size` in the font
+    /// of the layout
+    Text {
+        content: String,
+        position: Point,
+        width: f64,
+        font_size: f64,
+        direction: TextDirection,
+    },
+}
+
+/// Everything drawn for a diagram, in painting order.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scene {
+    pub width: f64,
+    pub height: f64,
+    /// How much larger text is measured than SVG documents ask for, as
+    /// viewers draw it in wider fonts than the one of the layout.
+    pub font_scale_factor: f64,
+    /// Height of the band at the top, such as the participant heads, that
+    /// starts every page when a backend spreads the scene over several.
+    pub header: f64,
+    /// Height of the band at the bottom that only ends the last page.
+    pub footer: f64,
+    pub items: Vec<Item>,
+}
+
+pub trait RenderBackend {
+    type Output;
+
+    fn render(&self, scene: &Scene) -> Self::Output;
+}
+
+pub struct SvgBackend;
+
+impl RenderBackend for SvgBackend {
+    type Output = SVG;
+
+    fn render(&self, scene: &Scene) -> SVG {
+        let doc = document(scene.width, scene.height);
+        scene.items.iter().fold(doc, |doc, item| match item {
+            Item::Rect {
+                x,
+                y,
+                width,
+                height,
+                fill,
+                stroke,
+                corner_radius,
+            } => doc.add(
+                Rectangle::new()
+                    .set("x", *x)
+                    .set("y", *y)
+                    .set("width", *width)
+                    .set("height", *height)
+                    .set("fill", fill.as_str())
+                    .set("stroke", stroke.as_str())
+                    .set("rx", *corner_radius)
+                    .set("stroke-width", 1),
+            ),
+            Item::Line { from, to } => doc.add(line(*from, *to)),
+            Item::Arrow { from, to } => {
+                // the marker starts where the line ends
+                let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                let length = dx.hypot(dy);
+                let shortened = if length > ARROW_TIP_LENGTH {
+                    let ratio = (length - ARROW_TIP_LENGTH) / length;
+                    (from.0 + dx * ratio, from.1 + dy * ratio)
+                } else {
+                    *from
+                };
+                doc.add(line(*from, shortened).set("marker-end", "url(#end-arrow)"))
+            }
+            Item::Text {
+                content,
+                position,
+                font_size,
+                direction,
+                ..
+            } => {
+                let text = Text::new()
+                    .set("x", position.0)
+                    .set("y", position.1)
+                    .set("fill", TEXT_COLOR)
+                    .set("font-family", "monospace")
+                    .set("font-size", font_size / scene.font_scale_factor)
+                    .set("unicode-bidi", "embed")
+                    .set(
+                        "direction",
+                        match direction {
+                            TextDirection::LeftToRight => "ltr",
+                            TextDirection::RightToLeft => "rtl",
+                        },
+                    );
+                doc.add(text.add(svg::node::Text::new(escaped(content))))
+            }
+        })
+    }
+}
+
+fn line(from: Point, to: Point) -> Line {
+    Line::new()
+        .set("x1", from.0)
+        .set("y1", from.1)
+        .set("x2", to.0)
+        .set("y2", to.1)
+        .set("stroke", LINE_COLOR)
+        .set("stroke-width", 1)
+}
@@ -0,0 +1,201 @@
+use crate::sequence_diagram::*;
+
+#[derive(PartialEq, Debug)]
+pub enum BuildError {
+    /// `activate` or `deactivate` of a participant no other statement names
+    UnknownParticipant(String),
+    /// `activate` or `deactivate` before any message
+    NoMessage(String),
+    /// `deactivate` of a participant without an open activation
+    NotActivated(String),
+    /// A note over no participant at all
+    EmptyNote,
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::UnknownParticipant(name) => write!(f, "unknown participant {name}"),
+            BuildError::NoMessage(name) => write!(f, "{name} activated before any message"),
+            BuildError::NotActivated(name) => write!(f, "{name} deactivated while not active"),
+            BuildError::EmptyNote => write!(f, "note over no participant"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds a [`SequenceDiagram`] statement by statement, as if they were the
+/// lines of its source.
+#[derive(Default)]
+pub struct SequenceDiagramBuilder {
+    lines: Vec<Line>,
+}
+
+impl SequenceDiagram {
+    pub fn builder() -> SequenceDiagramBuilder {
+        SequenceDiagramBuilder::default()
+    }
+}
+
+impl SequenceDiagramBuilder {
+    pub fn participant(mut self, name: &str) -> Self {
+        self.lines.push(Line::Participant(name.to_string(), None));
+        self
+    }
+
+    pub fn actor(mut self, name: &str) -> Self {
+        self.lines.push(Line::Actor(name.to_string(), None));
+        self
+    }
+
+    /// A message with a solid arrow, like `from->>to: msg`.
+    pub fn message(self, from: &str, to: &str, msg: &str) -> Self {
+        self.arrow(from, Arrow::SolidArrow, to, msg)
+    }
+
+    pub fn arrow(mut self, from: &str, arrow: Arrow, to: &str, msg: &str) -> Self {
+        self.lines.push(Line::Message(MessageLine {
+            from: from.to_string(),
+            arrow,
+            to: to.to_string(),
+            msg: msg.to_string(),
+            activation: None,
+        }));
+        self
+    }
+
+    /// Activates `name` from the last message on.
+    pub fn activate(mut self, name: &str) -> Self {
+        self.lines.push(Line::Activate(name.to_string()));
+        self
+    }
+
+    /// Ends the last activation of `name` at the last message.
+    pub fn deactivate(mut self, name: &str) -> Self {
+        self.lines.push(Line::Deactivate(name.to_string()));
+        self
+    }
+
+    pub fn note_left_of(self, name: &str, content: &str) -> Self {
+        self.note(NotePositioning::LeftOf(name.to_string()), content)
+    }
+
+    pub fn note_right_of(self, name: &str, content: &str) -> Self {
+        self.note(NotePositioning::RightOf(name.to_string()), content)
+    }
+
+    pub fn note_over(self, names: &[&str], content: &str) -> Self {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.note(NotePositioning::Over(names), content)
+    }
+
+    fn note(mut self, position: NotePositioning, content: &str) -> Self {
+        self.lines.push(Line::Note(position, content.to_string()));
+        self
+    }
+
+    /// The diagram, unless a statement refers to an activation that does not
+    /// exist. Participants are declared by the first statement naming them,
+    /// as in a source.
+    pub fn build(self) -> Result<SequenceDiagram, BuildError> {
+        self.validate()?;
+        build_diagram(self.lines)
+    }
+
+    /// What sources get away with: activations before any message are
+    /// ignored there, and notes over no participant cannot be written.
+    fn validate(&self) -> Result<(), BuildError> {
+        let mut messages = false;
+        for line in &self.lines {
+            match line {
+                Line::Message(_) => messages = true,
+                Line::Activate(name) | Line::Deactivate(name) if !messages => {
+                    return Err(BuildError::NoMessage(name.clone()))
+                }
+                Line::Note(NotePositioning::Over(names), _) if names.is_empty() => {
+                    return Err(BuildError::EmptyNote)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let built = SequenceDiagram::builder()
+            .participant("Alice")
+            .actor("John")
+            .message("Alice", "John", "Hello John")
+            .activate("John")
+            .note_right_of("John", "thinks")
+            .arrow("John", Arrow::DottedArrow, "Alice", "Great!")
+            .deactivate("John")
+            .note_over(&["Alice", "John"], "done")
+            .build();
+        let parsed = parse(
+            "participant Alice
+actor John
+Alice->>John: Hello John
+activate John
+Note right of John: thinks
+John-->>Alice: Great!
+deactivate John
+Note over Alice,John: done"
+                .to_string(),
+        );
+        assert_eq!(parsed.unwrap(), built.unwrap());
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let implicit = SequenceDiagram::builder()
+            .participant("Alice")
+            .message("Alice", "Bob", "hi")
+            .note_left_of("Carol", "listens")
+            .build();
+        assert_eq!(
+            parse("participant Alice\nAlice->>Bob: hi\nNote left of Carol: listens".to_string()),
+            Ok(implicit.unwrap())
+        );
+
+        let unknown = SequenceDiagram::builder()
+            .message("Alice", "Bob", "hi")
+            .activate("Carol")
+            .build();
+        assert_eq!(
+            Err(BuildError::UnknownParticipant("Carol".to_string())),
+            unknown
+        );
+
+        let not_activated = SequenceDiagram::builder()
+            .participant("Alice")
+            .participant("Bob")
+            .message("Alice", "Bob", "hi")
+            .deactivate("Bob")
+            .build();
+        assert_eq!(
+            Err(BuildError::NotActivated("Bob".to_string())),
+            not_activated
+        );
+
+        let no_message = SequenceDiagram::builder()
+            .participant("Alice")
+            .activate("Alice")
+            .build();
+        assert_eq!(Err(BuildError::NoMessage("Alice".to_string())), no_message);
+
+        let empty_note = SequenceDiagram::builder().note_over(&[], "nobody").build();
+        assert_eq!(Err(BuildError::EmptyNote), empty_note);
+        assert_eq!(
+            "Bob deactivated while not active",
+            BuildError::NotActivated("Bob".to_string()).to_string()
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use combine::error::StringStreamError;
+
+use crate::sequence_diagram::*;
+
+const INDENT: &str = "    ";
+
+fn arrow(arrow: &Arrow) -> &'static str {
+    match arrow {
+        Arrow::DottedNoArrow => "-->",
+        Arrow::SolidNoArrow => "->",
+        Arrow::DottedArrow => "-->>",
+        Arrow::SolidArrow => "->>",
+        Arrow::DottedCross => "--x",
+        Arrow::SolidCross => "-x",
+        Arrow::DottedOpen => "--)",
+        Arrow::SolidOpen => "-)",
+    }
+}
+
+/// `label: text`, without trailing blanks when the text is empty.
+fn with_text(label: String, text: &str) -> String {
+    format!("{label}: {text}").trim_end().to_string()
+}
+
+/// `keyword label`, without trailing blanks when the label is empty.
+fn with_label(keyword: &str, label: &str) -> String {
+    format!("{keyword} {label}").trim_end().to_string()
+}
+
+fn declaration(name: &str, alias: &Option<String>) -> String {
+    match alias {
+        Some(alias) => format!("{name} as {alias}"),
+        None => name.to_string(),
+    }
+}
+
+fn statement(line: &Line) -> Option<String> {
+    let statement = match line {
+        Line::Message(m) => {
+            let activation = match m.activation {
+                Some(ActivationChange::Activate) => "+",
+                Some(ActivationChange::Deactivate) => "-",
+                None => "",
+            };
+            with_text(
+                format!("{}{}{activation}{}", m.from, arrow(&m.arrow), m.to),
+                &m.msg,
+            )
+        }
+        Line::Participant(name, alias) => format!("participant {}", declaration(name, alias)),
+        Line::Actor(name, alias) => format!("actor {}", declaration(name, alias)),
+        Line::Note(position, content) => {
+            let position = match position {
+                NotePositioning::LeftOf(name) => format!("left of {name}"),
+                NotePositioning::RightOf(name) => format!("right of {name}"),
+                NotePositioning::Over(names) => format!("over {}", names.join(",")),
+            };
+            with_text(format!("Note {position}"), content)
+        }
+        Line::Activate(name) => format!("activate {name}"),
+        Line::Deactivate(name) => format!("deactivate {name}"),
+        Line::Title(title) => with_label("title", title),
+        Line::Autonumber(options) => with_label("autonumber", options),
+        Line::Block(block, label) => with_label(block.keyword(), label),
+        Line::Branch(branch, label) => with_label(branch.keyword(), label),
+        Line::End => "end".to_string(),
+        Line::Empty | Line::Header | Line::Comment(_) => return None,
+    };
+    Some(statement)
+}
+
+/// A statement with the comments right above it, which move along with it.
+struct Entry<'a> {
+    comments: Vec<&'a str>,
+    line: Option<&'a Line>,
+    blank_before: bool,
+}
+
+/// Canonical source for the lines of a sequence diagram: the header first,
+/// below the comments leading the source if any, then the declared
+/// participants, then everything else in its order, with statements indented
+/// one more level inside each block and at most one blank line in a row.
+pub fn format_lines(lines: &[Line]) -> String {
+    let mut out = String::new();
+    // Comments above the header, such as directives, stay there with the
+    // blank lines between them. Statements above it move below it instead.
+    let header = lines
+        .iter()
+        .position(|line| *line == Line::Header)
+        .filter(|&header| {
+            lines[..header]
+                .iter()
+                .all(|line| matches!(line, Line::Empty | Line::Comment(_)))
+        });
+    let body = match header {
+        Some(header) => {
+            let mut blank = false;
+            for line in &lines[..header] {
+                if let Line::Comment(comment) = line {
+                    if std::mem::take(&mut blank) && !out.is_empty() {
+                        out.push('\n');
+                    }
+                    out.push_str(&format!("%%{comment}\n"));
+                } else {
+                    blank = true;
+                }
+            }
+            if blank && !out.is_empty() {
+                out.push('\n');
+            }
+            &lines[header + 1..]
+        }
+        None => lines,
+    };
+    out.push_str("sequenceDiagram\n");
+
+    let mut entries = vec![];
+    let mut comments = vec![];
+    // whether a blank line comes before the next statement, or before the
+    // pending comments
+    let mut blank = false;
+    let mut comments_blank = false;
+    for line in body {
+        match line {
+            Line::Empty => {
+                // comments followed by a blank line stand on their own
+                if !comments.is_empty() {
+                    entries.push(Entry {
+                        comments: std::mem::take(&mut comments),
+                        line: None,
+                        blank_before: comments_blank,
+                    });
+                }
+                blank = true;
+            }
+            Line::Header => {}
+            Line::Comment(comment) => {
+                if comments.is_empty() {
+                    comments_blank = std::mem::take(&mut blank);
+                }
+                comments.push(comment.as_str());
+            }
+            line => {
+                let blank_before = if comments.is_empty() {
+                    blank
+                } else {
+                    comments_blank
+                };
+                entries.push(Entry {
+                    comments: std::mem::take(&mut comments),
+                    line: Some(line),
+                    blank_before,
+                });
+                blank = false;
+            }
+        }
+    }
+    if !comments.is_empty() {
+        entries.push(Entry {
+            comments,
+            line: None,
+            blank_before: comments_blank,
+        });
+    }
+
+    let (mut declarations, rest): (Vec<Entry>, Vec<Entry>) = entries
+        .into_iter()
+        .partition(|entry| matches!(entry.line, Some(Line::Participant(..) | Line::Actor(..))));
+    // Participants are drawn in the order they first show up, so the ones
+    // used before the last declared one get declared too.
+    let order = build_participants(body);
+    let is_declared = |name: &str| {
+        declarations.iter().any(
+            |entry| matches!(entry.line, Some(Line::Participant(n, _) | Line::Actor(n, _)) if n == name),
+        )
+    };
+    let last = order.iter().rposition(|p| is_declared(&p.name));
+    let implicit: Vec<Line> = order[..last.map_or(0, |last| last + 1)]
+        .iter()
+        .filter(|p| !is_declared(&p.name))
+        .map(|p| Line::Participant(p.name.clone(), None))
+        .collect();
+    declarations.extend(implicit.iter().map(|line| Entry {
+        comments: vec![],
+        line: Some(line),
+        blank_before: false,
+    }));
+    let position = |entry: &Entry| match entry.line {
+        Some(Line::Participant(name, _) | Line::Actor(name, _)) => {
+            order.iter().position(|p| &p.name == name)
+        }
+        _ => None,
+    };
+    declarations.sort_by_key(position);
+
+    let groups = [declarations, rest];
+    // blocks nest one level deeper than the lines around them
+    let mut depth = 0;
+    for (i, group) in groups.iter().filter(|group| !group.is_empty()).enumerate() {
+        for (j, entry) in group.iter().enumerate() {
+            // blank lines separate statements and the two groups, they
+            // never start a group
+            let blank = if j == 0 { i > 0 } else { entry.blank_before };
+            if blank {
+                out.push('\n');
+            }
+            let indent = INDENT.repeat(depth + 1);
+            for comment in &entry.comments {
+                out.push_str(&format!("{indent}%%{comment}\n"));
+            }
+            let statement_depth = match entry.line {
+                Some(Line::Block(..)) => {
+                    depth += 1;
+                    depth - 1
+                }
+                Some(Line::Branch(..)) => depth.saturating_sub(1),
+                Some(Line::End) => {
+                    depth = depth.saturating_sub(1);
+                    depth
+                }
+                _ => depth,
+            };
+            if let Some(statement) = entry.line.and_then(statement) {
+                let indent = INDENT.repeat(statement_depth + 1);
+                out.push_str(&format!("{indent}{statement}\n"));
+            }
+        }
+    }
+    out
+}
+
+pub fn format(src: &str) -> Result<String, StringStreamError> {
+    parse_lines(src).map(|lines| format_lines(&lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let input = "%%{init: {}}%%
+sequenceDiagram
+\tAlice ->>+ John :Hello John  
+
+
+  %% greet back
+  John-->>-Alice:Great!
+  participant John
+\tNote over Alice , John:done";
+        let expected = "%%{init: {}}%%
+sequenceDiagram
+    participant Alice
+    participant John
+
+    Alice->>+John: Hello John
+
+    %% greet back
+    John-->>-Alice: Great!
+    Note over Alice,John: done
+";
+        assert_eq!(Ok(expected.to_string()), format(input));
+    }
+
+    #[test]
+    fn test_format_before_header() {
+        assert_eq!(
+            Ok("%%{init: {}}%%\n\nsequenceDiagram\n    A->>B: x\n".to_string()),
+            format("\n%%{init: {}}%%\n\n\nsequenceDiagram\nA->>B: x")
+        );
+        assert_eq!(
+            Ok("sequenceDiagram\n    %% first\n    A->B: x\n    B->A: y\n".to_string()),
+            format("%% first\nA->B: x\nsequenceDiagram\nB->A: y\n")
+        );
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        let input = "sequenceDiagram
+loop Every minute
+A->>B: ping
+  alt is sick
+B->>A: no
+%% still in alt
+else
+B->>A: yes
+      end
+end
+end";
+        let expected = "sequenceDiagram
+    loop Every minute
+        A->>B: ping
+        alt is sick
+            B->>A: no
+            %% still in alt
+        else
+            B->>A: yes
+        end
+    end
+    end
+";
+        assert_eq!(Ok(expected.to_string()), format(input));
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        let corpus =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../resources/corpus/sequence");
+        let sources = std::fs::read_dir(corpus)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            // the declaration moves above the message naming B first
+            .chain(["A->>B: x\nparticipant B as Bob".to_string()]);
+        for src in sources {
+            let formatted = format(&src).unwrap();
+            assert_eq!(Ok(formatted.clone()), format(&formatted));
+            // ids come from line numbers, so compare what gets drawn
+            assert_eq!(
+                render(&src).map(|svg| svg.to_string()),
+                render(&formatted).map(|svg| svg.to_string())
+            );
+        }
+    }
+}
@@ -1,27 +1,62 @@
+pub mod builder;
+pub mod format;
 pub mod parser;
 pub mod render;
+pub mod text;
 
+pub use builder::*;
+pub use format::*;
 pub use parser::*;
 pub use render::*;
 use svg::node::element::SVG;
+pub use text::*;
 
-pub fn render(src: &str) -> Result<SVG, String>{
-    let svg_config =  crate::sequence_diagram::SvgConfig {
-        max_participant_head_length: 5,
-        max_msg_label_length: 60,
-        font_scale_factor: 1.2,
-        msg_gutter: 20.0,
-        font_size: 10.0,
-        padding: 5.0,
-        corner_radius: 2.0,
-    };
-
-    match crate::sequence_diagram::parser::parse(src.to_string()) {
-        Ok(diagram) => {
-            Ok(crate::sequence_diagram::render::to_svg(&diagram, &svg_config))
-        }
-        Err(e) => {
-            Err(format!("Error: {e:?}").to_owned())
+use crate::diagram::{Diagram, Options};
+
+pub struct Definition;
+
+impl Definition {
+    /// The configuration sequence diagrams are drawn with.
+    pub fn config(&self, options: &Options) -> SvgConfig {
+        SvgConfig {
+            max_participant_head_length: 5,
+            max_msg_label_length: 60,
+            max_note_length: 30,
+            font_scale_factor: options.font_scale_factor,
+            msg_gutter: 20.0,
+            font_size: options.font_size,
+            padding: options.padding,
+            corner_radius: 2.0,
         }
     }
 }
+
+impl Diagram for Definition {
+    type Ast = SequenceDiagram;
+    /// Every element of the diagram placed, to be drawn by any backend.
+    type Layout = SequenceLayout;
+
+    fn keywords(&self) -> &[&'static str] {
+        &["sequenceDiagram"]
+    }
+
+    fn parse(&self, src: &str) -> Result<SequenceDiagram, String> {
+        parser::parse(src.to_string()).map_err(|e| format!("Error: {e}"))
+    }
+
+    fn layout(&self, ast: &SequenceDiagram, options: &Options) -> SequenceLayout {
+        render::layout(ast, &self.config(options))
+    }
+
+    fn to_svg(&self, layout: &SequenceLayout) -> SVG {
+        layout.to_svg()
+    }
+
+    fn format(&self, src: &str) -> Result<String, String> {
+        format::format(src).map_err(|e| format!("Error: {e:?}"))
+    }
+}
+
+pub fn render(src: &str) -> Result<SVG, String> {
+    Definition.render(src, &Options::default())
+}
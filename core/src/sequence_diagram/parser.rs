@@ -5,19 +5,33 @@ use combine::parser::char::{char, newline, space, string};
 use combine::parser::choice::choice;
 use combine::parser::repeat::take_until;
 use combine::{
-    many1, none_of, optional, sep_by1, skip_many, skip_many1, ParseError, Parser, Stream,
+    attempt, eof, look_ahead, many1, none_of, optional, sep_by1, skip_many, ParseError, Parser,
+    Stream,
 };
 
+use crate::parsing::{self, blank, blank1};
+use crate::sequence_diagram::BuildError;
+
 pub type ParticipantId = usize;
 pub type MessageId = usize;
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Participant {
     pub id: ParticipantId,
     pub name: String,
+    /// Shown instead of the name, from `participant A as Alice`.
+    pub alias: Option<String>,
+}
+
+impl Participant {
+    pub fn label(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Activation {
     pub participant_id: ParticipantId,
     pub from: MessageId,
@@ -26,6 +40,7 @@ pub struct Activation {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Message {
     pub id: MessageId,
     pub left: ParticipantId,
@@ -36,6 +51,7 @@ pub struct Message {
 }
 
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrowDirection {
     ToRight,
     ToLeft,
@@ -44,6 +60,7 @@ pub enum ArrowDirection {
 pub type NoteId = usize;
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VerticalNotePosition {
     First,
     AfterMessage(MessageId),
@@ -51,6 +68,7 @@ pub enum VerticalNotePosition {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HorizontalNotePosition {
     LeftOf(ParticipantId),
     RightOf(ParticipantId),
@@ -58,6 +76,7 @@ pub enum HorizontalNotePosition {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Note {
     pub id: NoteId,
     pub content: String,
@@ -66,6 +85,7 @@ pub struct Note {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SequenceDiagram {
     pub participants: Vec<Participant>,
     pub messages: Vec<Message>,
@@ -74,12 +94,14 @@ pub struct SequenceDiagram {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ActivationChange {
     Activate,
     Deactivate,
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MessageLine {
     pub from: String,
     pub arrow: Arrow,
@@ -89,163 +111,281 @@ pub struct MessageLine {
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Line {
     Empty,
     Message(MessageLine),
-    Participant(String),
-    Actor(String),
+    /// The name and the alias of a participant.
+    Participant(String, Option<String>),
+    Actor(String, Option<String>),
     Note(NotePositioning, String),
+    Activate(String),
+    Deactivate(String),
+    Header,
+    /// The text after `%%`
+    Comment(String),
+    Title(String),
+    /// The numbering options after `autonumber`, if any.
+    Autonumber(String),
+    /// The start of a block around the lines up to its `end`, with its label.
+    Block(Block, String),
+    /// The start of another section of the enclosing block, with its label.
+    Branch(Branch, String),
+    End,
+}
+
+/// Blocks only group lines in a source, they are not drawn.
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Block {
+    Loop,
+    Alt,
+    Opt,
+    Par,
+    Critical,
+    Break,
+    Rect,
+}
+
+impl Block {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Block::Loop => "loop",
+            Block::Alt => "alt",
+            Block::Opt => "opt",
+            Block::Par => "par",
+            Block::Critical => "critical",
+            Block::Break => "break",
+            Block::Rect => "rect",
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Branch {
+    /// In `alt`
+    Else,
+    /// In `par`
+    And,
+    /// In `critical`
+    Option,
+}
+
+impl Branch {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Branch::Else => "else",
+            Branch::And => "and",
+            Branch::Option => "option",
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Arrow {
     DottedNoArrow,
     SolidNoArrow,
+    DottedArrow,
+    SolidArrow,
+    DottedCross,
+    SolidCross,
+    DottedOpen,
+    SolidOpen,
 }
 
-pub fn parse(src: String) -> Result<SequenceDiagram, StringStreamError> {
-    let mut parser = lines_parser();
-    match parser.parse(format!("{src}\n").as_str()) {
-        Ok((lines, _)) => Ok(build_diagram(lines)),
-        Err(e) => Err(e),
+/// Why a source is not a sequence diagram.
+#[derive(PartialEq, Debug)]
+pub enum SourceError {
+    Syntax(StringStreamError),
+    Build(BuildError),
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceError::Syntax(e) => write!(f, "{e:?}"),
+            SourceError::Build(e) => write!(f, "{e}"),
+        }
     }
 }
 
-fn build_participants(lines: &Vec<Line>) -> Vec<Participant> {
-    let mut participant_names = vec![];
+impl std::error::Error for SourceError {}
+
+pub fn parse(src: String) -> Result<SequenceDiagram, SourceError> {
+    let lines = parse_lines(&src).map_err(SourceError::Syntax)?;
+    build_diagram(lines).map_err(SourceError::Build)
+}
+
+/// The source line by line, keeping the header, the comments and the blank
+/// lines for tools that write it back.
+pub fn parse_lines(src: &str) -> Result<Vec<Line>, StringStreamError> {
+    src.lines()
+        .map(|line| {
+            let mut parser = (line_parser(), optional(newline()), eof()).map(|(line, _, _)| line);
+            parser
+                .parse(format!("{line}\n").as_str())
+                .map(|(line, _)| line)
+        })
+        .collect()
+}
+
+/// The participants in the order they are declared, explicitly or by the
+/// first message or note naming them. The last alias given to a participant
+/// is the one it shows, wherever it is declared.
+pub(crate) fn build_participants(lines: &[Line]) -> Vec<Participant> {
     let mut participants: Vec<Participant> = vec![];
+    let mut declare = |name: &String, alias: &Option<String>| match participants
+        .iter_mut()
+        .find(|p| &p.name == name)
+    {
+        Some(participant) => {
+            if alias.is_some() {
+                participant.alias = alias.clone();
+            }
+        }
+        None => participants.push(Participant {
+            id: participants.len(),
+            name: name.clone(),
+            alias: alias.clone(),
+        }),
+    };
     for line in lines {
         match line {
             Line::Message(MessageLine { from, to, .. }) => {
-                if !participant_names.contains(from) {
-                    let p: Participant = Participant {
-                        name: from.clone(),
-                        id: participants.len(),
-                    };
-                    participant_names.push(from.clone());
-                    participants.push(p);
-                }
-                if !participant_names.contains(to) {
-                    let p: Participant = Participant {
-                        name: to.clone(),
-                        id: participants.len(),
-                    };
-                    participant_names.push(to.clone());
-                    participants.push(p);
-                }
+                declare(from, &None);
+                declare(to, &None);
             }
-            Line::Participant(name) => {
-                if !participant_names.contains(name) {
-                    let p: Participant = Participant {
-                        name: name.clone(),
-                        id: participants.len(),
-                    };
-                    participant_names.push(name.clone());
-                    participants.push(p);
-                }
+            Line::Participant(name, alias) | Line::Actor(name, alias) => declare(name, alias),
+            Line::Note(NotePositioning::LeftOf(name) | NotePositioning::RightOf(name), _) => {
+                declare(name, &None)
             }
-            Line::Actor(name) => {
-                if !participant_names.contains(name) {
-                    let p: Participant = Participant {
-                        name: name.clone(),
-                        id: participants.len(),
-                    };
-                    participant_names.push(name.clone());
-                    participants.push(p);
+            Line::Note(NotePositioning::Over(names), _) => {
+                for name in names {
+                    declare(name, &None);
                 }
             }
-
             _ => {}
         }
     }
     participants
 }
 
-fn build_diagram(lines: Vec<Line>) -> SequenceDiagram {
+fn id_of(participants: &[Participant], name: &str) -> Result<ParticipantId, BuildError> {
+    participants
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.id)
+        .ok_or_else(|| BuildError::UnknownParticipant(name.to_string()))
+}
+
+type OpenActivations = HashMap<ParticipantId, Vec<(u16, MessageId)>>;
+
+fn activate(
+    open_activations: &mut OpenActivations,
+    participant_id: ParticipantId,
+    message_id: MessageId,
+) {
+    let open = open_activations.entry(participant_id).or_default();
+    let (last_level, _) = open.last().unwrap_or(&(0, 0));
+    open.push((last_level + 1, message_id));
+}
+
+fn deactivate(
+    open_activations: &mut OpenActivations,
+    activations: &mut Vec<Activation>,
+    participant: &Participant,
+    message_id: MessageId,
+) -> Result<(), BuildError> {
+    let (level, from) = open_activations
+        .get_mut(&participant.id)
+        .and_then(|open| open.pop())
+        .ok_or_else(|| BuildError::NotActivated(participant.name.clone()))?;
+    activations.push(Activation {
+        participant_id: participant.id,
+        from,
+        to: message_id,
+        level,
+    });
+    Ok(())
+}
+
+/// The diagram of the lines of a source, or the first deactivation without a
+/// matching activation.
+pub(crate) fn build_diagram(lines: Vec<Line>) -> Result<SequenceDiagram, BuildError> {
     let participants = build_participants(&lines);
     let mut activations: Vec<Activation> = vec![];
     // stores the level and start of the last activation of each participant
-    let mut open_activations: HashMap<ParticipantId, Vec<(u16, MessageId)>> = HashMap::new();
-    let mut messages = vec![];
+    let mut open_activations: OpenActivations = HashMap::new();
+    let mut messages: Vec<Message> = vec![];
     for (line_nr, line) in lines.iter().enumerate() {
-        if let Line::Message(line) = line {
-            let from_idx = participants.iter().find(|&p| p.name == line.from);
-            let to_idx = participants.iter().find(|&p| p.name == line.to);
-            match (from_idx, to_idx) {
-                (Some(from), Some(to)) => {
-                    let msg = if from.id > to.id {
-                        Message {
-                            id: line_nr,
-                            left: to.id,
-                            right: from.id,
-                            msg: line.msg.clone(),
-                            arrow: line.arrow.clone(),
-                            direction: ArrowDirection::ToLeft,
-                        }
-                    } else {
-                        Message {
-                            id: line_nr,
-                            left: from.id,
-                            right: to.id,
-                            msg: line.msg.clone(),
-                            arrow: line.arrow.clone(),
-                            direction: ArrowDirection::ToRight,
-                        }
-                    };
-                    if let Some(activation_change) = &line.activation {
-                        match activation_change {
-                            ActivationChange::Activate => {
-                                let open: &mut Vec<(u16, MessageId)> =
-                                    open_activations.entry(to.id).or_insert(vec![]);
-                                let (last_level, _) = open.last().unwrap_or(&(0, 0));
-                                open.push((last_level + 1, line_nr));
-                            }
-                            ActivationChange::Deactivate => {
-                                let open: &mut Vec<(u16, MessageId)> = open_activations
-                                    .get_mut(&from.id)
-                                    .expect("Wasn't activated");
-                                let (level, message_id) =
-                                    open.last().expect("Still wasn't activated");
-
-                                let activation = Activation {
-                                    participant_id: from.id,
-                                    from: *message_id,
-                                    to: line_nr,
-                                    level: *level,
-                                };
-                                open.pop();
-                                activations.push(activation);
-                            }
-                        }
+        // `activate` and `deactivate` lines act on the message before them
+        let last_message = messages.last().map(|m| m.id);
+        match line {
+            Line::Message(line) => {
+                let (from, to) = (
+                    id_of(&participants, &line.from)?,
+                    id_of(&participants, &line.to)?,
+                );
+                let (left, right, direction) = if from > to {
+                    (to, from, ArrowDirection::ToLeft)
+                } else {
+                    (from, to, ArrowDirection::ToRight)
+                };
+                messages.push(Message {
+                    id: line_nr,
+                    left,
+                    right,
+                    msg: line.msg.clone(),
+                    arrow: line.arrow.clone(),
+                    direction,
+                });
+                match &line.activation {
+                    Some(ActivationChange::Activate) => {
+                        activate(&mut open_activations, to, line_nr)
                     }
-                    messages.push(msg);
+                    Some(ActivationChange::Deactivate) => deactivate(
+                        &mut open_activations,
+                        &mut activations,
+                        &participants[from],
+                        line_nr,
+                    )?,
+                    None => {}
+                }
+            }
+            Line::Activate(name) => {
+                let participant = id_of(&participants, name)?;
+                if let Some(message) = last_message {
+                    activate(&mut open_activations, participant, message);
                 }
-                _ => {
-                    println!("NO {:?}, {:?}", from_idx, line.to);
+            }
+            Line::Deactivate(name) => {
+                let participant = &participants[id_of(&participants, name)?];
+                if let Some(message) = last_message {
+                    deactivate(
+                        &mut open_activations,
+                        &mut activations,
+                        participant,
+                        message,
+                    )?;
                 }
             }
+            _ => {}
         }
     }
     // so lowest level activations come first
     activations.reverse();
-    let notes = build_notes(&lines, &participants);
-    SequenceDiagram {
+    let notes = build_notes(&lines, &participants)?;
+    Ok(SequenceDiagram {
         participants,
         messages,
         activations,
         notes,
-    }
+    })
 }
 
-fn build_notes(lines: &Vec<Line>, participants: &Vec<Participant>) -> Vec<Note> {
-    let id_of = |participant_name| {
-        participants
-            .iter()
-            .find(|p| &p.name == participant_name)
-            .unwrap()
-            .id
-    };
-
+fn build_notes(lines: &[Line], participants: &[Participant]) -> Result<Vec<Note>, BuildError> {
     let mut vertical_position = VerticalNotePosition::First;
 
     let mut notes = vec![];
@@ -255,37 +395,32 @@ fn build_notes(lines: &Vec<Line>, participants: &Vec<Participant>) -> Vec<Note>
                 vertical_position = VerticalNotePosition::AfterMessage(line_nr);
             }
             Line::Note(pos, content) => {
-                let content = content.clone();
-                let note = match pos {
-                    NotePositioning::LeftOf(name) => Note {
-                        id: line_nr,
-                        content,
-                        horizontal_position: HorizontalNotePosition::LeftOf(id_of(name)),
-                        vertical_position,
-                    },
-                    NotePositioning::RightOf(name) => Note {
-                        id: line_nr,
-                        content,
-                        horizontal_position: HorizontalNotePosition::RightOf(id_of(name)),
-                        vertical_position,
-                    },
-                    NotePositioning::Over(names) => {
-                        let participant_ids = names.iter().map(|name| id_of(name)).collect();
-                        Note {
-                            id: line_nr,
-                            content,
-                            horizontal_position: HorizontalNotePosition::Over(participant_ids),
-                            vertical_position,
-                        }
+                let horizontal_position = match pos {
+                    NotePositioning::LeftOf(name) => {
+                        HorizontalNotePosition::LeftOf(id_of(participants, name)?)
                     }
+                    NotePositioning::RightOf(name) => {
+                        HorizontalNotePosition::RightOf(id_of(participants, name)?)
+                    }
+                    NotePositioning::Over(names) => HorizontalNotePosition::Over(
+                        names
+                            .iter()
+                            .map(|name| id_of(participants, name))
+                            .collect::<Result<_, _>>()?,
+                    ),
                 };
-                vertical_position = VerticalNotePosition::AfterNote(note.id);
-                notes.push(note);
+                notes.push(Note {
+                    id: line_nr,
+                    content: content.clone(),
+                    vertical_position,
+                    horizontal_position,
+                });
+                vertical_position = VerticalNotePosition::AfterNote(line_nr);
             }
             _ => {}
         }
     }
-    notes
+    Ok(notes)
 }
 
 fn arrow_parser<Input>() -> impl Parser<Input, Output = Arrow>
@@ -293,10 +428,18 @@ where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    let dotted_no_arrow_parser = string("-->").map(|_| Arrow::DottedNoArrow);
-    let solid_no_arrow_parser = string("->").map(|_| Arrow::SolidNoArrow);
-
-    choice((solid_no_arrow_parser, dotted_no_arrow_parser))
+    // longest first, as every arrow starts with `-`
+    let arrow = |s: &'static str, arrow: Arrow| attempt(string(s)).map(move |_| arrow.clone());
+    choice((
+        arrow("-->>", Arrow::DottedArrow),
+        arrow("--x", Arrow::DottedCross),
+        arrow("--)", Arrow::DottedOpen),
+        arrow("-->", Arrow::DottedNoArrow),
+        arrow("->>", Arrow::SolidArrow),
+        arrow("-x", Arrow::SolidCross),
+        arrow("-)", Arrow::SolidOpen),
+        arrow("->", Arrow::SolidNoArrow),
+    ))
 }
 
 fn activation_parser<Input>() -> impl Parser<Input, Output = Option<ActivationChange>>
@@ -310,12 +453,30 @@ where
     optional(choice((deactivate, activate)))
 }
 
+/// A keyword and the blanks after it, without consuming anything when the
+/// line only starts like the keyword, e.g. a message from `actress`.
+fn keyword<Input>(word: &'static str) -> impl Parser<Input, Output = ()>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((string(word), blank1())).map(|_| ())
+}
+
+fn rest_of_line<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    take_until(char('\n')).map(|s: String| s.trim().to_string())
+}
+
 fn sender_name_parser<Input>() -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    take_until(char('-'))
+    take_until(char('-')).map(|s: String| s.trim().to_string())
 }
 
 fn receiver_name_parser<Input>() -> impl Parser<Input, Output = String>
@@ -323,7 +484,7 @@ where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (skip_many(space()), take_until(char(':'))).map(|(_, s)| s)
+    (skip_many(space()), take_until(char(':'))).map(|(_, s): (_, String)| s.trim().to_string())
 }
 
 fn empty_line_parser<Input>() -> impl Parser<Input, Output = Line>
@@ -334,17 +495,40 @@ where
     (newline()).map(|_| Line::Empty)
 }
 
+fn header_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((string("sequenceDiagram"), blank(), look_ahead(newline()))).map(|_| Line::Header)
+}
+
+fn comment_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (string("%%"), take_until(char('\n')))
+        .map(|(_, comment): (_, String)| Line::Comment(comment.trim_end().to_string()))
+}
+
+/// A participant name and the alias after ` as `, if any.
+fn declaration(text: String) -> (String, Option<String>) {
+    match text.split_once(" as ") {
+        Some((name, alias)) => (name.trim().to_string(), Some(alias.trim().to_string())),
+        None => (text, None),
+    }
+}
+
 fn participant_line_parser<Input>() -> impl Parser<Input, Output = Line>
 where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (
-        string("participant"),
-        skip_many(char(' ')),
-        take_until::<String, Input, combine::parser::token::Token<Input>>(char('\n')),
-    )
-        .map(|(_, _, name)| Line::Participant(name.trim().to_string()))
+    (keyword("participant"), rest_of_line()).map(|(_, text)| {
+        let (name, alias) = declaration(text);
+        Line::Participant(name, alias)
+    })
 }
 
 fn actor_line_parser<Input>() -> impl Parser<Input, Output = Line>
@@ -352,15 +536,25 @@ where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (
-        string("actor"),
-        skip_many(char(' ')),
-        take_until::<String, Input, combine::parser::token::Token<Input>>(char('\n')),
-    )
-        .map(|(_, _, name)| Line::Actor(name.trim().to_string()))
+    (keyword("actor"), rest_of_line()).map(|(_, text)| {
+        let (name, alias) = declaration(text);
+        Line::Actor(name, alias)
+    })
+}
+
+fn activation_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (keyword("activate"), rest_of_line()).map(|(_, name)| Line::Activate(name)),
+        (keyword("deactivate"), rest_of_line()).map(|(_, name)| Line::Deactivate(name)),
+    ))
 }
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NotePositioning {
     LeftOf(String),
     Over(Vec<String>),
@@ -372,12 +566,8 @@ where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (
-        string("left of"),
-        skip_many1(char(' ')),
-        take_until(char(':')),
-    )
-        .map(|(_, _, name)| NotePositioning::LeftOf(name))
+    (string("left of"), blank1(), take_until(char(':')))
+        .map(|(_, _, name): (_, _, String)| NotePositioning::LeftOf(name.trim().to_string()))
 }
 
 fn right_of_parser<Input>() -> impl Parser<Input, Output = NotePositioning>
@@ -385,12 +575,8 @@ where
     Input: Stream<Token = char>,
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
-    (
-        string("right of"),
-        skip_many1(char(' ')),
-        take_until(char(':')),
-    )
-        .map(|(_, _, name)| NotePositioning::RightOf(name))
+    (string("right of"), blank1(), take_until(char(':')))
+        .map(|(_, _, name): (_, _, String)| NotePositioning::RightOf(name.trim().to_string()))
 }
 
 fn over_parser<Input>() -> impl Parser<Input, Output = NotePositioning>
@@ -402,7 +588,7 @@ where
     let separator = char(',');
     (
         string("over"),
-        skip_many1(char(' ')),
+        blank1(),
         sep_by1(participant_name, separator),
     )
         .map(|(_, _, names): (&str, (), Vec<String>)| {
@@ -424,14 +610,62 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     (
-        string("Note"),
-        skip_many1(char(' ')),
+        choice((keyword("Note"), keyword("note"))),
         note_position_parser(),
         char(':'),
-        skip_many(char(' ')),
-        take_until(char('\n')),
+        rest_of_line(),
+    )
+        .map(|(_, note_position, _, msg)| Line::Note(note_position, msg))
+}
+
+/// `title`, with or without a colon after it.
+fn title_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        parsing::keyword("title"),
+        optional(char(':')),
+        rest_of_line(),
     )
-        .map(|(_, _, note_position, _, _, msg)| Line::Note(note_position, msg))
+        .map(|(_, _, title)| Line::Title(title))
+}
+
+fn autonumber_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (parsing::keyword("autonumber"), rest_of_line()).map(|(_, options)| Line::Autonumber(options))
+}
+
+fn block_line_parser<Input>() -> impl Parser<Input, Output = Line>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let block = |block: Block| {
+        (parsing::keyword(block.keyword()), rest_of_line())
+            .map(move |(_, label)| Line::Block(block, label))
+    };
+    let branch = |branch: Branch| {
+        (parsing::keyword(branch.keyword()), rest_of_line())
+            .map(move |(_, label)| Line::Branch(branch, label))
+    };
+    choice((
+        block(Block::Loop),
+        block(Block::Alt),
+        block(Block::Opt),
+        block(Block::Par),
+        block(Block::Critical),
+        block(Block::Break),
+        block(Block::Rect),
+        branch(Branch::Else),
+        branch(Branch::And),
+        branch(Branch::Option),
+        (parsing::keyword("end"), rest_of_line()).map(|_| Line::End),
+    ))
 }
 
 fn msg_line_parser<Input>() -> impl Parser<Input, Output = Line>
@@ -440,7 +674,6 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     let colon = char(':');
-    let until_newline = take_until(char('\n'));
     let eol = char('\n');
 
     (
@@ -449,11 +682,10 @@ where
         activation_parser(),
         receiver_name_parser(),
         colon,
-        skip_many(char(' ')),
-        until_newline,
+        rest_of_line(),
         eol,
     )
-        .map(|(from, arrow, activation, to, _, _, msg, _)| {
+        .map(|(from, arrow, activation, to, _, msg, _)| {
             Line::Message(MessageLine {
                 from,
                 arrow,
@@ -470,30 +702,29 @@ where
     Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
 {
     (
-        skip_many(char(' ')),
+        blank(),
         choice((
             empty_line_parser(),
+            header_line_parser(),
+            comment_line_parser(),
             note_line_parser(),
             participant_line_parser(),
             actor_line_parser(),
+            activation_line_parser(),
+            title_line_parser(),
+            autonumber_line_parser(),
+            block_line_parser(),
             msg_line_parser(),
         )),
     )
         .map(|(_, l)| l)
 }
 
-fn lines_parser<Input>() -> impl Parser<Input, Output = Vec<Line>>
-where
-    Input: Stream<Token = char>,
-    // Necessary due to rust-lang/rust#24159
-    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
-{
-    many1(line_parser())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::diagram::Diagram;
+    use crate::sequence_diagram::Definition;
 
     #[test]
     fn test_single_message() {
@@ -502,10 +733,12 @@ mod tests {
                 Participant {
                     id: 0,
                     name: "Alice".to_string(),
+                    alias: None,
                 },
                 Participant {
                     id: 1,
                     name: "Bob".to_string(),
+                    alias: None,
                 },
             ],
             messages: vec![Message {
@@ -517,6 +750,7 @@ mod tests {
                 direction: ArrowDirection::ToRight,
             }],
             activations: vec![],
+            notes: vec![],
         };
         let input = "Alice->Bob: hello\n";
         assert_eq!(parse(input.to_string()), Ok(expected));
@@ -533,7 +767,7 @@ mod tests {
     #[test]
     fn test_participant_line_parser() {
         let expected = |n: &str| -> Result<(Line, &str), StringStreamError> {
-            Ok((Line::Participant(n.to_string()), "\n"))
+            Ok((Line::Participant(n.to_string(), None), "\n"))
         };
         assert_eq!(
             expected("Alice"),
@@ -658,10 +892,12 @@ mod tests {
                 Participant {
                     id: 0,
                     name: "Alice".to_string(),
+                    alias: None,
                 },
                 Participant {
                     id: 1,
                     name: "Bob".to_string(),
+                    alias: None,
                 },
             ],
             messages: vec![Message {
@@ -673,6 +909,7 @@ mod tests {
                 direction: ArrowDirection::ToRight,
             }],
             activations: vec![],
+            notes: vec![],
         };
         let input = " Alice->Bob:hello\n";
         assert_eq!(parse(input.to_string()), Ok(expected));
@@ -690,15 +927,17 @@ mod tests {
                 Participant {
                     id: 0,
                     name: "Alice".to_string(),
+                    alias: None,
                 },
                 Participant {
                     id: 1,
                     name: "Bob".to_string(),
+                    alias: None,
                 },
             ],
             messages: vec![
                 Message {
-                    id: 0,
+                    id: 1,
                     left: 0,
                     right: 1,
                     msg: "How are you?".to_string(),
@@ -706,7 +945,7 @@ mod tests {
                     direction: ArrowDirection::ToRight,
                 },
                 Message {
-                    id: 1,
+                    id: 2,
                     left: 0,
                     right: 1,
                     msg: "I'm fine!".to_string(),
@@ -715,7 +954,187 @@ mod tests {
                 },
             ],
             activations: vec![],
+            notes: vec![],
         };
         assert_eq!(parse(input.to_string()), Ok(expected));
     }
+
+    #[test]
+    fn test_mermaid_header_and_whitespace() {
+        let input = "sequenceDiagram\r\n\tAlice->>+John: Hello \r\n\tJohn-->>-Alice: Hi\t";
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(
+            vec!["Alice", "John"],
+            diagram
+                .participants
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("Hello", Arrow::SolidArrow), ("Hi", Arrow::DottedArrow)],
+            diagram
+                .messages
+                .iter()
+                .map(|m| (m.msg.as_str(), m.arrow.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, diagram.activations.len());
+    }
+
+    #[test]
+    fn test_mermaid_corpus() {
+        let corpus =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../resources/corpus/sequence");
+        let mut files = 0;
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let src = std::fs::read_to_string(&path).unwrap();
+            let diagram = parse(src).unwrap_or_else(|e| panic!("{path:?}: {e:?}"));
+            // a misread arrow ends up in a participant name
+            for participant in &diagram.participants {
+                assert!(
+                    !participant.name.contains(['-', '>', ')', ' ']),
+                    "{path:?}: {}",
+                    participant.name
+                );
+            }
+            files += 1;
+        }
+        assert!(files > 0);
+    }
+
+    #[test]
+    fn test_parse_lines_keeps_trivia() {
+        assert_eq!(
+            Ok(vec![
+                Line::Header,
+                Line::Comment(" a comment".to_string()),
+                Line::Empty,
+                Line::Activate("John".to_string()),
+            ]),
+            parse_lines("sequenceDiagram\n  %% a comment\n\t\n  activate John")
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(
+            Ok((
+                Line::Actor("J".to_string(), Some("John Smith".to_string())),
+                "\n"
+            )),
+            line_parser().parse("actor J as John Smith\n")
+        );
+        let diagram =
+            parse("participant A as Alice\nA->>B: hi\nparticipant B as Bob".to_string()).unwrap();
+        assert_eq!(
+            vec![("A", "Alice"), ("B", "Bob")],
+            diagram
+                .participants
+                .iter()
+                .map(|p| (p.name.as_str(), p.label()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_note_participants() {
+        let diagram =
+            parse("sequenceDiagram\nNote over A,B: x\nNote left of C: y\nB->>D: z".to_string())
+                .unwrap();
+        assert_eq!(
+            vec!["A", "B", "C", "D"],
+            diagram
+                .participants
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            HorizontalNotePosition::Over(vec![0, 1]),
+            diagram.notes[0].horizontal_position
+        );
+    }
+
+    #[test]
+    fn test_unmatched_deactivation() {
+        let not_activated = SourceError::Build(BuildError::NotActivated("B".to_string()));
+        assert_eq!(
+            Err(not_activated),
+            parse("A->>B: x\ndeactivate B".to_string())
+        );
+        assert_eq!(
+            Err(SourceError::Build(BuildError::NotActivated(
+                "B".to_string()
+            ))),
+            parse("A->>+B: x\nB->>-A: y\nB->>-A: z".to_string())
+        );
+        assert_eq!(
+            Err(SourceError::Build(BuildError::UnknownParticipant(
+                "C".to_string()
+            ))),
+            parse("A->>B: x\nactivate C".to_string())
+        );
+        assert_eq!(
+            Err("Error: B deactivated while not active".to_string()),
+            Definition.parse("A->>B: x\ndeactivate B").map(|_| ())
+        );
+        assert_eq!(
+            Err("Error: UnexpectedParse".to_string()),
+            Definition.parse("A->>B x").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let src = "sequenceDiagram
+autonumber
+title: Checkout
+loop Every minute
+  alt is sick
+    note right of B: rests
+  else is well
+    A->>B: ping
+  end
+end";
+        assert_eq!(
+            Ok(vec![
+                Line::Header,
+                Line::Autonumber(String::new()),
+                Line::Title("Checkout".to_string()),
+                Line::Block(Block::Loop, "Every minute".to_string()),
+                Line::Block(Block::Alt, "is sick".to_string()),
+                Line::Note(
+                    NotePositioning::RightOf("B".to_string()),
+                    "rests".to_string()
+                ),
+                Line::Branch(Branch::Else, "is well".to_string()),
+                Line::Message(MessageLine {
+                    from: "A".to_string(),
+                    arrow: Arrow::SolidArrow,
+                    to: "B".to_string(),
+                    msg: "ping".to_string(),
+                    activation: None,
+                }),
+                Line::End,
+                Line::End,
+            ]),
+            parse_lines(src)
+        );
+        let diagram = parse(src.to_string()).unwrap();
+        assert_eq!(2, diagram.participants.len());
+        assert_eq!(1, diagram.messages.len());
+        // a message from a participant that only starts like a keyword
+        assert_eq!(
+            Ok(vec![Line::Message(MessageLine {
+                from: "loops".to_string(),
+                arrow: Arrow::SolidArrow,
+                to: "ends".to_string(),
+                msg: "x".to_string(),
+                activation: None,
+            })]),
+            parse_lines("loops->>ends: x")
+        );
+    }
 }
@@ -1,195 +1,150 @@
 use itertools::Itertools;
-use svg::node::element::{Definitions, Group, Line, Marker, Polygon, Rectangle, Style, Text, SVG};
-use svg::Document;
+use svg::node::element::SVG;
 
-use cassowary::strength::{REQUIRED, STRONG};
+use cassowary::strength::{MEDIUM, REQUIRED};
 use cassowary::WeightedRelation::*;
 
-use crate::layout::{Layout,BlockId, Orientation};
+use crate::layout::{Block, BlockId, Layout, Orientation, SolvedLayout, TextDirection};
+use crate::scene::{Item, RenderBackend, Scene, SvgBackend};
 use crate::sequence_diagram::*;
+use crate::theme::*;
 
-struct ParticipantMarker {
-    lines: Vec<String>,
-    block_id: BlockId,
+pub(super) struct ParticipantMarker {
+    pub(super) participant_id: ParticipantId,
+    pub(super) lines: Vec<String>,
+    pub(super) block_id: BlockId,
 }
 
-struct ParticipantLine {
-    block: BlockId,
-    participant_id: ParticipantId,
+pub(super) struct ParticipantLine {
+    pub(super) block: BlockId,
+    pub(super) participant_id: ParticipantId,
 }
 
-struct MsgArrow {
-    msg_id: MessageId,
-    label: Vec<String>,
-    direction: ArrowDirection,
-    left: ParticipantId,
-    right: ParticipantId,
-    block: BlockId,
+pub(super) struct MsgArrow {
+    pub(super) msg_id: MessageId,
+    pub(super) label: Vec<String>,
+    pub(super) direction: ArrowDirection,
+    pub(super) left: ParticipantId,
+    pub(super) right: ParticipantId,
+    pub(super) block: BlockId,
 }
 
-struct ActivationMarker {
-    block: BlockId,
+pub(super) struct ActivationMarker {
+    /// index into the activations of the diagram
+    pub(super) id: usize,
+    pub(super) block: BlockId,
+}
+
+/// The wrapped lines of the text of `block`, `baseline(i)` being the baseline
+/// of the `i`th line relative to the top of the block.
+fn text(
+    items: &mut Vec<Item>,
+    layout: &Layout,
+    config: &SvgConfig,
+    lines: &[String],
+    block: &Block,
+    baseline: impl Fn(usize) -> f64,
+) {
+    let x = match block.direction {
+        TextDirection::LeftToRight => block.position.x + config.padding,
+        TextDirection::RightToLeft => block.position.x + block.width - config.padding,
+    };
+    // the size the blocks were measured at
+    let font_size = config.font_size * config.font_scale_factor;
+    for (i, line) in lines.iter().enumerate() {
+        items.push(Item::Text {
+            content: line.clone(),
+            position: (x, block.position.y + baseline(i)),
+            width: layout.text_width(line, font_size),
+            font_size,
+            direction: block.direction,
+        });
+    }
+}
+
+fn rect(block: &Block, fill: &str, stroke: &str, corner_radius: f64) -> Item {
+    Item::Rect {
+        x: block.position.x,
+        y: block.position.y,
+        width: block.width,
+        height: block.height,
+        fill: fill.to_string(),
+        stroke: stroke.to_string(),
+        corner_radius,
+    }
 }
 
 impl ParticipantMarker {
-    fn to_svg(&self, layout: &Layout, config: &SvgConfig) -> Group {
+    fn draw(&self, items: &mut Vec<Item>, layout: &Layout, config: &SvgConfig) {
         let block = layout.b(self.block_id).solved();
-        let mut group = Group::new().set(
-            "transform",
-            format!("translate({}, {})", block.position.x, block.position.y),
-        );
-
-        let rect = Rectangle::new()
-            .set("x", 0)
-            .set("y", 0)
-            .set("width", block.width)
-            .set("height", block.height)
-            .set("fill", "transparent")
-            .set("stroke", "black")
-            .set("rx", config.corner_radius)
-            .set("stroke-width", 1);
-        group = group.add(rect);
-        for (i, line) in self.lines.iter().enumerate() {
-            let t = Text::new()
-                .set("x", config.padding)
-                .set("y", config.padding + ((i + 1) as f64) * block.line_height)
-                .set("fill", "blue")
-                .set("font-family", "monospace")
-                .set("font-size", config.font_size)
-                .add(svg::node::Text::new(line));
-            group = group.add(t);
-        }
-        group
+        items.push(rect(block, "transparent", LINE_COLOR, config.corner_radius));
+        text(items, layout, config, &self.lines, block, |i| {
+            config.padding + ((i + 1) as f64) * block.line_height
+        });
     }
 }
 
 impl ParticipantLine {
-    fn to_svg(&self, layout: &Layout, _config: &SvgConfig) -> Group {
+    fn draw(&self, items: &mut Vec<Item>, layout: &Layout, _config: &SvgConfig) {
         let block = layout.b(self.block).solved();
-        let mut group = Group::new().set(
-            "transform",
-            format!("translate({}, {})", block.position.x, block.position.y),
-        );
-
-        let rect = Line::new()
-            .set("x1", 0)
-            .set("y1", 0)
-            .set("x2", 0)
-            .set("y2", block.height)
-            .set("stroke", "black")
-            .set("stroke-width", 1);
-        group = group.add(rect);
-        group
+        let (x, y) = (block.position.x, block.position.y);
+        items.push(Item::Line {
+            from: (x, y),
+            to: (x, y + block.height),
+        });
     }
 }
 
-const ARROW_TIP_LENGTH: f64 = 10.0;
-
 impl MsgArrow {
-    fn to_svg(&self, layout: &Layout, config: &SvgConfig) -> Group {
+    fn draw(&self, items: &mut Vec<Item>, layout: &Layout, config: &SvgConfig) {
         let block = layout.b(self.block).solved();
-        let mut group = Group::new().set(
-            "transform",
-            format!("translate({}, {})", block.position.x, block.position.y),
-        );
-
-        let mut rect = Line::new()
-            .set("x1", 0)
-            .set("y1", block.height)
-            .set("y2", block.height)
-            .set("stroke", "black")
-            .set("stroke-width", 1);
-
-        rect = match self.direction {
-            ArrowDirection::ToRight => rect
-                .set("x2", block.width - ARROW_TIP_LENGTH)
-                .set("marker-end", "url(#end-arrow)"),
-            ArrowDirection::ToLeft => rect
-                .set("x2", block.width)
-                .set("marker-start", "url(#start-arrow)"),
-        };
-        group = group.add(rect);
+        let y = block.position.y + block.height;
+        let (left, right) = (block.position.x, block.position.x + block.width);
+        items.push(match self.direction {
+            ArrowDirection::ToRight => Item::Arrow {
+                from: (left, y),
+                to: (right, y),
+            },
+            ArrowDirection::ToLeft => Item::Arrow {
+                from: (right, y),
+                to: (left, y),
+            },
+        });
         let text_height = (self.label.len() as f64) * block.line_height;
-        for (i, line) in self.label.iter().enumerate() {
-            let t = Text::new()
-                .set("x", config.padding)
-                .set(
-                    "y",
-                    (config.padding + text_height) - (i as f64) * block.line_height,
-                )
-                .set("fill", "blue")
-                .set("font-family", "monospace")
-                .set("font-size", config.font_size)
-                .add(svg::node::Text::new(line));
-
-            group = group.add(t);
-        }
-        group
+        text(items, layout, config, &self.label, block, |i| {
+            (config.padding + text_height) - (i as f64) * block.line_height
+        });
     }
 }
 
 impl ActivationMarker {
-    fn to_svg(&self, layout: &Layout, _config: &SvgConfig) -> Group {
+    fn draw(&self, items: &mut Vec<Item>, layout: &Layout, _config: &SvgConfig) {
         let block = layout.b(self.block).solved();
-        let mut group = Group::new().set(
-            "transform",
-            format!("translate({}, {})", block.position.x, block.position.y),
-        );
-
-        let rect = Rectangle::new()
-            .set("x", 0)
-            .set("y", 0)
-            .set("width", block.width)
-            .set("height", block.height)
-            .set("fill", "gray")
-            .set("stroke", "#333")
-            .set("stroke-width", 1);
-        group = group.add(rect);
-        group
+        items.push(rect(block, ACTIVATION_FILL, "#333", 0.0));
     }
 }
 
-struct Note {
-    id: NoteId,
-    block_id: BlockId,
-    lines: Vec<String>,
+pub(super) struct Note {
+    pub(super) id: NoteId,
+    pub(super) block_id: BlockId,
+    pub(super) lines: Vec<String>,
 }
 
 impl Note {
-    fn to_svg(&self, layout: &Layout, config: &SvgConfig) -> Group {
+    fn draw(&self, items: &mut Vec<Item>, layout: &Layout, config: &SvgConfig) {
         let block = layout.b(self.block_id).solved();
-        let mut group = Group::new().set(
-            "transform",
-            format!("translate({}, {})", block.position.x, block.position.y),
-        );
-
-        let rect = Rectangle::new()
-            .set("x", 0)
-            .set("y", 0)
-            .set("width", block.width)
-            .set("height", block.height)
-            .set("fill", "#DDD")
-            .set("stroke", "black")
-            .set("rx", config.corner_radius)
-            .set("stroke-width", 1);
-        group = group.add(rect);
-        for (i, line) in self.lines.iter().enumerate() {
-            let t = Text::new()
-                .set("x", config.padding)
-                .set("y", config.padding + ((i + 1) as f64) * block.line_height)
-                .set("fill", "blue")
-                .set("font-family", "monospace")
-                .set("font-size", config.font_size)
-                .add(svg::node::Text::new(line));
-            group = group.add(t);
-        }
-        group
+        items.push(rect(block, NOTE_FILL, LINE_COLOR, config.corner_radius));
+        text(items, layout, config, &self.lines, block, |i| {
+            config.padding + ((i + 1) as f64) * block.line_height
+        });
     }
 }
 
+#[derive(Clone)]
 pub struct SvgConfig {
     pub max_participant_head_length: usize,
     pub max_msg_label_length: usize,
+    pub max_note_length: usize,
     pub msg_gutter: f64,
     pub font_size: f64,
     pub padding: f64,
@@ -203,7 +158,23 @@ enum ArrowSide {
     Right(BlockId),
 }
 
+/// A sequence diagram with every element placed, ready to be drawn.
+pub struct SequenceLayout {
+    pub(super) layout: Layout<'static>,
+    pub(super) config: SvgConfig,
+    pub(super) heads: Vec<ParticipantMarker>,
+    pub(super) footers: Vec<ParticipantMarker>,
+    pub(super) participant_lines: Vec<ParticipantLine>,
+    pub(super) activation_markers: Vec<ActivationMarker>,
+    pub(super) arrows: Vec<MsgArrow>,
+    pub(super) notes: Vec<Note>,
+}
+
 pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
+    layout(diagram, config).to_svg()
+}
+
+pub fn layout(diagram: &SequenceDiagram, config: &SvgConfig) -> SequenceLayout {
     let mut layout = Layout::new();
     let mut arrows = vec![];
     for m in &diagram.messages {
@@ -225,10 +196,37 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
         arrows.push(msg_arrow);
     }
 
+    let mut notes = vec![];
+    for note in &diagram.notes {
+        let (block_id, lines) = layout.add_text_block(
+            &note.content,
+            config.max_note_length,
+            config.padding,
+            config.font_size * config.font_scale_factor,
+        );
+        notes.push((
+            Note {
+                id: note.id,
+                block_id,
+                lines,
+            },
+            note,
+        ));
+    }
+
+    // messages and notes are identified by their source line, so sorting by id
+    // stacks them in the order they were written
+    let rows: Vec<(usize, BlockId)> = arrows
+        .iter()
+        .map(|a| (a.msg_id, a.block))
+        .chain(notes.iter().map(|(n, _)| (n.id, n.block_id)))
+        .sorted()
+        .collect();
+
     layout.distribute(
         Orientation::Vertical,
         config.msg_gutter,
-        arrows.iter().map(|a| &a.block),
+        rows.iter().map(|(_, block)| block),
     );
 
     for participant in &diagram.participants {
@@ -280,42 +278,39 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
     }
 
     let mut participant_lines: Vec<ParticipantLine> = vec![];
-    if let (Some(first_arrow), Some(last_arrow)) = (arrows.first(), arrows.last()) {
-        let mut last_block = None;
-        for participant in &diagram.participants {
-            let block_id = layout.add_block();
+    let mut last_block = None;
+    for participant in &diagram.participants {
+        let block_id = layout.add_block();
 
+        if let (Some((_, first_row)), Some((_, last_row))) = (rows.first(), rows.last()) {
             let block = layout.b(block_id);
             layout.add_constraint(
-                block.top()
-                    | EQ(REQUIRED)
-                    | (layout.b(first_arrow.block).top() - config.msg_gutter),
+                block.top() | EQ(REQUIRED) | (layout.b(*first_row).top() - config.msg_gutter),
             );
             let block = layout.b(block_id);
             layout.add_constraint(
-                block.bottom()
-                    | GE(REQUIRED)
-                    | (layout.b(last_arrow.block).bottom() + config.msg_gutter),
+                block.bottom() | GE(REQUIRED) | (layout.b(*last_row).bottom() + config.msg_gutter),
             );
-            if let Some(prev_block_id) = last_block {
-                let block = layout.b(block_id);
-                layout.add_constraint(block.top() | EQ(REQUIRED) | layout.b(prev_block_id).top());
-                let block = layout.b(block_id);
-                layout.add_constraint(
-                    block.bottom() | EQ(REQUIRED) | layout.b(prev_block_id).bottom(),
-                );
-            }
-
-            last_block = Some(block_id);
-            participant_lines.push(ParticipantLine {
-                block: block_id,
-                participant_id: participant.id,
-            });
+        } else {
+            let block = layout.b(block_id);
+            layout.add_constraint(block.height | GE(REQUIRED) | (2.0 * config.msg_gutter));
         }
+        if let Some(prev_block_id) = last_block {
+            let block = layout.b(block_id);
+            layout.add_constraint(block.top() | EQ(REQUIRED) | layout.b(prev_block_id).top());
+            let block = layout.b(block_id);
+            layout.add_constraint(block.bottom() | EQ(REQUIRED) | layout.b(prev_block_id).bottom());
+        }
+
+        last_block = Some(block_id);
+        participant_lines.push(ParticipantLine {
+            block: block_id,
+            participant_id: participant.id,
+        });
     }
 
     let mut activation_markers = vec![];
-    for activation in &diagram.activations {
+    for (id, activation) in diagram.activations.iter().enumerate() {
         if let (Some(from), Some(to), Some(p_line)) = (
             arrows.iter().find(|a| a.msg_id == activation.from),
             arrows.iter().find(|a| a.msg_id == activation.to),
@@ -341,7 +336,10 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
                     | layout.b(p_line.block).left(),
             );
 
-            activation_markers.push(ActivationMarker { block: block_id });
+            activation_markers.push(ActivationMarker {
+                id,
+                block: block_id,
+            });
         }
     }
 
@@ -388,12 +386,16 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
             .find(|pl| pl.participant_id == participant.id)
             .unwrap();
         let (b, lines) = layout.add_text_block(
-            &participant.name,
+            participant.label(),
             config.max_participant_head_length,
             config.padding,
             config.font_size * config.font_scale_factor,
         );
-        let head = ParticipantMarker { block_id: b, lines };
+        let head = ParticipantMarker {
+            participant_id: participant.id,
+            block_id: b,
+            lines,
+        };
         layout.add_constraint(
             layout.b(b).bottom() | EQ(REQUIRED) | layout.b(participant_line.block).top(),
         );
@@ -405,12 +407,13 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
         heads.push(head);
 
         let (footer_b, lines) = layout.add_text_block(
-            &participant.name,
+            participant.label(),
             config.max_participant_head_length,
             config.padding,
             config.font_size * config.font_scale_factor,
         );
         let footer = ParticipantMarker {
+            participant_id: participant.id,
             block_id: footer_b,
             lines,
         };
@@ -425,187 +428,393 @@ pub fn to_svg(diagram: &SequenceDiagram, config: &SvgConfig) -> SVG {
         footers.push(footer);
     }
 
-    let mut notes = vec![];
-    for note in &diagram.notes {
-        let (block_id, lines) = layout.add_text_block(
-            &note.content,
-            config.max_participant_head_length,
-            config.padding,
-            config.font_size * config.font_scale_factor,
-        );
+    // keep the lifelines in declaration order and far enough apart for their heads
+    // and the widest label between two neighbouring participants, self messages
+    // included as their label goes right of their lifeline
+    for ((prev_line, prev_head), (next_line, next_head)) in
+        participant_lines.iter().zip(heads.iter()).tuple_windows()
+    {
+        let gap = layout.b(next_line.block).left() - layout.b(prev_line.block).left();
+        let heads_width = layout.b(prev_head.block_id).width + layout.b(next_head.block_id).width;
+        layout.add_constraint(gap.clone() | GE(REQUIRED) | (heads_width * 0.5 + config.msg_gutter));
+        let labels_in_gap = arrows.iter().filter(|a| {
+            a.left == prev_line.participant_id
+                && (a.right == next_line.participant_id || a.right == a.left)
+        });
+        for arrow in labels_in_gap {
+            layout.add_constraint(gap.clone() | GE(REQUIRED) | layout.b(arrow.block).width);
+        }
+    }
+
+    // messages skipping over participants keep every lifeline in between strictly inside
+    // the arrow and prefer to share their label width evenly across the gaps they span
+    for arrow in &arrows {
+        let position_of = |participant_id| {
+            participant_lines
+                .iter()
+                .position(|pl| pl.participant_id == participant_id)
+        };
+        let (Some(left), Some(right)) = (position_of(arrow.left), position_of(arrow.right)) else {
+            continue;
+        };
+        if right <= left + 1 {
+            continue;
+        }
+        let first = &participant_lines[left];
+        let last = &participant_lines[right];
+        let span = layout.b(last.block).left() - layout.b(first.block).left();
+        layout.add_constraint(span | GE(REQUIRED) | layout.b(arrow.block).width);
+
+        let share = layout.b(arrow.block).width * (1.0 / (right - left) as f64);
+        for (prev, next) in participant_lines[left..=right].iter().tuple_windows() {
+            layout.add_constraint(
+                (layout.b(next.block).left() - layout.b(prev.block).left()) | GE(MEDIUM) | share,
+            );
+        }
+        for between in &participant_lines[left + 1..right] {
+            layout.add_constraint(
+                layout.b(between.block).left()
+                    | GE(REQUIRED)
+                    | (layout.b(first.block).left() + config.msg_gutter),
+            );
+            layout.add_constraint(
+                layout.b(between.block).left()
+                    | LE(REQUIRED)
+                    | (layout.b(last.block).left() - config.msg_gutter),
+            );
+        }
+    }
+
+    for (Note { block_id, .. }, note) in &notes {
+        let block_id = *block_id;
+
+        let line_index_of = |participant_id: &ParticipantId| {
+            participant_lines
+                .iter()
+                .position(|l| l.participant_id == *participant_id)
+        };
 
         match &note.horizontal_position {
             HorizontalNotePosition::LeftOf(participant_id) => {
-                let prev_and_left_of = 
-                    participant_lines.iter()
-                    .tuple_windows()
-                    .find(|(_, left_of)| &left_of.participant_id == participant_id);
-
-                if let Some((prev, left_of)) = prev_and_left_of {
+                if let Some(idx) = line_index_of(participant_id) {
+                    let left_of = &participant_lines[idx];
                     layout.add_constraint(
                         layout.b(block_id).right()
-                        | LE(REQUIRED)
-                        | layout.b(left_of.block).left() - config.msg_gutter,
-                        );
-                    layout.add_constraint(
-                        layout.b(block_id).left()
-                        | GE(REQUIRED)
-                        | layout.b(prev.block).right() + config.msg_gutter,
-                        );
-
-                } else {
-                    let participant_line = participant_lines
-                        .iter()
-                        .find(|l| l.participant_id == *participant_id)
-                        .unwrap();
+                            | LE(REQUIRED)
+                            | (layout.b(left_of.block).left() - config.msg_gutter),
+                    );
                     layout.add_constraint(
                         layout.b(block_id).right()
-                        | LE(REQUIRED)
-                        | layout.b(participant_line.block).left() - config.msg_gutter,
+                            | EQ(MEDIUM)
+                            | (layout.b(left_of.block).left() - config.msg_gutter),
+                    );
+                    // the preceding participant has to make room for the note
+                    if let Some(prev) = idx.checked_sub(1).map(|i| &participant_lines[i]) {
+                        layout.add_constraint(
+                            layout.b(block_id).left()
+                                | GE(REQUIRED)
+                                | (layout.b(prev.block).left() + config.msg_gutter),
                         );
+                    }
                 }
             }
             HorizontalNotePosition::RightOf(participant_id) => {
-                let participant_line = participant_lines
-                    .iter()
-                    .find(|l| l.participant_id == *participant_id)
-                    .unwrap();
-
-                layout.add_constraint(
-                    layout.b(block_id).left()
-                        | GE(REQUIRED)
-                        | layout.b(participant_line.block).right() + config.msg_gutter,
-                );
+                if let Some(idx) = line_index_of(participant_id) {
+                    let right_of = &participant_lines[idx];
+                    layout.add_constraint(
+                        layout.b(block_id).left()
+                            | GE(REQUIRED)
+                            | (layout.b(right_of.block).left() + config.msg_gutter),
+                    );
+                    layout.add_constraint(
+                        layout.b(block_id).left()
+                            | EQ(MEDIUM)
+                            | (layout.b(right_of.block).left() + config.msg_gutter),
+                    );
+                    // the following participant has to make room for the note
+                    if let Some(next) = participant_lines.get(idx + 1) {
+                        layout.add_constraint(
+                            (layout.b(block_id).right() + config.msg_gutter)
+                                | LE(REQUIRED)
+                                | layout.b(next.block).left(),
+                        );
+                    }
+                }
             }
 
             HorizontalNotePosition::Over(participant_ids) => {
-                let ordered_participants: Vec<&ParticipantLine> = participant_lines
-                    .iter()
-                    .filter(|pl| participant_ids.iter().contains(&pl.participant_id))
-                    .collect();
-                let leftmost = ordered_participants.first().unwrap();
-                let rightmost = ordered_participants.last().unwrap();
-
-                layout.add_constraint(
-                    layout.b(block_id).left()
-                        | LE(REQUIRED)
-                        | layout.b(leftmost.block).left() - config.msg_gutter,
-                );
-
-                layout.add_constraint(
-                    layout.b(block_id).right()
-                        | GE(REQUIRED)
-                        | layout.b(rightmost.block).left() + config.msg_gutter,
-                );
+                let indices: Vec<usize> =
+                    participant_ids.iter().filter_map(line_index_of).collect();
+                if let (Some(&first), Some(&last)) = (indices.iter().min(), indices.iter().max()) {
+                    let first = &participant_lines[first];
+                    let last = &participant_lines[last];
+
+                    // the note covers every lifeline from the first to the last participant
+                    layout.add_constraint(
+                        layout.b(block_id).left()
+                            | LE(REQUIRED)
+                            | (layout.b(first.block).left() - config.msg_gutter),
+                    );
+                    layout.add_constraint(
+                        layout.b(block_id).right()
+                            | GE(REQUIRED)
+                            | (layout.b(last.block).left() + config.msg_gutter),
+                    );
+                    // and is centered on the span
+                    layout.add_constraint(
+                        (layout.b(block_id).left() * 2.0 + layout.b(block_id).width)
+                            | EQ(REQUIRED)
+                            | (layout.b(first.block).left() + layout.b(last.block).left()),
+                    );
+                }
             }
         };
+    }
 
-        notes.push((
-            Note {
-                id: note.id,
-                block_id,
-                lines,
-            },
-            note,
-        ));
+    layout.solve();
+    SequenceLayout {
+        layout,
+        config: config.clone(),
+        heads,
+        footers,
+        participant_lines,
+        activation_markers,
+        arrows,
+        notes: notes.into_iter().map(|(note, _)| note).collect(),
+    }
+}
+
+impl SequenceLayout {
+    pub fn to_svg(&self) -> SVG {
+        SvgBackend.render(&self.scene())
     }
 
-    if let Some((last_note, _)) = notes.last() {
-        for participant_line in &participant_lines {
-            layout.add_constraint(
-                layout.b(participant_line.block).bottom()
-                    | GE(REQUIRED)
-                    | (layout.b(last_note.block_id).bottom() + config.msg_gutter),
-            );
+    /// What is drawn for the diagram, for any [`RenderBackend`].
+    pub fn scene(&self) -> Scene {
+        let (layout, config) = (&self.layout, &self.config);
+        let mut items = vec![];
+        for elem in &self.heads {
+            elem.draw(&mut items, layout, config);
+        }
+        for elem in &self.footers {
+            elem.draw(&mut items, layout, config);
+        }
+        for elem in &self.participant_lines {
+            elem.draw(&mut items, layout, config);
+        }
+        for elem in &self.activation_markers {
+            elem.draw(&mut items, layout, config);
+        }
+        for elem in &self.arrows {
+            elem.draw(&mut items, layout, config);
+        }
+        for elem in &self.notes {
+            elem.draw(&mut items, layout, config);
+        }
+        let block = |id| layout.b(id).solved();
+        let header = self
+            .heads
+            .iter()
+            .map(|head| block(head.block_id))
+            .map(|b| b.position.y + b.height)
+            .fold(0.0, f64::max);
+        let footer = self
+            .footers
+            .iter()
+            .map(|footer| layout.height() - block(footer.block_id).position.y)
+            .fold(0.0, f64::max);
+        Scene {
+            width: layout.width(),
+            height: layout.height(),
+            font_scale_factor: config.font_scale_factor,
+            header,
+            footer,
+            items,
         }
     }
 
-    let find_note_by_id = |note_id| {
-        let (note, _) = notes.iter().find(|(n, _)| n.id == note_id).unwrap();
-        note
-    };
-
-    for (note, note_data) in &notes {
-        match note_data.vertical_position {
-            VerticalNotePosition::First => {
-                let head = heads.first().unwrap();
-                layout.add_constraint(
-                    layout.b(note.block_id).top()
-                        | EQ(STRONG)
-                        | layout.b(head.block_id).bottom() + config.msg_gutter,
-                );
-            }
-            VerticalNotePosition::AfterMessage(msg_id) => {
-                let msg = arrows.iter().find(|a| a.msg_id == msg_id).unwrap();
-                layout.add_constraint(
-                    layout.b(note.block_id).top()
-                        | EQ(STRONG)
-                        | layout.b(msg.block).bottom() + config.msg_gutter,
-                );
-            }
-            VerticalNotePosition::AfterNote(note_id) => {
-                let other_note = find_note_by_id(note_id);
-                layout.add_constraint(
-                    layout.b(note.block_id).top()
-                        | EQ(STRONG)
-                        | layout.b(other_note.block_id).bottom() + config.msg_gutter,
-                );
-            }
+    /// Every solved block, keyed by the kind and the id of its element.
+    pub fn solved(&self) -> SolvedLayout {
+        let block = |id: BlockId| self.layout.b(id).solved().clone();
+        let heads = self
+            .heads
+            .iter()
+            .map(|h| (format!("head-{}", h.participant_id), block(h.block_id)));
+        let footers = self
+            .footers
+            .iter()
+            .map(|f| (format!("footer-{}", f.participant_id), block(f.block_id)));
+        let lifelines = self
+            .participant_lines
+            .iter()
+            .map(|l| (format!("lifeline-{}", l.participant_id), block(l.block)));
+        let activations = self
+            .activation_markers
+            .iter()
+            .map(|a| (format!("activation-{}", a.id), block(a.block)));
+        let messages = self
+            .arrows
+            .iter()
+            .map(|a| (format!("message-{}", a.msg_id), block(a.block)));
+        let notes = self
+            .notes
+            .iter()
+            .map(|n| (format!("note-{}", n.id), block(n.block_id)));
+        SolvedLayout {
+            width: self.layout.width(),
+            height: self.layout.height(),
+            blocks: heads
+                .chain(footers)
+                .chain(lifelines)
+                .chain(activations)
+                .chain(messages)
+                .chain(notes)
+                .collect(),
         }
     }
+}
 
-    layout.solve();
-    let mut doc = Document::new().set("viewBox", (0, 0, layout.width(), layout.height()));
-    let defs = Definitions::new()
-        .add(Style::new("@font-face { font-family: Roboto-Regular; src: url(\"resources/fonts/Roboto-Regular.ttf\") }"))
-        .add(Style::new("text {font-family:Roboto-Regular,Roboto;}"))
-        .add(
-            Marker::new()
-                .set("id", "start-arrow")
-                .set("markerWidth", "10")
-                .set("markerHeight", "7")
-                .set("refX", 0)
-                .set("refY", 3.5)
-                .set("orient", "auto")
-                .add(Polygon::new().set("points", "10 0, 10 7, 0 3.5")),
-        )
-        .add(
-            Marker::new()
-                .set("id", "end-arrow")
-                .set("markerWidth", "10")
-                .set("markerHeight", "7")
-                .set("refX", 0)
-                .set("refY", 3.5)
-                .set("orient", "auto")
-                .add(Polygon::new().set("points", "0 0, 10 3.5, 0 7")),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::{Diagram, Options};
+
+    fn solved(src: &str) -> SolvedLayout {
+        let diagram = Definition.parse(src).unwrap();
+        Definition.layout(&diagram, &Options::default()).solved()
+    }
+
+    #[test]
+    fn test_solved() {
+        let solved = solved("Alice->>+John: Hello\nNote right of John: hi\nJohn-->>-Alice: Great!");
+        let keys: Vec<&str> = solved.blocks.keys().map(String::as_str).collect();
+        assert_eq!(
+            vec![
+                "activation-0",
+                "footer-0",
+                "footer-1",
+                "head-0",
+                "head-1",
+                "lifeline-0",
+                "lifeline-1",
+                "message-0",
+                "message-2",
+                "note-1",
+            ],
+            keys
         );
+        let (head, footer) = (&solved.blocks["head-0"], &solved.blocks["footer-0"]);
+        assert!(head.position.y + head.height <= footer.position.y);
+        assert!(footer.position.y + footer.height <= solved.height);
+    }
 
-    doc = doc.add(defs);
+    #[test]
+    fn test_note_over_several() {
+        let solved = solved("participant A\nparticipant B\nparticipant C\nNote over A,C: hi");
+        let note = &solved.blocks["note-3"];
+        let (first, last) = (&solved.blocks["lifeline-0"], &solved.blocks["lifeline-2"]);
+        assert!(note.position.x < first.position.x);
+        assert!(note.position.x + note.width > last.position.x);
+    }
 
-    for elem in heads {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_tall_note() {
+        let solved = solved(
+            "A->>B: one
+Note over A: a note long enough to be wrapped over several lines of text
+B->>A: two",
+        );
+        let note = &solved.blocks["note-1"];
+        assert!(note.height > 3.0 * note.line_height);
+        assert!(note.position.y + note.height <= solved.blocks["message-2"].position.y);
+        assert!(
+            solved.blocks["message-0"].position.y + solved.blocks["message-0"].height
+                <= note.position.y
+        );
     }
 
-    for elem in footers {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_silent_participants() {
+        let solved = solved("participant Charlie\nparticipant A\nparticipant Bob");
+        for (prev, next) in (0..3).tuple_windows() {
+            let x = |kind: &str, i: usize| solved.blocks[&format!("{kind}-{i}")].position.x;
+            let heads = solved.blocks[&format!("head-{prev}")].width
+                + solved.blocks[&format!("head-{next}")].width;
+            let gap = x("lifeline", next) - x("lifeline", prev);
+            assert!(gap >= heads / 2.0 + 20.0 - 1e-6);
+        }
     }
 
-    for elem in participant_lines {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_long_message() {
+        let solved = solved(
+            "participant A\nparticipant B\nparticipant C
+A->>C: a label long enough to need more room than the two heads give it",
+        );
+        let x = |i: usize| solved.blocks[&format!("lifeline-{i}")].position.x;
+        let message = &solved.blocks["message-3"];
+        assert!(x(2) - x(0) >= message.width - 1e-6);
+        assert!(x(0) + 20.0 <= x(1) + 1e-6 && x(1) <= x(2) - 20.0 + 1e-6);
     }
 
-    for elem in activation_markers {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_self_message() {
+        let solved =
+            solved("participant A\nparticipant B\nA->>A: a rather long self message label here");
+        let message = &solved.blocks["message-2"];
+        let b = &solved.blocks["lifeline-1"];
+        assert!(message.position.x + message.width <= b.position.x + 1e-6);
     }
 
-    for elem in arrows {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_right_to_left() {
+        let solved = solved("Alice->>John: مرحبا John\nNote over John: Hello مرحبا");
+        assert_eq!(
+            TextDirection::RightToLeft,
+            solved.blocks["message-0"].direction
+        );
+        assert_eq!(
+            TextDirection::LeftToRight,
+            solved.blocks["note-1"].direction
+        );
+        assert_eq!(
+            TextDirection::LeftToRight,
+            solved.blocks["head-0"].direction
+        );
     }
 
-    for (elem, _) in notes {
-        doc = doc.add(elem.to_svg(&layout, config));
+    #[test]
+    fn test_scene() {
+        let diagram = Definition.parse("Alice->>John: Hello").unwrap();
+        let layout = Definition.layout(&diagram, &Options::default());
+        let scene = layout.scene();
+        // heads and footers with their names, two lifelines, the arrow and its label
+        assert_eq!(12, scene.items.len());
+        let arrow = scene.items.iter().find_map(|item| match item {
+            Item::Arrow { from, to } => Some((from.0, to.0)),
+            _ => None,
+        });
+        let solved = layout.solved();
+        let lifeline = |key: &str| solved.blocks[key].position.x;
+        assert_eq!(
+            Some((lifeline("lifeline-0"), lifeline("lifeline-1"))),
+            arrow
+        );
     }
 
-    doc
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let src = "participant Alice\nAlice->>John: Hello\nNote over Alice,John: hi";
+        let diagram = Definition.parse(src).unwrap();
+        let json = serde_json::to_string(&diagram).unwrap();
+        assert_eq!(diagram, serde_json::from_str(&json).unwrap());
+
+        let solved = solved(src);
+        let json = serde_json::to_value(&solved).unwrap();
+        assert_eq!(
+            serde_json::json!(solved.blocks["message-1"].width),
+            json["blocks"]["message-1"]["width"]
+        );
+    }
 }
-
@@ -0,0 +1,435 @@
+use textwrap::wrap;
+
+use crate::sequence_diagram::parser::Note;
+use crate::sequence_diagram::*;
+
+/// How a sequence diagram is drawn with characters, for terminals and code
+/// comments.
+pub struct TextConfig {
+    /// Sticks to ASCII instead of box-drawing characters.
+    pub ascii: bool,
+    pub max_msg_label_length: usize,
+    pub max_note_length: usize,
+}
+
+impl Default for TextConfig {
+    fn default() -> Self {
+        TextConfig {
+            ascii: false,
+            max_msg_label_length: 60,
+            max_note_length: 30,
+        }
+    }
+}
+
+struct Charset {
+    vertical: char,
+    horizontal: char,
+    dotted: char,
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    /// Where a message leaves a lifeline to the right, and to the left
+    tees: (char, char),
+    active: char,
+    active_tees: (char, char),
+    arrows: (char, char),
+    open_arrows: (char, char),
+    cross: char,
+}
+
+const UNICODE: Charset = Charset {
+    vertical: '│',
+    horizontal: '─',
+    dotted: '┄',
+    top_left: '┌',
+    top_right: '┐',
+    bottom_left: '└',
+    bottom_right: '┘',
+    tees: ('├', '┤'),
+    active: '┃',
+    active_tees: ('┣', '┫'),
+    arrows: ('▶', '◀'),
+    open_arrows: ('▷', '◁'),
+    cross: '✕',
+};
+
+const ASCII: Charset = Charset {
+    vertical: '|',
+    horizontal: '-',
+    dotted: '.',
+    top_left: '+',
+    top_right: '+',
+    bottom_left: '+',
+    bottom_right: '+',
+    tees: ('+', '+'),
+    active: '#',
+    active_tees: ('#', '#'),
+    arrows: ('>', '<'),
+    open_arrows: (')', '('),
+    cross: 'x',
+};
+
+/// Cells of characters, which grow as they are written to.
+#[derive(Default)]
+struct Grid {
+    rows: Vec<Vec<char>>,
+}
+
+impl Grid {
+    fn get(&self, x: usize, y: usize) -> char {
+        self.rows
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(' ')
+    }
+
+    fn set(&mut self, x: usize, y: usize, c: char) {
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, vec![]);
+        }
+        let row = &mut self.rows[y];
+        if row.len() <= x {
+            row.resize(x + 1, ' ');
+        }
+        row[x] = c;
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.set(x + i, y, c);
+        }
+    }
+
+    /// A box `width` wide with a line of text on each row.
+    fn boxed(&mut self, left: usize, top: usize, width: usize, lines: &[String], chars: &Charset) {
+        let border = |left_corner: char, right_corner: char| {
+            let line = chars.horizontal.to_string().repeat(width - 2);
+            format!("{left_corner}{line}{right_corner}")
+        };
+        self.text(left, top, &border(chars.top_left, chars.top_right));
+        for (i, line) in lines.iter().enumerate() {
+            let padded = format!("{line:width$}", width = width - 4);
+            let row = format!("{} {padded} {}", chars.vertical, chars.vertical);
+            self.text(left, top + 1 + i, &row);
+        }
+        let bottom = top + 1 + lines.len();
+        self.text(left, bottom, &border(chars.bottom_left, chars.bottom_right));
+    }
+}
+
+impl std::fmt::Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in &self.rows {
+            let row: String = row.iter().collect();
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn wrapped(content: &str, max_length: usize) -> Vec<String> {
+    if content.is_empty() {
+        return vec![];
+    }
+    wrap(content, max_length)
+        .into_iter()
+        .map(|line| line.into_owned())
+        .collect()
+}
+
+fn text_width(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// The columns of the lifelines, as close to each other as the minimum
+/// distances between pairs of them and from the left edge allow.
+fn columns(count: usize, lower: &[usize], distances: &[(usize, usize, usize)]) -> Vec<usize> {
+    let mut columns: Vec<usize> = lower.to_vec();
+    // distances only go from a lifeline to one further right, so the
+    // columns on the left are final by the time they are needed
+    for right in 0..count {
+        for &(left, to, distance) in distances {
+            if to == right {
+                columns[right] = columns[right].max(columns[left] + distance);
+            }
+        }
+    }
+    columns
+}
+
+enum Row<'a> {
+    Message(&'a Message, Vec<String>),
+    Note(&'a Note, Vec<String>),
+}
+
+pub fn to_text(diagram: &SequenceDiagram, config: &TextConfig) -> String {
+    let chars = if config.ascii { &ASCII } else { &UNICODE };
+    let count = diagram.participants.len();
+    let head_widths: Vec<usize> = diagram
+        .participants
+        .iter()
+        .map(|p| p.label().chars().count() + 4)
+        .collect();
+
+    let mut rows: Vec<(usize, Row)> = diagram
+        .messages
+        .iter()
+        .map(|m| {
+            (
+                m.id,
+                Row::Message(m, wrapped(&m.msg, config.max_msg_label_length)),
+            )
+        })
+        .chain(diagram.notes.iter().map(|n| {
+            let lines = wrapped(&n.content, config.max_note_length);
+            (n.id, Row::Note(n, lines))
+        }))
+        .collect();
+    rows.sort_by_key(|(id, _)| *id);
+
+    // heads two columns apart, and room for the labels and the notes
+    let mut lower = vec![0; count];
+    let mut distances = vec![];
+    if let Some(width) = head_widths.first() {
+        lower[0] = width / 2;
+    }
+    for i in 1..count {
+        let (left, right) = (head_widths[i - 1], head_widths[i]);
+        distances.push((i - 1, i, left - left / 2 + right / 2 + 1));
+    }
+    for (_, row) in &rows {
+        match row {
+            Row::Message(m, label) if m.left == m.right => {
+                if m.left + 1 < count {
+                    distances.push((m.left, m.left + 1, (text_width(label) + 3).max(6)));
+                }
+            }
+            Row::Message(m, label) => {
+                distances.push((m.left, m.right, (text_width(label) + 4).max(6)));
+            }
+            Row::Note(note, lines) => {
+                let width = text_width(lines) + 4;
+                match &note.horizontal_position {
+                    HorizontalNotePosition::LeftOf(p) if *p == 0 => {
+                        lower[0] = lower[0].max(width + 1)
+                    }
+                    HorizontalNotePosition::LeftOf(p) => distances.push((p - 1, *p, width + 3)),
+                    HorizontalNotePosition::RightOf(p) if p + 1 < count => {
+                        distances.push((*p, p + 1, width + 3))
+                    }
+                    HorizontalNotePosition::RightOf(_) => {}
+                    HorizontalNotePosition::Over(ps) => {
+                        let first = ps.iter().copied().min().unwrap_or(0);
+                        let last = ps.iter().copied().max().unwrap_or(0);
+                        let half = if first == last { width / 2 } else { 2 };
+                        if first == 0 {
+                            lower[0] = lower[0].max(half);
+                        } else {
+                            distances.push((first - 1, first, half + 2));
+                        }
+                        if last + 1 < count {
+                            distances.push((last, last + 1, width - width / 2 + 2));
+                        }
+                        if first < last {
+                            distances.push((first, last, width.saturating_sub(5)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let x = columns(count, &lower, &distances);
+
+    // rows of the messages and notes, with a blank row between them
+    let body_top = 3;
+    let mut y = body_top + 1;
+    let mut placed = vec![];
+    let mut arrow_rows = std::collections::HashMap::new();
+    for (_, row) in &rows {
+        placed.push((y, row));
+        y += match row {
+            Row::Message(m, label) => {
+                arrow_rows.insert(m.id, y + label.len());
+                let arrow_height = if m.left == m.right { 2 } else { 1 };
+                label.len() + arrow_height + 1
+            }
+            Row::Note(_, lines) => lines.len() + 3,
+        };
+    }
+    let body_bottom = y;
+
+    let mut grid = Grid::default();
+    for &column in &x {
+        for row in body_top..body_bottom {
+            grid.set(column, row, chars.vertical);
+        }
+    }
+    for activation in &diagram.activations {
+        if let (Some(from), Some(to)) = (
+            arrow_rows.get(&activation.from),
+            arrow_rows.get(&activation.to),
+        ) {
+            for row in *from..=*to {
+                grid.set(x[activation.participant_id], row, chars.active);
+            }
+        }
+    }
+
+    for (top, row) in placed {
+        match row {
+            Row::Message(m, label) => {
+                for (i, line) in label.iter().enumerate() {
+                    grid.text(x[m.left] + 2, top + i, line);
+                }
+                draw_arrow(&mut grid, m, &x, top + label.len(), chars);
+            }
+            Row::Note(note, lines) => {
+                let width = text_width(lines) + 4;
+                let (left, width) = match &note.horizontal_position {
+                    HorizontalNotePosition::LeftOf(p) => (x[*p] - 1 - width, width),
+                    HorizontalNotePosition::RightOf(p) => (x[*p] + 2, width),
+                    HorizontalNotePosition::Over(ps) => {
+                        let first = x[ps.iter().copied().min().unwrap_or(0)];
+                        let last = x[ps.iter().copied().max().unwrap_or(0)];
+                        if first == last {
+                            (first.saturating_sub(width / 2), width)
+                        } else {
+                            let span = last - first + 5;
+                            let width = width.max(span);
+                            ((first + last + 1).saturating_sub(width) / 2, width)
+                        }
+                    }
+                };
+                grid.boxed(left, top, width, lines, chars);
+            }
+        }
+    }
+
+    for (p, participant) in diagram.participants.iter().enumerate() {
+        let width = head_widths[p];
+        let name = vec![participant.label().to_string()];
+        grid.boxed(x[p] - width / 2, 0, width, &name, chars);
+        grid.boxed(x[p] - width / 2, body_bottom, width, &name, chars);
+    }
+    grid.to_string()
+}
+
+fn draw_arrow(grid: &mut Grid, m: &Message, x: &[usize], y: usize, chars: &Charset) {
+    let (dotted, head) = match m.arrow {
+        Arrow::SolidNoArrow => (false, None),
+        Arrow::DottedNoArrow => (true, None),
+        Arrow::SolidArrow => (false, Some(chars.arrows)),
+        Arrow::DottedArrow => (true, Some(chars.arrows)),
+        Arrow::SolidCross => (false, Some((chars.cross, chars.cross))),
+        Arrow::DottedCross => (true, Some((chars.cross, chars.cross))),
+        Arrow::SolidOpen => (false, Some(chars.open_arrows)),
+        Arrow::DottedOpen => (true, Some(chars.open_arrows)),
+    };
+    let line = if dotted {
+        chars.dotted
+    } else {
+        chars.horizontal
+    };
+    let (left, right) = (x[m.left], x[m.right]);
+    let leaving_right = m.left == m.right || m.direction == ArrowDirection::ToRight;
+    let source = if leaving_right { left } else { right };
+    let tee = match (grid.get(source, y) == chars.active, leaving_right) {
+        (false, true) => chars.tees.0,
+        (false, false) => chars.tees.1,
+        (true, true) => chars.active_tees.0,
+        (true, false) => chars.active_tees.1,
+    };
+    grid.set(source, y, tee);
+
+    if m.left == m.right {
+        // a loop back to the same lifeline
+        grid.text(left + 1, y, &format!("{line}{line}{}", chars.top_right));
+        let head = head.map_or(line, |(_, to_left)| to_left);
+        grid.text(
+            left + 1,
+            y + 1,
+            &format!("{head}{line}{}", chars.bottom_right),
+        );
+        return;
+    }
+    for column in left + 1..right {
+        grid.set(column, y, line);
+    }
+    match (head, &m.direction) {
+        (Some((to_right, _)), ArrowDirection::ToRight) => grid.set(right - 1, y, to_right),
+        (Some((_, to_left)), ArrowDirection::ToLeft) => grid.set(left + 1, y, to_left),
+        (None, _) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_text() {
+        let diagram = parse(
+            "Alice->>+John: Hello John
+Note right of John: thinking
+John-->>-Alice: Great!"
+                .to_string(),
+        )
+        .unwrap();
+        let expected = "
+┌───────┐     ┌──────┐
+│ Alice │     │ John │
+└───────┘     └──────┘
+    │             │
+    │ Hello John  │
+    ├────────────▶┃
+    │             ┃
+    │             ┃ ┌──────────┐
+    │             ┃ │ thinking │
+    │             ┃ └──────────┘
+    │             ┃
+    │ Great!      ┃
+    │◀┄┄┄┄┄┄┄┄┄┄┄┄┫
+    │             │
+┌───────┐     ┌──────┐
+│ Alice │     │ John │
+└───────┘     └──────┘
+";
+        assert_eq!(
+            expected.trim_start(),
+            to_text(&diagram, &TextConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_to_text_ascii() {
+        let diagram = parse("A->>B: hi\nNote over A,B: done".to_string()).unwrap();
+        let config = TextConfig {
+            ascii: true,
+            ..TextConfig::default()
+        };
+        let expected = "
++---+ +---+
+| A | | B |
++---+ +---+
+  |     |
+  | hi  |
+  +---->|
+  |     |
++---------+
+| done    |
++---------+
+  |     |
++---+ +---+
+| A | | B |
++---+ +---+
+";
+        assert_eq!(expected.trim_start(), to_text(&diagram, &config));
+    }
+}
@@ -0,0 +1,642 @@
+use std::collections::HashMap;
+
+use combine::error::StringStreamError;
+use combine::parser::char::{alpha_num, char, string};
+use combine::parser::choice::choice;
+use combine::parser::repeat::take_until;
+use combine::{
+    attempt, eof, many, many1, none_of, not_followed_by, one_of, optional, skip_many1, ParseError,
+    Parser, Stream,
+};
+
+use crate::parsing::{blank, blank1, comment_parser, keyword, skipped_parser};
+
+pub use crate::flowchart::Direction;
+
+pub type StateId = usize;
+pub type TransitionId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StateKind {
+    Simple,
+    /// The `[*]` a scope starts from.
+    Start,
+    /// The `[*]` a scope ends in.
+    End,
+    Fork,
+    Join,
+    Choice,
+    Composite,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    pub id: StateId,
+    pub name: String,
+    pub label: String,
+    /// Text added with `State : description`, one entry per statement.
+    pub descriptions: Vec<String>,
+    pub kind: StateKind,
+    /// The composite state this one is nested in.
+    pub parent: Option<StateId>,
+    /// Concurrency region of the parent, regions are separated by `--`.
+    pub region: usize,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transition {
+    pub id: TransitionId,
+    pub from: StateId,
+    pub to: StateId,
+    pub label: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NotePosition {
+    LeftOf,
+    RightOf,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Note {
+    pub state: StateId,
+    pub position: NotePosition,
+    pub text: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateDiagram {
+    pub direction: Direction,
+    pub states: Vec<State>,
+    pub transitions: Vec<Transition>,
+    pub notes: Vec<Note>,
+}
+
+impl StateDiagram {
+    pub fn children(&self, parent: StateId) -> impl Iterator<Item = &State> {
+        self.states.iter().filter(move |s| s.parent == Some(parent))
+    }
+
+    /// Number of concurrency regions of a composite state.
+    pub fn regions(&self, parent: StateId) -> usize {
+        self.children(parent)
+            .map(|s| s.region + 1)
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StateRef {
+    /// `[*]`, a start state as source and an end state as target.
+    Terminal,
+    Named(String),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header,
+    Direction(Direction),
+    /// `state "label" as name <<annotation>>`, opening a composite state if followed by `{`.
+    Declaration {
+        name: String,
+        label: Option<String>,
+        kind: Option<StateKind>,
+        opens: bool,
+    },
+    CompositeEnd,
+    Concurrency,
+    Description(String, String),
+    Transition(StateRef, StateRef, Option<String>),
+    Note(NotePosition, String, String),
+}
+
+pub fn parse(src: String) -> Result<StateDiagram, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_state_diagram(statements),
+        Err(e) => Err(e),
+    }
+}
+
+struct Builder {
+    diagram: StateDiagram,
+    ids: HashMap<String, StateId>,
+    /// Open composite states and their current region, innermost last.
+    scopes: Vec<(StateId, usize)>,
+}
+
+impl Builder {
+    fn state(&mut self, name: &str, kind: StateKind) -> StateId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let (parent, region) = match self.scopes.last() {
+            Some(&(parent, region)) => (Some(parent), region),
+            None => (None, 0),
+        };
+        let id = self.diagram.states.len();
+        self.diagram.states.push(State {
+            id,
+            name: name.to_string(),
+            label: name.to_string(),
+            descriptions: vec![],
+            kind,
+            parent,
+            region,
+        });
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Every scope and region has its own start and end state.
+    fn terminal(&mut self, kind: StateKind) -> StateId {
+        let scope = match self.scopes.last() {
+            Some((parent, region)) => format!("{}-{region}", self.diagram.states[*parent].name),
+            None => String::new(),
+        };
+        let name = match kind {
+            StateKind::Start => format!("[*]start{scope}"),
+            _ => format!("[*]end{scope}"),
+        };
+        let id = self.state(&name, kind);
+        self.diagram.states[id].label = String::new();
+        id
+    }
+
+    fn resolve(&mut self, state: &StateRef, terminal: StateKind) -> StateId {
+        match state {
+            StateRef::Terminal => self.terminal(terminal),
+            StateRef::Named(name) => self.state(name, StateKind::Simple),
+        }
+    }
+}
+
+fn build_state_diagram(statements: Vec<Statement>) -> Result<StateDiagram, StringStreamError> {
+    let mut builder = Builder {
+        diagram: StateDiagram {
+            direction: Direction::TopToBottom,
+            states: vec![],
+            transitions: vec![],
+            notes: vec![],
+        },
+        ids: HashMap::new(),
+        scopes: vec![],
+    };
+
+    for statement in statements {
+        match statement {
+            Statement::Empty | Statement::Header => {}
+            Statement::Direction(direction) => {
+                // nested states are laid out along with the rest of the diagram
+                if builder.scopes.is_empty() {
+                    builder.diagram.direction = direction;
+                }
+            }
+            Statement::Declaration {
+                name,
+                label,
+                kind,
+                opens,
+            } => {
+                let id = builder.state(&name, StateKind::Simple);
+                let state = &mut builder.diagram.states[id];
+                if let Some(label) = label {
+                    state.label = label;
+                }
+                if let Some(kind) = kind {
+                    state.kind = kind;
+                }
+                if opens {
+                    state.kind = StateKind::Composite;
+                    builder.scopes.push((id, 0));
+                }
+            }
+            Statement::CompositeEnd => {
+                builder
+                    .scopes
+                    .pop()
+                    .ok_or(StringStreamError::UnexpectedParse)?;
+            }
+            Statement::Concurrency => match builder.scopes.last_mut() {
+                Some((_, region)) => *region += 1,
+                None => return Err(StringStreamError::UnexpectedParse),
+            },
+            Statement::Description(name, text) => {
+                let id = builder.state(&name, StateKind::Simple);
+                builder.diagram.states[id].descriptions.push(text);
+            }
+            Statement::Transition(from, to, label) => {
+                let from = builder.resolve(&from, StateKind::Start);
+                let to = builder.resolve(&to, StateKind::End);
+                let id = builder.diagram.transitions.len();
+                builder.diagram.transitions.push(Transition {
+                    id,
+                    from,
+                    to,
+                    label,
+                });
+            }
+            Statement::Note(position, name, text) => {
+                let state = builder.state(&name, StateKind::Simple);
+                builder.diagram.notes.push(Note {
+                    state,
+                    position,
+                    text,
+                });
+            }
+        }
+    }
+
+    if builder.scopes.is_empty() {
+        Ok(builder.diagram)
+    } else {
+        Err(StringStreamError::UnexpectedParse)
+    }
+}
+
+/// The rest of the line, without trailing blanks.
+fn rest_of_line<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many(none_of("\n".chars())).map(|text: String| text.trim().to_string())
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((keyword("stateDiagram-v2"), keyword("stateDiagram"))).map(|_| Statement::Header)
+}
+
+fn direction_statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let direction = choice((
+        attempt(string("TB")).map(|_| Direction::TopToBottom),
+        attempt(string("TD")).map(|_| Direction::TopToBottom),
+        string("BT").map(|_| Direction::BottomToTop),
+        string("LR").map(|_| Direction::LeftToRight),
+        string("RL").map(|_| Direction::RightToLeft),
+    ));
+    (keyword("direction"), blank1(), direction).map(|(_, _, d)| Statement::Direction(d))
+}
+
+fn state_name_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let class = attempt((
+        string(":::"),
+        skip_many1(choice((alpha_num(), one_of("_-".chars())))),
+    ));
+    (many1(choice((alpha_num(), char('_')))), optional(class)).map(|(name, _)| name)
+}
+
+fn state_ref_parser<Input>() -> impl Parser<Input, Output = StateRef>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        string("[*]").map(|_| StateRef::Terminal),
+        state_name_parser().map(StateRef::Named),
+    ))
+}
+
+fn annotation_parser<Input>() -> impl Parser<Input, Output = StateKind>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        string("<<"),
+        choice((
+            attempt(string("fork")).map(|_| StateKind::Fork),
+            string("join").map(|_| StateKind::Join),
+            string("choice").map(|_| StateKind::Choice),
+        )),
+        string(">>"),
+    )
+        .map(|(_, kind, _)| kind)
+}
+
+fn declaration_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let quoted = (char('"'), many(none_of("\"\n".chars())), char('"'));
+    let labelled = attempt((
+        quoted,
+        blank1(),
+        keyword("as"),
+        blank1(),
+        state_name_parser(),
+    ))
+    .map(|((_, label, _), _, _, _, name): ((_, String, _), _, _, _, _)| (name, Some(label)));
+    (
+        keyword("state"),
+        blank1(),
+        choice((labelled, state_name_parser().map(|name| (name, None)))),
+        optional(attempt((blank(), annotation_parser()))),
+        optional(attempt((blank(), char('{')))),
+    )
+        .map(
+            |(_, _, (name, label), kind, opens)| Statement::Declaration {
+                name,
+                label,
+                kind: kind.map(|(_, kind)| kind),
+                opens: opens.is_some(),
+            },
+        )
+}
+
+fn transition_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let label = optional(attempt((blank(), char(':'), rest_of_line())));
+    attempt((
+        state_ref_parser(),
+        blank(),
+        string("-->"),
+        blank(),
+        state_ref_parser(),
+    ))
+    .and(label)
+    .map(|((from, _, _, _, to), label)| {
+        Statement::Transition(from, to, label.map(|(_, _, text)| text))
+    })
+}
+
+fn description_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((state_name_parser(), blank(), char(':'), rest_of_line()))
+        .map(|(name, _, _, text)| Statement::Description(name, text))
+}
+
+/// `note left of State : text` or the text on the following lines up to `end note`.
+fn note_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let position = choice((
+        attempt(string("left of")).map(|_| NotePosition::LeftOf),
+        string("right of").map(|_| NotePosition::RightOf),
+    ));
+    let inline = (blank(), char(':'), rest_of_line()).map(|(_, _, text)| text);
+    let block = (
+        blank(),
+        char('\n'),
+        take_until(attempt((blank(), string("end note")))),
+        blank(),
+        string("end note"),
+    )
+        .map(|(_, _, text, _, _): (_, _, String, _, _)| {
+            text.lines()
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string()
+        });
+    (
+        keyword("note"),
+        blank1(),
+        position,
+        blank1(),
+        state_name_parser(),
+        choice((attempt(inline), block)),
+    )
+        .map(|(_, _, position, _, name, text)| Statement::Note(position, name, text))
+}
+
+/// Styling and accessibility statements don't affect the layout and are skipped.
+fn ignored_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    skipped_parser(&[
+        "classDef", "class", "style", "hide", "scale", "accTitle", "accDescr",
+    ])
+    .map(|_| Statement::Empty)
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            direction_statement_parser(),
+            declaration_parser(),
+            note_parser(),
+            ignored_parser(),
+            char('}').map(|_| Statement::CompositeEnd),
+            attempt(string("--").skip(not_followed_by(char('>')))).map(|_| Statement::Concurrency),
+            transition_parser(),
+            description_parser(),
+            state_name_parser().map(|name| Statement::Declaration {
+                name,
+                label: None,
+                kind: None,
+                opens: false,
+            }),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> StateRef {
+        StateRef::Named(name.to_string())
+    }
+
+    #[test]
+    fn test_transition_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Transition(StateRef::Terminal, named("Idle"), None),
+                "\n"
+            )),
+            transition_parser().parse("[*] --> Idle\n")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Transition(named("Idle"), named("Busy"), Some("start job".to_string())),
+                "\n"
+            )),
+            transition_parser().parse("Idle-->Busy : start job\n")
+        );
+    }
+
+    #[test]
+    fn test_declaration_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Declaration {
+                    name: "s2".to_string(),
+                    label: Some("Waiting for data".to_string()),
+                    kind: None,
+                    opens: false,
+                },
+                "\n"
+            )),
+            declaration_parser().parse("state \"Waiting for data\" as s2\n")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Declaration {
+                    name: "fork_state".to_string(),
+                    label: None,
+                    kind: Some(StateKind::Fork),
+                    opens: false,
+                },
+                ""
+            )),
+            declaration_parser().parse("state fork_state <<fork>>")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Declaration {
+                    name: "Active".to_string(),
+                    label: None,
+                    kind: None,
+                    opens: true,
+                },
+                ""
+            )),
+            declaration_parser().parse("state Active {")
+        );
+    }
+
+    #[test]
+    fn test_note_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Note(
+                    NotePosition::RightOf,
+                    "Idle".to_string(),
+                    "waiting".to_string()
+                ),
+                "\n"
+            )),
+            note_parser().parse("note right of Idle : waiting\n")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Note(
+                    NotePosition::LeftOf,
+                    "Busy".to_string(),
+                    "first line\nsecond line".to_string()
+                ),
+                "\n"
+            )),
+            note_parser().parse("note left of Busy\n    first line\n    second line\n  end note\n")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"stateDiagram-v2
+            direction LR
+            [*] --> Idle
+            Idle --> Active : connect
+            state Active {
+                [*] --> Sending
+                Sending --> Sending
+                --
+                [*] --> Receiving
+            }
+            Active --> [*]
+            Idle : waiting for a peer
+            note right of Idle : no connection yet
+        "#;
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(Direction::LeftToRight, diagram.direction);
+        assert_eq!(
+            vec![
+                "[*]start",
+                "Idle",
+                "Active",
+                "[*]startActive-0",
+                "Sending",
+                "[*]startActive-1",
+                "Receiving",
+                "[*]end"
+            ],
+            diagram
+                .states
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(StateKind::Composite, diagram.states[2].kind);
+        assert_eq!(2, diagram.regions(2));
+        assert_eq!(Some(2), diagram.states[6].parent);
+        assert_eq!(1, diagram.states[6].region);
+        assert_eq!(
+            vec!["waiting for a peer".to_string()],
+            diagram.states[1].descriptions
+        );
+        assert_eq!(6, diagram.transitions.len());
+        assert_eq!(Some("connect".to_string()), diagram.transitions[1].label);
+        assert_eq!(1, diagram.notes.len());
+    }
+
+    #[test]
+    fn test_parse_fork_and_choice() {
+        let input = "stateDiagram\nstate fork <<fork>>\nstate check <<choice>>\n[*] --> fork\nfork --> A\nfork --> B\nA --> check\ncheck --> [*]";
+        let diagram = parse(input.to_string()).unwrap();
+        assert_eq!(StateKind::Fork, diagram.states[0].kind);
+        assert_eq!(StateKind::Choice, diagram.states[1].kind);
+        assert_eq!(StateKind::Start, diagram.states[2].kind);
+    }
+
+    #[test]
+    fn test_parse_unbalanced_composite() {
+        assert!(parse("stateDiagram-v2\nstate A {\n[*] --> B\n".to_string()).is_err());
+        assert!(parse("stateDiagram-v2\n}\n".to_string()).is_err());
+    }
+}
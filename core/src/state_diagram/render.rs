@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+
+use svg::node::element::{Circle, Group, Polygon, SVG};
+use svg::Node as _;
+
+use crate::flowchart::{
+    self, edge_markers, edge_path, Arrangement, Edge, EdgeEnd, Flowchart, Node, NodeId, NodeShape,
+    NodeSize, Side, Stroke, Subgraph, SubgraphId,
+};
+use crate::layout::BlockId;
+use crate::state_diagram::*;
+use crate::theme::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub max_note_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub rank_gutter: f64,
+    pub node_gutter: f64,
+    pub subgraph_padding: f64,
+}
+
+const START_SIZE: f64 = 14.0;
+const END_SIZE: f64 = 18.0;
+const CHOICE_SIZE: f64 = 24.0;
+const BAR_LENGTH: f64 = 70.0;
+const BAR_THICKNESS: f64 = 8.0;
+
+/// The state diagram as a flowchart: composite states and their concurrency
+/// regions become subgraphs, all other states nodes.
+struct Lowered {
+    flowchart: Flowchart,
+    node_of: HashMap<StateId, NodeId>,
+    /// The subgraph of every composite state with states inside.
+    subgraph_of: HashMap<StateId, SubgraphId>,
+    regions: Vec<SubgraphId>,
+}
+
+fn is_container(diagram: &StateDiagram, state: &State) -> bool {
+    state.kind == StateKind::Composite && diagram.children(state.id).next().is_some()
+}
+
+fn lower(diagram: &StateDiagram) -> Lowered {
+    let mut lowered = Lowered {
+        flowchart: Flowchart {
+            direction: diagram.direction,
+            nodes: vec![],
+            edges: vec![],
+            subgraphs: vec![],
+        },
+        node_of: HashMap::new(),
+        subgraph_of: HashMap::new(),
+        regions: vec![],
+    };
+    // subgraphs holding the states of every region of a composite state
+    let mut region_subgraphs: HashMap<StateId, Vec<SubgraphId>> = HashMap::new();
+
+    // parents are declared before the states nested in them
+    for state in &diagram.states {
+        let container = state
+            .parent
+            .map(|parent| region_subgraphs[&parent][state.region]);
+        if is_container(diagram, state) {
+            let composite =
+                add_subgraph(&mut lowered.flowchart, &state.name, &state.label, container);
+            lowered.subgraph_of.insert(state.id, composite);
+            let regions = diagram.regions(state.id);
+            let subgraphs = if regions > 1 {
+                (0..regions)
+                    .map(|region| {
+                        let name = format!("{}-{region}", state.name);
+                        let id = add_subgraph(&mut lowered.flowchart, &name, "", Some(composite));
+                        lowered.regions.push(id);
+                        id
+                    })
+                    .collect()
+            } else {
+                vec![composite]
+            };
+            region_subgraphs.insert(state.id, subgraphs);
+            continue;
+        }
+
+        let shape = match state.kind {
+            StateKind::Start => NodeShape::Circle,
+            StateKind::End => NodeShape::DoubleCircle,
+            StateKind::Choice => NodeShape::Rhombus,
+            StateKind::Fork | StateKind::Join => NodeShape::Rectangle,
+            StateKind::Simple | StateKind::Composite => NodeShape::Round,
+        };
+        let label = std::iter::once(state.label.as_str())
+            .chain(state.descriptions.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let id = lowered.flowchart.nodes.len();
+        lowered.flowchart.nodes.push(Node {
+            id,
+            name: state.name.clone(),
+            label,
+            shape,
+        });
+        if let Some(subgraph) = container {
+            lowered.flowchart.subgraphs[subgraph].nodes.push(id);
+        }
+        lowered.node_of.insert(state.id, id);
+    }
+
+    for transition in &diagram.transitions {
+        for from in exit(diagram, transition.from) {
+            for to in entry(diagram, transition.to) {
+                let id = lowered.flowchart.edges.len();
+                lowered.flowchart.edges.push(Edge {
+                    id,
+                    from: lowered.node_of[&from],
+                    to: lowered.node_of[&to],
+                    label: transition.label.clone(),
+                    stroke: Stroke::Solid,
+                    start: EdgeEnd::None,
+                    end: EdgeEnd::Arrow,
+                    length: 1,
+                });
+            }
+        }
+    }
+    lowered
+}
+
+fn add_subgraph(
+    flowchart: &mut Flowchart,
+    name: &str,
+    title: &str,
+    parent: Option<SubgraphId>,
+) -> SubgraphId {
+    let id = flowchart.subgraphs.len();
+    flowchart.subgraphs.push(Subgraph {
+        id,
+        name: name.to_string(),
+        title: title.to_string(),
+        direction: None,
+        parent,
+        nodes: vec![],
+    });
+    id
+}
+
+/// Transitions into a composite state lead to the start state of every
+/// region, or the first state of regions without one.
+fn entry(diagram: &StateDiagram, state: StateId) -> Vec<StateId> {
+    if !is_container(diagram, &diagram.states[state]) {
+        return vec![state];
+    }
+    (0..diagram.regions(state))
+        .filter_map(|region| {
+            let mut in_region = diagram.children(state).filter(move |s| s.region == region);
+            diagram
+                .children(state)
+                .find(|s| s.region == region && s.kind == StateKind::Start)
+                .or_else(|| in_region.next())
+        })
+        .flat_map(|first| entry(diagram, first.id))
+        .collect()
+}
+
+/// Transitions out of a composite state leave from the end state of every
+/// region, or the last state of regions without one.
+fn exit(diagram: &StateDiagram, state: StateId) -> Vec<StateId> {
+    if !is_container(diagram, &diagram.states[state]) {
+        return vec![state];
+    }
+    (0..diagram.regions(state))
+        .filter_map(|region| {
+            let in_region = diagram.children(state).filter(move |s| s.region == region);
+            diagram
+                .children(state)
+                .find(|s| s.region == region && s.kind == StateKind::End)
+                .or_else(|| in_region.last())
+        })
+        .flat_map(|last| exit(diagram, last.id))
+        .collect()
+}
+
+fn state_to_svg(
+    state: &State,
+    node_id: NodeId,
+    arrangement: &Arrangement,
+    config: &SvgConfig,
+) -> Group {
+    let block = arrangement.node(node_id);
+    let (w, h) = (block.width, block.height);
+    let group = translated(&block);
+    match state.kind {
+        StateKind::Start => group.add(
+            Circle::new()
+                .set("cx", w * 0.5)
+                .set("cy", h * 0.5)
+                .set("r", w * 0.5)
+                .set("fill", LINE_COLOR),
+        ),
+        StateKind::End => group
+            .add(stroked(
+                Circle::new()
+                    .set("cx", w * 0.5)
+                    .set("cy", h * 0.5)
+                    .set("r", w * 0.5),
+                "transparent",
+            ))
+            .add(
+                Circle::new()
+                    .set("cx", w * 0.5)
+                    .set("cy", h * 0.5)
+                    .set("r", w * 0.5 - 4.0)
+                    .set("fill", LINE_COLOR),
+            ),
+        StateKind::Fork | StateKind::Join => group.add(outline(&block, LINE_COLOR, 0.0)),
+        StateKind::Choice => {
+            let points = format!(
+                "{},0 {w},{} {},{h} 0,{}",
+                w * 0.5,
+                h * 0.5,
+                w * 0.5,
+                h * 0.5
+            );
+            group.add(stroked(Polygon::new().set("points", points), "transparent"))
+        }
+        StateKind::Simple | StateKind::Composite => {
+            let (text_block, lines) = arrangement.node_text(node_id);
+            let mut text = translated(&text_block);
+            for t in text_lines(lines, &text_block, config.font_size, config.padding) {
+                text = text.add(t);
+            }
+            Group::new()
+                .add(group.add(outline(&block, "transparent", config.corner_radius * 2.5)))
+                .add(text)
+        }
+    }
+}
+
+fn note_to_svg(
+    arrangement: &Arrangement,
+    block_id: BlockId,
+    lines: &[String],
+    config: &SvgConfig,
+) -> Group {
+    let block = arrangement.block(block_id);
+    let mut group = translated(&block).add(outline(&block, NOTE_FILL, config.corner_radius));
+    for t in text_lines(lines, &block, config.font_size, config.padding) {
+        group = group.add(t);
+    }
+    group
+}
+
+/// A state diagram with its states, transitions and notes placed.
+pub struct StateLayout {
+    diagram: StateDiagram,
+    config: SvgConfig,
+    lowered: Lowered,
+    arrangement: Arrangement,
+    notes: Vec<(BlockId, Vec<String>)>,
+}
+
+pub fn layout(diagram: &StateDiagram, config: &SvgConfig) -> StateLayout {
+    let lowered = lower(diagram);
+    let flowchart = &lowered.flowchart;
+    let font_size = config.font_size * config.font_scale_factor;
+
+    let ranks_vertical = matches!(
+        diagram.direction,
+        Direction::TopToBottom | Direction::BottomToTop
+    );
+    let mut sizes = HashMap::new();
+    for state in &diagram.states {
+        let size = match state.kind {
+            StateKind::Start => NodeSize::Fixed(START_SIZE, START_SIZE),
+            StateKind::End => NodeSize::Fixed(END_SIZE, END_SIZE),
+            StateKind::Choice => NodeSize::Fixed(CHOICE_SIZE, CHOICE_SIZE),
+            StateKind::Fork | StateKind::Join if ranks_vertical => {
+                NodeSize::Fixed(BAR_LENGTH, BAR_THICKNESS)
+            }
+            StateKind::Fork | StateKind::Join => NodeSize::Fixed(BAR_THICKNESS, BAR_LENGTH),
+            StateKind::Simple | StateKind::Composite => continue,
+        };
+        if let Some(&node_id) = lowered.node_of.get(&state.id) {
+            sizes.insert(node_id, size);
+        }
+    }
+
+    let flowchart_config = flowchart::SvgConfig {
+        max_label_length: config.max_label_length,
+        font_size: config.font_size,
+        font_scale_factor: config.font_scale_factor,
+        padding: config.padding,
+        corner_radius: config.corner_radius,
+        rank_gutter: config.rank_gutter,
+        node_gutter: config.node_gutter,
+        subgraph_padding: config.subgraph_padding,
+    };
+    let mut arrangement = Arrangement::new(flowchart, &flowchart_config, &sizes);
+
+    let mut notes = vec![];
+    for note in &diagram.notes {
+        let (block, lines) = arrangement.layout().add_text_block(
+            &note.text,
+            config.max_note_length,
+            config.padding,
+            font_size,
+        );
+        let side = match note.position {
+            NotePosition::LeftOf => Side::Before,
+            NotePosition::RightOf => Side::After,
+        };
+        match lowered.subgraph_of.get(&note.state) {
+            Some(&subgraph_id) => arrangement.attach_to_subgraph(subgraph_id, block, side),
+            None => arrangement.attach(lowered.node_of[&note.state], block, side),
+        }
+        notes.push((block, lines));
+    }
+    arrangement.solve();
+    StateLayout {
+        diagram: diagram.clone(),
+        config: config.clone(),
+        lowered,
+        arrangement,
+        notes,
+    }
+}
+
+impl StateLayout {
+    pub fn to_svg(&self) -> SVG {
+        let StateLayout {
+            diagram,
+            config,
+            lowered,
+            arrangement,
+            notes,
+        } = self;
+        let flowchart = &lowered.flowchart;
+
+        let mut doc = document(arrangement.width(), arrangement.height()).add(edge_markers());
+        for subgraph in &flowchart.subgraphs {
+            let (block, title, title_lines) = arrangement.subgraph(subgraph.id);
+            if lowered.regions.contains(&subgraph.id) {
+                let mut separator = outline(&block, "none", 0.0);
+                separator.assign("stroke-dasharray", "5 3");
+                doc = doc.add(translated(&block).add(separator));
+                continue;
+            }
+            let mut title_group = translated(&title);
+            for t in text_lines(title_lines, &title, config.font_size, config.padding) {
+                title_group = title_group.add(t);
+            }
+            doc = doc
+                .add(translated(&block).add(outline(
+                    &block,
+                    CLUSTER_FILL,
+                    config.corner_radius * 2.5,
+                )))
+                .add(title_group);
+        }
+
+        for state in &diagram.states {
+            if let Some(&node_id) = lowered.node_of.get(&state.id) {
+                doc = doc.add(state_to_svg(state, node_id, arrangement, config));
+            }
+        }
+
+        for edge_id in arrangement.edges() {
+            doc = doc.add(edge_path(
+                &flowchart.edges[edge_id],
+                &arrangement.edge_points(edge_id),
+            ));
+        }
+        for edge_id in arrangement.loops() {
+            let edge = &flowchart.edges[edge_id];
+            let mut path = edge_path(edge, &[]);
+            path.assign("d", arrangement.loop_path(edge.from));
+            doc = doc.add(path);
+        }
+        for edge in &flowchart.edges {
+            if let Some((block, lines)) = arrangement.edge_label(edge.id) {
+                doc = doc.add(label_box(lines, &block, config.font_size, config.padding));
+            }
+        }
+
+        for (block, lines) in notes {
+            doc = doc.add(note_to_svg(arrangement, *block, lines, config));
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(diagram: &StateDiagram, config: &SvgConfig) -> SVG {
+    layout(diagram, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::layout::geometry::{inside, overlap};
+    use crate::layout::Block;
+    use crate::state_diagram::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_composite_note() {
+        let src = "stateDiagram-v2\n[*] --> Idle\nIdle --> Busy\nIdle --> Other\nstate Busy {\n[*] --> Working\nWorking --> Done\n}\nnote right of Busy : composite\nnote left of Idle : simple";
+        let diagram = parser::parse(src.to_string()).unwrap();
+        let StateLayout {
+            lowered,
+            arrangement,
+            notes,
+            ..
+        } = layout(&diagram, &config());
+        let id = |name: &str| diagram.states.iter().find(|s| s.name == name).unwrap().id;
+
+        let (busy, _, _) = arrangement.subgraph(lowered.subgraph_of[&id("Busy")]);
+        let composite = arrangement.block(notes[0].0);
+        assert!(composite.position.x >= busy.position.x + busy.width);
+        let (middle, busy_middle) = (
+            composite.position.y + composite.height * 0.5,
+            busy.position.y + busy.height * 0.5,
+        );
+        assert!((middle - busy_middle).abs() < 0.5);
+
+        let idle = arrangement.node(lowered.node_of[&id("Idle")]);
+        let simple = arrangement.block(notes[1].0);
+        assert!(simple.position.x + simple.width <= idle.position.x);
+
+        for note in [&composite, &simple] {
+            assert!(!overlap(note, &busy));
+            for &node_id in lowered.node_of.values() {
+                assert!(!overlap(note, &arrangement.node(node_id)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_regions() {
+        let src = "stateDiagram-v2\ndirection LR\nstate split <<fork>>\n[*] --> split\nsplit --> Active\nstate Active {\n[*] --> Reading\nReading --> [*]\n--\n[*] --> Writing\nWriting --> [*]\n}\nActive --> [*]";
+        let diagram = parser::parse(src.to_string()).unwrap();
+        let StateLayout {
+            lowered,
+            arrangement,
+            ..
+        } = layout(&diagram, &config());
+        let id = |name: &str| diagram.states.iter().find(|s| s.name == name).unwrap().id;
+
+        // the fork bar stands across the left to right flow
+        let split = arrangement.node(lowered.node_of[&id("split")]);
+        assert_eq!((BAR_THICKNESS, BAR_LENGTH), (split.width, split.height));
+
+        // the transition into Active forks into the start state of both regions
+        let from_split: Vec<&Edge> = lowered
+            .flowchart
+            .edges
+            .iter()
+            .filter(|e| e.from == lowered.node_of[&id("split")])
+            .collect();
+        assert_eq!(2, from_split.len());
+
+        let (active, _, _) = arrangement.subgraph(lowered.subgraph_of[&id("Active")]);
+        let regions: Vec<Block> = lowered
+            .regions
+            .iter()
+            .map(|region| arrangement.subgraph(*region).0)
+            .collect();
+        assert_eq!(2, regions.len());
+        assert!(!overlap(&regions[0], &regions[1]));
+        for (region, state) in regions.iter().zip(["Reading", "Writing"]) {
+            assert!(inside(region, &active));
+            assert!(inside(
+                &arrangement.node(lowered.node_of[&id(state)]),
+                region
+            ));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Colours, text and outline helpers shared by the SVG renderers of all diagram types.
+use svg::node::element::{Definitions, Group, Marker, Polygon, Rectangle, Style, Text};
+use svg::Document;
+
+use crate::layout::{Block, TextDirection};
+
+pub const TEXT_COLOR: &str = "blue";
+pub const LINE_COLOR: &str = "black";
+pub const NOTE_FILL: &str = "#DDD";
+pub const ACTIVATION_FILL: &str = "gray";
+pub const CLUSTER_FILL: &str = "#F4F4F4";
+pub const LABEL_FILL: &str = "white";
+/// Fills of the slices, bars and lines of charts, in order of appearance.
+pub const PALETTE: [&str; 12] = [
+    "#A6CEE3", "#B2DF8A", "#FB9A99", "#FDBF6F", "#CAB2D6", "#FFFF99", "#8DD3C7", "#BEBADA",
+    "#FB8072", "#80B1D3", "#FDB462", "#B3DE69",
+];
+
+/// A group whose origin is the top left corner of `block`.
+pub fn translated(block: &Block) -> Group {
+    Group::new().set(
+        "transform",
+        format!("translate({}, {})", block.position.x, block.position.y),
+    )
+}
+
+/// The outline of `block`, to be added to the group returned by [`translated`].
+pub fn outline(block: &Block, fill: &str, corner_radius: f64) -> Rectangle {
+    Rectangle::new()
+        .set("x", 0)
+        .set("y", 0)
+        .set("width", block.width)
+        .set("height", block.height)
+        .set("fill", fill)
+        .set("stroke", LINE_COLOR)
+        .set("rx", corner_radius)
+        .set("stroke-width", 1)
+}
+
+/// Applies the line colour and `fill` to any shape.
+pub fn stroked<T: svg::Node>(mut element: T, fill: &str) -> T {
+    element.assign("fill", fill);
+    element.assign("stroke", LINE_COLOR);
+    element.assign("stroke-width", 1);
+    element
+}
+
+/// A single line of a wrapped label. Right-to-left paragraphs are anchored at
+/// the right edge of their block so they end up right aligned.
+pub fn text_line(content: &str, y: f64, block: &Block, font_size: f64, padding: f64) -> Text {
+    let text = Text::new()
+        .set("y", y)
+        .set("fill", TEXT_COLOR)
+        .set("font-family", "monospace")
+        .set("font-size", font_size)
+        .set("unicode-bidi", "embed");
+    let text = match block.direction {
+        TextDirection::LeftToRight => text.set("x", padding).set("direction", "ltr"),
+        TextDirection::RightToLeft => text.set("x", block.width - padding).set("direction", "rtl"),
+    };
+    text.add(svg::node::Text::new(escaped(content)))
+}
+
+/// Escapes the characters with a meaning in XML, such as the angle brackets
+/// of generic class names.
+pub fn escaped(content: &str) -> String {
+    content
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A short text centered on a point, for labels placed by geometry rather
+/// than by the layout.
+pub fn text_at(content: &str, x: f64, y: f64, font_size: f64) -> Text {
+    Text::new()
+        .set("x", x)
+        .set("y", y + font_size * 0.35)
+        .set("fill", TEXT_COLOR)
+        .set("font-family", "monospace")
+        .set("font-size", font_size)
+        .set("text-anchor", "middle")
+        .add(svg::node::Text::new(escaped(content)))
+}
+
+/// The wrapped lines of a text block from top to bottom.
+pub fn text_lines<'a>(
+    lines: &'a [String],
+    block: &'a Block,
+    font_size: f64,
+    padding: f64,
+) -> impl Iterator<Item = Text> + 'a {
+    lines.iter().enumerate().map(move |(i, line)| {
+        let y = padding + ((i + 1) as f64) * block.line_height;
+        text_line(line, y, block, font_size, padding)
+    })
+}
+
+/// Wrapped text on a plain background, used for labels drawn over lines.
+pub fn label_box(lines: &[String], block: &Block, font_size: f64, padding: f64) -> Group {
+    let background = Rectangle::new()
+        .set("x", 0)
+        .set("y", 0)
+        .set("width", block.width)
+        .set("height", block.height)
+        .set("fill", LABEL_FILL);
+    text_lines(lines, block, font_size, padding).fold(translated(block).add(background), Group::add)
+}
+
+/// An empty document of the given size that already carries the font and the
+/// arrow markers every diagram type refers to.
+pub fn document(width: f64, height: f64) -> Document {
+    let defs = Definitions::new()
+        .add(Style::new("@font-face { font-family: Roboto-Regular; src: url(\"resources/fonts/Roboto-Regular.ttf\") }"))
+        .add(Style::new("text {font-family:Roboto-Regular,Roboto;}"))
+        .add(
+            Marker::new()
+                .set("id", "start-arrow")
+                .set("markerWidth", "10")
+                .set("markerHeight", "7")
+                .set("refX", 0)
+                .set("refY", 3.5)
+                .set("orient", "auto")
+                .add(Polygon::new().set("points", "10 0, 10 7, 0 3.5")),
+        )
+        .add(
+            Marker::new()
+                .set("id", "end-arrow")
+                .set("markerWidth", "10")
+                .set("markerHeight", "7")
+                .set("refX", 0)
+                .set("refY", 3.5)
+                .set("orient", "auto")
+                .add(Polygon::new().set("points", "0 0, 10 3.5, 0 7")),
+        );
+
+    Document::new()
+        .set("viewBox", (0, 0, width, height))
+        .add(defs)
+}
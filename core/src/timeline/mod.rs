@@ -0,0 +1,17 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(Timeline, TimelineLayout, ["timeline"], |options| {
+    SvgConfig {
+        max_label_length: 20,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        corner_radius: 5.0,
+        column_width: 120.0,
+        gutter: 20.0,
+    }
+});
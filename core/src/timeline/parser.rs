@@ -0,0 +1,187 @@
+use combine::error::StringStreamError;
+use combine::parser::char::char;
+use combine::parser::choice::choice;
+use combine::{eof, many, many1, none_of, optional, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, comment_parser, keyword, keyword_line};
+
+pub type SectionId = usize;
+pub type PeriodId = usize;
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub id: SectionId,
+    pub name: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Period {
+    pub id: PeriodId,
+    pub section: Option<SectionId>,
+    pub label: String,
+    pub events: Vec<String>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timeline {
+    pub title: Option<String>,
+    pub sections: Vec<Section>,
+    pub periods: Vec<Period>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Title(String),
+    Section(String),
+    /// A period and its events. Lines starting with `:` have no period and
+    /// add events to the previous one.
+    Period(Option<String>, Vec<String>),
+}
+
+pub fn parse(src: String) -> Result<Timeline, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => build_timeline(statements),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_timeline(statements: Vec<Statement>) -> Result<Timeline, StringStreamError> {
+    let mut timeline = Timeline {
+        title: None,
+        sections: vec![],
+        periods: vec![],
+    };
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Title(title) => timeline.title = Some(title),
+            Statement::Section(name) => timeline.sections.push(Section {
+                id: timeline.sections.len(),
+                name,
+            }),
+            Statement::Period(Some(label), events) => timeline.periods.push(Period {
+                id: timeline.periods.len(),
+                section: timeline.sections.last().map(|section| section.id),
+                label,
+                events,
+            }),
+            Statement::Period(None, events) => timeline
+                .periods
+                .last_mut()
+                .ok_or(StringStreamError::UnexpectedParse)?
+                .events
+                .extend(events),
+        }
+    }
+    Ok(timeline)
+}
+
+/// `period : event : event` or `: event`
+fn period_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    let event = || {
+        (char(':'), many(none_of(":\n".chars())))
+            .map(|(_, event): (_, String)| event.trim().to_string())
+    };
+    let events = |events: Vec<String>| events.into_iter().filter(|e| !e.is_empty()).collect();
+    choice((
+        (
+            none_of(":\n%".chars()),
+            many(none_of(":\n".chars())),
+            many(event()),
+        )
+            .map(move |(first, rest, list): (char, String, _)| {
+                let label = format!("{first}{rest}").trim().to_string();
+                Statement::Period(Some(label), events(list))
+            }),
+        many1(event()).map(move |list| Statement::Period(None, events(list))),
+    ))
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            keyword("timeline").map(|_| Statement::Empty),
+            keyword_line("title").map(Statement::Title),
+            keyword_line("section").map(Statement::Section),
+            accessibility_parser().map(|_| Statement::Empty),
+            period_parser(),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_period_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Period(
+                    Some("2004".to_string()),
+                    vec!["Facebook".to_string(), "Google".to_string()]
+                ),
+                ""
+            )),
+            period_parser().parse("2004 : Facebook : Google")
+        );
+        assert_eq!(
+            Ok((Statement::Period(None, vec!["Twitter".to_string()]), "")),
+            period_parser().parse(": Twitter")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"timeline
+            title History of Social Media Platform
+            2002 : LinkedIn
+            section Early days
+            2004 : Facebook
+                 : Google
+            %% video
+            2005 : YouTube
+        "#;
+        let timeline = parse(input.to_string()).unwrap();
+        assert_eq!(
+            Some("History of Social Media Platform".to_string()),
+            timeline.title
+        );
+        assert_eq!(3, timeline.periods.len());
+        assert_eq!(None, timeline.periods[0].section);
+        assert_eq!(Some(0), timeline.periods[1].section);
+        assert_eq!(
+            vec!["Facebook".to_string(), "Google".to_string()],
+            timeline.periods[1].events
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use svg::node::element::{Group, Line, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{AlignmentAnchor, BlockId, Layout, Orientation};
+use crate::theme::*;
+use crate::timeline::*;
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    pub corner_radius: f64,
+    /// Minimum width of the column of a period.
+    pub column_width: f64,
+    /// Space between columns, and between a period and its events.
+    pub gutter: f64,
+}
+
+struct Text {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+struct Column {
+    header: Text,
+    events: Vec<Text>,
+    color: &'static str,
+}
+
+fn add_text(
+    layout: &mut Layout,
+    content: &str,
+    max_label_length: usize,
+    config: &SvgConfig,
+) -> Text {
+    let font_size = config.font_size * config.font_scale_factor;
+    let (block, lines) =
+        layout.add_text_block(content, max_label_length, config.padding, font_size);
+    Text { block, lines }
+}
+
+fn text_box(layout: &Layout, text: &Text, fill: &str, config: &SvgConfig) -> Group {
+    let b = layout.b(text.block).solved();
+    let group = translated(b).add(outline(b, fill, config.corner_radius));
+    text_lines(&text.lines, b, config.font_size, config.padding).fold(group, Group::add)
+}
+
+/// A timeline with its periods, events and sections placed.
+pub struct TimelineLayout {
+    timeline: Timeline,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    title: Option<Text>,
+    columns: Vec<Column>,
+    sections: Vec<Text>,
+}
+
+pub fn layout(timeline: &Timeline, config: &SvgConfig) -> TimelineLayout {
+    let mut layout = Layout::new();
+
+    let title = timeline
+        .title
+        .as_ref()
+        .map(|title| add_text(&mut layout, title, config.max_label_length * 3, config));
+
+    // One column per period: the period on top, its events stacked below.
+    let columns: Vec<Column> = timeline
+        .periods
+        .iter()
+        .map(|period| {
+            let header = add_text(&mut layout, &period.label, config.max_label_length, config);
+            let width = layout.b(header.block).width;
+            layout.add_constraint(width | GE(REQUIRED) | config.column_width);
+            let events: Vec<Text> = period
+                .events
+                .iter()
+                .map(|event| {
+                    let text = add_text(&mut layout, event, config.max_label_length, config);
+                    layout.add_constraint(layout.b(text.block).width | EQ(REQUIRED) | width);
+                    text
+                })
+                .collect();
+            let blocks: Vec<BlockId> = std::iter::once(header.block)
+                .chain(events.iter().map(|event| event.block))
+                .collect();
+            layout.align(
+                Orientation::Horizontal,
+                AlignmentAnchor::Start,
+                blocks.iter(),
+            );
+            if let Some(first) = events.first() {
+                layout.add_constraint(
+                    layout.b(first.block).top()
+                        | EQ(REQUIRED)
+                        | (layout.b(header.block).bottom() + config.gutter),
+                );
+            }
+            for (previous, next) in blocks.iter().skip(1).zip(blocks.iter().skip(2)) {
+                layout.add_constraint(
+                    layout.b(*next).top()
+                        | EQ(REQUIRED)
+                        | (layout.b(*previous).bottom() + config.padding),
+                );
+            }
+            let color = match period.section {
+                Some(section) => PALETTE[section % PALETTE.len()],
+                None => PALETTE[period.id % PALETTE.len()],
+            };
+            Column {
+                header,
+                events,
+                color,
+            }
+        })
+        .collect();
+
+    let headers: Vec<BlockId> = columns.iter().map(|column| column.header.block).collect();
+    layout.distribute(Orientation::Horizontal, config.gutter, headers.iter());
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::Start,
+        headers.iter(),
+    );
+    for (previous, next) in headers.iter().zip(headers.iter().skip(1)) {
+        layout.add_constraint(layout.b(*previous).height | EQ(REQUIRED) | layout.b(*next).height);
+        layout.add_constraint(
+            (layout.b(*previous).right() + config.gutter) | EQ(WEAK) | layout.b(*next).left(),
+        );
+    }
+    if let Some(&first) = headers.first() {
+        layout.add_constraint(layout.b(first).left() | EQ(REQUIRED) | config.padding);
+    }
+
+    // Sections span the columns of their periods.
+    let sections: Vec<Text> = timeline
+        .sections
+        .iter()
+        .map(|section| {
+            let text = add_text(&mut layout, &section.name, config.max_label_length, config);
+            let b = text.block;
+            let periods: Vec<BlockId> = timeline
+                .periods
+                .iter()
+                .filter(|period| period.section == Some(section.id))
+                .map(|period| columns[period.id].header.block)
+                .collect();
+            match (periods.first(), periods.last()) {
+                (Some(&first), Some(&last)) => {
+                    layout
+                        .add_constraint(layout.b(b).left() | EQ(REQUIRED) | layout.b(first).left());
+                    layout.add_constraint(
+                        layout.b(b).right() | GE(REQUIRED) | layout.b(last).right(),
+                    );
+                    layout
+                        .add_constraint(layout.b(b).right() | EQ(STRONG) | layout.b(last).right());
+                    layout.add_constraint(
+                        (layout.b(b).bottom() + config.gutter)
+                            | EQ(REQUIRED)
+                            | layout.b(first).top(),
+                    );
+                }
+                _ => layout.add_constraint(layout.b(b).width | EQ(REQUIRED) | 0.0),
+            }
+            text
+        })
+        .collect();
+    let section_blocks: Vec<BlockId> = sections.iter().map(|section| section.block).collect();
+    layout.align(
+        Orientation::Vertical,
+        AlignmentAnchor::End,
+        section_blocks.iter(),
+    );
+
+    if let Some(title) = &title {
+        let b = title.block;
+        layout.add_constraint(layout.b(b).top() | EQ(REQUIRED) | 0.0);
+        if let (Some(&first), Some(&last)) = (headers.first(), headers.last()) {
+            layout.add_constraint(
+                (layout.b(b).left() + layout.b(b).width * 0.5)
+                    | EQ(STRONG)
+                    | ((layout.b(first).left() + layout.b(last).right()) * 0.5),
+            );
+        }
+        for &other in section_blocks.iter().chain(headers.iter()) {
+            layout.add_constraint(
+                layout.b(other).top() | GE(REQUIRED) | (layout.b(b).bottom() + config.padding),
+            );
+        }
+    }
+    layout.solve();
+
+    TimelineLayout {
+        timeline: timeline.clone(),
+        config: config.clone(),
+        layout,
+        title,
+        columns,
+        sections,
+    }
+}
+
+impl TimelineLayout {
+    pub fn to_svg(&self) -> SVG {
+        let TimelineLayout {
+            timeline,
+            config,
+            layout,
+            title,
+            columns,
+            sections,
+        } = self;
+
+        // Room for the arrow head at the end of the time axis.
+        let mut doc = document(
+            layout.width() + config.gutter,
+            layout.height() + config.padding,
+        );
+
+        // The time axis runs between the periods and their events.
+        if let (Some(first), Some(last)) = (columns.first(), columns.last()) {
+            let (first, last) = (
+                layout.b(first.header.block).solved(),
+                layout.b(last.header.block).solved(),
+            );
+            let y = first.position.y + first.height + config.gutter * 0.5;
+            doc = doc.add(
+                Line::new()
+                    .set("x1", first.position.x)
+                    .set("y1", y)
+                    .set("x2", last.position.x + last.width + config.gutter * 0.5)
+                    .set("y2", y)
+                    .set("stroke", LINE_COLOR)
+                    .set("stroke-width", 2)
+                    .set("marker-end", "url(#end-arrow)"),
+            );
+        }
+
+        for column in columns {
+            let header = layout.b(column.header.block).solved();
+            if let Some(last) = column.events.last() {
+                let last = layout.b(last.block).solved();
+                let x = header.position.x + header.width * 0.5;
+                doc = doc.add(
+                    Line::new()
+                        .set("x1", x)
+                        .set("y1", header.position.y + header.height)
+                        .set("x2", x)
+                        .set("y2", last.position.y)
+                        .set("stroke", LINE_COLOR)
+                        .set("stroke-dasharray", "4 4"),
+                );
+            }
+            doc = doc.add(text_box(layout, &column.header, column.color, config));
+            for event in &column.events {
+                doc = doc.add(text_box(layout, event, column.color, config));
+            }
+        }
+        for (section, text) in timeline.sections.iter().zip(sections.iter()) {
+            if !timeline
+                .periods
+                .iter()
+                .any(|p| p.section == Some(section.id))
+            {
+                continue;
+            }
+            doc = doc.add(text_box(
+                layout,
+                text,
+                PALETTE[section.id % PALETTE.len()],
+                config,
+            ));
+        }
+        if let Some(title) = &title {
+            let b = layout.b(title.block).solved();
+            doc = doc.add(
+                text_lines(&title.lines, b, config.font_size, config.padding)
+                    .fold(translated(b), Group::add),
+            );
+        }
+
+        doc
+    }
+}
+
+pub fn to_svg(timeline: &Timeline, config: &SvgConfig) -> SVG {
+    layout(timeline, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::Options;
+    use crate::timeline::Definition;
+
+    fn config() -> SvgConfig {
+        Definition.config(&Options::default())
+    }
+
+    #[test]
+    fn test_columns() {
+        let input = "timeline
+title History
+section Early
+2002 : LinkedIn
+2004 : Facebook : Google with a label long enough to wrap over lines
+section Late
+2005 : YouTube
+2006 : Twitter";
+        let timeline = parse(input.to_string()).unwrap();
+        let config = config();
+        let chart = layout(&timeline, &config);
+        let b = |id: BlockId| chart.layout.b(id).solved();
+
+        let headers: Vec<_> = chart.columns.iter().map(|c| b(c.header.block)).collect();
+        for (previous, next) in headers.iter().zip(&headers[1..]) {
+            assert!(previous.position.x + previous.width + config.gutter <= next.position.x + 1e-6);
+            assert_eq!(previous.position.y, next.position.y);
+            assert!((previous.height - next.height).abs() < 1e-6);
+        }
+        for (column, header) in chart.columns.iter().zip(&headers) {
+            assert!(header.width >= config.column_width);
+            // events under their period, as wide as it, in order
+            let mut top = header.position.y + header.height + config.gutter;
+            for event in &column.events {
+                let event = b(event.block);
+                assert_eq!(header.position.x, event.position.x);
+                assert!((event.width - header.width).abs() < 1e-6);
+                assert!((event.position.y - top).abs() < 1e-6);
+                top = event.position.y + event.height + config.padding;
+            }
+        }
+        assert_eq!(2, chart.columns[1].events.len());
+
+        // sections over their periods, the title over everything
+        for (section, periods) in chart.sections.iter().zip([0..2, 2..4]) {
+            let section = b(section.block);
+            let (first, last) = (&headers[periods.start], &headers[periods.end - 1]);
+            assert!((section.position.x - first.position.x).abs() < 1e-6);
+            assert!(section.position.x + section.width >= last.position.x + last.width - 1e-6);
+            assert!(
+                (section.position.y + section.height + config.gutter - first.position.y).abs()
+                    < 1e-6
+            );
+        }
+        let title = b(chart.title.as_ref().unwrap().block);
+        assert_eq!(0.0, title.position.y);
+        for section in &chart.sections {
+            assert!(title.position.y + title.height <= b(section.block).position.y);
+        }
+    }
+}
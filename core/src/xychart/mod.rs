@@ -0,0 +1,21 @@
+pub mod parser;
+pub mod render;
+
+pub use parser::*;
+pub use render::*;
+
+crate::diagram::diagram_type!(
+    XyChart,
+    XyChartLayout,
+    ["xychart-beta", "xychart"],
+    |options| SvgConfig {
+        max_label_length: 30,
+        font_scale_factor: options.font_scale_factor,
+        font_size: options.font_size,
+        padding: options.padding,
+        plot_width: 500.0,
+        plot_height: 300.0,
+        max_ticks: 8,
+        legend_gutter: 30.0,
+    }
+);
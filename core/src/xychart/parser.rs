@@ -0,0 +1,424 @@
+use combine::error::StringStreamError;
+use combine::parser::char::{char, digit, string};
+use combine::parser::choice::choice;
+use combine::{attempt, eof, many, many1, none_of, optional, sep_by, ParseError, Parser, Stream};
+
+use crate::parsing::{accessibility_parser, blank, blank1, comment_parser, keyword};
+
+pub type SeriesId = usize;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SeriesKind {
+    Bar,
+    Line,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Series {
+    pub id: SeriesId,
+    pub kind: SeriesKind,
+    pub title: Option<String>,
+    pub values: Vec<f64>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum XAxis {
+    Categories(Vec<String>),
+    /// Evenly spaced values from the first to the second bound.
+    Range(f64, f64),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XyChart {
+    pub title: Option<String>,
+    /// Whether the bars grow from left to right instead of upwards.
+    pub horizontal: bool,
+    pub x_title: Option<String>,
+    pub x_axis: Option<XAxis>,
+    pub y_title: Option<String>,
+    pub y_range: Option<(f64, f64)>,
+    pub series: Vec<Series>,
+}
+
+impl XyChart {
+    /// Number of points along the x axis.
+    pub fn len(&self) -> usize {
+        let values = self.series.iter().map(|s| s.values.len()).max();
+        match &self.x_axis {
+            Some(XAxis::Categories(categories)) => categories.len().max(values.unwrap_or(0)),
+            _ => values.unwrap_or(0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Statement {
+    Empty,
+    Header {
+        horizontal: bool,
+    },
+    Title(String),
+    XAxis {
+        title: Option<String>,
+        axis: Option<XAxis>,
+    },
+    YAxis {
+        title: Option<String>,
+        range: Option<(f64, f64)>,
+    },
+    Series {
+        kind: SeriesKind,
+        title: Option<String>,
+        values: Vec<f64>,
+    },
+}
+
+pub fn parse(src: String) -> Result<XyChart, StringStreamError> {
+    let src = src.replace("\r\n", "\n");
+    let mut parser = (statements_parser(), eof()).map(|(statements, _)| statements);
+    match parser.parse(format!("{src}\n").as_str()) {
+        Ok((statements, _)) => Ok(build_chart(statements)),
+        Err(e) => Err(e),
+    }
+}
+
+fn build_chart(statements: Vec<Statement>) -> XyChart {
+    let mut chart = XyChart {
+        title: None,
+        horizontal: false,
+        x_title: None,
+        x_axis: None,
+        y_title: None,
+        y_range: None,
+        series: vec![],
+    };
+    for statement in statements {
+        match statement {
+            Statement::Empty => {}
+            Statement::Header { horizontal } => chart.horizontal = horizontal,
+            Statement::Title(title) => chart.title = Some(title),
+            Statement::XAxis { title, axis } => {
+                chart.x_title = title.or(chart.x_title);
+                chart.x_axis = axis.or(chart.x_axis);
+            }
+            Statement::YAxis { title, range } => {
+                chart.y_title = title.or(chart.y_title);
+                chart.y_range = range.or(chart.y_range);
+            }
+            Statement::Series {
+                kind,
+                title,
+                values,
+            } => chart.series.push(Series {
+                id: chart.series.len(),
+                kind,
+                title,
+                values,
+            }),
+        }
+    }
+    chart
+}
+
+/// A quoted string or a single word.
+fn text_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, text, _)| text),
+        many1(none_of(" \t\n[\"%".chars())),
+    ))
+}
+
+/// A decimal number such as `-3`, `42` or `42.5`.
+fn number_parser<Input>() -> impl Parser<Input, Output = f64>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        optional(char('-')),
+        many1(digit()),
+        optional((char('.'), many1(digit()))),
+    )
+        .map(
+            |(sign, whole, fraction): (Option<char>, String, Option<(_, String)>)| {
+                let fraction = fraction.map(|(_, f)| format!(".{f}")).unwrap_or_default();
+                let value: f64 = format!("{whole}{fraction}").parse().unwrap_or(0.0);
+                if sign.is_some() {
+                    -value
+                } else {
+                    value
+                }
+            },
+        )
+}
+
+/// `min --> max`
+fn range_parser<Input>() -> impl Parser<Input, Output = (f64, f64)>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    attempt((
+        number_parser(),
+        blank(),
+        string("-->"),
+        blank(),
+        number_parser(),
+    ))
+    .map(|(min, _, _, _, max)| (min, max))
+}
+
+fn list_parser<Input, P>(item: P) -> impl Parser<Input, Output = Vec<P::Output>>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+    P: Parser<Input>,
+{
+    (
+        char('['),
+        blank(),
+        sep_by((item, blank()).map(|(item, _)| item), (char(','), blank())),
+        char(']'),
+    )
+        .map(|(_, _, items, _)| items)
+}
+
+fn category_parser<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        (char('"'), many(none_of("\"\n".chars())), char('"')).map(|(_, text, _)| text),
+        many1(none_of(",]\n\"".chars())).map(|text: String| text.trim().to_string()),
+    ))
+}
+
+fn x_axis_values_parser<Input>() -> impl Parser<Input, Output = XAxis>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    choice((
+        range_parser().map(|(min, max)| XAxis::Range(min, max)),
+        list_parser(category_parser()).map(XAxis::Categories),
+    ))
+}
+
+/// `x-axis [title] [[a, b, c] | min --> max]`
+fn x_axis_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("x-axis"),
+        blank(),
+        choice((
+            x_axis_values_parser().map(|axis| (None, Some(axis))),
+            (text_parser(), blank(), optional(x_axis_values_parser()))
+                .map(|(title, _, axis)| (Some(title), axis)),
+        )),
+    )
+        .map(|(_, _, (title, axis))| Statement::XAxis { title, axis })
+}
+
+/// `y-axis [title] [min --> max]`
+fn y_axis_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        keyword("y-axis"),
+        blank(),
+        choice((
+            range_parser().map(|range| (None, Some(range))),
+            (text_parser(), blank(), optional(range_parser()))
+                .map(|(title, _, range)| (Some(title), range)),
+        )),
+    )
+        .map(|(_, _, (title, range))| Statement::YAxis { title, range })
+}
+
+/// `bar [title] [1, 2, 3]` or `line [title] [1, 2, 3]`
+fn series_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        choice((
+            keyword("bar").map(|_| SeriesKind::Bar),
+            keyword("line").map(|_| SeriesKind::Line),
+        )),
+        blank(),
+        optional((text_parser(), blank()).map(|(title, _)| title)),
+        list_parser(number_parser()),
+    )
+        .map(|(kind, _, title, values)| Statement::Series {
+            kind,
+            title,
+            values,
+        })
+}
+
+fn header_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        choice((keyword("xychart-beta"), keyword("xychart"))),
+        optional(attempt((
+            blank1(),
+            choice((
+                keyword("horizontal").map(|_| true),
+                keyword("vertical").map(|_| false),
+            )),
+        ))),
+    )
+        .map(|(_, orientation)| Statement::Header {
+            horizontal: orientation
+                .map(|(_, horizontal)| horizontal)
+                .unwrap_or(false),
+        })
+}
+
+fn title_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (keyword("title"), blank1(), many1(none_of("\n".chars()))).map(
+        |(_, _, title): (_, _, String)| {
+            let title = title.trim();
+            let title = title
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+                .unwrap_or(title);
+            Statement::Title(title.to_string())
+        },
+    )
+}
+
+fn statement_parser<Input>() -> impl Parser<Input, Output = Statement>
+where
+    Input: Stream<Token = char>,
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    (
+        blank(),
+        optional(choice((
+            header_parser(),
+            title_parser(),
+            x_axis_parser(),
+            y_axis_parser(),
+            series_parser(),
+            accessibility_parser().map(|_| Statement::Empty),
+        ))),
+        blank(),
+        optional(comment_parser()),
+        char('\n'),
+    )
+        .map(|(_, statement, _, _, _)| statement.unwrap_or(Statement::Empty))
+}
+
+fn statements_parser<Input>() -> impl Parser<Input, Output = Vec<Statement>>
+where
+    Input: Stream<Token = char>,
+    // Necessary due to rust-lang/rust#24159
+    Input::Error: ParseError<Input::Token, Input::Range, Input::Position>,
+{
+    many1(statement_parser())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_x_axis_parser() {
+        assert_eq!(
+            Ok((
+                Statement::XAxis {
+                    title: Some("Month".to_string()),
+                    axis: Some(XAxis::Categories(vec![
+                        "jan".to_string(),
+                        "feb".to_string(),
+                        "early march".to_string(),
+                    ])),
+                },
+                ""
+            )),
+            x_axis_parser().parse("x-axis Month [jan, feb, \"early march\"]")
+        );
+        assert_eq!(
+            Ok((
+                Statement::XAxis {
+                    title: Some("Day of month".to_string()),
+                    axis: Some(XAxis::Range(1.0, 31.0)),
+                },
+                ""
+            )),
+            x_axis_parser().parse("x-axis \"Day of month\" 1 --> 31")
+        );
+    }
+
+    #[test]
+    fn test_series_parser() {
+        assert_eq!(
+            Ok((
+                Statement::Series {
+                    kind: SeriesKind::Line,
+                    title: Some("Revenue".to_string()),
+                    values: vec![1.5, -2.0, 300.0],
+                },
+                ""
+            )),
+            series_parser().parse("line Revenue [1.5, -2, 300]")
+        );
+        assert_eq!(
+            Ok((
+                Statement::Series {
+                    kind: SeriesKind::Bar,
+                    title: None,
+                    values: vec![],
+                },
+                ""
+            )),
+            series_parser().parse("bar []")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"xychart-beta horizontal
+            title "Sales Revenue"
+            x-axis [jan, feb, mar]
+            y-axis "Revenue (in $)" 4000 --> 11000
+            %% two series
+            bar [5000, 6000, 7500]
+            line [5000, 6000, 7500, 8200]
+        "#;
+        let chart = parse(input.to_string()).unwrap();
+        assert!(chart.horizontal);
+        assert_eq!(Some("Sales Revenue".to_string()), chart.title);
+        assert_eq!(Some((4000.0, 11000.0)), chart.y_range);
+        assert_eq!(2, chart.series.len());
+        assert_eq!(4, chart.len());
+    }
+}
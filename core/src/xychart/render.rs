@@ -0,0 +1,553 @@
+use svg::node::element::{Group, Line, Polyline, Rectangle, SVG};
+
+use cassowary::strength::{REQUIRED, STRONG, WEAK};
+use cassowary::WeightedRelation::*;
+
+use crate::layout::{BlockId, Layout};
+use crate::pie::{format_value, Legend};
+use crate::theme::*;
+use crate::xychart::*;
+
+const GRID_COLOR: &str = "#DDD";
+
+#[derive(Clone)]
+pub struct SvgConfig {
+    pub max_label_length: usize,
+    pub font_size: f64,
+    pub font_scale_factor: f64,
+    pub padding: f64,
+    /// Size of the area the bars and lines are drawn in.
+    pub plot_width: f64,
+    pub plot_height: f64,
+    /// Maximum number of ticks on the value axis.
+    pub max_ticks: usize,
+    /// Space between the plot and its legend.
+    pub legend_gutter: f64,
+}
+
+/// The values shown on the value axis, on round multiples of `step`.
+struct Scale {
+    min: f64,
+    max: f64,
+    step: f64,
+}
+
+impl Scale {
+    fn new(chart: &XyChart, max_ticks: usize) -> Scale {
+        let values = chart.series.iter().flat_map(|s| s.values.iter().copied());
+        let (min, max) = match chart.y_range {
+            Some((min, max)) => (min, max),
+            None => values.fold((0.0, 0.0), |(min, max): (f64, f64), v| {
+                (min.min(v), max.max(v))
+            }),
+        };
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            (min, min + 1.0)
+        };
+
+        let raw_step = (max - min) / max_ticks.max(1) as f64;
+        let magnitude = 10f64.powf(raw_step.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|factor| factor * magnitude)
+            .find(|&step| step >= raw_step)
+            .unwrap_or(10.0 * magnitude);
+        match chart.y_range {
+            Some(_) => Scale { min, max, step },
+            None => Scale {
+                min: (min / step).floor() * step,
+                max: (max / step).ceil() * step,
+                step,
+            },
+        }
+    }
+
+    /// Position of `value` between 0 at the minimum and 1 at the maximum.
+    fn fraction(&self, value: f64) -> f64 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    fn ticks(&self) -> Vec<f64> {
+        let first = (self.min / self.step).ceil() as i64;
+        let last = (self.max / self.step + 1e-9).floor() as i64;
+        (first..=last).map(|i| i as f64 * self.step).collect()
+    }
+
+    fn label(&self, value: f64) -> String {
+        let decimals = (-self.step.log10().floor()).max(0.0) as usize;
+        format_value(format!("{value:.decimals$}").parse().unwrap_or(value))
+    }
+}
+
+fn category_labels(chart: &XyChart) -> Vec<String> {
+    let len = chart.len();
+    match &chart.x_axis {
+        Some(XAxis::Categories(categories)) => (0..len)
+            .map(|i| categories.get(i).cloned().unwrap_or_default())
+            .collect(),
+        Some(XAxis::Range(min, max)) => (0..len)
+            .map(|i| {
+                let fraction = if len > 1 {
+                    i as f64 / (len - 1) as f64
+                } else {
+                    0.0
+                };
+                format_value((min + (max - min) * fraction * 100.0).round() / 100.0)
+            })
+            .collect(),
+        None => (1..=len).map(|i| i.to_string()).collect(),
+    }
+}
+
+struct AxisLabel {
+    block: BlockId,
+    lines: Vec<String>,
+}
+
+impl AxisLabel {
+    fn new(
+        layout: &mut Layout,
+        content: &str,
+        max_label_length: usize,
+        config: &SvgConfig,
+    ) -> AxisLabel {
+        let font_size = config.font_size * config.font_scale_factor;
+        let (block, lines) =
+            layout.add_text_block(content, max_label_length, config.padding, font_size);
+        layout.add_constraint(layout.b(block).left() | GE(REQUIRED) | 0.0);
+        AxisLabel { block, lines }
+    }
+
+    fn to_svg(&self, layout: &Layout, config: &SvgConfig) -> Group {
+        let b = layout.b(self.block).solved();
+        text_lines(&self.lines, b, config.font_size, config.padding).fold(translated(b), Group::add)
+    }
+}
+
+/// The series, position and size of every bar in a plot of `width` by
+/// `height`, from the top left of the plot. Bars of the same category stand
+/// side by side in the middle of its band, from the zero line to their value.
+fn bar_rects(
+    chart: &XyChart,
+    scale: &Scale,
+    width: f64,
+    height: f64,
+) -> Vec<(usize, f64, f64, f64, f64)> {
+    let bars: Vec<&Series> = chart
+        .series
+        .iter()
+        .filter(|series| series.kind == SeriesKind::Bar)
+        .collect();
+    let band = 1.0 / chart.len().max(1) as f64;
+    let baseline = scale.fraction(0.0);
+    let (length, depth) = if chart.horizontal {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let bar_width = length * band * 0.8 / bars.len().max(1) as f64;
+    let mut rects = vec![];
+    for (i, series) in bars.iter().enumerate() {
+        for (j, &value) in series.values.iter().enumerate() {
+            let offset = length * band * (j as f64 + 0.1) + bar_width * i as f64;
+            let (from, to) = (
+                baseline.min(scale.fraction(value)),
+                baseline.max(scale.fraction(value)),
+            );
+            rects.push(match chart.horizontal {
+                false => (
+                    series.id,
+                    offset,
+                    depth * (1.0 - to),
+                    bar_width,
+                    depth * (to - from),
+                ),
+                true => (
+                    series.id,
+                    depth * from,
+                    offset,
+                    depth * (to - from),
+                    bar_width,
+                ),
+            });
+        }
+    }
+    rects
+}
+
+/// An xy chart with its plot, axis labels and legend placed.
+pub struct XyChartLayout {
+    chart: XyChart,
+    config: SvgConfig,
+    layout: Layout<'static>,
+    scale: Scale,
+    plot: BlockId,
+    value_labels: Vec<(f64, AxisLabel)>,
+    categories: Vec<(f64, AxisLabel)>,
+    left_title: Option<AxisLabel>,
+    bottom_title: Option<AxisLabel>,
+    title: Option<AxisLabel>,
+    legend: Legend,
+}
+
+pub fn layout(chart: &XyChart, config: &SvgConfig) -> XyChartLayout {
+    let mut layout = Layout::new();
+    let font_size = config.font_size * config.font_scale_factor;
+    let scale = Scale::new(chart, config.max_ticks);
+    let len = chart.len().max(1);
+
+    let plot = layout.add_block();
+    layout.add_constraint(layout.b(plot).width | EQ(REQUIRED) | config.plot_width);
+    layout.add_constraint(layout.b(plot).height | EQ(REQUIRED) | config.plot_height);
+    layout.add_constraint(layout.b(plot).left() | EQ(WEAK) | 0.0);
+    layout.add_constraint(layout.b(plot).top() | EQ(WEAK) | 0.0);
+    layout.add_constraint(layout.b(plot).top() | GE(REQUIRED) | config.padding);
+
+    // Labels along the left side and along the bottom of the plot, as
+    // fractions of its height and width.
+    let ticks = scale.ticks();
+    let value_labels: Vec<(f64, AxisLabel)> = ticks
+        .iter()
+        .map(|&tick| {
+            let label = AxisLabel::new(&mut layout, &scale.label(tick), 12, config);
+            (scale.fraction(tick), label)
+        })
+        .collect();
+    let band = 1.0 / len as f64;
+    let categories: Vec<(f64, AxisLabel)> = category_labels(chart)
+        .iter()
+        .enumerate()
+        .map(|(i, category)| {
+            let label = AxisLabel::new(&mut layout, category, config.max_label_length / 2, config);
+            ((i as f64 + 0.5) * band, label)
+        })
+        .collect();
+    let (left_labels, bottom_labels) = match chart.horizontal {
+        false => (
+            value_labels
+                .iter()
+                .map(|(fraction, label)| (1.0 - fraction, label))
+                .collect::<Vec<_>>(),
+            categories.iter().map(|(f, l)| (*f, l)).collect::<Vec<_>>(),
+        ),
+        true => (
+            categories.iter().map(|(f, l)| (*f, l)).collect(),
+            value_labels.iter().map(|(f, l)| (*f, l)).collect(),
+        ),
+    };
+    for (fraction, label) in &left_labels {
+        let b = label.block;
+        layout.add_constraint(
+            layout.b(b).right() | LE(REQUIRED) | (layout.b(plot).left() - config.padding),
+        );
+        layout.add_constraint(
+            layout.b(b).right() | EQ(STRONG) | (layout.b(plot).left() - config.padding),
+        );
+        layout.add_constraint(
+            (layout.b(b).top() + layout.b(b).height * 0.5)
+                | EQ(REQUIRED)
+                | (layout.b(plot).top() + config.plot_height * fraction),
+        );
+        layout.add_constraint(layout.b(b).top() | GE(REQUIRED) | 0.0);
+    }
+    for (fraction, label) in &bottom_labels {
+        let b = label.block;
+        layout.add_constraint(
+            layout.b(b).top() | EQ(REQUIRED) | (layout.b(plot).bottom() + config.padding),
+        );
+        layout.add_constraint(
+            (layout.b(b).left() + layout.b(b).width * 0.5)
+                | EQ(STRONG)
+                | (layout.b(plot).left() + config.plot_width * fraction),
+        );
+    }
+
+    // Axis titles are written level, above the left labels and below the
+    // bottom ones.
+    let (left_title, bottom_title) = match chart.horizontal {
+        false => (&chart.y_title, &chart.x_title),
+        true => (&chart.x_title, &chart.y_title),
+    };
+    let left_title = left_title
+        .as_ref()
+        .map(|title| AxisLabel::new(&mut layout, title, config.max_label_length, config));
+    if let Some(title) = &left_title {
+        let b = title.block;
+        layout.add_constraint(
+            layout.b(b).bottom() | LE(REQUIRED) | (layout.b(plot).top() - config.padding),
+        );
+        for (_, label) in &left_labels {
+            layout
+                .add_constraint(layout.b(b).bottom() | LE(REQUIRED) | layout.b(label.block).top());
+        }
+        layout.add_constraint(layout.b(b).left() | EQ(STRONG) | 0.0);
+        layout.add_constraint(layout.b(b).top() | GE(REQUIRED) | 0.0);
+    }
+    let bottom_title = bottom_title
+        .as_ref()
+        .map(|title| AxisLabel::new(&mut layout, title, config.max_label_length * 2, config));
+    if let Some(title) = &bottom_title {
+        let b = title.block;
+        layout.add_constraint(
+            layout.b(b).top() | GE(REQUIRED) | (layout.b(plot).bottom() + config.padding),
+        );
+        for (_, label) in &bottom_labels {
+            layout
+                .add_constraint(layout.b(b).top() | GE(REQUIRED) | layout.b(label.block).bottom());
+        }
+        layout.add_constraint(
+            (layout.b(b).left() + layout.b(b).width * 0.5)
+                | EQ(STRONG)
+                | (layout.b(plot).left() + config.plot_width * 0.5),
+        );
+    }
+
+    let title = chart
+        .title
+        .as_ref()
+        .map(|title| AxisLabel::new(&mut layout, title, config.max_label_length * 3, config));
+    if let Some(title) = &title {
+        let b = title.block;
+        layout.add_constraint(layout.b(b).top() | EQ(REQUIRED) | 0.0);
+        layout.add_constraint(
+            (layout.b(b).left() + layout.b(b).width * 0.5)
+                | EQ(STRONG)
+                | (layout.b(plot).left() + config.plot_width * 0.5),
+        );
+        layout.add_constraint(
+            layout.b(plot).top() | GE(REQUIRED) | (layout.b(b).bottom() + config.padding),
+        );
+        for (_, label) in &left_labels {
+            layout
+                .add_constraint(layout.b(label.block).top() | GE(REQUIRED) | layout.b(b).bottom());
+        }
+        if let Some(left_title) = &left_title {
+            layout.add_constraint(
+                layout.b(left_title.block).top() | GE(REQUIRED) | layout.b(b).bottom(),
+            );
+        }
+    }
+
+    let legend_labels: Vec<String> = chart
+        .series
+        .iter()
+        .map(|series| match (&series.title, series.kind) {
+            (Some(title), _) => title.clone(),
+            (None, SeriesKind::Bar) => format!("bar {}", series.id + 1),
+            (None, SeriesKind::Line) => format!("line {}", series.id + 1),
+        })
+        .collect();
+    let legend = Legend::new(
+        &mut layout,
+        &legend_labels,
+        config.max_label_length,
+        config.padding,
+        font_size,
+    );
+    let legend_block = legend.block();
+    layout.add_constraint(
+        layout.b(legend_block).left()
+            | EQ(REQUIRED)
+            | (layout.b(plot).right() + config.legend_gutter),
+    );
+    layout.add_constraint(layout.b(legend_block).top() | GE(REQUIRED) | layout.b(plot).top());
+    layout.add_constraint(
+        (layout.b(legend_block).top() + layout.b(legend_block).height * 0.5)
+            | EQ(STRONG)
+            | (layout.b(plot).top() + config.plot_height * 0.5),
+    );
+    layout.solve();
+    XyChartLayout {
+        chart: chart.clone(),
+        config: config.clone(),
+        layout,
+        scale,
+        plot,
+        value_labels,
+        categories,
+        left_title,
+        bottom_title,
+        title,
+        legend,
+    }
+}
+
+impl XyChartLayout {
+    pub fn to_svg(&self) -> SVG {
+        let XyChartLayout {
+            chart,
+            config,
+            layout,
+            scale,
+            plot,
+            value_labels,
+            categories,
+            left_title,
+            bottom_title,
+            title,
+            legend,
+        } = self;
+        let ticks = scale.ticks();
+        let band = 1.0 / chart.len().max(1) as f64;
+        let p = layout.b(*plot).solved();
+        let (x0, y0, w, h) = (p.position.x, p.position.y, p.width, p.height);
+        let mut doc = document(
+            layout.width() + config.padding,
+            layout.height() + config.padding,
+        );
+
+        // Grid lines at the ticks, then the bars and lines over them.
+        for &tick in &ticks {
+            let fraction = scale.fraction(tick);
+            let (x1, y1, x2, y2) = match chart.horizontal {
+                false => (
+                    x0,
+                    y0 + h * (1.0 - fraction),
+                    x0 + w,
+                    y0 + h * (1.0 - fraction),
+                ),
+                true => (x0 + w * fraction, y0, x0 + w * fraction, y0 + h),
+            };
+            doc = doc.add(
+                Line::new()
+                    .set("x1", x1)
+                    .set("y1", y1)
+                    .set("x2", x2)
+                    .set("y2", y2)
+                    .set("stroke", GRID_COLOR),
+            );
+        }
+
+        for (series_id, x, y, width, height) in bar_rects(chart, scale, w, h) {
+            let rectangle = Rectangle::new()
+                .set("x", x0 + x)
+                .set("y", y0 + y)
+                .set("width", width)
+                .set("height", height);
+            doc = doc.add(stroked(rectangle, PALETTE[series_id % PALETTE.len()]));
+        }
+
+        let (length, depth) = if chart.horizontal { (h, w) } else { (w, h) };
+        for series in chart.series.iter().filter(|s| s.kind == SeriesKind::Line) {
+            let points: Vec<String> = series
+                .values
+                .iter()
+                .enumerate()
+                .map(|(j, &value)| {
+                    let along = length * band * (j as f64 + 0.5);
+                    let across = depth * scale.fraction(value);
+                    match chart.horizontal {
+                        false => format!("{},{}", x0 + along, y0 + h - across),
+                        true => format!("{},{}", x0 + across, y0 + along),
+                    }
+                })
+                .collect();
+            doc = doc.add(
+                Polyline::new()
+                    .set("points", points.join(" "))
+                    .set("fill", "none")
+                    .set("stroke", PALETTE[series.id % PALETTE.len()])
+                    .set("stroke-width", 3),
+            );
+        }
+
+        // The axes along the left side and the bottom of the plot.
+        doc = doc.add(
+            Polyline::new()
+                .set(
+                    "points",
+                    format!("{x0},{y0} {x0},{} {},{}", y0 + h, x0 + w, y0 + h),
+                )
+                .set("fill", "none")
+                .set("stroke", LINE_COLOR),
+        );
+
+        let labels = value_labels
+            .iter()
+            .chain(categories.iter())
+            .map(|(_, label)| label)
+            .chain(left_title.iter())
+            .chain(bottom_title.iter())
+            .chain(title.iter());
+        for label in labels {
+            doc = doc.add(label.to_svg(layout, config));
+        }
+        doc = doc.add(legend.to_svg(layout, config.font_size, config.padding));
+
+        doc
+    }
+}
+
+pub fn to_svg(chart: &XyChart, config: &SvgConfig) -> SVG {
+    layout(chart, config).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale() {
+        let chart = parse("xychart-beta\nbar [10, -5, 30]".to_string()).unwrap();
+        let scale = Scale::new(&chart, 5);
+        assert_eq!(vec![-10.0, 0.0, 10.0, 20.0, 30.0], scale.ticks());
+        assert_eq!(0.25, scale.fraction(0.0));
+        assert_eq!(1.0, scale.fraction(40.0));
+        assert_eq!("-10", scale.label(-10.0));
+
+        let fixed = parse("xychart-beta\ny-axis 0 --> 1\nline [0.5]".to_string()).unwrap();
+        let scale = Scale::new(&fixed, 4);
+        assert_eq!(vec![0.0, 0.5, 1.0], scale.ticks());
+        assert_eq!("0.5", scale.label(0.5));
+    }
+
+    #[test]
+    fn test_bar_rects() {
+        let (width, height) = (300.0, 200.0);
+        for direction in ["", " horizontal"] {
+            let chart = parse(format!(
+                "xychart-beta{direction}\nbar [10, -5, 30]\nbar [20, 0, 15]"
+            ))
+            .unwrap();
+            let scale = Scale::new(&chart, 5);
+            let rects = bar_rects(&chart, &scale, width, height);
+            assert_eq!(6, rects.len());
+            let (length, depth) = if chart.horizontal {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            let zero = depth * scale.fraction(0.0);
+            let values = chart.series.iter().flat_map(|s| s.values.iter());
+            let mut spans = vec![];
+            for (&(_, x, y, w, h), value) in rects.iter().zip(values) {
+                // along the value axis, from the zero line to the value
+                let (along, across, start, end) = match chart.horizontal {
+                    false => ((x, w), h, depth - (y + h), depth - y),
+                    true => ((y, h), w, x, x + w),
+                };
+                assert!(
+                    (across - depth * value.abs() / 40.0).abs() < 1e-9,
+                    "{direction}"
+                );
+                let expected = if *value < 0.0 { end } else { start };
+                assert!((expected - zero).abs() < 1e-9, "{direction}");
+                assert!(along.0 >= 0.0 && along.0 + along.1 <= length, "{direction}");
+                spans.push(along);
+            }
+            // bars of a category side by side, inside its band
+            for category in 0..3 {
+                let (first, second) = (spans[category], spans[3 + category]);
+                assert!((first.0 + first.1 - second.0).abs() < 1e-9, "{direction}");
+                let band = length / 3.0;
+                assert!(first.0 >= band * category as f64, "{direction}");
+                assert!(
+                    second.0 + second.1 <= band * (category + 1) as f64,
+                    "{direction}"
+                );
+            }
+        }
+    }
+}